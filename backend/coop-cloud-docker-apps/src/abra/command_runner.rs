@@ -0,0 +1,157 @@
+use std::{
+    io::{BufRead, BufReader, Read},
+    path::PathBuf,
+    process::{Command, Stdio},
+    sync::mpsc::{self, Sender},
+    thread::{self, JoinHandle},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogStream {
+    Stdout,
+    Stderr,
+}
+
+/// A single line of output from a running command.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LogLine {
+    pub stream: LogStream,
+    pub text: String,
+}
+
+/// Runs a command line program, reporting each line of its output as it is
+/// produced. Implemented by [`ProcessCommandRunner`] for real use, and can be
+/// swapped out to run against a fake binary in tests.
+pub trait CommandRunner: Send + Sync {
+    fn run(&self, args: &[String], on_line: &mut dyn FnMut(LogLine)) -> Result<(), anyhow::Error>;
+}
+
+/// Runs a program as a child process, streaming both stdout and stderr.
+pub struct ProcessCommandRunner {
+    program: PathBuf,
+}
+
+impl ProcessCommandRunner {
+    pub fn new(program: impl Into<PathBuf>) -> Self {
+        Self {
+            program: program.into(),
+        }
+    }
+}
+
+impl CommandRunner for ProcessCommandRunner {
+    fn run(&self, args: &[String], on_line: &mut dyn FnMut(LogLine)) -> Result<(), anyhow::Error> {
+        let mut child = Command::new(&self.program)
+            .args(args)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| anyhow::anyhow!("Failed to execute command: {}", e))?;
+
+        let (tx, rx) = mpsc::channel::<LogLine>();
+        let readers = [
+            spawn_line_reader(child.stdout.take(), LogStream::Stdout, tx.clone()),
+            spawn_line_reader(child.stderr.take(), LogStream::Stderr, tx),
+        ];
+
+        // Ends once both readers have hit EOF and dropped their senders.
+        for line in rx {
+            on_line(line);
+        }
+        for reader in readers.into_iter().flatten() {
+            let _ = reader.join();
+        }
+
+        let status = child
+            .wait()
+            .map_err(|e| anyhow::anyhow!("Failed to wait for command: {}", e))?;
+
+        if status.success() {
+            Ok(())
+        } else {
+            Err(anyhow::anyhow!(
+                "{} exited with {}",
+                self.program.display(),
+                status
+            ))
+        }
+    }
+}
+
+fn spawn_line_reader<R: Read + Send + 'static>(
+    reader: Option<R>,
+    stream: LogStream,
+    tx: Sender<LogLine>,
+) -> Option<JoinHandle<()>> {
+    let reader = reader?;
+    Some(thread::spawn(move || {
+        for line in BufReader::new(reader).lines() {
+            let Ok(text) = line else { break };
+            if tx.send(LogLine { stream, text }).is_err() {
+                break;
+            }
+        }
+    }))
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+
+    fn fake_binary(dir: &tempfile::TempDir, script: &str) -> PathBuf {
+        let path = dir.path().join("fake-abra");
+        std::fs::write(&path, format!("#!/bin/sh\n{script}\n")).unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+        path
+    }
+
+    fn run_collecting(
+        runner: &ProcessCommandRunner,
+        args: &[&str],
+    ) -> (Result<(), anyhow::Error>, Vec<LogLine>) {
+        let args: Vec<String> = args.iter().map(|a| a.to_string()).collect();
+        let mut lines = Vec::new();
+        let result = runner.run(&args, &mut |line| lines.push(line));
+        (result, lines)
+    }
+
+    #[test]
+    fn test_streams_stdout_and_stderr() {
+        let dir = tempfile::tempdir().unwrap();
+        let runner = ProcessCommandRunner::new(fake_binary(&dir, "echo \"out $1\"\necho err >&2"));
+
+        let (result, lines) = run_collecting(&runner, &["hello"]);
+
+        assert!(result.is_ok());
+        assert!(lines.contains(&LogLine {
+            stream: LogStream::Stdout,
+            text: "out hello".to_string()
+        }));
+        assert!(lines.contains(&LogLine {
+            stream: LogStream::Stderr,
+            text: "err".to_string()
+        }));
+    }
+
+    #[test]
+    fn test_non_zero_exit_is_an_error() {
+        let dir = tempfile::tempdir().unwrap();
+        let runner = ProcessCommandRunner::new(fake_binary(&dir, "echo failing\nexit 3"));
+
+        let (result, lines) = run_collecting(&runner, &[]);
+
+        assert!(result.is_err());
+        assert_eq!(lines.len(), 1);
+    }
+
+    #[test]
+    fn test_missing_binary_is_an_error() {
+        let runner = ProcessCommandRunner::new("/nonexistent/abra");
+        let (result, lines) = run_collecting(&runner, &[]);
+
+        assert!(result.is_err());
+        assert!(lines.is_empty());
+    }
+}
@@ -0,0 +1,273 @@
+use std::sync::Arc;
+
+mod command_runner;
+
+pub use command_runner::{CommandRunner, LogLine, LogStream, ProcessCommandRunner};
+
+/// The subset of `abra` commands used to manage the lifecycle of an app.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AbraCommand {
+    New {
+        recipe: String,
+        domain: String,
+        server: String,
+        version: Option<String>,
+    },
+    Deploy {
+        domain: String,
+    },
+    Upgrade {
+        domain: String,
+        version: Option<String>,
+    },
+    Undeploy {
+        domain: String,
+    },
+}
+
+impl AbraCommand {
+    pub fn domain(&self) -> &str {
+        match self {
+            AbraCommand::New { domain, .. }
+            | AbraCommand::Deploy { domain }
+            | AbraCommand::Upgrade { domain, .. }
+            | AbraCommand::Undeploy { domain } => domain,
+        }
+    }
+
+    /// Checks every user supplied value, so that nothing can be interpreted
+    /// by `abra` as a flag.
+    pub fn validate(&self) -> Result<(), anyhow::Error> {
+        if !valid_domain(self.domain()) {
+            return Err(anyhow::anyhow!("Invalid domain: {}", self.domain()));
+        }
+
+        match self {
+            AbraCommand::New {
+                recipe,
+                server,
+                version,
+                ..
+            } => {
+                if !valid_name(recipe) {
+                    return Err(anyhow::anyhow!("Invalid recipe: {}", recipe));
+                }
+                if !valid_name(server) && !valid_domain(server) {
+                    return Err(anyhow::anyhow!("Invalid server: {}", server));
+                }
+                validate_version(version)
+            }
+            AbraCommand::Upgrade { version, .. } => validate_version(version),
+            AbraCommand::Deploy { .. } | AbraCommand::Undeploy { .. } => Ok(()),
+        }
+    }
+
+    pub fn args(&self) -> Vec<String> {
+        let mut args: Vec<String> = match self {
+            AbraCommand::New {
+                recipe,
+                domain,
+                server,
+                version,
+            } => {
+                let mut args = vec!["app".into(), "new".into(), recipe.clone()];
+                args.extend(version.clone());
+                args.extend([
+                    "--server".into(),
+                    server.clone(),
+                    "--domain".into(),
+                    domain.clone(),
+                    "--secrets".into(),
+                ]);
+                args
+            }
+            AbraCommand::Deploy { domain } => vec!["app".into(), "deploy".into(), domain.clone()],
+            AbraCommand::Upgrade { domain, version } => {
+                let mut args = vec!["app".into(), "upgrade".into(), domain.clone()];
+                args.extend(version.clone());
+                args
+            }
+            AbraCommand::Undeploy { domain } => {
+                vec!["app".into(), "undeploy".into(), domain.clone()]
+            }
+        };
+
+        args.push("--no-input".into());
+        args
+    }
+}
+
+/// Runs [`AbraCommand`]s through a [`CommandRunner`].
+#[derive(Clone)]
+pub struct Abra {
+    runner: Arc<dyn CommandRunner>,
+}
+
+impl Abra {
+    pub fn new(runner: Arc<dyn CommandRunner>) -> Self {
+        Self { runner }
+    }
+
+    /// Validates and runs the command, blocking until it exits. Output is
+    /// passed to `on_line` as it arrives.
+    pub fn run(
+        &self,
+        command: &AbraCommand,
+        on_line: &mut dyn FnMut(LogLine),
+    ) -> Result<(), anyhow::Error> {
+        command.validate()?;
+        self.runner.run(&command.args(), on_line)
+    }
+}
+
+fn validate_version(version: &Option<String>) -> Result<(), anyhow::Error> {
+    match version {
+        Some(version) if !valid_version(version) => {
+            Err(anyhow::anyhow!("Invalid version: {}", version))
+        }
+        _ => Ok(()),
+    }
+}
+
+fn valid_name(value: &str) -> bool {
+    !value.is_empty()
+        && !value.starts_with('-')
+        && value
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || c == '_')
+}
+
+fn valid_domain(value: &str) -> bool {
+    value.contains('.')
+        && value.split('.').all(|label| {
+            !label.is_empty()
+                && !label.starts_with('-')
+                && !label.ends_with('-')
+                && label
+                    .chars()
+                    .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+        })
+}
+
+fn valid_version(value: &str) -> bool {
+    !value.is_empty()
+        && !value.starts_with('-')
+        && value
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '+' | '-' | '_'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    struct RecordingRunner {
+        calls: Mutex<Vec<Vec<String>>>,
+    }
+
+    impl CommandRunner for RecordingRunner {
+        fn run(
+            &self,
+            args: &[String],
+            on_line: &mut dyn FnMut(LogLine),
+        ) -> Result<(), anyhow::Error> {
+            self.calls.lock().unwrap().push(args.to_vec());
+            on_line(LogLine {
+                stream: LogStream::Stdout,
+                text: "ok".to_string(),
+            });
+            Ok(())
+        }
+    }
+
+    fn new_command(domain: &str) -> AbraCommand {
+        AbraCommand::New {
+            recipe: "wordpress".to_string(),
+            domain: domain.to_string(),
+            server: "default".to_string(),
+            version: Some("2.1.0+6.5.2".to_string()),
+        }
+    }
+
+    #[test]
+    fn test_new_args() {
+        assert_eq!(
+            new_command("blog.example.org").args(),
+            vec![
+                "app",
+                "new",
+                "wordpress",
+                "2.1.0+6.5.2",
+                "--server",
+                "default",
+                "--domain",
+                "blog.example.org",
+                "--secrets",
+                "--no-input"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_upgrade_without_version_args() {
+        let command = AbraCommand::Upgrade {
+            domain: "blog.example.org".to_string(),
+            version: None,
+        };
+        assert_eq!(
+            command.args(),
+            vec!["app", "upgrade", "blog.example.org", "--no-input"]
+        );
+    }
+
+    #[test]
+    fn test_rejects_flag_like_values() {
+        assert!(new_command("--chaos").validate().is_err());
+
+        let command = AbraCommand::Upgrade {
+            domain: "blog.example.org".to_string(),
+            version: Some("--force".to_string()),
+        };
+        assert!(command.validate().is_err());
+    }
+
+    #[test]
+    fn test_rejects_invalid_domains() {
+        assert!(new_command("localhost").validate().is_err());
+        assert!(new_command("blog..example.org").validate().is_err());
+        assert!(new_command("blog.example.org; rm").validate().is_err());
+        assert!(new_command("blog.example.org").validate().is_ok());
+    }
+
+    #[test]
+    fn test_run_does_not_call_runner_when_invalid() {
+        let runner = Arc::new(RecordingRunner {
+            calls: Mutex::new(vec![]),
+        });
+        let abra = Abra::new(runner.clone());
+
+        let result = abra.run(&new_command("not a domain"), &mut |_| {});
+
+        assert!(result.is_err());
+        assert!(runner.calls.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_run_passes_output_through() {
+        let runner = Arc::new(RecordingRunner {
+            calls: Mutex::new(vec![]),
+        });
+        let abra = Abra::new(runner.clone());
+        let command = AbraCommand::Deploy {
+            domain: "blog.example.org".to_string(),
+        };
+
+        let mut lines = vec![];
+        abra.run(&command, &mut |line| lines.push(line.text))
+            .unwrap();
+
+        assert_eq!(lines, vec!["ok"]);
+        assert_eq!(runner.calls.lock().unwrap()[0], command.args());
+    }
+}
@@ -45,7 +45,7 @@ fn get_service_labels(service_id: &str) -> Result<CoopCloudServiceLabels, anyhow
 }
 
 fn get_app_service_from_list(
-    services: &Vec<DockerStackServicesResult>,
+    services: &[DockerStackServicesResult],
 ) -> Option<&DockerStackServicesResult> {
     services
         .iter()
@@ -0,0 +1,132 @@
+use std::collections::HashMap;
+
+const PREFIX: &str = "backupbot.";
+
+/// Backup settings declared on a service with `backupbot.*` labels, following
+/// the conventions of Co-op Cloud's backup-bot-two.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BackupConfig {
+    pub pre_hook: Option<String>,
+    pub post_hook: Option<String>,
+    pub restore_pre_hook: Option<String>,
+    pub restore_post_hook: Option<String>,
+    /// Volumes opted out with `backupbot.backup.volumes.<name>=false`.
+    pub excluded_volumes: Vec<String>,
+}
+
+impl BackupConfig {
+    /// Returns `None` unless the service opts in with `backupbot.backup=true`.
+    pub fn from_labels(labels: &HashMap<String, String>) -> Option<Self> {
+        let get = |key: &str| labels.get(&format!("{PREFIX}{key}")).cloned();
+
+        if !get("backup").is_some_and(|v| is_true(&v)) {
+            return None;
+        }
+
+        let volumes_prefix = format!("{PREFIX}backup.volumes.");
+        let mut excluded_volumes: Vec<String> = labels
+            .iter()
+            .filter_map(|(key, value)| {
+                let volume = key.strip_prefix(&volumes_prefix)?;
+                if !volume.contains('.') && is_false(value) {
+                    Some(volume.to_string())
+                } else {
+                    None
+                }
+            })
+            .collect();
+        excluded_volumes.sort();
+
+        Some(BackupConfig {
+            pre_hook: get("backup.pre-hook"),
+            post_hook: get("backup.post-hook"),
+            restore_pre_hook: get("restore.pre-hook"),
+            restore_post_hook: get("restore.post-hook"),
+            excluded_volumes,
+        })
+    }
+
+    /// Volume labels use the name from the compose file, while mounts use the
+    /// stack-prefixed name, so accept either.
+    pub fn includes_volume(&self, stack_name: &str, volume: &str) -> bool {
+        let short_name = volume
+            .strip_prefix(&format!("{stack_name}_"))
+            .unwrap_or(volume);
+
+        !self
+            .excluded_volumes
+            .iter()
+            .any(|excluded| excluded == volume || excluded == short_name)
+    }
+}
+
+fn is_true(value: &str) -> bool {
+    value.trim().eq_ignore_ascii_case("true")
+}
+
+fn is_false(value: &str) -> bool {
+    value.trim().eq_ignore_ascii_case("false")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn labels(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_no_config_without_backup_label() {
+        let labels = labels(&[("backupbot.backup.pre-hook", "echo hi")]);
+        assert_eq!(BackupConfig::from_labels(&labels), None);
+    }
+
+    #[test]
+    fn test_no_config_when_backup_disabled() {
+        let labels = labels(&[("backupbot.backup", "false")]);
+        assert_eq!(BackupConfig::from_labels(&labels), None);
+    }
+
+    #[test]
+    fn test_parses_hooks_and_excluded_volumes() {
+        let labels = labels(&[
+            ("backupbot.backup", "true"),
+            ("backupbot.backup.pre-hook", "pg_dump -f /var/lib/db.sql"),
+            ("backupbot.backup.post-hook", "rm /var/lib/db.sql"),
+            ("backupbot.restore.post-hook", "psql -f /var/lib/db.sql"),
+            ("backupbot.backup.volumes.postgres", "false"),
+            ("backupbot.backup.volumes.data", "true"),
+            ("backupbot.backup.volumes.data.path", "files"),
+        ]);
+
+        let config = BackupConfig::from_labels(&labels).unwrap();
+
+        assert_eq!(
+            config.pre_hook.as_deref(),
+            Some("pg_dump -f /var/lib/db.sql")
+        );
+        assert_eq!(config.post_hook.as_deref(), Some("rm /var/lib/db.sql"));
+        assert_eq!(config.restore_pre_hook, None);
+        assert_eq!(
+            config.restore_post_hook.as_deref(),
+            Some("psql -f /var/lib/db.sql")
+        );
+        assert_eq!(config.excluded_volumes, vec!["postgres".to_string()]);
+    }
+
+    #[test]
+    fn test_includes_volume_matches_stack_prefixed_names() {
+        let config = BackupConfig {
+            excluded_volumes: vec!["postgres".to_string()],
+            ..Default::default()
+        };
+
+        assert!(!config.includes_volume("gitea_example_com", "gitea_example_com_postgres"));
+        assert!(!config.includes_volume("gitea_example_com", "postgres"));
+        assert!(config.includes_volume("gitea_example_com", "gitea_example_com_data"));
+    }
+}
@@ -0,0 +1,171 @@
+use std::{collections::HashMap, path::Path};
+use tracing::warn;
+
+use crate::docker::{
+    docker_service::docker_service_inspect,
+    docker_stack::docker_stack_services,
+    docker_volume::{docker_service_exec, docker_volume_archive, docker_volume_restore},
+};
+
+mod labels;
+
+pub use labels::BackupConfig;
+
+#[derive(Debug, Clone)]
+pub struct ServiceBackup {
+    pub service_name: String,
+    pub config: BackupConfig,
+    /// Docker volume names (stack-prefixed) to back up for this service.
+    pub volumes: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct AppBackupPlan {
+    pub stack_name: String,
+    pub services: Vec<ServiceBackup>,
+}
+
+impl AppBackupPlan {
+    pub fn is_empty(&self) -> bool {
+        self.services.iter().all(|s| s.volumes.is_empty())
+    }
+}
+
+/// Works out what to back up for a stack from the `backupbot.*` labels on its
+/// services. Services without `backupbot.backup=true` are skipped.
+pub fn app_backup_plan(stack_name: &str) -> Result<AppBackupPlan, anyhow::Error> {
+    let services = docker_stack_services(stack_name)?;
+    let mut backups = Vec::new();
+
+    for service in services {
+        let details = docker_service_inspect(&service.name)?;
+        let labels: HashMap<String, String> = details.spec.labels.unwrap_or_default();
+
+        let Some(config) = BackupConfig::from_labels(&labels) else {
+            continue;
+        };
+
+        let volumes = volume_mounts(&details.spec.task_template)
+            .into_iter()
+            .filter(|volume| config.includes_volume(stack_name, volume))
+            .collect();
+
+        backups.push(ServiceBackup {
+            service_name: service.name,
+            config,
+            volumes,
+        });
+    }
+
+    Ok(AppBackupPlan {
+        stack_name: stack_name.to_string(),
+        services: backups,
+    })
+}
+
+pub fn volume_archive_name(volume: &str) -> String {
+    format!("{volume}.tar.gz")
+}
+
+/// Archives every volume in the plan into `dir`, running the services' pre and
+/// post hooks around it. Returns the archive file names written.
+pub fn backup_app(plan: &AppBackupPlan, dir: &Path) -> Result<Vec<String>, anyhow::Error> {
+    let mut archives = Vec::new();
+
+    for service in &plan.services {
+        if let Some(hook) = &service.config.pre_hook {
+            docker_service_exec(&service.service_name, hook)?;
+        }
+
+        let result = service.volumes.iter().try_for_each(|volume| {
+            let archive_name = volume_archive_name(volume);
+            docker_volume_archive(volume, dir, &archive_name)?;
+            archives.push(archive_name);
+            Ok::<(), anyhow::Error>(())
+        });
+
+        // Always give the service a chance to clean up, even if archiving failed.
+        if let Some(hook) = &service.config.post_hook
+            && let Err(e) = docker_service_exec(&service.service_name, hook)
+        {
+            warn!(
+                "Backup post-hook failed for {}: {:?}",
+                service.service_name, e
+            );
+        }
+
+        result?;
+    }
+
+    Ok(archives)
+}
+
+/// Restores the volumes in the plan from archives in `dir`. Volumes without an
+/// archive are left untouched.
+pub fn restore_app(plan: &AppBackupPlan, dir: &Path) -> Result<(), anyhow::Error> {
+    for service in &plan.services {
+        if let Some(hook) = &service.config.restore_pre_hook {
+            docker_service_exec(&service.service_name, hook)?;
+        }
+
+        for volume in &service.volumes {
+            let archive_name = volume_archive_name(volume);
+            if !dir.join(&archive_name).exists() {
+                warn!("No archive for volume {} in backup, skipping", volume);
+                continue;
+            }
+            docker_volume_restore(volume, dir, &archive_name)?;
+        }
+
+        if let Some(hook) = &service.config.restore_post_hook {
+            docker_service_exec(&service.service_name, hook)?;
+        }
+    }
+
+    Ok(())
+}
+
+fn volume_mounts(task_template: &serde_json::Value) -> Vec<String> {
+    task_template["ContainerSpec"]["Mounts"]
+        .as_array()
+        .map(|mounts| {
+            mounts
+                .iter()
+                .filter(|mount| mount["Type"].as_str() == Some("volume"))
+                .filter_map(|mount| mount["Source"].as_str().map(str::to_string))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_volume_mounts_only_returns_named_volumes() {
+        let task_template = serde_json::json!({
+            "ContainerSpec": {
+                "Mounts": [
+                    {"Type": "volume", "Source": "gitea_example_com_data", "Target": "/data"},
+                    {"Type": "bind", "Source": "/etc/timezone", "Target": "/etc/timezone"},
+                    {"Type": "volume", "Source": "gitea_example_com_postgres", "Target": "/var/lib/postgresql"}
+                ]
+            }
+        });
+
+        assert_eq!(
+            volume_mounts(&task_template),
+            vec![
+                "gitea_example_com_data".to_string(),
+                "gitea_example_com_postgres".to_string()
+            ]
+        );
+    }
+
+    #[test]
+    fn test_volume_mounts_without_mounts() {
+        let task_template = serde_json::json!({ "ContainerSpec": {} });
+        assert!(volume_mounts(&task_template).is_empty());
+    }
+}
@@ -76,6 +76,7 @@ mod tests {
             vec![
                 version_label("foobar", "1.2.3"),
                 stack_namespace_label("foobar"),
+                recipe_label("foobar", "my-recipe"),
             ]
             .into_iter()
             .collect(),
@@ -89,7 +90,12 @@ mod tests {
     #[test]
     fn test_build_has_no_version_if_not_specified() {
         let labels = CoopCloudServiceLabels::new(
-            vec![stack_namespace_label("foobar")].into_iter().collect(),
+            vec![
+                stack_namespace_label("foobar"),
+                recipe_label("foobar", "my-recipe"),
+            ]
+            .into_iter()
+            .collect(),
         )
         .unwrap();
 
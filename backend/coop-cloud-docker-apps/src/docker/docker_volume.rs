@@ -0,0 +1,94 @@
+use std::{path::Path, process::Command};
+
+/// Small image used to run `tar` against a volume.
+const HELPER_IMAGE: &str = "alpine:3";
+
+/// Writes the contents of `volume` to `archive_name` (a gzipped tarball) in `dir`.
+pub fn docker_volume_archive(
+    volume: &str,
+    dir: &Path,
+    archive_name: &str,
+) -> Result<(), anyhow::Error> {
+    run_docker(&[
+        "run".to_string(),
+        "--rm".to_string(),
+        "-v".to_string(),
+        format!("{volume}:/volume:ro"),
+        "-v".to_string(),
+        format!("{}:/backup", dir.display()),
+        HELPER_IMAGE.to_string(),
+        "tar".to_string(),
+        "czf".to_string(),
+        format!("/backup/{archive_name}"),
+        "-C".to_string(),
+        "/volume".to_string(),
+        ".".to_string(),
+    ])
+}
+
+/// Replaces the contents of `volume` with the tarball `archive_name` in `dir`.
+pub fn docker_volume_restore(
+    volume: &str,
+    dir: &Path,
+    archive_name: &str,
+) -> Result<(), anyhow::Error> {
+    run_docker(&[
+        "run".to_string(),
+        "--rm".to_string(),
+        "-v".to_string(),
+        format!("{volume}:/volume"),
+        "-v".to_string(),
+        format!("{}:/backup:ro", dir.display()),
+        HELPER_IMAGE.to_string(),
+        "sh".to_string(),
+        "-c".to_string(),
+        r#"find /volume -mindepth 1 -delete && tar xzf "$1" -C /volume"#.to_string(),
+        "sh".to_string(),
+        format!("/backup/{archive_name}"),
+    ])
+}
+
+/// Runs a shell command inside a running container of a swarm service.
+pub fn docker_service_exec(service_name: &str, command: &str) -> Result<(), anyhow::Error> {
+    let output = Command::new("docker")
+        .arg("ps")
+        .arg("-q")
+        .arg("--filter")
+        .arg(format!(
+            "label=com.docker.swarm.service.name={service_name}"
+        ))
+        .output()
+        .map_err(|e| anyhow::anyhow!("Failed to execute command: {}", e))?;
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let container_id = stdout
+        .lines()
+        .map(str::trim)
+        .find(|line| !line.is_empty())
+        .ok_or_else(|| anyhow::anyhow!("No running container for service: {}", service_name))?;
+
+    run_docker(&[
+        "exec".to_string(),
+        container_id.to_string(),
+        "sh".to_string(),
+        "-c".to_string(),
+        command.to_string(),
+    ])
+}
+
+fn run_docker(args: &[String]) -> Result<(), anyhow::Error> {
+    let output = Command::new("docker")
+        .args(args)
+        .output()
+        .map_err(|e| anyhow::anyhow!("Failed to execute command: {}", e))?;
+
+    if !output.status.success() {
+        return Err(anyhow::anyhow!(
+            "docker {} failed: {}",
+            args.first().map(String::as_str).unwrap_or(""),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+
+    Ok(())
+}
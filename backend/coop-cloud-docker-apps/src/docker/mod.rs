@@ -1,5 +1,6 @@
 pub mod docker_service;
 pub mod docker_stack;
+pub mod docker_volume;
 mod helpers;
 
 #[derive(Debug, Clone)]
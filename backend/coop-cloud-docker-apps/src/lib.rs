@@ -2,12 +2,19 @@ use tracing::warn;
 
 use crate::docker::docker_stack::docker_stack_ls;
 
+mod abra;
 mod apps;
+mod backup;
 mod coop_cloud_app;
 mod docker;
 mod service_labels;
 
+pub use abra::{Abra, AbraCommand, CommandRunner, LogLine, LogStream, ProcessCommandRunner};
 pub use apps::build_coop_cloud_app;
+pub use backup::{
+    AppBackupPlan, BackupConfig, ServiceBackup, app_backup_plan, backup_app, restore_app,
+    volume_archive_name,
+};
 pub use coop_cloud_app::{AppUrl, CoopCloudApp, LoResApp};
 pub use docker::{
     DockerService, DockerStack, DockerStackWithServices, docker_stacks_with_services,
@@ -0,0 +1,183 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use clap::Args;
+use serde::{Deserialize, Serialize};
+use tokio_stream::StreamExt as _;
+
+use crate::connect;
+use crate::payload::to_cbor;
+
+#[derive(Args)]
+pub struct BenchArgs {
+    /// Number of operations to publish
+    #[arg(long, default_value_t = 500)]
+    count: usize,
+
+    /// Bytes of padding in each payload
+    #[arg(long, default_value_t = 256)]
+    payload_size: usize,
+
+    /// Publishes in flight at once
+    #[arg(long, default_value_t = 1)]
+    concurrency: usize,
+
+    /// Node to receive the operations on. Defaults to --server.
+    #[arg(long)]
+    peer_server: Option<String>,
+
+    /// App token on the peer node. Defaults to --token.
+    #[arg(long)]
+    peer_token: Option<String>,
+
+    /// Instance of the app on the peer node. Defaults to --instance-id.
+    #[arg(long)]
+    peer_instance_id: Option<String>,
+
+    /// Seconds to wait for operations to reach the peer once publishing is done
+    #[arg(long, default_value_t = 30)]
+    timeout: u64,
+}
+
+/// Payload of a benchmark operation. Latency is measured against the sender's
+/// wall clock, so it is only meaningful between nodes on the same host or with
+/// synchronised clocks.
+#[derive(Serialize, Deserialize)]
+struct BenchPayload {
+    run: String,
+    seq: u64,
+    sent_at_micros: u64,
+    padding: String,
+}
+
+pub async fn run(
+    server: &str,
+    token: &str,
+    app_id: &str,
+    instance_id: &str,
+    args: BenchArgs,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let run_id = format!("{:x}", now_micros());
+    let peer_server = args.peer_server.as_deref().unwrap_or(server);
+    let peer_token = args.peer_token.as_deref().unwrap_or(token);
+    let peer_instance_id = args.peer_instance_id.as_deref().unwrap_or(instance_id);
+
+    // Subscribe before publishing so every operation can be counted.
+    let mut subscription = connect(peer_server, peer_token)?
+        .subscribe(app_id, peer_instance_id)
+        .await?
+        .into_inner();
+    let expected = args.count;
+    let received = Arc::new(Mutex::new(Vec::with_capacity(expected)));
+    let mut receiver = tokio::spawn({
+        let (run_id, received) = (run_id.clone(), received.clone());
+        async move {
+            while let Some(Ok(event)) = subscription.next().await {
+                let Ok(payload) = ciborium::from_reader::<BenchPayload, _>(&event.payload[..])
+                else {
+                    continue;
+                };
+                if payload.run != run_id {
+                    continue;
+                }
+                let micros = now_micros().saturating_sub(payload.sent_at_micros);
+                let mut received = received.lock().unwrap();
+                received.push(Duration::from_micros(micros));
+                if received.len() >= expected {
+                    return;
+                }
+            }
+        }
+    });
+
+    println!(
+        "publishing {} operations from {server} to {peer_server}...",
+        args.count
+    );
+    let next_seq = Arc::new(AtomicUsize::new(0));
+    let padding = "x".repeat(args.payload_size);
+    let started = Instant::now();
+    let mut workers = Vec::new();
+    for _ in 0..args.concurrency.max(1) {
+        let mut client = connect(server, token)?;
+        let (app_id, instance_id) = (app_id.to_string(), instance_id.to_string());
+        let (run_id, padding, next_seq) = (run_id.clone(), padding.clone(), next_seq.clone());
+        workers.push(tokio::spawn(async move {
+            let mut acks = Vec::new();
+            loop {
+                let seq = next_seq.fetch_add(1, Ordering::Relaxed);
+                if seq >= expected {
+                    return Ok::<_, String>(acks);
+                }
+                let payload = to_cbor(&BenchPayload {
+                    run: run_id.clone(),
+                    seq: seq as u64,
+                    sent_at_micros: now_micros(),
+                    padding: padding.clone(),
+                })?;
+                let sent = Instant::now();
+                client
+                    .publish(&app_id, &instance_id, payload, None)
+                    .await
+                    .map_err(|e| e.to_string())?;
+                acks.push(sent.elapsed());
+            }
+        }));
+    }
+
+    let mut acks = Vec::with_capacity(args.count);
+    for worker in workers {
+        acks.extend(worker.await??);
+    }
+    let elapsed = started.elapsed();
+    println!(
+        "published {} in {:.2}s ({:.1} ops/s)",
+        acks.len(),
+        elapsed.as_secs_f64(),
+        acks.len() as f64 / elapsed.as_secs_f64()
+    );
+    println!("publish ack latency: {}", summary(&mut acks));
+
+    if tokio::time::timeout(Duration::from_secs(args.timeout), &mut receiver)
+        .await
+        .is_err()
+    {
+        eprintln!("timed out after {}s waiting for operations", args.timeout);
+        receiver.abort();
+    }
+    let mut latencies = std::mem::take(&mut *received.lock().unwrap());
+    println!(
+        "received {} of {} on {peer_server}",
+        latencies.len(),
+        args.count
+    );
+    println!("end-to-end latency: {}", summary(&mut latencies));
+
+    Ok(())
+}
+
+fn summary(latencies: &mut [Duration]) -> String {
+    if latencies.is_empty() {
+        return "no samples".to_string();
+    }
+    latencies.sort();
+    let percentile = |p: f64| {
+        let index = ((latencies.len() - 1) as f64 * p).round() as usize;
+        latencies[index].as_secs_f64() * 1000.0
+    };
+    format!(
+        "p50 {:.1}ms, p95 {:.1}ms, p99 {:.1}ms, max {:.1}ms",
+        percentile(0.5),
+        percentile(0.95),
+        percentile(0.99),
+        percentile(1.0)
+    )
+}
+
+fn now_micros() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_micros() as u64
+}
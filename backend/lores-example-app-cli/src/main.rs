@@ -1,12 +1,19 @@
+use std::path::PathBuf;
+
 use clap::{Parser, Subcommand};
-use lores_p2panda_client::PandaClient;
-use serde::{Deserialize, Serialize};
+use lores_p2panda_client::proto::{Beginning, Frontier, OperationEvent};
+use lores_p2panda_client::{
+    ConnectionState, PandaClient, ReconnectPolicy, StartFrom, SubscriptionEvent, TypedEvent,
+    TypedPandaClient,
+};
+use serde::Serialize;
 use tokio::io::AsyncBufReadExt as _;
+use tokio_stream::StreamExt as _;
 
-#[derive(Debug, Serialize, Deserialize)]
-struct MessagePayload {
-    message: String,
-}
+mod bench;
+mod payload;
+mod replay;
+use payload::{MessagePayload, PayloadFormat};
 
 #[derive(Parser)]
 #[command(name = "lores-panda", about = "CLI for the lores p2panda gRPC server")]
@@ -15,28 +22,125 @@ struct Cli {
     #[arg(long, default_value = "http://localhost:50051")]
     server: String,
 
+    /// App token issued when this app was bound to a region. Falls back to the
+    /// LORES_APP_TOKEN environment variable.
+    #[arg(long)]
+    token: Option<String>,
+
+    /// App to publish and subscribe as
+    #[arg(long, default_value = APP_ID)]
+    app_id: String,
+
+    /// Instance of the app on this node. A real app would use the
+    /// `lores.instance_id` Docker service label value.
+    #[arg(long, default_value = INSTANCE_ID)]
+    instance_id: String,
+
     #[command(subcommand)]
     command: Command,
 }
 
 #[derive(Subcommand)]
 enum Command {
-    /// Publish a message to a region
+    /// Publish a payload to a region
     Publish {
-        /// Message to publish (comma-delimited string)
-        message: String,
+        /// Payload to publish, read according to --format
+        payload: String,
+
+        /// How to read the payload
+        #[arg(long, value_enum, default_value_t = PayloadFormat::Message)]
+        format: PayloadFormat,
+
+        /// Key the server uses to ignore the publish if it is repeated
+        #[arg(long)]
+        idempotency_key: Option<String>,
     },
 
+    /// Print operations as JSON lines, one per operation, until interrupted.
+    /// Connection changes are reported on stderr.
+    Subscribe {
+        /// How to show payloads
+        #[arg(long, value_enum, default_value_t = PayloadFormat::Message)]
+        format: PayloadFormat,
+
+        /// Replay every stored operation first
+        #[arg(long, conflicts_with_all = ["since", "after"])]
+        from_beginning: bool,
+
+        /// Replay stored operations from this Unix timestamp in milliseconds
+        #[arg(long, conflicts_with = "after")]
+        since: Option<u64>,
+
+        /// Replay stored operations after this hex-encoded operation id
+        #[arg(long)]
+        after: Option<String>,
+    },
+
+    /// Publish every non-empty line of a file as a payload, in order. Each line
+    /// is published with an idempotency key made from --key-prefix and its line
+    /// number, so replaying the same file again publishes only what's missing.
+    Replay {
+        file: PathBuf,
+
+        /// How to read each line
+        #[arg(long, value_enum, default_value_t = PayloadFormat::Message)]
+        format: PayloadFormat,
+
+        /// Start of each line's idempotency key. Defaults to the file name.
+        #[arg(long)]
+        key_prefix: Option<String>,
+
+        /// Lines published per call
+        #[arg(long, default_value_t = 100)]
+        batch_size: usize,
+    },
+
+    /// Measure publish throughput, and end-to-end latency from publishing on
+    /// --server to receiving on --peer-server. Publishes count against the
+    /// app's quota on the server.
+    Bench(bench::BenchArgs),
+
     /// Enter interactive live mode: publish messages line by line and print incoming messages.
     /// Press Ctrl+C to exit.
     Live,
 }
 
 const APP_ID: &str = "lores-example-app-cli";
-/// Fixed instance ID for this CLI tool. A real app would use the `lores.instance_id`
-/// Docker service label value.
 const INSTANCE_ID: &str = "test-instance";
 
+/// An operation printed by `subscribe`.
+#[derive(Serialize)]
+struct OperationLine {
+    operation_id: String,
+    author: String,
+    topic_id: String,
+    timestamp: u64,
+    /// The payload read according to --format, if it could be.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    payload: Option<serde_json::Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    decode_error: Option<String>,
+    payload_hex: String,
+}
+
+impl OperationLine {
+    fn new(event: OperationEvent, format: PayloadFormat) -> Self {
+        let (payload, decode_error) = match format.decode(&event.payload) {
+            Ok(value) => (Some(value), None),
+            Err(e) => (None, Some(e)),
+        };
+        Self {
+            operation_id: hex::encode(&event.operation_id),
+            author: hex::encode(&event.author),
+            topic_id: hex::encode(&event.topic_id),
+            timestamp: event.timestamp,
+            payload,
+            decode_error,
+            payload_hex: hex::encode(&event.payload),
+        }
+    }
+}
+
 #[tokio::main]
 async fn main() {
     let cli = Cli::parse();
@@ -49,45 +153,151 @@ async fn main() {
 
 async fn run(cli: Cli) -> Result<(), Box<dyn std::error::Error>> {
     let server = cli.server.clone();
+    let token = cli
+        .token
+        .clone()
+        .or_else(|| std::env::var("LORES_APP_TOKEN").ok())
+        .ok_or("an app token is required: pass --token or set LORES_APP_TOKEN")?;
+    let app_id = cli.app_id.clone();
+    let instance_id = cli.instance_id.clone();
     match cli.command {
-        Command::Publish { message } => {
-            let payload_struct = MessagePayload { message };
-            let mut payload_bytes: Vec<u8> = Vec::new();
-            ciborium::into_writer(&payload_struct, &mut payload_bytes)
-                .map_err(|e| format!("failed to encode payload as CBOR: {e}"))?;
+        Command::Publish {
+            payload,
+            format,
+            idempotency_key,
+        } => {
+            let mut client = connect(&server, &token)?;
+            let payload = format.encode(&payload)?;
 
-            let mut client = connect(&server)?;
+            let published = client
+                .publish(
+                    &app_id,
+                    &instance_id,
+                    payload,
+                    idempotency_key.map(String::into_bytes),
+                )
+                .await?
+                .into_inner();
 
-            client
-                .publish(APP_ID, INSTANCE_ID, payload_bytes, None)
-                .await?;
+            println!(
+                "published {} (seq {}){}",
+                hex::encode(&published.operation_id),
+                published.seq_num,
+                if published.duplicate {
+                    " - already published with this idempotency key"
+                } else {
+                    ""
+                }
+            );
+        }
+        Command::Subscribe {
+            format,
+            from_beginning,
+            since,
+            after,
+        } => {
+            let start_from = if from_beginning {
+                StartFrom::Beginning(Beginning {})
+            } else if let Some(timestamp) = since {
+                StartFrom::SinceTimestamp(timestamp)
+            } else if let Some(operation_id) = after {
+                StartFrom::AfterOperationId(hex::decode(operation_id)?)
+            } else {
+                StartFrom::Frontier(Frontier {})
+            };
+
+            let client = connect(&server, &token)?;
+            let mut stream = client.subscribe_reconnecting(
+                &app_id,
+                &instance_id,
+                start_from,
+                ReconnectPolicy::default(),
+            );
+
+            while let Some(item) = stream.next().await {
+                match item? {
+                    SubscriptionEvent::Operation(event) => {
+                        let line = OperationLine::new(event, format);
+                        println!("{}", serde_json::to_string(&line)?);
+                    }
+                    SubscriptionEvent::ConnectionState(ConnectionState::Connected) => {
+                        eprintln!("(connected)")
+                    }
+                    SubscriptionEvent::ConnectionState(ConnectionState::Disconnected {
+                        reason,
+                        retry_in,
+                        ..
+                    }) => {
+                        eprintln!("(disconnected: {reason}; retrying in {retry_in:?})")
+                    }
+                }
+            }
+        }
+        Command::Replay {
+            file,
+            format,
+            key_prefix,
+            batch_size,
+        } => {
+            let key_prefix = key_prefix.unwrap_or_else(|| {
+                file.file_name()
+                    .map(|name| name.to_string_lossy().into_owned())
+                    .unwrap_or_default()
+            });
+            let items = replay::read_lines(&file, format, &key_prefix).await?;
+
+            let mut client = connect(&server, &token)?;
+            let summary =
+                replay::publish(&mut client, &app_id, &instance_id, &items, batch_size).await?;
 
-            println!("published");
+            println!(
+                "published {}, skipped {} already published",
+                summary.published, summary.duplicates
+            );
+        }
+        Command::Bench(args) => {
+            bench::run(&server, &token, &app_id, &instance_id, args).await?;
         }
         Command::Live => {
             // Two separate connections: one for subscribe, one for publish.
-            let mut subscribe_client = connect(&server)?;
-            let mut publish_client = connect(&server)?;
+            let subscribe_client = connect(&server, &token)?;
+            let mut publish_client = connect_typed(&server, &token, &app_id, &instance_id)?;
 
-            let stream_response = subscribe_client.subscribe(APP_ID, INSTANCE_ID).await?;
-            let mut stream = stream_response.into_inner();
+            // Reconnects on its own if the server restarts, picking up after
+            // the last message printed.
+            let mut stream = subscribe_client.subscribe_reconnecting(
+                &app_id,
+                &instance_id,
+                StartFrom::Frontier(Frontier {}),
+                ReconnectPolicy::default(),
+            );
 
             // Spawn a task that prints every incoming operation.
             tokio::spawn(async move {
-                loop {
-                    match stream.message().await {
-                        Ok(Some(event)) => {
-                            let author = hex::encode(&event.author);
-                            match ciborium::from_reader::<MessagePayload, _>(
-                                event.payload.as_slice(),
-                            ) {
-                                Ok(p) => println!("[{}...] {}", &author[..8], p.message),
-                                Err(_) => {
+                while let Some(item) = stream.next().await {
+                    match item {
+                        Ok(SubscriptionEvent::Operation(event)) => {
+                            match TypedEvent::<MessagePayload>::decode(event) {
+                                TypedEvent::Decoded { meta, value } => {
+                                    let author = hex::encode(&meta.author);
+                                    println!("[{}...] {}", &author[..8], value.message)
+                                }
+                                TypedEvent::Undecodable { meta, .. } => {
+                                    let author = hex::encode(&meta.author);
                                     println!("[{}...] <unparseable payload>", &author[..8])
                                 }
                             }
                         }
-                        Ok(None) => break,
+                        Ok(SubscriptionEvent::ConnectionState(ConnectionState::Connected)) => {
+                            println!("(connected)")
+                        }
+                        Ok(SubscriptionEvent::ConnectionState(ConnectionState::Disconnected {
+                            reason,
+                            retry_in,
+                            ..
+                        })) => {
+                            eprintln!("(disconnected: {reason}; retrying in {retry_in:?})")
+                        }
                         Err(e) => {
                             eprintln!("subscription stream error: {e}");
                             break;
@@ -108,13 +318,8 @@ async fn run(cli: Cli) -> Result<(), Box<dyn std::error::Error>> {
                     line = lines.next_line() => {
                         match line? {
                             Some(text) if !text.trim().is_empty() => {
-                                let payload_struct = MessagePayload { message: text };
-                                let mut payload_bytes: Vec<u8> = Vec::new();
-                                ciborium::into_writer(&payload_struct, &mut payload_bytes)
-                                    .map_err(|e| format!("failed to encode payload as CBOR: {e}"))?;
-
                                 publish_client
-                                    .publish(APP_ID, INSTANCE_ID, payload_bytes, None)
+                                    .publish(&MessagePayload { message: text }, None)
                                     .await?;
                             }
                             Some(_) => {} // blank line, ignore
@@ -133,7 +338,20 @@ async fn run(cli: Cli) -> Result<(), Box<dyn std::error::Error>> {
     Ok(())
 }
 
-fn connect(server: &str) -> Result<PandaClient, Box<dyn std::error::Error>> {
-    PandaClient::connect_lazy(server.to_string())
+fn connect(server: &str, token: &str) -> Result<PandaClient, Box<dyn std::error::Error>> {
+    PandaClient::connect_lazy_uri(server, Some(token))
         .map_err(|e| format!("could not connect to gRPC server at {server}: {e}").into())
 }
+
+fn connect_typed(
+    server: &str,
+    token: &str,
+    app_id: &str,
+    instance_id: &str,
+) -> Result<TypedPandaClient<MessagePayload>, Box<dyn std::error::Error>> {
+    Ok(TypedPandaClient::new(
+        connect(server, token)?,
+        app_id,
+        instance_id,
+    ))
+}
@@ -0,0 +1,73 @@
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+
+/// The example app's own payload: a CBOR map with a `message` string.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MessagePayload {
+    pub message: String,
+}
+
+/// How payloads are read from the command line or a file, and shown when
+/// received.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum PayloadFormat {
+    /// Text wrapped as the example app's `{"message": ...}` CBOR map.
+    Message,
+    /// Text published as UTF-8 bytes, unchanged.
+    Raw,
+    /// JSON published as UTF-8 JSON text.
+    Json,
+    /// JSON converted to CBOR before publishing, and CBOR shown as JSON.
+    Cbor,
+}
+
+impl PayloadFormat {
+    /// Turns a payload given as text into the bytes to publish.
+    pub fn encode(self, input: &str) -> Result<Vec<u8>, String> {
+        match self {
+            PayloadFormat::Message => to_cbor(&MessagePayload {
+                message: input.to_string(),
+            }),
+            PayloadFormat::Raw => Ok(input.as_bytes().to_vec()),
+            PayloadFormat::Json => {
+                let value: serde_json::Value =
+                    serde_json::from_str(input).map_err(|e| format!("invalid JSON: {e}"))?;
+                Ok(value.to_string().into_bytes())
+            }
+            PayloadFormat::Cbor => {
+                let value: serde_json::Value =
+                    serde_json::from_str(input).map_err(|e| format!("invalid JSON: {e}"))?;
+                to_cbor(&value)
+            }
+        }
+    }
+
+    /// Turns received payload bytes into JSON for display. Raw payloads are
+    /// shown as a string when they are UTF-8.
+    pub fn decode(self, payload: &[u8]) -> Result<serde_json::Value, String> {
+        match self {
+            PayloadFormat::Message => {
+                let value: MessagePayload = ciborium::from_reader(payload)
+                    .map_err(|e| format!("not a message payload: {e}"))?;
+                Ok(serde_json::Value::String(value.message))
+            }
+            PayloadFormat::Raw => std::str::from_utf8(payload)
+                .map(|text| serde_json::Value::String(text.to_string()))
+                .map_err(|e| format!("not UTF-8: {e}")),
+            PayloadFormat::Json => {
+                serde_json::from_slice(payload).map_err(|e| format!("not JSON: {e}"))
+            }
+            PayloadFormat::Cbor => {
+                let value: ciborium::Value =
+                    ciborium::from_reader(payload).map_err(|e| format!("not CBOR: {e}"))?;
+                serde_json::to_value(&value).map_err(|e| format!("CBOR has no JSON form: {e}"))
+            }
+        }
+    }
+}
+
+pub fn to_cbor<T: Serialize>(value: &T) -> Result<Vec<u8>, String> {
+    let mut payload = Vec::new();
+    ciborium::into_writer(value, &mut payload).map_err(|e| e.to_string())?;
+    Ok(payload)
+}
@@ -0,0 +1,243 @@
+use std::path::Path;
+
+use lores_p2panda_client::PandaClient;
+
+use crate::payload::PayloadFormat;
+
+/// A payload to publish and its idempotency key.
+pub type Item = (Vec<u8>, Option<Vec<u8>>);
+
+/// How many lines a replay published, and how many the server had already
+/// published under the same keys.
+#[derive(Debug, Default, PartialEq)]
+pub struct ReplaySummary {
+    pub published: usize,
+    pub duplicates: usize,
+}
+
+/// Reads every non-empty line of `file` before anything is published, so a
+/// bad line doesn't leave the file half published. Each line's key is
+/// `key_prefix` followed by its line number.
+pub async fn read_lines(
+    file: &Path,
+    format: PayloadFormat,
+    key_prefix: &str,
+) -> Result<Vec<Item>, Box<dyn std::error::Error>> {
+    let contents = tokio::fs::read_to_string(file).await?;
+
+    let mut items = Vec::new();
+    for (index, line) in contents.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let line_number = index + 1;
+        let payload = format
+            .encode(line)
+            .map_err(|e| format!("line {line_number}: {e}"))?;
+        let key = format!("{key_prefix}:{line_number}").into_bytes();
+        items.push((payload, Some(key)));
+    }
+    Ok(items)
+}
+
+/// Publishes `items` in batches of `batch_size`, stopping at the first failed
+/// batch. The server skips items whose key it has already seen, so running
+/// the same items again after a failure publishes only the rest.
+pub async fn publish(
+    client: &mut PandaClient,
+    app_id: &str,
+    instance_id: &str,
+    items: &[Item],
+    batch_size: usize,
+) -> Result<ReplaySummary, Box<dyn std::error::Error>> {
+    let mut summary = ReplaySummary::default();
+    for batch in items.chunks(batch_size.max(1)) {
+        let response = client
+            .publish_batch(app_id, instance_id, batch.to_vec())
+            .await?
+            .into_inner();
+        for result in response.results {
+            if result.duplicate {
+                summary.duplicates += 1;
+            } else {
+                summary.published += 1;
+            }
+        }
+    }
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+    use std::sync::{Arc, Mutex};
+
+    use lores_p2panda_client::proto::panda_server::{Panda, PandaServer};
+    use lores_p2panda_client::proto::*;
+    use tokio::net::TcpListener;
+    use tokio_stream::wrappers::TcpListenerStream;
+    use tonic::{Request, Response, Status};
+
+    use super::*;
+
+    /// Publishes batch items and records their keys the way the server does,
+    /// failing at the item numbered `fail_at` (counting every item it has been
+    /// sent) if set.
+    #[derive(Default)]
+    struct FakePanda {
+        fail_at: Option<usize>,
+        received: Mutex<usize>,
+        keys: Mutex<HashSet<Vec<u8>>>,
+        published: Mutex<Vec<Vec<u8>>>,
+    }
+
+    #[tonic::async_trait]
+    impl Panda for FakePanda {
+        async fn publish(
+            &self,
+            _: Request<PublishRequest>,
+        ) -> Result<Response<PublishResponse>, Status> {
+            Err(Status::unimplemented(""))
+        }
+
+        async fn publish_batch(
+            &self,
+            request: Request<PublishBatchRequest>,
+        ) -> Result<Response<PublishBatchResponse>, Status> {
+            let mut results = Vec::new();
+            for item in request.into_inner().items {
+                let mut received = self.received.lock().unwrap();
+                *received += 1;
+                if self.keys.lock().unwrap().contains(&item.idempotency_key) {
+                    results.push(PublishResponse {
+                        duplicate: true,
+                        ..Default::default()
+                    });
+                    continue;
+                }
+                if self.fail_at == Some(*received) {
+                    return Err(Status::unavailable("publish failed"));
+                }
+                self.keys.lock().unwrap().insert(item.idempotency_key);
+                self.published.lock().unwrap().push(item.payload);
+                results.push(PublishResponse::default());
+            }
+            Ok(Response::new(PublishBatchResponse { results }))
+        }
+
+        type SubscribeStream = tokio_stream::Empty<Result<OperationEvent, Status>>;
+
+        async fn subscribe(
+            &self,
+            _: Request<SubscribeRequest>,
+        ) -> Result<Response<Self::SubscribeStream>, Status> {
+            Err(Status::unimplemented(""))
+        }
+
+        async fn list_operations(
+            &self,
+            _: Request<ListOperationsRequest>,
+        ) -> Result<Response<ListOperationsResponse>, Status> {
+            Err(Status::unimplemented(""))
+        }
+
+        async fn get_operation(
+            &self,
+            _: Request<GetOperationRequest>,
+        ) -> Result<Response<GetOperationResponse>, Status> {
+            Err(Status::unimplemented(""))
+        }
+
+        async fn broadcast(
+            &self,
+            _: Request<BroadcastRequest>,
+        ) -> Result<Response<BroadcastResponse>, Status> {
+            Err(Status::unimplemented(""))
+        }
+
+        type SubscribeEphemeralStream = tokio_stream::Empty<Result<EphemeralEvent, Status>>;
+
+        async fn subscribe_ephemeral(
+            &self,
+            _: Request<SubscribeEphemeralRequest>,
+        ) -> Result<Response<Self::SubscribeEphemeralStream>, Status> {
+            Err(Status::unimplemented(""))
+        }
+
+        async fn list_peers(
+            &self,
+            _: Request<ListPeersRequest>,
+        ) -> Result<Response<ListPeersResponse>, Status> {
+            Err(Status::unimplemented(""))
+        }
+
+        async fn register_schema(
+            &self,
+            _: Request<RegisterSchemaRequest>,
+        ) -> Result<Response<RegisterSchemaResponse>, Status> {
+            Err(Status::unimplemented(""))
+        }
+    }
+
+    async fn fake_client(fake: Arc<FakePanda>) -> PandaClient {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = tonic::transport::Server::builder()
+            .add_service(PandaServer::from_arc(fake))
+            .serve_with_incoming(TcpListenerStream::new(listener));
+        tokio::spawn(server);
+
+        PandaClient::connect(format!("http://{addr}"))
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_replaying_again_after_a_failure_publishes_the_rest() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("lines.txt");
+        tokio::fs::write(&file, "one\ntwo\n\nthree\nfour\nfive\n")
+            .await
+            .unwrap();
+        let items = read_lines(&file, PayloadFormat::Raw, "lines.txt")
+            .await
+            .unwrap();
+        assert_eq!(items.len(), 5);
+
+        // The second batch fails after publishing its first item.
+        let fake = Arc::new(FakePanda {
+            fail_at: Some(4),
+            ..Default::default()
+        });
+        let mut client = fake_client(fake.clone()).await;
+        assert!(
+            publish(&mut client, "app", "instance", &items, 2)
+                .await
+                .is_err()
+        );
+        assert_eq!(fake.published.lock().unwrap().len(), 3);
+
+        let summary = publish(&mut client, "app", "instance", &items, 2)
+            .await
+            .unwrap();
+        assert_eq!(
+            summary,
+            ReplaySummary {
+                published: 2,
+                duplicates: 3,
+            }
+        );
+        let published = fake.published.lock().unwrap();
+        let published: Vec<&[u8]> = published.iter().map(|payload| payload.as_slice()).collect();
+        assert_eq!(
+            published,
+            vec![
+                b"one".as_slice(),
+                b"two".as_slice(),
+                b"three".as_slice(),
+                b"four".as_slice(),
+                b"five".as_slice()
+            ]
+        );
+    }
+}
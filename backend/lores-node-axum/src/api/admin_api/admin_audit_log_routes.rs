@@ -0,0 +1,90 @@
+use axum::{Extension, Json, extract::Query, http::StatusCode, response::IntoResponse};
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+use utoipa_axum::{router::OpenApiRouter, routes};
+
+use crate::{
+    DatabaseState,
+    api::helpers::internal_server_error,
+    data::node_data::audit_log_repo::{AuditActor, AuditLogEntry, AuditLogFilter, AuditLogRepo},
+};
+
+const DEFAULT_PAGE_SIZE: u32 = 50;
+const MAX_PAGE_SIZE: u32 = 500;
+
+pub fn router() -> OpenApiRouter {
+    OpenApiRouter::new().routes(routes!(list_audit_log))
+}
+
+#[derive(Deserialize, IntoParams, Debug)]
+#[into_params(parameter_in = Query)]
+pub struct AuditLogQuery {
+    /// Return entries older than this one, to page back through the log
+    pub before_id: Option<i64>,
+    /// Entries per page, up to 500. Defaults to 50.
+    pub limit: Option<u32>,
+    pub actor: Option<AuditActor>,
+    pub node_steward_id: Option<String>,
+    /// HTTP method, or `CLI` for admin commands
+    pub method: Option<String>,
+    pub path_prefix: Option<String>,
+    /// Unix timestamp; only entries at or after it
+    pub since: Option<i64>,
+    /// Unix timestamp; only entries before it
+    pub until: Option<i64>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct AuditLogPage {
+    pub entries: Vec<AuditLogEntry>,
+    /// Pass as `before_id` to get the next page. Not set on the last page.
+    pub next_before_id: Option<i64>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/",
+    params(AuditLogQuery),
+    responses(
+        (status = OK, body = AuditLogPage),
+        (status = INTERNAL_SERVER_ERROR, body = String),
+    ),
+)]
+async fn list_audit_log(
+    Extension(db): Extension<DatabaseState>,
+    Query(query): Query<AuditLogQuery>,
+) -> impl IntoResponse {
+    let limit = query
+        .limit
+        .unwrap_or(DEFAULT_PAGE_SIZE)
+        .clamp(1, MAX_PAGE_SIZE);
+    let filter = AuditLogFilter {
+        actor: query.actor,
+        node_steward_id: query.node_steward_id,
+        method: query.method.map(|method| method.to_uppercase()),
+        path_prefix: query.path_prefix,
+        since: query.since,
+        until: query.until,
+    };
+
+    match AuditLogRepo::init()
+        .page(&db.node_data_pool, &filter, query.before_id, limit)
+        .await
+    {
+        Ok(entries) => {
+            let next_before_id = match entries.len() as u32 == limit {
+                true => entries.last().map(|entry| entry.id),
+                false => None,
+            };
+            (
+                StatusCode::OK,
+                Json(AuditLogPage {
+                    entries,
+                    next_before_id,
+                }),
+            )
+                .into_response()
+        }
+        Err(e) => internal_server_error(e).into_response(),
+    }
+}
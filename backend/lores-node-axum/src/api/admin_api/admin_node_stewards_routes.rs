@@ -4,14 +4,18 @@ use axum::{
     http::StatusCode,
     response::IntoResponse,
 };
-use serde::{Deserialize, Serialize};
+use serde::Deserialize;
 use tracing::warn;
 use utoipa::ToSchema;
 use utoipa_axum::{router::OpenApiRouter, routes};
 
 use crate::{
     DatabaseState,
-    data::node_data::node_stewards::{NodeStewardIdentifier, NodeStewardRow, NodeStewardsRepo},
+    data::node_data::node_stewards::NodeStewardRole,
+    node_admin::{
+        NodeAdminError,
+        node_stewards::{self, NodeSteward, NodeStewardCreationResult},
+    },
 };
 
 pub fn router() -> OpenApiRouter {
@@ -21,44 +25,7 @@ pub fn router() -> OpenApiRouter {
         .routes(routes!(reset_node_steward_token))
         .routes(routes!(disable_node_steward))
         .routes(routes!(enable_node_steward))
-}
-
-#[derive(Serialize, ToSchema)]
-pub enum NodeStewardStatus {
-    Enabled,
-    Disabled,
-    Invited,
-    TokenExpired,
-}
-
-#[derive(Serialize, ToSchema)]
-pub struct NodeSteward {
-    pub id: String,
-    pub name: String,
-    pub created_at: String,
-    pub status: NodeStewardStatus,
-}
-
-impl NodeSteward {
-    pub fn from_row(row: &NodeStewardRow) -> Self {
-        let mut status: NodeStewardStatus = match row.enabled {
-            true => NodeStewardStatus::Enabled,
-            false => NodeStewardStatus::Disabled,
-        };
-        if row.hashed_password.is_none() {
-            match row.token_expired() {
-                true => status = NodeStewardStatus::TokenExpired,
-                false => status = NodeStewardStatus::Invited,
-            }
-        }
-
-        NodeSteward {
-            id: row.id.clone(),
-            name: row.name.clone(),
-            created_at: row.created_at.unwrap_or_default().to_string(),
-            status,
-        }
-    }
+        .routes(routes!(set_node_steward_roles))
 }
 
 #[utoipa::path(get, path = "/",
@@ -68,16 +35,8 @@ impl NodeSteward {
     ),
 )]
 async fn list_node_stewards(Extension(db): Extension<DatabaseState>) -> impl IntoResponse {
-    let repo = NodeStewardsRepo::init();
-
-    match repo.all(&db.node_data_pool).await {
-        Ok(stewards) => {
-            let results: Vec<NodeSteward> = stewards
-                .into_iter()
-                .map(|steward| NodeSteward::from_row(&steward))
-                .collect();
-            (StatusCode::OK, Json(results)).into_response()
-        }
+    match node_stewards::list(&db.node_data_pool).await {
+        Ok(results) => (StatusCode::OK, Json(results)).into_response(),
         Err(_) => (StatusCode::INTERNAL_SERVER_ERROR, ()).into_response(),
     }
 }
@@ -85,12 +44,8 @@ async fn list_node_stewards(Extension(db): Extension<DatabaseState>) -> impl Int
 #[derive(Deserialize, ToSchema)]
 pub struct NodeStewardCreationData {
     pub name: String,
-}
-
-#[derive(Serialize, ToSchema)]
-pub struct NodeStewardCreationResult {
-    pub node_steward: NodeSteward,
-    pub password_reset_token: String,
+    /// Defaults to every role
+    pub roles: Option<Vec<NodeStewardRole>>,
 }
 
 #[utoipa::path(post, path = "/",
@@ -104,20 +59,8 @@ async fn create_node_steward(
     Extension(db): Extension<DatabaseState>,
     extract::Json(input): extract::Json<NodeStewardCreationData>,
 ) -> impl IntoResponse {
-    let mut new_row = NodeStewardRow::new(input.name);
-    new_row.set_password_reset_token();
-
-    let repo = NodeStewardsRepo::init();
-    let result = repo.create(&db.node_data_pool, &new_row).await;
-
-    match result {
-        Ok(_) => {
-            let creation_result = NodeStewardCreationResult {
-                node_steward: NodeSteward::from_row(&new_row),
-                password_reset_token: new_row.password_reset_token.unwrap_or_default(),
-            };
-            (StatusCode::CREATED, Json(creation_result)).into_response()
-        }
+    match node_stewards::create(&db.node_data_pool, input.name, input.roles).await {
+        Ok(creation_result) => (StatusCode::CREATED, Json(creation_result)).into_response(),
         Err(_) => (
             StatusCode::INTERNAL_SERVER_ERROR,
             "server error".to_string(),
@@ -142,35 +85,9 @@ async fn reset_node_steward_token(
     Extension(db): Extension<DatabaseState>,
     Path(steward_id): Path<String>,
 ) -> impl IntoResponse {
-    let repo = NodeStewardsRepo::init();
-    let identifier = NodeStewardIdentifier { id: steward_id };
-
-    let mut row = match repo.find(&db.node_data_pool, &identifier).await {
-        Ok(Some(row)) => row,
-        Ok(None) => return (StatusCode::NOT_FOUND, ()).into_response(),
-        Err(e) => {
-            warn!("Error finding node steward: {:?}", e);
-            return (StatusCode::INTERNAL_SERVER_ERROR, ()).into_response();
-        }
-    };
-    row.set_password_reset_token();
-
-    let result = repo
-        .update_password_reset_token(&db.node_data_pool, &row)
-        .await;
-
-    match result {
-        Ok(_) => {
-            let creation_result = NodeStewardCreationResult {
-                node_steward: NodeSteward::from_row(&row),
-                password_reset_token: row.password_reset_token.unwrap_or_default(),
-            };
-            (StatusCode::OK, Json(creation_result)).into_response()
-        }
-        Err(e) => {
-            warn!("Error saving reset token: {:?}", e);
-            (StatusCode::INTERNAL_SERVER_ERROR, ()).into_response()
-        }
+    match node_stewards::reset_token(&db.node_data_pool, steward_id).await {
+        Ok(creation_result) => (StatusCode::OK, Json(creation_result)).into_response(),
+        Err(e) => error_response(e),
     }
 }
 
@@ -212,28 +129,51 @@ async fn enable_node_steward(
     toggle_node_steward_status(db, steward_id, true).await
 }
 
+#[derive(Deserialize, ToSchema)]
+pub struct NodeStewardRolesData {
+    pub roles: Vec<NodeStewardRole>,
+}
+
+#[utoipa::path(
+    put,
+    path = "/roles/{steward_id}",
+    params(
+        ("steward_id" = String, Path),
+    ),
+    request_body(content = NodeStewardRolesData, content_type = "application/json"),
+    responses(
+        (status = OK, body = NodeSteward),
+        (status = NOT_FOUND, body = ()),
+        (status = INTERNAL_SERVER_ERROR, body = ()),
+    ),
+)]
+async fn set_node_steward_roles(
+    Extension(db): Extension<DatabaseState>,
+    Path(steward_id): Path<String>,
+    extract::Json(input): extract::Json<NodeStewardRolesData>,
+) -> impl IntoResponse {
+    match node_stewards::set_roles(&db.node_data_pool, steward_id, &input.roles).await {
+        Ok(steward) => (StatusCode::OK, Json(steward)).into_response(),
+        Err(e) => error_response(e),
+    }
+}
+
 async fn toggle_node_steward_status(
     db: DatabaseState,
     steward_id: String,
     enabled: bool,
 ) -> impl IntoResponse {
-    let repo = NodeStewardsRepo::init();
-    let identifier = NodeStewardIdentifier { id: steward_id };
-
-    let result = repo
-        .update_enabled(&db.node_data_pool, &identifier, enabled)
-        .await;
-
-    if let Err(e) = result {
-        warn!("Error updating node steward status: {:?}", e);
-        return (StatusCode::INTERNAL_SERVER_ERROR, ()).into_response();
+    match node_stewards::set_enabled(&db.node_data_pool, steward_id, enabled).await {
+        Ok(steward) => (StatusCode::OK, Json(steward)).into_response(),
+        Err(e) => error_response(e),
     }
+}
 
-    match repo.find(&db.node_data_pool, &identifier).await {
-        Ok(Some(row)) => (StatusCode::OK, Json(NodeSteward::from_row(&row))).into_response(),
-        Ok(None) => (StatusCode::NOT_FOUND, ()).into_response(),
-        Err(e) => {
-            warn!("Error finding node steward: {:?}", e);
+fn error_response(error: NodeAdminError) -> axum::response::Response {
+    match error {
+        NodeAdminError::NotFound(_) => (StatusCode::NOT_FOUND, ()).into_response(),
+        e => {
+            warn!("Error updating node steward: {:?}", e);
             (StatusCode::INTERNAL_SERVER_ERROR, ()).into_response()
         }
     }
@@ -1,7 +1,10 @@
 use utoipa_axum::router::OpenApiRouter;
 
+mod admin_audit_log_routes;
 mod admin_node_stewards_routes;
 
 pub fn admin_api_router() -> OpenApiRouter {
-    OpenApiRouter::new().nest("/node_stewards", admin_node_stewards_routes::router())
+    OpenApiRouter::new()
+        .nest("/node_stewards", admin_node_stewards_routes::router())
+        .nest("/audit_log", admin_audit_log_routes::router())
 }
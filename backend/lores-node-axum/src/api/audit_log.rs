@@ -0,0 +1,147 @@
+use axum::{Extension, extract::Request, http::Method, middleware::Next, response::Response};
+use tracing::warn;
+
+use crate::{
+    DatabaseState,
+    api::auth_api::auth_backend::AuthSession,
+    data::node_data::audit_log_repo::{AuditActor, AuditLogRepo, NewAuditLogEntry},
+};
+
+/// Records every request that can change something, with who made it and
+/// how it went, in the audit log. Request bodies aren't recorded, so
+/// passwords and tokens never end up in the log. Anonymous requests aren't
+/// recorded either: they can't change anything, and anyone could fill the
+/// log with them.
+pub async fn audit_log(
+    Extension(db): Extension<DatabaseState>,
+    auth_session: AuthSession,
+    request: Request,
+    next: Next,
+) -> Response {
+    let method = request.method().clone();
+    let Some(user) = auth_session.user else {
+        return next.run(request).await;
+    };
+    if matches!(method, Method::GET | Method::HEAD | Method::OPTIONS) {
+        return next.run(request).await;
+    }
+    let path = request.uri().path().to_string();
+
+    let response = next.run(request).await;
+
+    let (actor, node_steward_id, api_token_id) = if user.is_node_steward() {
+        (AuditActor::NodeSteward, Some(user.id), user.api_token_id)
+    } else {
+        (AuditActor::Admin, None, None)
+    };
+    let entry = NewAuditLogEntry {
+        actor,
+        node_steward_id,
+        api_token_id,
+        method: method.to_string(),
+        path,
+        status: Some(response.status().as_u16().into()),
+    };
+    if let Err(e) = AuditLogRepo::init()
+        .append(&db.node_data_pool, &entry)
+        .await
+    {
+        warn!("Failed to write audit log entry: {:?}", e);
+    }
+
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::{Method, StatusCode};
+
+    use crate::{
+        api::test_helpers::{TestApp, request, with_token},
+        data::node_data::{
+            api_tokens_repo::ApiTokensRepo,
+            audit_log_repo::{AuditLogEntry, AuditLogFilter},
+            node_stewards::NodeStewardRole,
+        },
+    };
+
+    use super::*;
+
+    const PATH: &str = "/node_steward_api/app_backups/apps/gitea";
+
+    async fn entries(app: &TestApp) -> Vec<AuditLogEntry> {
+        AuditLogRepo::init()
+            .page(&app.node_data_pool, &AuditLogFilter::default(), None, 100)
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_changes_are_recorded_with_who_made_them() {
+        let app = TestApp::new().await;
+        let steward_id = app.steward(&[NodeStewardRole::Viewer]).await;
+        let (api_token, token) = ApiTokensRepo::init()
+            .create(&app.node_data_pool, &steward_id, "script", &[], None)
+            .await
+            .unwrap();
+
+        let response = app
+            .send(with_token(request(Method::POST, PATH), &token), None)
+            .await;
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+        let entries = entries(&app).await;
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].actor, AuditActor::NodeSteward);
+        assert_eq!(
+            entries[0].node_steward_id.as_deref(),
+            Some(steward_id.as_str())
+        );
+        assert_eq!(
+            entries[0].api_token_id.as_deref(),
+            Some(api_token.id.as_str())
+        );
+        assert_eq!(entries[0].method, "POST");
+        assert_eq!(entries[0].path, PATH);
+        assert_eq!(entries[0].status, Some(403));
+    }
+
+    #[tokio::test]
+    async fn test_anonymous_requests_are_not_recorded() {
+        let app = TestApp::new().await;
+
+        let response = app.send(request(Method::POST, PATH), None).await;
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        let body = serde_json::json!({ "id": "nobody", "password": "wrong" });
+        app.send(
+            request(Method::POST, "/auth_api/node_steward/login"),
+            Some(body),
+        )
+        .await;
+
+        assert!(entries(&app).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_reads_are_not_recorded() {
+        let app = TestApp::new().await;
+        let steward_id = app.steward(&[NodeStewardRole::Viewer]).await;
+        let (_, token) = ApiTokensRepo::init()
+            .create(
+                &app.node_data_pool,
+                &steward_id,
+                "script",
+                &[NodeStewardRole::Viewer],
+                None,
+            )
+            .await
+            .unwrap();
+
+        let response = app
+            .send(with_token(request(Method::GET, PATH), &token), None)
+            .await;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        assert!(entries(&app).await.is_empty());
+    }
+}
@@ -113,5 +113,5 @@ async fn admin_login(
             .into_response();
     }
 
-    return (StatusCode::OK, Json(UserRef::from_backend_user(&user))).into_response();
+    (StatusCode::OK, Json(UserRef::from_backend_user(&user))).into_response()
 }
@@ -42,7 +42,7 @@ impl AdminUserRepo {
                 GeneratePasswordError::ServerError
             })?;
 
-        return Ok(password);
+        Ok(password)
     }
 
     pub async fn get_hashed_password(&self) -> Option<String> {
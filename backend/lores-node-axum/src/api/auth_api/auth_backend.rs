@@ -12,7 +12,10 @@ use utoipa::ToSchema;
 
 use crate::{
     config::config_state::LoresNodeConfigState,
-    data::node_data::node_stewards::{NodeStewardIdentifier, NodeStewardsRepo},
+    data::node_data::{
+        api_tokens_repo::ApiTokensRepo,
+        node_stewards::{NodeStewardIdentifier, NodeStewardRole, NodeStewardsRepo},
+    },
 };
 
 use super::admin_user_repo::AdminUserRepo;
@@ -23,6 +26,11 @@ const ADMIN_USER_ID: &str = "admin";
 pub struct User {
     pub id: String,
     password_hash: String,
+    /// Empty for the admin user, whose access doesn't depend on roles.
+    pub roles: Vec<NodeStewardRole>,
+    /// Set when the steward authenticated with an API token rather than
+    /// logging in.
+    pub api_token_id: Option<String>,
 }
 
 impl AuthUser for User {
@@ -61,6 +69,8 @@ pub struct NodeStewardCredentials {
 pub enum Credentials {
     Admin(AdminCredentials),
     NodeSteward(NodeStewardCredentials),
+    /// An API token from an `Authorization: Bearer` header
+    ApiToken(String),
 }
 
 #[derive(Debug, Clone)]
@@ -109,14 +119,17 @@ impl AuthnBackend for AppAuthBackend {
             Self::Credentials::NodeSteward(node_steward_creds) => {
                 self.authenticate_steward_user(&node_steward_creds).await
             }
+            Self::Credentials::ApiToken(token) => self.authenticate_api_token(&token).await,
         }
     }
 
     async fn get_user(&self, user_id: &UserId<Self>) -> Result<Option<Self::User>, Self::Error> {
-        if user_id.to_string() == ADMIN_USER_ID.to_string() {
+        if *user_id == ADMIN_USER_ID {
             let user = Self::User {
                 id: ADMIN_USER_ID.to_string(),
                 password_hash: self.expect_hashed_password().await?,
+                roles: vec![],
+                api_token_id: None,
             };
 
             return Ok(Some(user));
@@ -141,6 +154,8 @@ impl AppAuthBackend {
         Ok(Some(User {
             id: ADMIN_USER_ID.into(),
             password_hash: hashed_password,
+            roles: vec![],
+            api_token_id: None,
         }))
     }
 
@@ -187,9 +202,74 @@ impl AppAuthBackend {
         Ok(Some(User {
             id: steward.id.clone(),
             password_hash: hashed_password,
+            roles: steward.roles(),
+            api_token_id: None,
         }))
     }
 
+    async fn authenticate_api_token(&self, token: &str) -> Result<Option<User>, AuthError> {
+        let api_token = match ApiTokensRepo::init()
+            .find_usable(&self.node_data_pool, token)
+            .await
+        {
+            Ok(Some(api_token)) => api_token,
+            Ok(None) => {
+                warn!("AUTH FAILED: API token not found, revoked or expired");
+                return Err(AuthError::InvalidCredentials);
+            }
+            Err(e) => {
+                warn!("AUTH FAILED: Failed to find API token: {:?}", e);
+                return Err(AuthError::ServerError);
+            }
+        };
+
+        let repo = NodeStewardsRepo::init();
+        let id = NodeStewardIdentifier {
+            id: api_token.node_steward_id.clone(),
+        };
+        let steward = match repo.find(&self.node_data_pool, &id).await {
+            Ok(Some(steward)) => steward,
+            Ok(None) => {
+                warn!("AUTH FAILED: API token's node steward not found");
+                return Err(AuthError::UserNotFound);
+            }
+            Err(e) => {
+                warn!("AUTH FAILED: Failed to find node steward: {:?}", e);
+                return Err(AuthError::ServerError);
+            }
+        };
+
+        if !steward.enabled {
+            warn!("AUTH FAILED: The API token's node steward account is disabled");
+            return Err(AuthError::AccountDisabled);
+        }
+
+        // A token can't do more than its steward currently can.
+        let steward_roles = steward.roles();
+        let roles = api_token
+            .scopes
+            .into_iter()
+            .filter(|role| steward_roles.contains(role))
+            .collect();
+
+        Ok(Some(User {
+            id: steward.id.clone(),
+            password_hash: steward.hashed_password.unwrap_or_default(),
+            roles,
+            api_token_id: Some(api_token.id),
+        }))
+    }
+
+    /// Adds a request made with an API token to the token's audit trail.
+    pub async fn record_api_token_use(&self, token_id: &str, method: &str, path: &str) {
+        if let Err(e) = ApiTokensRepo::init()
+            .record_use(&self.node_data_pool, token_id, method, path)
+            .await
+        {
+            warn!("Failed to record API token use: {:?}", e);
+        }
+    }
+
     async fn get_steward_user(&self, user_id: &UserId<Self>) -> Result<Option<User>, AuthError> {
         let repo = NodeStewardsRepo::init();
 
@@ -218,8 +298,10 @@ impl AppAuthBackend {
             Some(password_hash) => Ok(Some(User {
                 id: steward.id.clone(),
                 password_hash,
+                roles: steward.roles(),
+                api_token_id: None,
             })),
-            None => return Err(AuthError::NoPasswordSet),
+            None => Err(AuthError::NoPasswordSet),
         }
     }
 
@@ -275,10 +357,19 @@ impl AuthzBackend for AppAuthBackend {
     ) -> Result<HashSet<Self::Permission>, Self::Error> {
         let mut perms = HashSet::new();
 
-        if user.id == ADMIN_USER_ID.to_string() {
+        if user.id == ADMIN_USER_ID {
             perms.insert(Permission::from("admin"));
         } else {
-            perms.insert(Permission::from("steward"));
+            // Stewards manage their account and tokens only when logged in,
+            // so a leaked token can't be used to mint more.
+            if user.api_token_id.is_none() {
+                perms.insert(Permission::from("steward"));
+            }
+            for role in &user.roles {
+                perms.insert(Permission::from(role.as_str()));
+                // Managing apps or regions means being able to see them too.
+                perms.insert(Permission::from(NodeStewardRole::Viewer.as_str()));
+            }
         }
 
         return Ok(perms);
@@ -289,3 +380,53 @@ impl AuthzBackend for AppAuthBackend {
 //
 // Note that we've supplied our concrete backend here.
 pub type AuthSession = axum_login::AuthSession<AppAuthBackend>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::config::LoresNodeConfig;
+
+    fn backend() -> AppAuthBackend {
+        let config_state = LoresNodeConfigState::new(&LoresNodeConfig::default());
+        let pool = SqlitePool::connect_lazy("sqlite::memory:").unwrap();
+        AppAuthBackend::new(&config_state, &pool)
+    }
+
+    fn steward(roles: Vec<NodeStewardRole>) -> User {
+        User {
+            id: "steward".to_string(),
+            password_hash: String::new(),
+            roles,
+            api_token_id: None,
+        }
+    }
+
+    async fn permissions(user: &User) -> Vec<String> {
+        let mut names: Vec<String> = backend()
+            .get_group_permissions(user)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|permission| permission.name)
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[tokio::test]
+    async fn test_managers_can_also_view() {
+        assert_eq!(
+            permissions(&steward(vec![NodeStewardRole::AppManager])).await,
+            vec!["app-manager", "steward", "viewer"]
+        );
+        assert_eq!(
+            permissions(&steward(vec![NodeStewardRole::RegionManager])).await,
+            vec!["region-manager", "steward", "viewer"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_steward_without_roles_can_only_manage_their_account() {
+        assert_eq!(permissions(&steward(vec![])).await, vec!["steward"]);
+    }
+}
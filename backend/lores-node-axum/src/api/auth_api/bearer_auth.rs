@@ -0,0 +1,196 @@
+use axum::{
+    extract::Request,
+    http::{StatusCode, header},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use axum_login::AuthnBackend;
+
+use super::auth_backend::{AuthSession, Credentials};
+
+/// Lets scripts authenticate with an API token in an `Authorization: Bearer`
+/// header instead of a session cookie. The token's steward is only set as the
+/// user for this request, so nothing is written to the session.
+///
+/// Requests with an invalid, revoked or expired token are refused rather than
+/// treated as anonymous, so a broken script fails loudly.
+pub async fn bearer_auth(
+    mut auth_session: AuthSession,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    let Some(token) = bearer_token(&request) else {
+        return next.run(request).await;
+    };
+
+    let user = match auth_session
+        .backend
+        .authenticate(Credentials::ApiToken(token))
+        .await
+    {
+        Ok(Some(user)) => user,
+        Ok(None) | Err(_) => return (StatusCode::UNAUTHORIZED, ()).into_response(),
+    };
+
+    if let Some(token_id) = &user.api_token_id {
+        auth_session
+            .backend
+            .record_api_token_use(token_id, request.method().as_str(), request.uri().path())
+            .await;
+    }
+
+    auth_session.user = Some(user);
+    request.extensions_mut().insert(auth_session);
+    next.run(request).await
+}
+
+fn bearer_token(request: &Request) -> Option<String> {
+    let value = request
+        .headers()
+        .get(header::AUTHORIZATION)?
+        .to_str()
+        .ok()?;
+    let token = value.strip_prefix("Bearer ")?.trim();
+    (!token.is_empty()).then(|| token.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::Method;
+
+    use crate::{
+        api::test_helpers::{TestApp, request, with_cookie, with_token},
+        data::node_data::{
+            api_tokens_repo::ApiTokensRepo,
+            node_stewards::{NodeStewardIdentifier, NodeStewardRole, NodeStewardsRepo},
+        },
+    };
+
+    use super::*;
+
+    const VIEWER_PATH: &str = "/node_steward_api/app_backups/apps/gitea";
+    const TOKENS_PATH: &str = "/node_steward_api/api_tokens";
+
+    async fn token(app: &TestApp, steward_id: &str, expires_at: Option<i64>) -> (String, String) {
+        let (api_token, token) = ApiTokensRepo::init()
+            .create(
+                &app.node_data_pool,
+                steward_id,
+                "script",
+                &NodeStewardRole::ALL,
+                expires_at,
+            )
+            .await
+            .unwrap();
+        (api_token.id, token)
+    }
+
+    #[tokio::test]
+    async fn test_valid_token_is_accepted() {
+        let app = TestApp::new().await;
+        let steward_id = app.steward(&[NodeStewardRole::Viewer]).await;
+        let (_, token) = token(&app, &steward_id, None).await;
+
+        let response = app
+            .send(with_token(request(Method::GET, VIEWER_PATH), &token), None)
+            .await;
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_unknown_expired_and_revoked_tokens_are_refused() {
+        let app = TestApp::new().await;
+        let steward_id = app.steward(&[NodeStewardRole::Viewer]).await;
+        let (_, expired) = token(&app, &steward_id, Some(1)).await;
+        let (revoked_id, revoked) = token(&app, &steward_id, None).await;
+        ApiTokensRepo::init()
+            .revoke(&app.node_data_pool, &steward_id, &revoked_id)
+            .await
+            .unwrap();
+
+        for token in ["lores_unknown", expired.as_str(), revoked.as_str()] {
+            let response = app
+                .send(with_token(request(Method::GET, VIEWER_PATH), token), None)
+                .await;
+            assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_disabled_stewards_tokens_are_refused() {
+        let app = TestApp::new().await;
+        let steward_id = app.steward(&[NodeStewardRole::Viewer]).await;
+        let (_, token) = token(&app, &steward_id, None).await;
+        NodeStewardsRepo::init()
+            .update_enabled(
+                &app.node_data_pool,
+                &NodeStewardIdentifier {
+                    id: steward_id.clone(),
+                },
+                false,
+            )
+            .await
+            .unwrap();
+
+        let response = app
+            .send(with_token(request(Method::GET, VIEWER_PATH), &token), None)
+            .await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn test_token_can_not_do_more_than_its_steward() {
+        let app = TestApp::new().await;
+        // The token is scoped to every role, but the steward is only a viewer.
+        let steward_id = app.steward(&[NodeStewardRole::Viewer]).await;
+        let (_, token) = token(&app, &steward_id, None).await;
+
+        let response = app
+            .send(with_token(request(Method::POST, VIEWER_PATH), &token), None)
+            .await;
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    }
+
+    #[tokio::test]
+    async fn test_tokens_can_not_mint_tokens() {
+        let app = TestApp::new().await;
+        let steward_id = app.steward(&NodeStewardRole::ALL).await;
+        let (_, token) = token(&app, &steward_id, None).await;
+        let body = serde_json::json!({ "name": "another" });
+
+        let response = app
+            .send(
+                with_token(request(Method::POST, TOKENS_PATH), &token),
+                Some(body.clone()),
+            )
+            .await;
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+        let cookie = app.log_in(&steward_id).await;
+        let response = app
+            .send(
+                with_cookie(request(Method::POST, TOKENS_PATH), &cookie),
+                Some(body),
+            )
+            .await;
+        assert!(response.status().is_success());
+    }
+
+    #[tokio::test]
+    async fn test_token_uses_are_recorded() {
+        let app = TestApp::new().await;
+        let steward_id = app.steward(&[NodeStewardRole::Viewer]).await;
+        let (token_id, token) = token(&app, &steward_id, None).await;
+
+        app.send(with_token(request(Method::GET, VIEWER_PATH), &token), None)
+            .await;
+
+        let uses = ApiTokensRepo::init()
+            .uses(&app.node_data_pool, &token_id, 10)
+            .await
+            .unwrap();
+        assert_eq!(uses.len(), 1);
+        assert_eq!(uses[0].method, "GET");
+        assert_eq!(uses[0].path, VIEWER_PATH);
+    }
+}
@@ -1,10 +1,16 @@
+use axum::http::{header, HeaderMap};
 use serde::Serialize;
+use tower_sessions::Session;
+use tracing::warn;
 use utoipa::ToSchema;
 use utoipa_axum::router::OpenApiRouter;
 
+use crate::{data::node_data::node_steward_sessions_repo::NodeStewardSessionsRepo, DatabaseState};
+
 mod admin_auth_routes;
 mod admin_user_repo;
 pub mod auth_backend;
+pub mod bearer_auth;
 mod node_steward_auth_routes;
 
 pub fn auth_api_router() -> OpenApiRouter {
@@ -25,3 +31,36 @@ impl UserRef {
         }
     }
 }
+
+/// Keeps track of the session a steward has just logged in with, so they can
+/// list and end it later. Logging in still works if this fails.
+pub(crate) async fn record_session(
+    db: &DatabaseState,
+    session: &Session,
+    node_steward_id: &str,
+    headers: &HeaderMap,
+) {
+    // Logging in gives the session a new id, which it only gets once saved.
+    if let Err(e) = session.save().await {
+        warn!("Failed to save node steward session: {:?}", e);
+        return;
+    }
+    let Some(session_id) = session.id() else {
+        return;
+    };
+
+    let user_agent = headers
+        .get(header::USER_AGENT)
+        .and_then(|value| value.to_str().ok());
+    if let Err(e) = NodeStewardSessionsRepo::init()
+        .record(
+            &db.node_data_pool,
+            &session_id.to_string(),
+            node_steward_id,
+            user_agent,
+        )
+        .await
+    {
+        warn!("Failed to record node steward session: {:?}", e);
+    }
+}
@@ -1,20 +1,27 @@
-use axum::{http::StatusCode, response::IntoResponse, Extension, Json};
+use axum::{
+    http::{HeaderMap, StatusCode},
+    response::IntoResponse,
+    Extension, Json,
+};
 use tracing::warn;
 use password_auth::generate_hash;
 use serde::{Deserialize, Serialize};
+use tower_sessions::Session;
 use utoipa::ToSchema;
 use utoipa_axum::{router::OpenApiRouter, routes};
 
 use crate::{
     config::config_state::LoresNodeConfigState,
-    data::node_data::node_stewards::{NodeStewardIdentifier, NodeStewardsRepo},
+    data::node_data::node_stewards::{
+        password_is_valid, NodeStewardIdentifier, NodeStewardRole, NodeStewardsRepo,
+    },
     DatabaseState,
 };
 
 use super::{
     admin_user_repo::AdminUserRepo,
     auth_backend::{AuthError, AuthSession, Credentials, NodeStewardCredentials},
-    UserRef,
+    record_session, UserRef,
 };
 
 pub fn router() -> OpenApiRouter {
@@ -28,6 +35,7 @@ pub fn router() -> OpenApiRouter {
 struct NodeStewardUser {
     pub id: String,
     pub name: String,
+    pub roles: Vec<NodeStewardRole>,
 }
 
 #[derive(Debug, Clone, Serialize, ToSchema)]
@@ -90,6 +98,7 @@ async fn get_current_user(
     let node_steward_user = NodeStewardUser {
         id: steward.id.clone(),
         name: steward.name.clone(),
+        roles: steward.roles(),
     };
 
     (StatusCode::OK, Json(Some(node_steward_user))).into_response()
@@ -113,7 +122,10 @@ enum NodeStewardLoginError {
     )
 )]
 async fn node_steward_login(
+    Extension(db): Extension<DatabaseState>,
     mut auth_session: AuthSession,
+    session: Session,
+    headers: HeaderMap,
     axum::extract::Json(node_steward_creds): axum::extract::Json<NodeStewardCredentials>,
 ) -> impl IntoResponse {
     let creds = Credentials::NodeSteward(node_steward_creds);
@@ -164,8 +176,9 @@ async fn node_steward_login(
         )
             .into_response();
     }
+    record_session(&db, &session, &user.id, &headers).await;
 
-    return (StatusCode::OK, Json(UserRef::from_backend_user(&user))).into_response();
+    (StatusCode::OK, Json(UserRef::from_backend_user(&user))).into_response()
 }
 
 #[derive(Debug, Clone, Deserialize, ToSchema)]
@@ -265,7 +278,3 @@ async fn node_steward_set_password(
         }
     }
 }
-
-fn password_is_valid(password: &str) -> bool {
-    password.len() >= 8
-}
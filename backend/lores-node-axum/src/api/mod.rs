@@ -1,13 +1,17 @@
+use axum::middleware;
 use axum_login::permission_required;
 use utoipa_axum::router::OpenApiRouter;
 
 use crate::api::auth_api::auth_backend::AppAuthBackend;
 
 mod admin_api;
+mod audit_log;
 pub mod auth_api;
 mod helpers;
 mod node_steward_api;
 pub mod public_api;
+#[cfg(test)]
+pub(crate) mod test_helpers;
 
 pub fn api_router() -> OpenApiRouter {
     OpenApiRouter::new()
@@ -20,7 +24,90 @@ pub fn api_router() -> OpenApiRouter {
         )
         .nest(
             "/node_steward_api",
-            node_steward_api::node_steward_api_router()
-                .route_layer(permission_required!(AppAuthBackend, "steward")),
+            node_steward_api::steward_router()
+                .route_layer(permission_required!(AppAuthBackend, "steward"))
+                .merge(
+                    node_steward_api::viewer_router()
+                        .route_layer(permission_required!(AppAuthBackend, "viewer")),
+                )
+                .merge(
+                    node_steward_api::app_manager_router()
+                        .route_layer(permission_required!(AppAuthBackend, "app-manager")),
+                )
+                .merge(
+                    node_steward_api::region_manager_router()
+                        .route_layer(permission_required!(AppAuthBackend, "region-manager")),
+                ),
         )
+        .layer(middleware::from_fn(audit_log::audit_log))
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::{Method, StatusCode};
+
+    use crate::{
+        api::test_helpers::{TestApp, request, with_cookie},
+        data::node_data::node_stewards::NodeStewardRole,
+    };
+
+    #[tokio::test]
+    async fn test_viewer_cannot_use_manager_routes() {
+        let app = TestApp::new().await;
+        let steward_id = app.steward(&[NodeStewardRole::Viewer]).await;
+        let cookie = app.log_in(&steward_id).await;
+
+        let get = |path: &str| with_cookie(request(Method::GET, path), &cookie);
+        let post = |path: &str| with_cookie(request(Method::POST, path), &cookie);
+
+        let response = app
+            .send(get("/node_steward_api/app_backups/apps/gitea"), None)
+            .await;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let response = app
+            .send(post("/node_steward_api/app_backups/apps/gitea"), None)
+            .await;
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+        let body = serde_json::json!({ "name": "Region", "slug": "region" });
+        let response = app
+            .send(post("/node_steward_api/my_regions/create"), Some(body))
+            .await;
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    }
+
+    #[tokio::test]
+    async fn test_read_and_manage_routes_share_paths() {
+        let app = TestApp::new().await;
+        let steward_id = app.steward(&[NodeStewardRole::AppManager]).await;
+        let cookie = app.log_in(&steward_id).await;
+
+        // GET comes from the viewer router, POST from the app manager router.
+        let path = "/node_steward_api/app_backups/apps/.hidden";
+        let response = app
+            .send(with_cookie(request(Method::GET, path), &cookie), None)
+            .await;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        // Reaches the handler, which refuses the stack name.
+        let response = app
+            .send(with_cookie(request(Method::POST, path), &cookie), None)
+            .await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_anonymous_requests_are_refused() {
+        let app = TestApp::new().await;
+
+        let response = app
+            .send(
+                request(Method::GET, "/node_steward_api/app_backups/apps/gitea"),
+                None,
+            )
+            .await;
+        // `permission_required!` answers 403 for anonymous requests too.
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    }
 }
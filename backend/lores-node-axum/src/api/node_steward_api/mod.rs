@@ -2,10 +2,34 @@ use utoipa_axum::router::OpenApiRouter;
 
 mod routes;
 
-pub fn node_steward_api_router() -> OpenApiRouter {
+/// The steward's own account, for stewards who logged in rather than using
+/// an API token.
+pub fn steward_router() -> OpenApiRouter {
+    OpenApiRouter::new()
+        .nest("/account", routes::account::router())
+        .nest("/api_tokens", routes::api_tokens::router())
+}
+
+/// Routes that only read, for stewards with any role.
+pub fn viewer_router() -> OpenApiRouter {
+    OpenApiRouter::new()
+        .nest("/app_deployments", routes::app_deployments::read_router())
+        .nest("/recipes", routes::recipes::router())
+        .nest("/app_backups", routes::app_backups::read_router())
+        .nest("/app_quotas", routes::app_quotas::router())
+        .nest("/network", routes::network::read_router())
+}
+
+pub fn app_manager_router() -> OpenApiRouter {
     OpenApiRouter::new()
-        .nest("/my_region_nodes", routes::my_region_nodes::router())
         .nest("/local_apps", routes::local_apps::router())
+        .nest("/app_deployments", routes::app_deployments::router())
+        .nest("/app_backups", routes::app_backups::router())
+}
+
+pub fn region_manager_router() -> OpenApiRouter {
+    OpenApiRouter::new()
+        .nest("/my_region_nodes", routes::my_region_nodes::router())
         .nest("/my_regions", routes::my_regions::router())
         .nest("/network", routes::network::router())
 }
@@ -0,0 +1,391 @@
+use axum::{
+    Extension, Json,
+    extract::Path,
+    http::{HeaderMap, StatusCode},
+    response::IntoResponse,
+};
+use axum_login::AuthnBackend;
+use password_auth::generate_hash;
+use serde::{Deserialize, Serialize};
+use tower_sessions::Session;
+use tracing::{info, warn};
+use utoipa::ToSchema;
+use utoipa_axum::{router::OpenApiRouter, routes};
+
+use crate::{
+    DatabaseState,
+    api::{
+        auth_api::{
+            auth_backend::{AuthError, AuthSession, Credentials, NodeStewardCredentials},
+            record_session,
+        },
+        helpers::{bad_request, internal_server_error},
+    },
+    data::node_data::{
+        node_steward_sessions_repo::{NodeStewardSession, NodeStewardSessionsRepo},
+        node_stewards::{NodeStewardIdentifier, NodeStewardsRepo, password_is_valid},
+    },
+    node_admin::node_stewards::NodeSteward,
+};
+
+pub fn router() -> OpenApiRouter {
+    OpenApiRouter::new()
+        .routes(routes!(get_account))
+        .routes(routes!(update_name))
+        .routes(routes!(change_password))
+        .routes(routes!(list_sessions))
+        .routes(routes!(end_session))
+}
+
+#[utoipa::path(get, path = "/", responses(
+    (status = OK, body = NodeSteward),
+    (status = INTERNAL_SERVER_ERROR, body = String),
+),)]
+async fn get_account(
+    Extension(db): Extension<DatabaseState>,
+    auth_session: AuthSession,
+) -> impl IntoResponse {
+    let Some(user) = auth_session.user else {
+        return (StatusCode::UNAUTHORIZED, ()).into_response();
+    };
+
+    let identifier = NodeStewardIdentifier { id: user.id };
+    match NodeStewardsRepo::init()
+        .find(&db.node_data_pool, &identifier)
+        .await
+    {
+        Ok(Some(row)) => (StatusCode::OK, Json(NodeSteward::from_row(&row))).into_response(),
+        Ok(None) => (StatusCode::UNAUTHORIZED, ()).into_response(),
+        Err(e) => internal_server_error(e).into_response(),
+    }
+}
+
+#[derive(Deserialize, ToSchema, Debug)]
+pub struct UpdateNameData {
+    pub name: String,
+}
+
+#[utoipa::path(
+    put,
+    path = "/name",
+    request_body(content = UpdateNameData, content_type = "application/json"),
+    responses(
+        (status = OK, body = NodeSteward),
+        (status = BAD_REQUEST, body = String),
+        (status = INTERNAL_SERVER_ERROR, body = String),
+    ),
+)]
+async fn update_name(
+    Extension(db): Extension<DatabaseState>,
+    auth_session: AuthSession,
+    axum::extract::Json(data): axum::extract::Json<UpdateNameData>,
+) -> impl IntoResponse {
+    let Some(user) = auth_session.user else {
+        return (StatusCode::UNAUTHORIZED, ()).into_response();
+    };
+
+    let name = data.name.trim();
+    if name.is_empty() {
+        return bad_request("Name is required").into_response();
+    }
+
+    let repo = NodeStewardsRepo::init();
+    let identifier = NodeStewardIdentifier { id: user.id };
+    if let Err(e) = repo
+        .update_name(&db.node_data_pool, &identifier, name)
+        .await
+    {
+        return internal_server_error(e).into_response();
+    }
+
+    match repo.find(&db.node_data_pool, &identifier).await {
+        Ok(Some(row)) => (StatusCode::OK, Json(NodeSteward::from_row(&row))).into_response(),
+        Ok(None) => (StatusCode::UNAUTHORIZED, ()).into_response(),
+        Err(e) => internal_server_error(e).into_response(),
+    }
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct ChangePasswordData {
+    pub current_password: String,
+    pub new_password: String,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub enum ChangePasswordError {
+    InvalidCurrentPassword,
+    InvalidNewPassword,
+    InternalServerError,
+}
+
+/// Changes the steward's password and ends all their other sessions. The
+/// session the request was made with stays logged in.
+#[utoipa::path(
+    put,
+    path = "/password",
+    request_body(content = ChangePasswordData, content_type = "application/json"),
+    responses(
+        (status = OK, body = ()),
+        (status = BAD_REQUEST, body = ChangePasswordError),
+        (status = UNAUTHORIZED, body = ChangePasswordError),
+        (status = INTERNAL_SERVER_ERROR, body = ChangePasswordError),
+    ),
+)]
+async fn change_password(
+    Extension(db): Extension<DatabaseState>,
+    mut auth_session: AuthSession,
+    session: Session,
+    headers: HeaderMap,
+    axum::extract::Json(data): axum::extract::Json<ChangePasswordData>,
+) -> impl IntoResponse {
+    let Some(user) = auth_session.user.clone() else {
+        return (StatusCode::UNAUTHORIZED, ()).into_response();
+    };
+    let server_error = || {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ChangePasswordError::InternalServerError),
+        )
+            .into_response()
+    };
+
+    let creds = Credentials::NodeSteward(NodeStewardCredentials {
+        id: user.id.clone(),
+        password: data.current_password,
+    });
+    match auth_session.authenticate(creds).await {
+        Ok(Some(_)) => {}
+        Ok(None) | Err(axum_login::Error::Backend(AuthError::InvalidCredentials)) => {
+            return (
+                StatusCode::UNAUTHORIZED,
+                Json(ChangePasswordError::InvalidCurrentPassword),
+            )
+                .into_response();
+        }
+        Err(e) => {
+            warn!("Failed to check node steward password: {:?}", e);
+            return server_error();
+        }
+    }
+
+    if !password_is_valid(&data.new_password) {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(ChangePasswordError::InvalidNewPassword),
+        )
+            .into_response();
+    }
+
+    let identifier = NodeStewardIdentifier {
+        id: user.id.clone(),
+    };
+    let hashed_password = generate_hash(&data.new_password);
+    if let Err(e) = NodeStewardsRepo::init()
+        .update_password_and_clear_token(&db.node_data_pool, &identifier, &hashed_password)
+        .await
+    {
+        warn!("Failed to update node steward password: {:?}", e);
+        return server_error();
+    }
+
+    // Sessions hold the old password hash as their auth hash, so they would
+    // be refused from now on anyway; removing them also keeps them out of
+    // the session list.
+    if let Err(e) = NodeStewardSessionsRepo::init()
+        .end_all_for_steward(&db.node_data_pool, &user.id)
+        .await
+    {
+        warn!("Failed to end node steward sessions: {:?}", e);
+        return server_error();
+    }
+
+    // Log this session back in with the new auth hash.
+    let user = match auth_session.backend.get_user(&user.id).await {
+        Ok(Some(user)) => user,
+        Ok(None) | Err(_) => return server_error(),
+    };
+    if let Err(e) = auth_session.login(&user).await {
+        warn!("Failed to log node steward back in: {:?}", e);
+        return server_error();
+    }
+    record_session(&db, &session, &user.id, &headers).await;
+
+    info!("Node steward {} changed their password", user.id);
+    (StatusCode::OK, ()).into_response()
+}
+
+#[utoipa::path(get, path = "/sessions", responses(
+    (status = OK, body = Vec<NodeStewardSession>),
+    (status = INTERNAL_SERVER_ERROR, body = String),
+),)]
+async fn list_sessions(
+    Extension(db): Extension<DatabaseState>,
+    auth_session: AuthSession,
+    session: Session,
+) -> impl IntoResponse {
+    let Some(user) = auth_session.user else {
+        return (StatusCode::UNAUTHORIZED, ()).into_response();
+    };
+
+    let current_session_id = session.id().map(|id| id.to_string());
+    match NodeStewardSessionsRepo::init()
+        .active_for_steward(&db.node_data_pool, &user.id, current_session_id.as_deref())
+        .await
+    {
+        Ok(sessions) => (StatusCode::OK, Json(sessions)).into_response(),
+        Err(e) => internal_server_error(e).into_response(),
+    }
+}
+
+/// Logs one of the steward's sessions out. Ending the current session is the
+/// same as logging out.
+#[utoipa::path(
+    post,
+    path = "/sessions/end/{session_id}",
+    params(
+        ("session_id" = String, Path),
+    ),
+    responses(
+        (status = OK, body = ()),
+        (status = NOT_FOUND, body = ()),
+        (status = INTERNAL_SERVER_ERROR, body = String),
+    ),
+)]
+async fn end_session(
+    Extension(db): Extension<DatabaseState>,
+    auth_session: AuthSession,
+    Path(session_id): Path<String>,
+) -> impl IntoResponse {
+    let Some(user) = auth_session.user else {
+        return (StatusCode::UNAUTHORIZED, ()).into_response();
+    };
+
+    match NodeStewardSessionsRepo::init()
+        .end(&db.node_data_pool, &user.id, &session_id)
+        .await
+    {
+        Ok(true) => (StatusCode::OK, ()).into_response(),
+        Ok(false) => (StatusCode::NOT_FOUND, ()).into_response(),
+        Err(e) => internal_server_error(e).into_response(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::Method;
+
+    use crate::{
+        api::test_helpers::{PASSWORD, TestApp, json_body, request, session_cookie, with_cookie},
+        data::node_data::node_stewards::NodeStewardRole,
+    };
+
+    use super::*;
+
+    const PASSWORD_PATH: &str = "/node_steward_api/account/password";
+    const SESSIONS_PATH: &str = "/node_steward_api/account/sessions";
+
+    #[tokio::test]
+    async fn test_wrong_current_password_is_refused() {
+        let app = TestApp::new().await;
+        let steward_id = app.steward(&NodeStewardRole::ALL).await;
+        let cookie = app.log_in(&steward_id).await;
+
+        let body = serde_json::json!({
+            "current_password": "not the password",
+            "new_password": "a new password",
+        });
+        let response = app
+            .send(
+                with_cookie(request(Method::PUT, PASSWORD_PATH), &cookie),
+                Some(body),
+            )
+            .await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        let response = app
+            .send(
+                with_cookie(request(Method::GET, SESSIONS_PATH), &cookie),
+                None,
+            )
+            .await;
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_changing_password_ends_other_sessions() {
+        let app = TestApp::new().await;
+        let steward_id = app.steward(&NodeStewardRole::ALL).await;
+        let cookie = app.log_in(&steward_id).await;
+        let other_cookie = app.log_in(&steward_id).await;
+
+        let body = serde_json::json!({
+            "current_password": PASSWORD,
+            "new_password": "a new password",
+        });
+        let response = app
+            .send(
+                with_cookie(request(Method::PUT, PASSWORD_PATH), &cookie),
+                Some(body),
+            )
+            .await;
+        assert_eq!(response.status(), StatusCode::OK);
+        // Logging back in gives the session a new id.
+        let cookie = session_cookie(&response).unwrap_or(cookie);
+
+        let response = app
+            .send(
+                with_cookie(request(Method::GET, SESSIONS_PATH), &other_cookie),
+                None,
+            )
+            .await;
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+        let response = app
+            .send(
+                with_cookie(request(Method::GET, SESSIONS_PATH), &cookie),
+                None,
+            )
+            .await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let sessions = json_body(response).await;
+        let sessions = sessions.as_array().unwrap();
+        assert_eq!(sessions.len(), 1);
+        assert_eq!(sessions[0]["current"], true);
+    }
+
+    #[tokio::test]
+    async fn test_ending_a_session_logs_it_out() {
+        let app = TestApp::new().await;
+        let steward_id = app.steward(&NodeStewardRole::ALL).await;
+        let cookie = app.log_in(&steward_id).await;
+        let other_cookie = app.log_in(&steward_id).await;
+
+        let response = app
+            .send(
+                with_cookie(request(Method::GET, SESSIONS_PATH), &cookie),
+                None,
+            )
+            .await;
+        let sessions = json_body(response).await;
+        let other = sessions
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|session| session["current"] == false)
+            .unwrap();
+        let path = format!("{SESSIONS_PATH}/end/{}", other["id"].as_str().unwrap());
+
+        let response = app
+            .send(with_cookie(request(Method::POST, &path), &cookie), None)
+            .await;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let response = app
+            .send(
+                with_cookie(request(Method::GET, SESSIONS_PATH), &other_cookie),
+                None,
+            )
+            .await;
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    }
+}
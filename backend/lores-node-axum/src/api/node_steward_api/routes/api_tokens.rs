@@ -0,0 +1,191 @@
+use axum::{Extension, Json, extract::Path, http::StatusCode, response::IntoResponse};
+use serde::{Deserialize, Serialize};
+use tracing::info;
+use utoipa::ToSchema;
+use utoipa_axum::{router::OpenApiRouter, routes};
+
+use crate::{
+    DatabaseState,
+    api::{
+        auth_api::auth_backend::AuthSession,
+        helpers::{bad_request, internal_server_error},
+    },
+    data::node_data::{
+        api_tokens_repo::{ApiToken, ApiTokenUse, ApiTokensRepo},
+        node_stewards::NodeStewardRole,
+    },
+};
+
+/// How many uses of a token are shown.
+const TOKEN_USES_LIMIT: u32 = 100;
+
+pub fn router() -> OpenApiRouter {
+    OpenApiRouter::new()
+        .routes(routes!(list_api_tokens, create_api_token))
+        .routes(routes!(revoke_api_token))
+        .routes(routes!(list_api_token_uses))
+}
+
+#[derive(Deserialize, ToSchema, Debug)]
+pub struct ApiTokenCreationData {
+    pub name: String,
+    /// Roles the token can use. Defaults to all of the steward's roles.
+    pub scopes: Option<Vec<NodeStewardRole>>,
+    /// Days until the token stops working. Without it the token works until
+    /// it is revoked.
+    pub expires_in_days: Option<u32>,
+}
+
+#[derive(Serialize, ToSchema, Debug)]
+pub struct ApiTokenCreationResult {
+    pub api_token: ApiToken,
+    /// Sent as `Authorization: Bearer <token>`. It is only shown once.
+    pub token: String,
+}
+
+#[utoipa::path(get, path = "/", responses(
+    (status = OK, body = Vec<ApiToken>),
+    (status = INTERNAL_SERVER_ERROR, body = String),
+),)]
+async fn list_api_tokens(
+    Extension(db): Extension<DatabaseState>,
+    auth_session: AuthSession,
+) -> impl IntoResponse {
+    let Some(user) = auth_session.user else {
+        return (StatusCode::UNAUTHORIZED, ()).into_response();
+    };
+
+    match ApiTokensRepo::init()
+        .for_steward(&db.node_data_pool, &user.id)
+        .await
+    {
+        Ok(tokens) => (StatusCode::OK, Json(tokens)).into_response(),
+        Err(e) => internal_server_error(e).into_response(),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/",
+    request_body(content = ApiTokenCreationData, content_type = "application/json"),
+    responses(
+        (status = CREATED, body = ApiTokenCreationResult),
+        (status = BAD_REQUEST, body = String),
+        (status = INTERNAL_SERVER_ERROR, body = String),
+    ),
+)]
+async fn create_api_token(
+    Extension(db): Extension<DatabaseState>,
+    auth_session: AuthSession,
+    axum::extract::Json(data): axum::extract::Json<ApiTokenCreationData>,
+) -> impl IntoResponse {
+    let Some(user) = auth_session.user else {
+        return (StatusCode::UNAUTHORIZED, ()).into_response();
+    };
+
+    let name = data.name.trim();
+    if name.is_empty() {
+        return bad_request("Token name is required").into_response();
+    }
+
+    let scopes = data.scopes.unwrap_or_else(|| user.roles.clone());
+    if let Some(role) = scopes.iter().find(|role| !user.roles.contains(role)) {
+        return bad_request(format!("You don't have the {} role", role.as_str())).into_response();
+    }
+
+    let expires_at = data
+        .expires_in_days
+        .map(|days| (chrono::Utc::now() + chrono::Duration::days(days.into())).timestamp());
+
+    match ApiTokensRepo::init()
+        .create(&db.node_data_pool, &user.id, name, &scopes, expires_at)
+        .await
+    {
+        Ok((api_token, token)) => {
+            info!(
+                "Created API token {} for node steward {}",
+                api_token.id, user.id
+            );
+            (
+                StatusCode::CREATED,
+                Json(ApiTokenCreationResult { api_token, token }),
+            )
+                .into_response()
+        }
+        Err(e) => internal_server_error(e).into_response(),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/revoke/{token_id}",
+    params(
+        ("token_id" = String, Path),
+    ),
+    responses(
+        (status = OK, body = ApiToken),
+        (status = NOT_FOUND, body = ()),
+        (status = INTERNAL_SERVER_ERROR, body = String),
+    ),
+)]
+async fn revoke_api_token(
+    Extension(db): Extension<DatabaseState>,
+    auth_session: AuthSession,
+    Path(token_id): Path<String>,
+) -> impl IntoResponse {
+    let Some(user) = auth_session.user else {
+        return (StatusCode::UNAUTHORIZED, ()).into_response();
+    };
+
+    match ApiTokensRepo::init()
+        .revoke(&db.node_data_pool, &user.id, &token_id)
+        .await
+    {
+        Ok(Some(api_token)) => {
+            info!("Revoked API token {} of node steward {}", token_id, user.id);
+            (StatusCode::OK, Json(api_token)).into_response()
+        }
+        Ok(None) => (StatusCode::NOT_FOUND, ()).into_response(),
+        Err(e) => internal_server_error(e).into_response(),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/uses/{token_id}",
+    params(
+        ("token_id" = String, Path),
+    ),
+    responses(
+        (status = OK, body = Vec<ApiTokenUse>),
+        (status = NOT_FOUND, body = ()),
+        (status = INTERNAL_SERVER_ERROR, body = String),
+    ),
+)]
+async fn list_api_token_uses(
+    Extension(db): Extension<DatabaseState>,
+    auth_session: AuthSession,
+    Path(token_id): Path<String>,
+) -> impl IntoResponse {
+    let Some(user) = auth_session.user else {
+        return (StatusCode::UNAUTHORIZED, ()).into_response();
+    };
+
+    let repo = ApiTokensRepo::init();
+    match repo
+        .find_for_steward(&db.node_data_pool, &user.id, &token_id)
+        .await
+    {
+        Ok(Some(_)) => {}
+        Ok(None) => return (StatusCode::NOT_FOUND, ()).into_response(),
+        Err(e) => return internal_server_error(e).into_response(),
+    }
+
+    match repo
+        .uses(&db.node_data_pool, &token_id, TOKEN_USES_LIMIT)
+        .await
+    {
+        Ok(uses) => (StatusCode::OK, Json(uses)).into_response(),
+        Err(e) => internal_server_error(e).into_response(),
+    }
+}
@@ -0,0 +1,129 @@
+use axum::{Extension, Json, extract::Path, http::StatusCode, response::IntoResponse};
+use utoipa_axum::{router::OpenApiRouter, routes};
+
+use crate::{
+    DatabaseState,
+    api::{
+        helpers::{bad_request, internal_server_error},
+        public_api::realtime::RealtimeState,
+    },
+    data::node_data::app_backups_repo::{AppBackup, AppBackupsRepo},
+    local_apps::backups::{AppBackupError, AppBackups},
+};
+
+pub fn read_router() -> OpenApiRouter {
+    OpenApiRouter::new()
+        .routes(routes!(list_backups))
+        .routes(routes!(list_app_backups))
+}
+
+pub fn router() -> OpenApiRouter {
+    OpenApiRouter::new()
+        .routes(routes!(trigger_app_backup))
+        .routes(routes!(restore_backup))
+}
+
+#[utoipa::path(get, path = "/", responses(
+    (status = OK, body = Vec<AppBackup>),
+    (status = INTERNAL_SERVER_ERROR, body = String),
+),)]
+async fn list_backups(Extension(db): Extension<DatabaseState>) -> impl IntoResponse {
+    match AppBackupsRepo::init().all(&db.node_data_pool).await {
+        Ok(backups) => (StatusCode::OK, Json(backups)).into_response(),
+        Err(e) => internal_server_error(e).into_response(),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/apps/{stack_name}",
+    params(
+        ("stack_name" = String, Path),
+    ),
+    responses(
+        (status = OK, body = Vec<AppBackup>),
+        (status = INTERNAL_SERVER_ERROR, body = String),
+    ),
+)]
+async fn list_app_backups(
+    Extension(db): Extension<DatabaseState>,
+    Path(stack_name): Path<String>,
+) -> impl IntoResponse {
+    match AppBackupsRepo::init()
+        .for_stack(&db.node_data_pool, &stack_name)
+        .await
+    {
+        Ok(backups) => (StatusCode::OK, Json(backups)).into_response(),
+        Err(e) => internal_server_error(e).into_response(),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/apps/{stack_name}",
+    params(
+        ("stack_name" = String, Path),
+    ),
+    responses(
+        (status = ACCEPTED, body = AppBackup),
+        (status = BAD_REQUEST, body = String),
+        (status = CONFLICT, body = String),
+        (status = INTERNAL_SERVER_ERROR, body = String),
+    ),
+)]
+async fn trigger_app_backup(
+    Extension(db): Extension<DatabaseState>,
+    Extension(realtime_state): Extension<RealtimeState>,
+    Extension(backups): Extension<AppBackups>,
+    Path(stack_name): Path<String>,
+) -> impl IntoResponse {
+    match backups
+        .trigger(&db.node_data_pool, &realtime_state, &stack_name)
+        .await
+    {
+        Ok(backup) => (StatusCode::ACCEPTED, Json(backup)).into_response(),
+        Err(e) => backup_error_response(e),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/{backup_id}/restore",
+    params(
+        ("backup_id" = String, Path),
+    ),
+    responses(
+        (status = OK, body = ()),
+        (status = BAD_REQUEST, body = String),
+        (status = NOT_FOUND, body = String),
+        (status = CONFLICT, body = String),
+        (status = INTERNAL_SERVER_ERROR, body = String),
+    ),
+)]
+async fn restore_backup(
+    Extension(db): Extension<DatabaseState>,
+    Extension(backups): Extension<AppBackups>,
+    Path(backup_id): Path<String>,
+) -> impl IntoResponse {
+    match backups.restore(&db.node_data_pool, &backup_id).await {
+        Ok(()) => (StatusCode::OK, ()).into_response(),
+        Err(e) => backup_error_response(e),
+    }
+}
+
+fn backup_error_response(error: AppBackupError) -> axum::response::Response {
+    match error {
+        AppBackupError::InvalidStackName(_)
+        | AppBackupError::NotConfigured(_)
+        | AppBackupError::NotRestorable(_) => bad_request(error.to_string()).into_response(),
+        AppBackupError::NotFound(_) => {
+            (StatusCode::NOT_FOUND, Json(error.to_string())).into_response()
+        }
+        AppBackupError::AlreadyRunning(_) => {
+            (StatusCode::CONFLICT, Json(error.to_string())).into_response()
+        }
+        AppBackupError::Database(_) | AppBackupError::Docker(_) => {
+            internal_server_error(error).into_response()
+        }
+    }
+}
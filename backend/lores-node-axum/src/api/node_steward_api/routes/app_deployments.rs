@@ -0,0 +1,169 @@
+use axum::{Extension, Json, extract::Path, http::StatusCode, response::IntoResponse};
+use serde::Deserialize;
+use utoipa::ToSchema;
+use utoipa_axum::{router::OpenApiRouter, routes};
+
+use crate::{
+    api::{helpers::bad_request, public_api::realtime::RealtimeState},
+    local_apps::deployments::{
+        AppDeploymentJob, AppDeploymentJobWithLogs, AppDeploymentRequest, AppDeployments,
+        StartDeploymentError,
+    },
+};
+
+pub fn router() -> OpenApiRouter {
+    OpenApiRouter::new()
+        .routes(routes!(new_app))
+        .routes(routes!(deploy_app))
+        .routes(routes!(upgrade_app))
+        .routes(routes!(undeploy_app))
+}
+
+pub fn read_router() -> OpenApiRouter {
+    OpenApiRouter::new()
+        .routes(routes!(list_jobs))
+        .routes(routes!(show_job))
+}
+
+#[derive(Deserialize, ToSchema, Debug)]
+pub struct NewAppData {
+    pub recipe: String,
+    pub domain: String,
+    pub version: Option<String>,
+}
+
+#[derive(Deserialize, ToSchema, Debug)]
+pub struct AppDomainData {
+    pub domain: String,
+}
+
+#[derive(Deserialize, ToSchema, Debug)]
+pub struct UpgradeAppData {
+    pub domain: String,
+    pub version: Option<String>,
+}
+
+#[utoipa::path(
+    post, path = "/new",
+    request_body(content = NewAppData, content_type = "application/json"),
+    responses(
+        (status = ACCEPTED, body = AppDeploymentJob),
+        (status = BAD_REQUEST, body = String),
+        (status = CONFLICT, body = String),
+    )
+)]
+async fn new_app(
+    Extension(deployments): Extension<AppDeployments>,
+    Extension(realtime_state): Extension<RealtimeState>,
+    Json(data): Json<NewAppData>,
+) -> impl IntoResponse {
+    let request = AppDeploymentRequest::New {
+        recipe: data.recipe,
+        domain: data.domain,
+        version: data.version,
+    };
+    start_job(deployments, realtime_state, request).await
+}
+
+#[utoipa::path(
+    post, path = "/deploy",
+    request_body(content = AppDomainData, content_type = "application/json"),
+    responses(
+        (status = ACCEPTED, body = AppDeploymentJob),
+        (status = BAD_REQUEST, body = String),
+        (status = CONFLICT, body = String),
+    )
+)]
+async fn deploy_app(
+    Extension(deployments): Extension<AppDeployments>,
+    Extension(realtime_state): Extension<RealtimeState>,
+    Json(data): Json<AppDomainData>,
+) -> impl IntoResponse {
+    let request = AppDeploymentRequest::Deploy {
+        domain: data.domain,
+    };
+    start_job(deployments, realtime_state, request).await
+}
+
+#[utoipa::path(
+    post, path = "/upgrade",
+    request_body(content = UpgradeAppData, content_type = "application/json"),
+    responses(
+        (status = ACCEPTED, body = AppDeploymentJob),
+        (status = BAD_REQUEST, body = String),
+        (status = CONFLICT, body = String),
+    )
+)]
+async fn upgrade_app(
+    Extension(deployments): Extension<AppDeployments>,
+    Extension(realtime_state): Extension<RealtimeState>,
+    Json(data): Json<UpgradeAppData>,
+) -> impl IntoResponse {
+    let request = AppDeploymentRequest::Upgrade {
+        domain: data.domain,
+        version: data.version,
+    };
+    start_job(deployments, realtime_state, request).await
+}
+
+#[utoipa::path(
+    post, path = "/undeploy",
+    request_body(content = AppDomainData, content_type = "application/json"),
+    responses(
+        (status = ACCEPTED, body = AppDeploymentJob),
+        (status = BAD_REQUEST, body = String),
+        (status = CONFLICT, body = String),
+    )
+)]
+async fn undeploy_app(
+    Extension(deployments): Extension<AppDeployments>,
+    Extension(realtime_state): Extension<RealtimeState>,
+    Json(data): Json<AppDomainData>,
+) -> impl IntoResponse {
+    let request = AppDeploymentRequest::Undeploy {
+        domain: data.domain,
+    };
+    start_job(deployments, realtime_state, request).await
+}
+
+async fn start_job(
+    deployments: AppDeployments,
+    realtime_state: RealtimeState,
+    request: AppDeploymentRequest,
+) -> axum::response::Response {
+    match deployments.start(request, realtime_state).await {
+        Ok(job) => (StatusCode::ACCEPTED, Json(job)).into_response(),
+        Err(StartDeploymentError::Invalid(e)) => bad_request(e).into_response(),
+        Err(e @ StartDeploymentError::AlreadyRunning(_)) => {
+            (StatusCode::CONFLICT, Json(e.to_string())).into_response()
+        }
+    }
+}
+
+#[utoipa::path(get, path = "/jobs", responses(
+    (status = OK, body = Vec<AppDeploymentJob>),
+),)]
+async fn list_jobs(Extension(deployments): Extension<AppDeployments>) -> impl IntoResponse {
+    (StatusCode::OK, Json(deployments.list().await)).into_response()
+}
+
+#[utoipa::path(
+    get,
+    path = "/jobs/{job_id}",
+    params(
+        ("job_id" = String, Path),
+    ),
+    responses(
+        (status = OK, body = AppDeploymentJobWithLogs),
+        (status = NOT_FOUND, body = ()),
+    ),
+)]
+async fn show_job(
+    Extension(deployments): Extension<AppDeployments>,
+    Path(job_id): Path<String>,
+) -> impl IntoResponse {
+    match deployments.find(&job_id).await {
+        Some(job) => (StatusCode::OK, Json(job)).into_response(),
+        None => (StatusCode::NOT_FOUND, ()).into_response(),
+    }
+}
@@ -0,0 +1,78 @@
+use axum::{http::StatusCode, response::IntoResponse, Extension, Json};
+use lores_p2panda_server::QuotaTracker;
+use serde::Serialize;
+use tracing::warn;
+use utoipa::ToSchema;
+use utoipa_axum::{router::OpenApiRouter, routes};
+
+pub fn router() -> OpenApiRouter {
+    OpenApiRouter::new().routes(routes!(get_app_quotas))
+}
+
+#[derive(Serialize, ToSchema)]
+struct AppQuotaLimits {
+    max_payload_bytes: u64,
+    ops_per_minute: u32,
+    bytes_per_day: u64,
+}
+
+#[derive(Serialize, ToSchema)]
+struct AppQuotaUsage {
+    app_id: String,
+    instance_id: String,
+    ops_today: u64,
+    bytes_today: u64,
+    /// Publishes refused today for being over a quota.
+    rejected_today: u64,
+    ops_this_minute: u32,
+}
+
+#[derive(Serialize, ToSchema)]
+struct AppQuotasResult {
+    limits: AppQuotaLimits,
+    usage: Vec<AppQuotaUsage>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/",
+    responses(
+        (status = 200, body = AppQuotasResult),
+        (status = 500, body = String),
+    )
+)]
+async fn get_app_quotas(Extension(quota_tracker): Extension<QuotaTracker>) -> impl IntoResponse {
+    let usage = match quota_tracker.usage_today().await {
+        Ok(usage) => usage,
+        Err(e) => {
+            warn!("Failed to read app quota usage: {e}");
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Failed to read app quota usage: {e}"),
+            )
+                .into_response();
+        }
+    };
+
+    let config = quota_tracker.config();
+    let result = AppQuotasResult {
+        limits: AppQuotaLimits {
+            max_payload_bytes: config.max_payload_bytes as u64,
+            ops_per_minute: config.ops_per_minute,
+            bytes_per_day: config.bytes_per_day,
+        },
+        usage: usage
+            .into_iter()
+            .map(|usage| AppQuotaUsage {
+                app_id: usage.app_id,
+                instance_id: usage.instance_id,
+                ops_today: usage.ops,
+                bytes_today: usage.bytes,
+                rejected_today: usage.rejected,
+                ops_this_minute: usage.ops_this_minute,
+            })
+            .collect(),
+    };
+
+    (StatusCode::OK, Json(result)).into_response()
+}
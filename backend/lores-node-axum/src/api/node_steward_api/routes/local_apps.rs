@@ -1,6 +1,6 @@
 use axum::{Extension, Json, http::StatusCode, response::IntoResponse};
 use tracing::{info, warn};
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use utoipa::ToSchema;
 use utoipa_axum::{router::OpenApiRouter, routes};
 
@@ -28,6 +28,7 @@ pub fn router() -> OpenApiRouter {
         .routes(routes!(register_app))
         .routes(routes!(create_local_app))
         .routes(routes!(update_local_app))
+        .routes(routes!(rotate_app_token))
 }
 
 #[derive(Deserialize, ToSchema, Debug, Clone)]
@@ -36,6 +37,19 @@ pub struct AppRegionReference {
     pub app: LocalApp,
 }
 
+#[derive(Serialize, ToSchema, Debug, Clone)]
+pub struct AppTokenResult {
+    /// Token the app sends as `authorization: Bearer <token>` to the gRPC Panda
+    /// service. It is only shown once; `None` means the existing token was kept.
+    pub token: Option<String>,
+}
+
+#[derive(Deserialize, ToSchema, Debug, Clone)]
+pub struct LocalAppIdentifier {
+    pub name: String,
+    pub instance_id: Option<String>,
+}
+
 #[derive(Deserialize, ToSchema, Debug, Clone)]
 pub struct LocalAppFormData {
     pub name: String,
@@ -71,7 +85,7 @@ impl LocalAppFormData {
     post, path = "/register",
     request_body(content = AppRegionReference, content_type = "application/json"),
     responses(
-        (status = 200, body = ()),
+        (status = 200, body = AppTokenResult),
         (status = INTERNAL_SERVER_ERROR, body = ()),
     )
 )]
@@ -113,8 +127,8 @@ async fn register_app(
     };
 
     // Persist the binding in node_data before announcing to the network
-    let binding_event = if !already_bound {
-        if let Err(e) = LocalAppsRepo::init()
+    let (binding_event, token) = if !already_bound {
+        let token = match LocalAppsRepo::init()
             .bind_to_region(
                 &db.node_data_pool,
                 &payload.app.name,
@@ -123,19 +137,21 @@ async fn register_app(
             )
             .await
         {
-            return internal_server_error(e).into_response();
-        }
+            Ok(token) => token,
+            Err(e) => return internal_server_error(e).into_response(),
+        };
 
         let mut updated_app = existing_app;
         updated_app.bound_to_region_id = Some(payload.region_id.clone());
-        Some(ClientEvent::LocalAppUpdated(updated_app))
+        (Some(ClientEvent::LocalAppUpdated(updated_app)), Some(token))
     } else {
-        None
+        (None, None)
     };
 
     let event_payload = LoResEventPayload::AppRegistered(AppRegisteredDataV1 {
         name: payload.app.name.clone(),
         version: payload.app.version.clone(),
+        url: payload.app.url.clone(),
     });
     info!("Prepared event payload: {:?}", event_payload);
 
@@ -155,7 +171,38 @@ async fn register_app(
         realtime_state.broadcast_app_event(event).await;
     }
 
-    (StatusCode::OK, ()).into_response()
+    (StatusCode::OK, Json(AppTokenResult { token })).into_response()
+}
+
+#[utoipa::path(
+    post, path = "/rotate_token",
+    request_body(content = LocalAppIdentifier, content_type = "application/json"),
+    responses(
+        (status = 200, body = AppTokenResult),
+        (status = NOT_FOUND, body = String),
+        (status = INTERNAL_SERVER_ERROR, body = String),
+    )
+)]
+async fn rotate_app_token(
+    _auth_session: AuthSession,
+    Extension(db): Extension<DatabaseState>,
+    Json(payload): Json<LocalAppIdentifier>,
+) -> impl IntoResponse {
+    match LocalAppsRepo::init()
+        .rotate_token(&db.node_data_pool, &payload.name, &payload.instance_id)
+        .await
+    {
+        Ok(Some(token)) => {
+            info!("Rotated app token for {}", payload.name);
+            (StatusCode::OK, Json(AppTokenResult { token: Some(token) })).into_response()
+        }
+        Ok(None) => (
+            StatusCode::NOT_FOUND,
+            Json("App is not bound to a region"),
+        )
+            .into_response(),
+        Err(e) => internal_server_error(e).into_response(),
+    }
 }
 
 #[utoipa::path(
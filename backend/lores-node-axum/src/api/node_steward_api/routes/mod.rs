@@ -1,4 +1,10 @@
+pub mod account;
+pub mod api_tokens;
+pub mod app_backups;
+pub mod app_deployments;
+pub mod app_quotas;
 pub mod local_apps;
 pub mod my_region_nodes;
 pub mod my_regions;
 pub mod network;
+pub mod recipes;
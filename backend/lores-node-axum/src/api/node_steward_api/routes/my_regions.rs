@@ -1,7 +1,6 @@
 use axum::{Extension, Json, extract::DefaultBodyLimit, http::StatusCode, response::IntoResponse};
 use serde::Deserialize;
-use sqlx::SqlitePool;
-use tracing::{info, warn};
+use tracing::warn;
 use utoipa::ToSchema;
 use utoipa_axum::{router::OpenApiRouter, routes};
 
@@ -13,15 +12,18 @@ use crate::{
         public_api::{client_events::ClientEvent, realtime::RealtimeState},
     },
     config::config_state::LoresNodeConfigState,
-    data::{entities::LatLng, projections_read::regions::RegionsReadRepo},
-    panda_comms::{
-        PandaContainer, PandaSubscriptionError, RegionAdminTopic, RegionId,
-        SubscriptionError,
-        lores_events::{
-            LoResEventPayload, RegionCreatedDataV1, RegionJoinRequestApprovedDataV1,
-            RegionJoinRequestedDataV1, RegionMapUpdatedDataV1,
+    data::entities::LatLng,
+    node_admin::{
+        NodeAdminError,
+        regions::{
+            self, ApproveJoinRequestData, CreateRegionData, JoinRegionRequestData,
+            ensure_controller_node,
         },
     },
+    panda_comms::{
+        PandaContainer, RegionAdminTopic, RegionId,
+        lores_events::{LoResEventPayload, RegionMapUpdatedDataV1},
+    },
 };
 
 pub fn router() -> OpenApiRouter {
@@ -37,18 +39,6 @@ pub fn router() -> OpenApiRouter {
         .routes(routes!(forget_region))
 }
 
-#[derive(Deserialize, ToSchema, Debug)]
-#[allow(dead_code)]
-pub struct CreateRegionData {
-    pub slug: String,
-    pub name: String,
-    pub organisation_name: Option<String>,
-    pub organisation_url: Option<String>,
-    pub node_steward_conduct_url: Option<String>,
-    pub user_conduct_url: Option<String>,
-    pub user_privacy_url: Option<String>,
-}
-
 #[utoipa::path(
     post,
     path = "/create",
@@ -65,70 +55,11 @@ async fn create_region(
     Extension(config_state): Extension<LoresNodeConfigState>,
     axum::extract::Json(data): axum::extract::Json<CreateRegionData>,
 ) -> impl IntoResponse {
-    info!("Creating region with data: {:?}", data);
-
-    if data.slug.is_empty() || data.name.is_empty() {
-        return (
-            StatusCode::BAD_REQUEST,
-            Json("Slug and name are required".to_string()),
-        )
-            .into_response();
-    }
-
-    info!(
-        "Validated region data: slug={}, name={}",
-        data.slug, data.name
-    );
-
-    // Generate a region ID and store it in the config
-    let region_id = match store_new_region_id(&config_state).await {
-        Ok(id) => {
-            info!("Generated new region ID: {}", id);
-            id
-        }
-        Err(e) => return internal_server_error(e).into_response(),
-    };
-
-    // Subscribe to the new region
-    if let Err(e) = panda_container.join_region(region_id.clone()).await {
-        return internal_server_error(e).into_response();
-    }
-
-    // Publish the RegionCreated event
-    let event_payload = LoResEventPayload::RegionCreated(RegionCreatedDataV1 {
-        slug: data.slug.clone(),
-        name: data.name.clone(),
-        organisation_name: data.organisation_name.clone(),
-        organisation_url: data.organisation_url.clone(),
-        node_steward_conduct_url: data.node_steward_conduct_url.clone(),
-        user_conduct_url: data.user_conduct_url.clone(),
-        user_privacy_url: data.user_privacy_url.clone(),
-    });
-    info!("Prepared event payload: {:?}", event_payload);
-
-    if let Err(e) = panda_container
-        .publish_persisted(
-            &RegionAdminTopic::new(region_id.clone()),
-            event_payload,
-            auth_session.user,
-        )
-        .await
+    match regions::create_region(&panda_container, &config_state, &data, auth_session.user).await
     {
-        return internal_server_error(e).into_response();
+        Ok(_) => (StatusCode::OK, ()).into_response(),
+        Err(e) => error_response(e),
     }
-
-    info!("Created new region with ID: {:?}", region_id);
-
-    return (StatusCode::OK, ()).into_response();
-}
-
-#[derive(Deserialize, ToSchema, Debug)]
-#[allow(dead_code)]
-pub struct JoinRegionRequestData {
-    pub region_id: String,
-    pub about_your_node: String,
-    pub about_your_stewards: String,
-    pub agreed_node_steward_conduct_url: Option<String>,
 }
 
 #[utoipa::path(
@@ -147,72 +78,10 @@ async fn join_region(
     Extension(config_state): Extension<LoresNodeConfigState>,
     axum::extract::Json(data): axum::extract::Json<JoinRegionRequestData>,
 ) -> impl IntoResponse {
-    // Validate data
-    if data.region_id.is_empty()
-        || data.region_id.len() != 64
-        || data.about_your_node.is_empty()
-        || data.about_your_stewards.is_empty()
-    {
-        return (
-            StatusCode::BAD_REQUEST,
-            Json("Invalid request data".to_string()),
-        )
-            .into_response();
-    }
-
-    let region_id = match RegionId::from_hex(data.region_id.as_str()) {
-        Ok(id) => id,
-        Err(e) => {
-            warn!("Invalid region ID: {:?}", e);
-            return (
-                StatusCode::BAD_REQUEST,
-                Json("Invalid region ID".to_string()),
-            )
-                .into_response();
-        }
-    };
-
-    // Store the region ID in the config if it's not already there
-    if let Err(e) = store_region_id(&config_state, &region_id).await {
-        return internal_server_error(e).into_response();
+    match regions::join_region(&panda_container, &config_state, &data, auth_session.user).await {
+        Ok(()) => (StatusCode::OK, ()).into_response(),
+        Err(e) => error_response(e),
     }
-
-    // Subscribe to the new region
-    if let Err(e) = panda_container.join_region(region_id.clone()).await {
-        if matches!(e, PandaSubscriptionError::SubscriptionError(SubscriptionError::AlreadySubscribed(_))) {
-            warn!("Already subscribed to region {:?}, proceeding", region_id);
-        } else {
-            return internal_server_error(e).into_response();
-        }
-    }
-
-    // Publish the RegionCreated event
-    let event_payload = LoResEventPayload::RegionJoinRequested(RegionJoinRequestedDataV1 {
-        about_your_node: data.about_your_node.clone(),
-        about_your_stewards: data.about_your_stewards.clone(),
-        agreed_node_steward_conduct_url: data.agreed_node_steward_conduct_url.clone(),
-    });
-    info!("Prepared event payload: {:?}", event_payload);
-
-    if let Err(e) = panda_container
-        .publish_persisted(
-            &RegionAdminTopic::new(region_id),
-            event_payload,
-            auth_session.user,
-        )
-        .await
-    {
-        return internal_server_error(e).into_response();
-    }
-
-    return (StatusCode::OK, ()).into_response();
-}
-
-#[derive(Deserialize, ToSchema, Debug)]
-#[allow(dead_code)]
-pub struct ApproveJoinRequestData {
-    pub region_id: String,
-    pub node_id: String,
 }
 
 #[utoipa::path(
@@ -231,59 +100,17 @@ async fn approve_join_request(
     Extension(db): Extension<DatabaseState>,
     axum::extract::Json(data): axum::extract::Json<ApproveJoinRequestData>,
 ) -> impl IntoResponse {
-    // Validate data
-    if data.region_id.is_empty()
-        || data.region_id.len() != 64
-        || data.node_id.is_empty()
-        || data.node_id.len() != 64
-    {
-        return (
-            StatusCode::BAD_REQUEST,
-            Json("Invalid request data".to_string()),
-        )
-            .into_response();
-    }
-
-    let region_id = match RegionId::from_hex(data.region_id.as_str()) {
-        Ok(id) => id,
-        Err(e) => {
-            warn!("Invalid region ID: {:?}", e);
-            return (
-                StatusCode::BAD_REQUEST,
-                Json("Invalid region ID".to_string()),
-            )
-                .into_response();
-        }
-    };
-
-    // Check that I am the controller node for this region
-    if let Err(e) = ensure_controller_node(&db.projections_pool, &region_id, &panda_container).await
-    {
-        warn!("Controller node check failed: {:?}", e);
-        return (
-            StatusCode::BAD_REQUEST,
-            Json(format!("Controller node check failed: {}", e)),
-        )
-            .into_response();
-    }
-
-    // Publish the RegionJoinRequestApproved event
-    let event_payload =
-        LoResEventPayload::RegionJoinRequestApproved(RegionJoinRequestApprovedDataV1 {
-            node_id: data.node_id.clone(),
-        });
-    if let Err(e) = panda_container
-        .publish_persisted(
-            &RegionAdminTopic::new(region_id),
-            event_payload,
-            auth_session.user,
-        )
-        .await
+    match regions::approve_join_request(
+        &panda_container,
+        &db.projections_pool,
+        &data,
+        auth_session.user,
+    )
+    .await
     {
-        return internal_server_error(e).into_response();
+        Ok(()) => (StatusCode::OK, ()).into_response(),
+        Err(e) => error_response(e),
     }
-
-    return (StatusCode::OK, ()).into_response();
 }
 
 #[derive(Deserialize, ToSchema, Debug)]
@@ -383,7 +210,7 @@ async fn update_map(
         return internal_server_error(e).into_response();
     }
 
-    return (StatusCode::OK, ()).into_response();
+    (StatusCode::OK, ()).into_response()
 }
 
 #[derive(Deserialize, ToSchema, Debug)]
@@ -405,33 +232,13 @@ pub struct ForgetRegionData {
 async fn forget_region(
     _auth_session: AuthSession,
     Extension(config_state): Extension<LoresNodeConfigState>,
+    Extension(panda_container): Extension<PandaContainer>,
     Extension(realtime_state): Extension<RealtimeState>,
     axum::extract::Json(data): axum::extract::Json<ForgetRegionData>,
 ) -> impl IntoResponse {
-    if data.region_id.is_empty() {
-        return (
-            StatusCode::BAD_REQUEST,
-            Json("Invalid region ID".to_string()),
-        )
-            .into_response();
-    }
-
-    if let Err(e) = config_state
-        .update(|config| {
-            let mut result = config.clone();
-            result.region_ids = Some(
-                result
-                    .region_ids
-                    .unwrap_or_default()
-                    .into_iter()
-                    .filter(|id| id != &data.region_id)
-                    .collect(),
-            );
-            result
-        })
-        .await
+    if let Err(e) = regions::forget_region(&panda_container, &config_state, &data.region_id).await
     {
-        return internal_server_error(e).into_response();
+        return error_response(e);
     }
 
     realtime_state
@@ -441,88 +248,12 @@ async fn forget_region(
     (StatusCode::OK, ()).into_response()
 }
 
-async fn store_new_region_id(
-    config_state: &LoresNodeConfigState,
-) -> Result<RegionId, anyhow::Error> {
-    let mut region_id_string: Option<String> = None;
-    config_state
-        .update(|config| {
-            let mut result = config.clone();
-            let mut region_ids: Vec<String> = result.region_ids.unwrap_or_else(|| vec![]);
-
-            while region_id_string.is_none()
-                || region_ids.contains(&region_id_string.clone().unwrap())
-            {
-                let new_id_string = RegionId::generate().to_hex();
-                info!("Trying new region id {}", new_id_string);
-                if !region_ids.contains(&new_id_string) {
-                    region_id_string = Some(new_id_string.clone());
-                }
-            }
-
-            region_ids.push(region_id_string.clone().unwrap());
-
-            info!("Setting region_ids to {:?}", region_ids);
-
-            result.region_ids = Some(region_ids);
-            result
-        })
-        .await?;
-
-    match region_id_string {
-        Some(id_string) => match RegionId::from_hex(&id_string) {
-            Ok(id) => Ok(id),
-            Err(e) => Err(anyhow::anyhow!("Failed to parse new region ID: {:?}", e)),
-        },
-        None => Err(anyhow::anyhow!("Failed to store new region ID")),
-    }
-}
-
-async fn store_region_id(
-    config_state: &LoresNodeConfigState,
-    region_id: &RegionId,
-) -> Result<(), anyhow::Error> {
-    config_state
-        .update(|config| {
-            let mut result = config.clone();
-            let mut region_ids: Vec<String> = result.region_ids.unwrap_or_else(|| vec![]);
-
-            if !region_ids.contains(&region_id.to_hex()) {
-                region_ids.push(region_id.to_hex());
-            }
-
-            info!("Setting region_ids to {:?}", region_ids);
-
-            result.region_ids = Some(region_ids);
-            result
-        })
-        .await?;
-
-    Ok(())
-}
-
-async fn ensure_controller_node(
-    pool: &SqlitePool,
-    region_id: &RegionId,
-    panda_container: &PandaContainer,
-) -> Result<(), String> {
-    let region = RegionsReadRepo::init()
-        .find(pool, &region_id.to_hex())
-        .await
-        .map_err(|_| "Failed to read region".to_string())?;
-    if region.is_none() {
-        return Err("Region not found".to_string());
-    }
-
-    let my_node_id_string = panda_container
-        .get_public_key()
-        .await
-        .map_err(|_| "Failed to get my node ID".to_string())?
-        .to_hex();
-
-    if region.unwrap().creator_node_id != Some(my_node_id_string) {
-        return Err("Only the controller node can perform this action".to_string());
+fn error_response(error: NodeAdminError) -> axum::response::Response {
+    match error {
+        NodeAdminError::BadRequest(message) => {
+            (StatusCode::BAD_REQUEST, Json(message)).into_response()
+        }
+        NodeAdminError::NotFound(message) => (StatusCode::NOT_FOUND, Json(message)).into_response(),
+        NodeAdminError::Internal(e) => internal_server_error(e).into_response(),
     }
-
-    Ok(())
 }
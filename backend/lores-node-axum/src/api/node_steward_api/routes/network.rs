@@ -1,23 +1,27 @@
 use axum::{http::StatusCode, response::IntoResponse, Extension, Json};
 use tracing::warn;
 use serde::Deserialize;
-use serde::Serialize;
 use utoipa::ToSchema;
 use utoipa_axum::{router::OpenApiRouter, routes};
 
 use crate::{
     config::config_state::LoresNodeConfigState,
-    data::projections_write::truncate_all,
-    panda_comms::{build_public_key_from_hex, PandaContainer},
+    node_admin::{
+        network::{self, OperationCountEntry},
+        NodeAdminError,
+    },
+    panda_comms::PandaContainer,
     DatabaseState,
 };
-use lores_p2panda::RelayUrl;
 
 pub fn router() -> OpenApiRouter {
     OpenApiRouter::new()
         .routes(routes!(add_bootstrap_node))
         .routes(routes!(replay_projections))
-        .routes(routes!(get_operation_counts))
+}
+
+pub fn read_router() -> OpenApiRouter {
+    OpenApiRouter::new().routes(routes!(get_operation_counts))
 }
 
 #[derive(Deserialize, ToSchema, Debug)]
@@ -25,16 +29,6 @@ struct BootstrapNodeRequest {
     node_id: String,
 }
 
-impl BootstrapNodeRequest {
-    fn validate(&self) -> Result<(), String> {
-        build_public_key_from_hex(&self.node_id).map_err(|_| {
-            "Invalid node_id format. Must be a hex string representing a public key.".to_string()
-        })?;
-
-        Ok(())
-    }
-}
-
 #[utoipa::path(
     post,
     path = "/bootstrap",
@@ -49,43 +43,11 @@ async fn add_bootstrap_node(
     Extension(config_state): Extension<LoresNodeConfigState>,
     axum::Json(payload): axum::Json<BootstrapNodeRequest>,
 ) -> impl IntoResponse {
-    if let Err(err) = payload.validate() {
-        return (StatusCode::BAD_REQUEST, err).into_response();
-    }
-
-    // Add the bootstrap node to the current PandaContainer
-    if let Err(e) = panda_container
-        .add_bootstrap_node(&payload.node_id, None)
-        .await
-    {
-        return (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            format!("Failed to add bootstrap node: {}", e),
-        )
-            .into_response();
-    }
-
-    // Add the new bootstrap node ID to the config
-    let update_result = config_state
-        .update(|config| {
-            let mut result = config.clone();
-            let mut bootstrap_node_ids = result.bootstrap_node_ids.unwrap_or_default();
-            if !bootstrap_node_ids.contains(&payload.node_id) {
-                bootstrap_node_ids.push(payload.node_id.clone());
-            }
-            result.bootstrap_node_ids = Some(bootstrap_node_ids);
-            result
-        })
-        .await;
-    if let Err(e) = update_result {
-        return (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            format!("Failed to update config: {}", e),
-        )
-            .into_response();
+    match network::add_bootstrap_node(&panda_container, &config_state, &payload.node_id).await {
+        Ok(()) => (StatusCode::OK, ()).into_response(),
+        Err(NodeAdminError::BadRequest(err)) => (StatusCode::BAD_REQUEST, err).into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
     }
-
-    (StatusCode::OK, ()).into_response()
 }
 
 #[utoipa::path(
@@ -100,39 +62,19 @@ async fn replay_projections(
     Extension(db): Extension<DatabaseState>,
     Extension(panda_container): Extension<PandaContainer>,
 ) -> impl IntoResponse {
-    if let Err(e) = truncate_all(&db.projections_pool).await {
-        warn!("Failed to truncate projection tables: {e}");
-        return (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            format!("Failed to truncate projection tables: {e}"),
-        )
-            .into_response();
-    }
-
-    match panda_container.replay_all_regions().await {
+    match network::start_replay(&db.projections_pool, &panda_container).await {
         Ok(count) => (
             StatusCode::OK,
             format!("Replaying {count} region(s) from the operations store"),
         )
             .into_response(),
         Err(e) => {
-            warn!("Failed to start replay: {e}");
-            (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                format!("Failed to start replay: {e}"),
-            )
-                .into_response()
+            warn!("{e}");
+            (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response()
         }
     }
 }
 
-#[derive(Serialize, ToSchema)]
-struct OperationCountEntry {
-    topic: String,
-    author_node_id: String,
-    count: i64,
-}
-
 #[utoipa::path(
     get,
     path = "/operations/counts",
@@ -144,18 +86,8 @@ struct OperationCountEntry {
 async fn get_operation_counts(
     Extension(panda_container): Extension<PandaContainer>,
 ) -> impl IntoResponse {
-    match panda_container.get_operation_counts_by_topic().await {
-        Ok(counts) => {
-            let entries: Vec<OperationCountEntry> = counts
-                .into_iter()
-                .map(|c| OperationCountEntry {
-                    topic: c.topic_hex,
-                    author_node_id: c.author_node_id,
-                    count: c.count,
-                })
-                .collect();
-            Json(entries).into_response()
-        }
+    match network::operation_counts(&panda_container).await {
+        Ok(entries) => Json(entries).into_response(),
         Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
     }
 }
@@ -0,0 +1,54 @@
+use axum::{Extension, Json, http::StatusCode, response::IntoResponse};
+use tracing::warn;
+use utoipa_axum::{router::OpenApiRouter, routes};
+
+use crate::{
+    DatabaseState,
+    api::helpers::internal_server_error,
+    local_apps::{
+        find_local_apps,
+        recipe_catalogue::{AppUpgrade, Recipe, RecipeCatalogue},
+    },
+};
+
+pub fn router() -> OpenApiRouter {
+    OpenApiRouter::new()
+        .routes(routes!(list_recipes))
+        .routes(routes!(list_app_upgrades))
+}
+
+#[utoipa::path(get, path = "/", responses(
+    (status = OK, body = Vec<Recipe>),
+    (status = INTERNAL_SERVER_ERROR, body = String),
+),)]
+async fn list_recipes(Extension(catalogue): Extension<RecipeCatalogue>) -> impl IntoResponse {
+    match catalogue.recipes() {
+        Ok(recipes) => (StatusCode::OK, Json(recipes)).into_response(),
+        Err(e) => {
+            warn!("{}", e);
+            internal_server_error(e).into_response()
+        }
+    }
+}
+
+#[utoipa::path(get, path = "/upgrades", responses(
+    (status = OK, body = Vec<AppUpgrade>),
+    (status = INTERNAL_SERVER_ERROR, body = String),
+),)]
+async fn list_app_upgrades(
+    Extension(db): Extension<DatabaseState>,
+    Extension(catalogue): Extension<RecipeCatalogue>,
+) -> impl IntoResponse {
+    let apps = match find_local_apps(&db.node_data_pool).await {
+        Ok(apps) => apps,
+        Err(e) => return internal_server_error(e).into_response(),
+    };
+
+    match catalogue.upgrades_for(&apps) {
+        Ok(upgrades) => (StatusCode::OK, Json(upgrades)).into_response(),
+        Err(e) => {
+            warn!("{}", e);
+            internal_server_error(e).into_response()
+        }
+    }
+}
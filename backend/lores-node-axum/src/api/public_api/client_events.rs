@@ -1,8 +1,17 @@
 use serde::Serialize;
 use utoipa::ToSchema;
 
-use crate::data::entities::{
-    LocalApp, Region, RegionAppWithInstallations, RegionNodeDetails, RegionWithNodes,
+use crate::{
+    data::{
+        entities::{
+            LocalApp, Region, RegionAppWithInstallations, RegionNodeDetails, RegionWithNodes,
+        },
+        node_data::app_backups_repo::AppBackup,
+    },
+    local_apps::{
+        deployments::{AppDeploymentJob, AppDeploymentLogLine},
+        recipe_catalogue::AppUpgrade,
+    },
 };
 
 #[derive(Debug, Clone, Serialize, ToSchema)]
@@ -14,4 +23,8 @@ pub enum ClientEvent {
     RegionForgotten(String),
     LocalAppCreated(LocalApp),
     LocalAppUpdated(LocalApp),
+    AppDeploymentJobUpdated(AppDeploymentJob),
+    AppDeploymentLog(AppDeploymentLogLine),
+    AppUpgradesAvailable(Vec<AppUpgrade>),
+    AppBackupUpdated(AppBackup),
 }
@@ -13,7 +13,7 @@ use futures_util::{
 use std::sync::Arc;
 use tokio::sync::{
     Mutex,
-    broadcast::{self, Receiver, Sender},
+    broadcast::{self, Receiver, Sender, error::RecvError},
 };
 
 use tracing::{info, warn};
@@ -26,7 +26,7 @@ pub struct RealtimeState {
 }
 impl RealtimeState {
     pub fn new() -> Self {
-        let (tx, _) = broadcast::channel::<ClientEvent>(32);
+        let (tx, _) = broadcast::channel::<ClientEvent>(256);
         Self {
             broadcast_tx: Arc::new(Mutex::new(tx)),
         }
@@ -38,6 +38,11 @@ impl RealtimeState {
         }
     }
 
+    #[cfg(test)]
+    pub(crate) async fn subscribe(&self) -> Receiver<ClientEvent> {
+        self.broadcast_tx.lock().await.subscribe()
+    }
+
     pub async fn broadcast_app_event(&self, event: ClientEvent) {
         match self.broadcast_tx.lock().await.send(event.clone()) {
             Ok(_) => {}
@@ -87,7 +92,17 @@ async fn recv_broadcast(
     client_tx_mutex: Arc<Mutex<SplitSink<WebSocket, Message>>>,
     mut broadcast_rx: Receiver<ClientEvent>,
 ) {
-    while let Ok(msg) = broadcast_rx.recv().await {
+    loop {
+        let msg = match broadcast_rx.recv().await {
+            Ok(msg) => msg,
+            Err(RecvError::Lagged(skipped)) => {
+                // Deployment logs can arrive in bursts, so skip ahead rather than disconnecting.
+                warn!("Websocket client lagged, skipped {} events", skipped);
+                continue;
+            }
+            Err(RecvError::Closed) => return,
+        };
+
         let mut client_tx = client_tx_mutex.lock().await;
 
         if client_tx.send(message_from_event(&msg)).await.is_err() {
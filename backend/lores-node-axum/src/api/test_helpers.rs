@@ -0,0 +1,142 @@
+use axum::{
+    Extension, Router,
+    body::Body,
+    http::{HeaderValue, Method, Request, Response, StatusCode, header},
+    middleware,
+};
+use axum_login::AuthManagerLayerBuilder;
+use password_auth::generate_hash;
+use sqlx::SqlitePool;
+use tempfile::TempDir;
+use tower::ServiceExt;
+use tower_sessions::SessionManagerLayer;
+use utoipa_axum::router::OpenApiRouter;
+
+use crate::{
+    DatabaseState,
+    api::{
+        api_router,
+        auth_api::{auth_backend::AppAuthBackend, bearer_auth::bearer_auth},
+        public_api::realtime::RealtimeState,
+    },
+    config::{config::LoresNodeConfig, config_state::LoresNodeConfigState},
+    data::{
+        node_data::node_stewards::{NodeStewardRole, NodeStewardRow, NodeStewardsRepo},
+        setup::{
+            prepare_session_store, prepare_test_node_data_database,
+            prepare_test_projections_database,
+        },
+    },
+    local_apps::backups::AppBackups,
+};
+
+pub(crate) const PASSWORD: &str = "correct horse";
+
+/// The HTTP API with the layers `main` puts around it, on fresh databases.
+pub(crate) struct TestApp {
+    router: Router,
+    pub node_data_pool: SqlitePool,
+    _dir: TempDir,
+}
+
+impl TestApp {
+    pub async fn new() -> Self {
+        let dir = tempfile::tempdir().unwrap();
+        let projections_pool = prepare_test_projections_database(&dir).await.unwrap();
+        let node_data_pool = prepare_test_node_data_database(&dir).await.unwrap();
+
+        let config_state = LoresNodeConfigState::new(&LoresNodeConfig::default());
+        let session_store = prepare_session_store(&node_data_pool).await.unwrap();
+        let session_layer = SessionManagerLayer::new(session_store).with_secure(false);
+        let backend = AppAuthBackend::new(&config_state, &node_data_pool);
+        let auth_layer = AuthManagerLayerBuilder::new(backend, session_layer).build();
+
+        let (router, _) = OpenApiRouter::new().merge(api_router()).split_for_parts();
+        let router = router
+            .layer(Extension(DatabaseState {
+                projections_pool,
+                node_data_pool: node_data_pool.clone(),
+            }))
+            .layer(Extension(config_state))
+            .layer(middleware::from_fn(bearer_auth))
+            .layer(auth_layer)
+            .layer(Extension(RealtimeState::new()))
+            .layer(Extension(AppBackups::from_env()));
+
+        TestApp {
+            router,
+            node_data_pool,
+            _dir: dir,
+        }
+    }
+
+    /// Creates an enabled steward with [`PASSWORD`] and returns their id.
+    pub async fn steward(&self, roles: &[NodeStewardRole]) -> String {
+        let mut row = NodeStewardRow::new("Sam".to_string()).with_roles(roles);
+        row.hashed_password = Some(generate_hash(PASSWORD));
+        NodeStewardsRepo::init()
+            .create(&self.node_data_pool, &row)
+            .await
+            .unwrap();
+        row.id
+    }
+
+    /// Logs the steward in and returns the session cookie.
+    pub async fn log_in(&self, steward_id: &str) -> HeaderValue {
+        let body = serde_json::json!({ "id": steward_id, "password": PASSWORD });
+        let response = self
+            .send(
+                request(Method::POST, "/auth_api/node_steward/login"),
+                Some(body),
+            )
+            .await;
+        assert_eq!(response.status(), StatusCode::OK);
+        session_cookie(&response).unwrap()
+    }
+
+    pub async fn send(
+        &self,
+        request: axum::http::request::Builder,
+        json: Option<serde_json::Value>,
+    ) -> Response<Body> {
+        let request = match json {
+            Some(json) => request
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(Body::from(json.to_string())),
+            None => request.body(Body::empty()),
+        };
+        self.router.clone().oneshot(request.unwrap()).await.unwrap()
+    }
+}
+
+/// The session cookie the response sets, if any.
+pub(crate) fn session_cookie(response: &Response<Body>) -> Option<HeaderValue> {
+    let set_cookie = response.headers().get(header::SET_COOKIE)?.to_str().ok()?;
+    let cookie = set_cookie.split(';').next()?;
+    HeaderValue::from_str(cookie).ok()
+}
+
+pub(crate) async fn json_body(response: Response<Body>) -> serde_json::Value {
+    let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    serde_json::from_slice(&bytes).unwrap()
+}
+
+pub(crate) fn request(method: Method, path: &str) -> axum::http::request::Builder {
+    Request::builder().method(method).uri(path)
+}
+
+pub(crate) fn with_cookie(
+    request: axum::http::request::Builder,
+    cookie: &HeaderValue,
+) -> axum::http::request::Builder {
+    request.header(header::COOKIE, cookie)
+}
+
+pub(crate) fn with_token(
+    request: axum::http::request::Builder,
+    token: &str,
+) -> axum::http::request::Builder {
+    request.header(header::AUTHORIZATION, format!("Bearer {token}"))
+}
@@ -0,0 +1,551 @@
+//! `lores-node` subcommands for administering a node from a shell, e.g. over
+//! SSH on a headless machine. They use the same databases, config file and
+//! p2panda identity as the running server, so run them from the same working
+//! directory and environment.
+//!
+//! Commands that publish to a region or touch the network start this node's
+//! p2panda node in-process. Stop the `lores-node` service before running them,
+//! so two nodes with the same identity aren't online at once; what they
+//! publish is synced to the region when the service starts again.
+
+use clap::{Args, Parser, Subcommand};
+use serde::Serialize;
+use serde_json::json;
+use sqlx::SqlitePool;
+use tokio::sync::mpsc;
+use tracing_subscriber::EnvFilter;
+
+use crate::{
+    api::public_api::realtime::RealtimeState,
+    config::{config::LoresNodeConfig, config_state::LoresNodeConfigState},
+    data::{
+        self,
+        entities::RegionNodeStatus,
+        node_data::{
+            audit_log_repo::{AuditActor, AuditLogRepo, NewAuditLogEntry},
+            node_stewards::NodeStewardRole,
+        },
+    },
+    node_admin::{
+        NodeAdminError, network, node_stewards,
+        regions::{self, ApproveJoinRequestData, CreateRegionData, JoinRegionRequestData},
+    },
+    panda_comms::{
+        PandaContainer, build_public_key_from_hex, lores_events::LoResEvent, start_panda,
+        start_panda_event_handler,
+    },
+};
+
+#[derive(Parser)]
+#[command(name = "lores-node", version, about = "LoRes Node")]
+pub struct Cli {
+    /// Print results as JSON, for scripting
+    #[arg(long, global = true)]
+    pub json: bool,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Run the node: web server, gRPC server and p2panda node. This is the
+    /// default when no command is given.
+    Serve,
+
+    /// Show this node's identity and configuration
+    Status,
+
+    /// Create, join and manage regions
+    #[command(subcommand)]
+    Region(RegionCommand),
+
+    /// Manage this node's p2panda network connection and data
+    #[command(subcommand)]
+    Network(NetworkCommand),
+
+    /// Manage the node stewards who can log in to this node
+    #[command(subcommand)]
+    Steward(StewardCommand),
+}
+
+#[derive(Subcommand)]
+pub enum RegionCommand {
+    /// List the regions this node has created or joined, with their nodes and
+    /// pending join requests
+    List,
+
+    /// Create a region with this node as its controller
+    Create(CreateRegionArgs),
+
+    /// Ask to join an existing region
+    Join(JoinRegionArgs),
+
+    /// Let a node that asked to join a region in. Only the region's controller
+    /// node can do this.
+    ApproveJoin { region_id: String, node_id: String },
+
+    /// Stop following a region and remove it from this node's config
+    Forget { region_id: String },
+}
+
+#[derive(Args)]
+pub struct CreateRegionArgs {
+    #[arg(long)]
+    slug: String,
+    #[arg(long)]
+    name: String,
+    #[arg(long)]
+    organisation_name: Option<String>,
+    #[arg(long)]
+    organisation_url: Option<String>,
+    #[arg(long)]
+    node_steward_conduct_url: Option<String>,
+    #[arg(long)]
+    user_conduct_url: Option<String>,
+    #[arg(long)]
+    user_privacy_url: Option<String>,
+}
+
+#[derive(Args)]
+pub struct JoinRegionArgs {
+    region_id: String,
+    /// Tell the region's stewards about this node
+    #[arg(long)]
+    about_your_node: String,
+    /// Tell the region's stewards about who looks after this node
+    #[arg(long)]
+    about_your_stewards: String,
+    /// The region's node steward code of conduct, if you have agreed to it
+    #[arg(long)]
+    agreed_node_steward_conduct_url: Option<String>,
+}
+
+#[derive(Subcommand)]
+pub enum NetworkCommand {
+    /// Connect to another node to find peers through
+    AddBootstrap { node_id: String },
+
+    /// Rebuild the projection tables from the operations store
+    Replay,
+
+    /// Count stored operations by topic and author
+    OperationCounts,
+}
+
+#[derive(Subcommand)]
+pub enum StewardCommand {
+    /// List node stewards
+    List,
+
+    /// Invite a node steward. Prints the token they set their password with.
+    Create {
+        name: String,
+        /// viewer, app-manager or region-manager; repeat for more than one.
+        /// Defaults to every role.
+        #[arg(long = "role")]
+        roles: Vec<NodeStewardRole>,
+    },
+
+    /// Replace a node steward's roles
+    SetRoles {
+        steward_id: String,
+        /// viewer, app-manager or region-manager
+        roles: Vec<NodeStewardRole>,
+    },
+
+    /// Issue a new password reset token for a node steward
+    ResetToken { steward_id: String },
+
+    /// Stop a node steward from logging in
+    Disable { steward_id: String },
+
+    /// Let a disabled node steward log in again
+    Enable { steward_id: String },
+}
+
+#[derive(Serialize)]
+struct NodeStatus {
+    node_id: Option<String>,
+    network_name: Option<String>,
+    region_ids: Vec<String>,
+    bootstrap_node_ids: Vec<String>,
+}
+
+/// The node's shared state, as the server sets it up.
+struct NodeContext {
+    config_state: LoresNodeConfigState,
+    projections_pool: SqlitePool,
+    node_data_pool: SqlitePool,
+    realtime_state: RealtimeState,
+    panda_container: PandaContainer,
+    events_rx: Option<mpsc::Receiver<LoResEvent>>,
+}
+
+impl NodeContext {
+    async fn open() -> Result<Self, anyhow::Error> {
+        let config = LoresNodeConfig::load();
+        let projections_pool = data::setup::prepare_projections_database().await?;
+        let node_data_pool = data::setup::prepare_node_data_database().await?;
+        let (events_tx, events_rx) = mpsc::channel(32);
+
+        Ok(Self {
+            config_state: LoresNodeConfigState::new(&config),
+            projections_pool,
+            node_data_pool,
+            realtime_state: RealtimeState::new(),
+            panda_container: PandaContainer::new(events_tx),
+            events_rx: Some(events_rx),
+        })
+    }
+
+    /// Starts the p2panda node and joins the configured regions, as the
+    /// server does on startup.
+    async fn start_panda(&mut self) -> Result<(), anyhow::Error> {
+        if let Some(events_rx) = self.events_rx.take() {
+            start_panda_event_handler(
+                events_rx,
+                self.projections_pool.clone(),
+                self.realtime_state.clone(),
+            );
+        }
+        start_panda(
+            &self.config_state,
+            &self.panda_container,
+            &self.projections_pool,
+        )
+        .await;
+
+        if !self.panda_container.is_started().await {
+            return Err(anyhow::anyhow!(
+                "the p2panda node could not be started; see the log above"
+            ));
+        }
+        Ok(())
+    }
+
+    async fn node_id(&self) -> Result<lores_p2panda::p2panda_core::VerifyingKey, anyhow::Error> {
+        let config = self.config_state.get().await;
+        let public_key_hex = config
+            .public_key_hex
+            .ok_or_else(|| anyhow::anyhow!("this node has no key yet; run the server once"))?;
+        build_public_key_from_hex(&public_key_hex)
+    }
+}
+
+pub async fn run(command: Command, json: bool) {
+    // Logs go to stderr so that output can be piped.
+    tracing_subscriber::fmt()
+        .with_writer(std::io::stderr)
+        .with_env_filter(
+            EnvFilter::try_from_default_env()
+                .or_else(|_| EnvFilter::try_new("warn"))
+                .unwrap(),
+        )
+        .init();
+
+    if let Err(e) = run_command(command, json).await {
+        eprintln!("error: {e}");
+        std::process::exit(1);
+    }
+}
+
+async fn run_command(command: Command, json: bool) -> Result<(), anyhow::Error> {
+    let mut node = NodeContext::open().await?;
+    let audited = changes_node(&command);
+
+    match command {
+        Command::Serve => unreachable!("serve is handled by main"),
+        Command::Status => {
+            let config = node.config_state.get().await;
+            let status = NodeStatus {
+                node_id: config.public_key_hex,
+                network_name: config.network_name,
+                region_ids: config.region_ids.unwrap_or_default(),
+                bootstrap_node_ids: config.bootstrap_node_ids.unwrap_or_default(),
+            };
+            print(json, &status, |status| {
+                let mut lines = vec![
+                    format!(
+                        "node id:      {}",
+                        status.node_id.as_deref().unwrap_or("(not set)")
+                    ),
+                    format!(
+                        "network name: {}",
+                        status.network_name.as_deref().unwrap_or("(not set)")
+                    ),
+                ];
+                lines.extend(
+                    status
+                        .region_ids
+                        .iter()
+                        .map(|id| format!("region:       {id}")),
+                );
+                lines.extend(
+                    status
+                        .bootstrap_node_ids
+                        .iter()
+                        .map(|id| format!("bootstrap:    {id}")),
+                );
+                lines.join("\n")
+            })?;
+        }
+        Command::Region(command) => run_region_command(&mut node, command, json).await?,
+        Command::Network(command) => run_network_command(&mut node, command, json).await?,
+        Command::Steward(command) => run_steward_command(&node, command, json).await?,
+    }
+
+    if audited {
+        let entry = NewAuditLogEntry {
+            actor: AuditActor::Cli,
+            node_steward_id: None,
+            api_token_id: None,
+            method: "CLI".to_string(),
+            path: std::env::args().skip(1).collect::<Vec<_>>().join(" "),
+            status: None,
+        };
+        AuditLogRepo::init()
+            .append(&node.node_data_pool, &entry)
+            .await?;
+    }
+
+    Ok(())
+}
+
+/// Commands that change the node rather than only reading it. These are
+/// recorded in the audit log.
+fn changes_node(command: &Command) -> bool {
+    match command {
+        Command::Serve | Command::Status => false,
+        Command::Region(command) => !matches!(command, RegionCommand::List),
+        Command::Network(command) => !matches!(command, NetworkCommand::OperationCounts),
+        Command::Steward(command) => !matches!(command, StewardCommand::List),
+    }
+}
+
+async fn run_region_command(
+    node: &mut NodeContext,
+    command: RegionCommand,
+    json: bool,
+) -> Result<(), NodeAdminError> {
+    match command {
+        RegionCommand::List => {
+            let node_id = node.node_id().await?;
+            let regions =
+                regions::list_regions(&node.config_state, &node.projections_pool, &node_id).await?;
+            print(json, &regions, |regions| {
+                let mut lines = Vec::new();
+                for entry in regions {
+                    let region = &entry.region;
+                    lines.push(format!(
+                        "{}  {}  ({} nodes)",
+                        region.id,
+                        region.name.as_deref().unwrap_or("(unnamed)"),
+                        entry.nodes.len()
+                    ));
+                    for region_node in &entry.nodes {
+                        if matches!(region_node.status, Some(RegionNodeStatus::RequestedToJoin)) {
+                            lines.push(format!(
+                                "  join request from {}  {}",
+                                region_node.node_id,
+                                region_node.name.as_deref().unwrap_or("")
+                            ));
+                        }
+                    }
+                }
+                lines.join("\n")
+            })?;
+        }
+        RegionCommand::Create(args) => {
+            node.start_panda().await?;
+            let data = CreateRegionData {
+                slug: args.slug,
+                name: args.name,
+                organisation_name: args.organisation_name,
+                organisation_url: args.organisation_url,
+                node_steward_conduct_url: args.node_steward_conduct_url,
+                user_conduct_url: args.user_conduct_url,
+                user_privacy_url: args.user_privacy_url,
+            };
+            let region_id =
+                regions::create_region(&node.panda_container, &node.config_state, &data, None)
+                    .await?;
+            let region_id = region_id.to_hex();
+            print(json, &json!({ "region_id": region_id }), |_| {
+                format!("created region {region_id}")
+            })?;
+        }
+        RegionCommand::Join(args) => {
+            node.start_panda().await?;
+            let data = JoinRegionRequestData {
+                region_id: args.region_id,
+                about_your_node: args.about_your_node,
+                about_your_stewards: args.about_your_stewards,
+                agreed_node_steward_conduct_url: args.agreed_node_steward_conduct_url,
+            };
+            regions::join_region(&node.panda_container, &node.config_state, &data, None).await?;
+            print(json, &json!({ "region_id": data.region_id }), |_| {
+                format!("asked to join region {}", data.region_id)
+            })?;
+        }
+        RegionCommand::ApproveJoin { region_id, node_id } => {
+            node.start_panda().await?;
+            let data = ApproveJoinRequestData { region_id, node_id };
+            regions::approve_join_request(
+                &node.panda_container,
+                &node.projections_pool,
+                &data,
+                None,
+            )
+            .await?;
+            print(
+                json,
+                &json!({ "region_id": data.region_id, "node_id": data.node_id }),
+                |_| {
+                    format!(
+                        "approved {} to join region {}",
+                        data.node_id, data.region_id
+                    )
+                },
+            )?;
+        }
+        RegionCommand::Forget { region_id } => {
+            node.start_panda().await?;
+            regions::forget_region(&node.panda_container, &node.config_state, &region_id).await?;
+            print(json, &json!({ "region_id": region_id }), |_| {
+                format!("forgot region {region_id}")
+            })?;
+        }
+    }
+
+    Ok(())
+}
+
+async fn run_network_command(
+    node: &mut NodeContext,
+    command: NetworkCommand,
+    json: bool,
+) -> Result<(), NodeAdminError> {
+    node.start_panda().await?;
+
+    match command {
+        NetworkCommand::AddBootstrap { node_id } => {
+            network::add_bootstrap_node(&node.panda_container, &node.config_state, &node_id)
+                .await?;
+            print(json, &json!({ "node_id": node_id }), |_| {
+                format!("added bootstrap node {node_id}")
+            })?;
+        }
+        NetworkCommand::Replay => {
+            let count = network::replay(
+                &node.projections_pool,
+                &node.panda_container,
+                &node.realtime_state,
+            )
+            .await?;
+            print(json, &json!({ "regions": count }), |_| {
+                format!("replayed {count} region(s) from the operations store")
+            })?;
+        }
+        NetworkCommand::OperationCounts => {
+            let counts = network::operation_counts(&node.panda_container).await?;
+            print(json, &counts, |counts| {
+                counts
+                    .iter()
+                    .map(|c| format!("{}  {}  {}", c.topic, c.author_node_id, c.count))
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            })?;
+        }
+    }
+
+    Ok(())
+}
+
+async fn run_steward_command(
+    node: &NodeContext,
+    command: StewardCommand,
+    json: bool,
+) -> Result<(), NodeAdminError> {
+    let pool = &node.node_data_pool;
+
+    match command {
+        StewardCommand::List => {
+            let stewards = node_stewards::list(pool).await?;
+            print(json, &stewards, |stewards| {
+                stewards
+                    .iter()
+                    .map(|s| {
+                        format!(
+                            "{}  {}  {:?}  {}",
+                            s.id,
+                            s.name,
+                            s.status,
+                            role_names(&s.roles)
+                        )
+                    })
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            })?;
+        }
+        StewardCommand::Create { name, roles } => {
+            let roles = (!roles.is_empty()).then_some(roles);
+            let created = node_stewards::create(pool, name, roles).await?;
+            print(json, &created, |created| {
+                format!(
+                    "created node steward {} ({})\npassword reset token: {}",
+                    created.node_steward.name,
+                    created.node_steward.id,
+                    created.password_reset_token
+                )
+            })?;
+        }
+        StewardCommand::ResetToken { steward_id } => {
+            let reset = node_stewards::reset_token(pool, steward_id).await?;
+            print(json, &reset, |reset| {
+                format!("password reset token: {}", reset.password_reset_token)
+            })?;
+        }
+        StewardCommand::SetRoles { steward_id, roles } => {
+            let steward = node_stewards::set_roles(pool, steward_id, &roles).await?;
+            print(json, &steward, |s| {
+                format!("{}  {}  {}", s.id, s.name, role_names(&s.roles))
+            })?;
+        }
+        StewardCommand::Disable { steward_id } => {
+            let steward = node_stewards::set_enabled(pool, steward_id, false).await?;
+            print(json, &steward, |s| {
+                format!("{}  {}  {:?}", s.id, s.name, s.status)
+            })?;
+        }
+        StewardCommand::Enable { steward_id } => {
+            let steward = node_stewards::set_enabled(pool, steward_id, true).await?;
+            print(json, &steward, |s| {
+                format!("{}  {}  {:?}", s.id, s.name, s.status)
+            })?;
+        }
+    }
+
+    Ok(())
+}
+
+fn role_names(roles: &[NodeStewardRole]) -> String {
+    let names: Vec<&str> = roles.iter().map(|role| role.as_str()).collect();
+    names.join(",")
+}
+
+fn print<T: Serialize>(
+    json: bool,
+    value: &T,
+    text: impl FnOnce(&T) -> String,
+) -> Result<(), anyhow::Error> {
+    if json {
+        println!("{}", serde_json::to_string_pretty(value)?);
+    } else {
+        let text = text(value);
+        if !text.is_empty() {
+            println!("{text}");
+        }
+    }
+    Ok(())
+}
@@ -167,25 +167,20 @@ pub struct RegionWithNodes {
     pub nodes: Vec<RegionNodeDetails>,
 }
 
-#[derive(Serialize, Deserialize, ToSchema, Debug, Clone)]
+#[derive(Serialize, Deserialize, ToSchema, Debug, Clone, PartialEq)]
 pub struct NodeAppUrl {
     pub internet_url: Option<String>,
     pub local_network_url: Option<String>,
 }
 
-#[derive(Serialize, Deserialize, ToSchema, Debug, Clone, PartialEq, Eq)]
+#[derive(Serialize, Deserialize, ToSchema, Debug, Clone, PartialEq, Eq, Default)]
 #[serde(rename_all = "lowercase")]
 pub enum LocalAppSource {
+    #[default]
     Docker,
     Db,
 }
 
-impl Default for LocalAppSource {
-    fn default() -> Self {
-        Self::Docker
-    }
-}
-
 #[derive(Serialize, Deserialize, ToSchema, Debug, Clone)]
 pub struct LocalApp {
     pub name: String,
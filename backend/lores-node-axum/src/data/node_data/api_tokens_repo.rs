@@ -0,0 +1,339 @@
+use p2panda_core::Hash;
+use pwgen2::pwgen::{PasswordConfig, generate_password};
+use serde::Serialize;
+use short_uuid::ShortUuid;
+use sqlx::{Sqlite, SqlitePool};
+use utoipa::ToSchema;
+
+use super::node_stewards::{NodeStewardRole, parse_roles, roles_column};
+
+/// Prefix of every API token, so they are easy to spot in scripts and logs.
+const TOKEN_PREFIX: &str = "lores_";
+
+#[derive(sqlx::FromRow)]
+struct ApiTokenRow {
+    id: String,
+    node_steward_id: String,
+    name: String,
+    scopes: String,
+    expires_at: Option<i64>,
+    created_at: i64,
+    last_used_at: Option<i64>,
+    revoked_at: Option<i64>,
+}
+
+#[derive(Serialize, ToSchema, Debug, Clone)]
+pub struct ApiToken {
+    pub id: String,
+    pub node_steward_id: String,
+    pub name: String,
+    /// Roles the token can use, as long as its steward still has them
+    pub scopes: Vec<NodeStewardRole>,
+    pub expires_at: Option<i64>,
+    pub created_at: i64,
+    pub last_used_at: Option<i64>,
+    pub revoked_at: Option<i64>,
+}
+
+impl From<ApiTokenRow> for ApiToken {
+    fn from(row: ApiTokenRow) -> Self {
+        ApiToken {
+            id: row.id,
+            node_steward_id: row.node_steward_id,
+            name: row.name,
+            scopes: parse_roles(&row.scopes),
+            expires_at: row.expires_at,
+            created_at: row.created_at,
+            last_used_at: row.last_used_at,
+            revoked_at: row.revoked_at,
+        }
+    }
+}
+
+#[derive(sqlx::FromRow, Serialize, ToSchema, Debug, Clone)]
+pub struct ApiTokenUse {
+    pub method: String,
+    pub path: String,
+    pub used_at: i64,
+}
+
+pub struct ApiTokensRepo {}
+
+impl ApiTokensRepo {
+    pub fn init() -> Self {
+        ApiTokensRepo {}
+    }
+
+    /// Mints a token for the steward. Only its hash is stored, so the
+    /// returned token has to be handed over now.
+    pub async fn create(
+        &self,
+        pool: &SqlitePool,
+        node_steward_id: &str,
+        name: &str,
+        scopes: &[NodeStewardRole],
+        expires_at: Option<i64>,
+    ) -> Result<(ApiToken, String), sqlx::Error> {
+        let token = new_api_token();
+
+        let row = sqlx::query_as::<Sqlite, ApiTokenRow>(
+            "
+            INSERT INTO api_tokens (id, node_steward_id, name, token_hash, scopes, expires_at)
+            VALUES (?, ?, ?, ?, ?, ?)
+            RETURNING id, node_steward_id, name, scopes, expires_at, created_at, last_used_at, revoked_at
+            ",
+        )
+        .bind(ShortUuid::generate().to_string())
+        .bind(node_steward_id)
+        .bind(name)
+        .bind(hash_api_token(&token))
+        .bind(roles_column(scopes))
+        .bind(expires_at)
+        .fetch_one(pool)
+        .await?;
+
+        Ok((row.into(), token))
+    }
+
+    pub async fn for_steward(
+        &self,
+        pool: &SqlitePool,
+        node_steward_id: &str,
+    ) -> Result<Vec<ApiToken>, sqlx::Error> {
+        let rows = sqlx::query_as::<Sqlite, ApiTokenRow>(
+            "
+            SELECT id, node_steward_id, name, scopes, expires_at, created_at, last_used_at, revoked_at
+            FROM api_tokens
+            WHERE node_steward_id = ?
+            ORDER BY created_at DESC, rowid DESC
+            ",
+        )
+        .bind(node_steward_id)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(rows.into_iter().map(ApiToken::from).collect())
+    }
+
+    pub async fn find_for_steward(
+        &self,
+        pool: &SqlitePool,
+        node_steward_id: &str,
+        id: &str,
+    ) -> Result<Option<ApiToken>, sqlx::Error> {
+        let row = sqlx::query_as::<Sqlite, ApiTokenRow>(
+            "
+            SELECT id, node_steward_id, name, scopes, expires_at, created_at, last_used_at, revoked_at
+            FROM api_tokens
+            WHERE node_steward_id = ? AND id = ?
+            ",
+        )
+        .bind(node_steward_id)
+        .bind(id)
+        .fetch_optional(pool)
+        .await?;
+
+        Ok(row.map(ApiToken::from))
+    }
+
+    /// The token with this secret, unless it has been revoked or has expired.
+    pub async fn find_usable(
+        &self,
+        pool: &SqlitePool,
+        token: &str,
+    ) -> Result<Option<ApiToken>, sqlx::Error> {
+        let row = sqlx::query_as::<Sqlite, ApiTokenRow>(
+            "
+            SELECT id, node_steward_id, name, scopes, expires_at, created_at, last_used_at, revoked_at
+            FROM api_tokens
+            WHERE token_hash = ?
+                AND revoked_at IS NULL
+                AND (expires_at IS NULL OR expires_at > unixepoch())
+            ",
+        )
+        .bind(hash_api_token(token))
+        .fetch_optional(pool)
+        .await?;
+
+        Ok(row.map(ApiToken::from))
+    }
+
+    /// Revokes one of the steward's tokens. Returns `None` if they have no
+    /// token with this id; revoking a revoked token keeps its first
+    /// revocation time.
+    pub async fn revoke(
+        &self,
+        pool: &SqlitePool,
+        node_steward_id: &str,
+        id: &str,
+    ) -> Result<Option<ApiToken>, sqlx::Error> {
+        let row = sqlx::query_as::<Sqlite, ApiTokenRow>(
+            "
+            UPDATE api_tokens
+            SET revoked_at = COALESCE(revoked_at, unixepoch())
+            WHERE node_steward_id = ? AND id = ?
+            RETURNING id, node_steward_id, name, scopes, expires_at, created_at, last_used_at, revoked_at
+            ",
+        )
+        .bind(node_steward_id)
+        .bind(id)
+        .fetch_optional(pool)
+        .await?;
+
+        Ok(row.map(ApiToken::from))
+    }
+
+    pub async fn record_use(
+        &self,
+        pool: &SqlitePool,
+        id: &str,
+        method: &str,
+        path: &str,
+    ) -> Result<(), sqlx::Error> {
+        let mut tx = pool.begin().await?;
+
+        sqlx::query::<Sqlite>("UPDATE api_tokens SET last_used_at = unixepoch() WHERE id = ?")
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query::<Sqlite>(
+            "
+            INSERT INTO api_token_uses (token_id, method, path)
+            VALUES (?, ?, ?)
+            ",
+        )
+        .bind(id)
+        .bind(method)
+        .bind(path)
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await
+    }
+
+    /// The token's most recent uses, newest first.
+    pub async fn uses(
+        &self,
+        pool: &SqlitePool,
+        id: &str,
+        limit: u32,
+    ) -> Result<Vec<ApiTokenUse>, sqlx::Error> {
+        sqlx::query_as::<Sqlite, ApiTokenUse>(
+            "
+            SELECT method, path, used_at
+            FROM api_token_uses
+            WHERE token_id = ?
+            ORDER BY used_at DESC, id DESC
+            LIMIT ?
+            ",
+        )
+        .bind(id)
+        .bind(limit)
+        .fetch_all(pool)
+        .await
+    }
+}
+
+fn new_api_token() -> String {
+    let pw_config = PasswordConfig::alphanumeric(40).unwrap();
+    format!("{}{}", TOKEN_PREFIX, generate_password(&pw_config))
+}
+
+fn hash_api_token(token: &str) -> String {
+    Hash::digest(token.as_bytes()).to_hex()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::setup::prepare_test_node_data_database;
+
+    #[tokio::test]
+    async fn test_only_the_hash_is_stored() {
+        let dir = tempfile::tempdir().unwrap();
+        let pool = prepare_test_node_data_database(&dir).await.unwrap();
+        let repo = ApiTokensRepo::init();
+
+        let (api_token, token) = repo
+            .create(&pool, "steward", "script", &[NodeStewardRole::Viewer], None)
+            .await
+            .unwrap();
+        assert!(token.starts_with(TOKEN_PREFIX));
+
+        let (stored,): (String,) = sqlx::query_as("SELECT token_hash FROM api_tokens")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_ne!(stored, token);
+
+        let found = repo.find_usable(&pool, &token).await.unwrap().unwrap();
+        assert_eq!(found.id, api_token.id);
+        assert_eq!(found.scopes, vec![NodeStewardRole::Viewer]);
+    }
+
+    #[tokio::test]
+    async fn test_revoked_and_expired_tokens_are_not_usable() {
+        let dir = tempfile::tempdir().unwrap();
+        let pool = prepare_test_node_data_database(&dir).await.unwrap();
+        let repo = ApiTokensRepo::init();
+
+        let (_, expired) = repo
+            .create(&pool, "steward", "old", &[], Some(1))
+            .await
+            .unwrap();
+        let (revoked, token) = repo
+            .create(&pool, "steward", "leaked", &[], None)
+            .await
+            .unwrap();
+        assert!(repo.find_usable(&pool, &token).await.unwrap().is_some());
+
+        let first = repo.revoke(&pool, "steward", &revoked.id).await.unwrap();
+        let again = repo.revoke(&pool, "steward", &revoked.id).await.unwrap();
+        assert!(first.unwrap().revoked_at.is_some());
+        assert!(repo.find_usable(&pool, &token).await.unwrap().is_none());
+        assert!(repo.find_usable(&pool, &expired).await.unwrap().is_none());
+        assert!(again.unwrap().revoked_at.is_some());
+
+        // Stewards can't revoke each other's tokens.
+        assert!(
+            repo.revoke(&pool, "someone-else", &revoked.id)
+                .await
+                .unwrap()
+                .is_none()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_record_use() {
+        let dir = tempfile::tempdir().unwrap();
+        let pool = prepare_test_node_data_database(&dir).await.unwrap();
+        let repo = ApiTokensRepo::init();
+        let (api_token, _) = repo
+            .create(&pool, "steward", "script", &[], None)
+            .await
+            .unwrap();
+
+        repo.record_use(&pool, &api_token.id, "GET", "/node_steward_api/recipes")
+            .await
+            .unwrap();
+        repo.record_use(
+            &pool,
+            &api_token.id,
+            "POST",
+            "/node_steward_api/app_deployments/deploy",
+        )
+        .await
+        .unwrap();
+
+        let uses = repo.uses(&pool, &api_token.id, 10).await.unwrap();
+        let methods: Vec<&str> = uses.iter().map(|u| u.method.as_str()).collect();
+        assert_eq!(methods, vec!["POST", "GET"]);
+
+        let found = repo
+            .find_for_steward(&pool, "steward", &api_token.id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(found.last_used_at.is_some());
+    }
+}
@@ -0,0 +1,196 @@
+use serde::Serialize;
+use sqlx::{Sqlite, SqlitePool};
+use utoipa::ToSchema;
+
+#[derive(Serialize, ToSchema, sqlx::Type, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+#[sqlx(rename_all = "lowercase")]
+pub enum AppBackupStatus {
+    Running,
+    Succeeded,
+    Failed,
+}
+
+#[derive(sqlx::FromRow, Serialize, ToSchema, Debug, Clone)]
+pub struct AppBackup {
+    pub id: String,
+    pub stack_name: String,
+    pub status: AppBackupStatus,
+    pub error: Option<String>,
+    pub created_at: i64,
+    pub finished_at: Option<i64>,
+}
+
+pub struct AppBackupsRepo {}
+
+impl AppBackupsRepo {
+    pub fn init() -> Self {
+        AppBackupsRepo {}
+    }
+
+    pub async fn all(&self, pool: &SqlitePool) -> Result<Vec<AppBackup>, sqlx::Error> {
+        sqlx::query_as::<Sqlite, AppBackup>(
+            "
+            SELECT id, stack_name, status, error, created_at, finished_at
+            FROM app_backups
+            ORDER BY created_at DESC, rowid DESC
+            ",
+        )
+        .fetch_all(pool)
+        .await
+    }
+
+    pub async fn for_stack(
+        &self,
+        pool: &SqlitePool,
+        stack_name: &str,
+    ) -> Result<Vec<AppBackup>, sqlx::Error> {
+        sqlx::query_as::<Sqlite, AppBackup>(
+            "
+            SELECT id, stack_name, status, error, created_at, finished_at
+            FROM app_backups
+            WHERE stack_name = ?
+            ORDER BY created_at DESC, rowid DESC
+            ",
+        )
+        .bind(stack_name)
+        .fetch_all(pool)
+        .await
+    }
+
+    pub async fn find(
+        &self,
+        pool: &SqlitePool,
+        id: &str,
+    ) -> Result<Option<AppBackup>, sqlx::Error> {
+        sqlx::query_as::<Sqlite, AppBackup>(
+            "
+            SELECT id, stack_name, status, error, created_at, finished_at
+            FROM app_backups
+            WHERE id = ?
+            ",
+        )
+        .bind(id)
+        .fetch_optional(pool)
+        .await
+    }
+
+    pub async fn create(
+        &self,
+        pool: &SqlitePool,
+        id: &str,
+        stack_name: &str,
+    ) -> Result<AppBackup, sqlx::Error> {
+        sqlx::query_as::<Sqlite, AppBackup>(
+            "
+            INSERT INTO app_backups (id, stack_name, status)
+            VALUES (?, ?, 'running')
+            RETURNING id, stack_name, status, error, created_at, finished_at
+            ",
+        )
+        .bind(id)
+        .bind(stack_name)
+        .fetch_one(pool)
+        .await
+    }
+
+    pub async fn finish(
+        &self,
+        pool: &SqlitePool,
+        id: &str,
+        status: AppBackupStatus,
+        error: Option<String>,
+    ) -> Result<Option<AppBackup>, sqlx::Error> {
+        sqlx::query_as::<Sqlite, AppBackup>(
+            "
+            UPDATE app_backups
+            SET status = ?, error = ?, finished_at = unixepoch()
+            WHERE id = ?
+            RETURNING id, stack_name, status, error, created_at, finished_at
+            ",
+        )
+        .bind(status)
+        .bind(error)
+        .bind(id)
+        .fetch_optional(pool)
+        .await
+    }
+
+    pub async fn delete(&self, pool: &SqlitePool, id: &str) -> Result<(), sqlx::Error> {
+        sqlx::query::<Sqlite>("DELETE FROM app_backups WHERE id = ?")
+            .bind(id)
+            .execute(pool)
+            .await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::setup::prepare_test_node_data_database;
+
+    #[tokio::test]
+    async fn test_create_and_finish() {
+        let dir = tempfile::tempdir().unwrap();
+        let pool = prepare_test_node_data_database(&dir).await.unwrap();
+        let repo = AppBackupsRepo::init();
+
+        let backup = repo.create(&pool, "b1", "gitea").await.unwrap();
+        assert_eq!(backup.status, AppBackupStatus::Running);
+        assert_eq!(backup.finished_at, None);
+
+        let backup = repo
+            .finish(
+                &pool,
+                "b1",
+                AppBackupStatus::Failed,
+                Some("disk full".into()),
+            )
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(backup.status, AppBackupStatus::Failed);
+        assert_eq!(backup.error.as_deref(), Some("disk full"));
+        assert!(backup.finished_at.is_some());
+
+        assert!(
+            repo.finish(&pool, "missing", AppBackupStatus::Succeeded, None)
+                .await
+                .unwrap()
+                .is_none()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_backups_created_together_list_newest_first() {
+        let dir = tempfile::tempdir().unwrap();
+        let pool = prepare_test_node_data_database(&dir).await.unwrap();
+        let repo = AppBackupsRepo::init();
+
+        // Created within the same second, so only insertion order tells them
+        // apart.
+        repo.create(&pool, "b1", "gitea").await.unwrap();
+        repo.create(&pool, "b2", "nextcloud").await.unwrap();
+        repo.create(&pool, "b3", "gitea").await.unwrap();
+
+        let ids = |backups: Vec<AppBackup>| backups.into_iter().map(|b| b.id).collect::<Vec<_>>();
+        assert_eq!(ids(repo.all(&pool).await.unwrap()), vec!["b3", "b2", "b1"]);
+        assert_eq!(
+            ids(repo.for_stack(&pool, "gitea").await.unwrap()),
+            vec!["b3", "b1"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_delete() {
+        let dir = tempfile::tempdir().unwrap();
+        let pool = prepare_test_node_data_database(&dir).await.unwrap();
+        let repo = AppBackupsRepo::init();
+
+        repo.create(&pool, "b1", "gitea").await.unwrap();
+        repo.delete(&pool, "b1").await.unwrap();
+
+        assert!(repo.find(&pool, "b1").await.unwrap().is_none());
+    }
+}
@@ -0,0 +1,292 @@
+use serde::{Deserialize, Serialize};
+use sqlx::{Sqlite, SqlitePool};
+use utoipa::ToSchema;
+
+/// Who took an audited action.
+#[derive(Serialize, Deserialize, ToSchema, sqlx::Type, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+#[sqlx(rename_all = "snake_case")]
+pub enum AuditActor {
+    Admin,
+    NodeSteward,
+    /// A request made without logging in. Only written by older versions;
+    /// anonymous requests are no longer recorded.
+    Anonymous,
+    /// A `lores-node` admin command run on the node itself
+    Cli,
+}
+
+#[derive(sqlx::FromRow, Serialize, ToSchema, Debug, Clone)]
+pub struct AuditLogEntry {
+    pub id: i64,
+    pub created_at: i64,
+    pub actor: AuditActor,
+    pub node_steward_id: Option<String>,
+    /// Set when a node steward acted with an API token
+    pub api_token_id: Option<String>,
+    /// HTTP method, or `CLI` for admin commands
+    pub method: String,
+    /// Request path, or the admin command's arguments
+    pub path: String,
+    /// HTTP status of the response. Not set for admin commands.
+    pub status: Option<i64>,
+}
+
+pub struct NewAuditLogEntry {
+    pub actor: AuditActor,
+    pub node_steward_id: Option<String>,
+    pub api_token_id: Option<String>,
+    pub method: String,
+    pub path: String,
+    pub status: Option<i64>,
+}
+
+/// Narrows a page of the audit log. Every field that is set must match.
+#[derive(Default)]
+pub struct AuditLogFilter {
+    pub actor: Option<AuditActor>,
+    pub node_steward_id: Option<String>,
+    pub method: Option<String>,
+    pub path_prefix: Option<String>,
+    /// Unix timestamps; `since` is inclusive and `until` exclusive
+    pub since: Option<i64>,
+    pub until: Option<i64>,
+}
+
+pub struct AuditLogRepo {}
+
+impl AuditLogRepo {
+    pub fn init() -> Self {
+        AuditLogRepo {}
+    }
+
+    pub async fn append(
+        &self,
+        pool: &SqlitePool,
+        entry: &NewAuditLogEntry,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query::<Sqlite>(
+            "
+            INSERT INTO audit_log (actor, node_steward_id, api_token_id, method, path, status)
+            VALUES (?, ?, ?, ?, ?, ?)
+            ",
+        )
+        .bind(entry.actor)
+        .bind(&entry.node_steward_id)
+        .bind(&entry.api_token_id)
+        .bind(&entry.method)
+        .bind(&entry.path)
+        .bind(entry.status)
+        .execute(pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Entries matching `filter`, newest first. Pass the id of the last entry
+    /// of a page as `before_id` to get the next one.
+    pub async fn page(
+        &self,
+        pool: &SqlitePool,
+        filter: &AuditLogFilter,
+        before_id: Option<i64>,
+        limit: u32,
+    ) -> Result<Vec<AuditLogEntry>, sqlx::Error> {
+        sqlx::query_as::<Sqlite, AuditLogEntry>(
+            "
+            SELECT id, created_at, actor, node_steward_id, api_token_id, method, path, status
+            FROM audit_log
+            WHERE (? IS NULL OR id < ?)
+                AND (? IS NULL OR actor = ?)
+                AND (? IS NULL OR node_steward_id = ?)
+                AND (? IS NULL OR method = ?)
+                AND (? IS NULL OR substr(path, 1, length(?)) = ?)
+                AND (? IS NULL OR created_at >= ?)
+                AND (? IS NULL OR created_at < ?)
+            ORDER BY id DESC
+            LIMIT ?
+            ",
+        )
+        .bind(before_id)
+        .bind(before_id)
+        .bind(filter.actor)
+        .bind(filter.actor)
+        .bind(&filter.node_steward_id)
+        .bind(&filter.node_steward_id)
+        .bind(&filter.method)
+        .bind(&filter.method)
+        .bind(&filter.path_prefix)
+        .bind(&filter.path_prefix)
+        .bind(&filter.path_prefix)
+        .bind(filter.since)
+        .bind(filter.since)
+        .bind(filter.until)
+        .bind(filter.until)
+        .bind(limit)
+        .fetch_all(pool)
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::setup::prepare_test_node_data_database;
+
+    fn entry(
+        actor: AuditActor,
+        node_steward_id: Option<&str>,
+        method: &str,
+        path: &str,
+    ) -> NewAuditLogEntry {
+        NewAuditLogEntry {
+            actor,
+            node_steward_id: node_steward_id.map(str::to_string),
+            api_token_id: None,
+            method: method.to_string(),
+            path: path.to_string(),
+            status: Some(200),
+        }
+    }
+
+    async fn test_pool(dir: &tempfile::TempDir) -> SqlitePool {
+        let pool = prepare_test_node_data_database(dir).await.unwrap();
+        let repo = AuditLogRepo::init();
+        for new_entry in [
+            entry(AuditActor::Admin, None, "POST", "/admin_api/node_stewards"),
+            entry(
+                AuditActor::NodeSteward,
+                Some("sam"),
+                "POST",
+                "/node_steward_api/app_backups/apps/gitea",
+            ),
+            entry(
+                AuditActor::NodeSteward,
+                Some("kim"),
+                "PUT",
+                "/node_steward_api/account/name",
+            ),
+            entry(
+                AuditActor::NodeSteward,
+                Some("sam"),
+                "POST",
+                "/node_steward_api/my_regions/create",
+            ),
+            entry(AuditActor::Cli, None, "CLI", "steward create Kim"),
+        ] {
+            repo.append(&pool, &new_entry).await.unwrap();
+        }
+        pool
+    }
+
+    fn paths(entries: &[AuditLogEntry]) -> Vec<&str> {
+        entries.iter().map(|entry| entry.path.as_str()).collect()
+    }
+
+    #[tokio::test]
+    async fn test_entries_can_not_be_changed_or_removed() {
+        let dir = tempfile::tempdir().unwrap();
+        let pool = test_pool(&dir).await;
+
+        let update = sqlx::query("UPDATE audit_log SET path = '/'")
+            .execute(&pool)
+            .await;
+        assert!(update.is_err());
+        let delete = sqlx::query("DELETE FROM audit_log").execute(&pool).await;
+        assert!(delete.is_err());
+
+        let all = AuditLogRepo::init()
+            .page(&pool, &AuditLogFilter::default(), None, 100)
+            .await
+            .unwrap();
+        assert_eq!(all.len(), 5);
+    }
+
+    #[tokio::test]
+    async fn test_page_filters() {
+        let dir = tempfile::tempdir().unwrap();
+        let pool = test_pool(&dir).await;
+        let repo = AuditLogRepo::init();
+
+        let filter = AuditLogFilter {
+            node_steward_id: Some("sam".to_string()),
+            ..Default::default()
+        };
+        let entries = repo.page(&pool, &filter, None, 100).await.unwrap();
+        assert_eq!(
+            paths(&entries),
+            vec![
+                "/node_steward_api/my_regions/create",
+                "/node_steward_api/app_backups/apps/gitea",
+            ]
+        );
+
+        let filter = AuditLogFilter {
+            actor: Some(AuditActor::NodeSteward),
+            method: Some("PUT".to_string()),
+            ..Default::default()
+        };
+        let entries = repo.page(&pool, &filter, None, 100).await.unwrap();
+        assert_eq!(paths(&entries), vec!["/node_steward_api/account/name"]);
+
+        let filter = AuditLogFilter {
+            path_prefix: Some("/admin_api/".to_string()),
+            ..Default::default()
+        };
+        let entries = repo.page(&pool, &filter, None, 100).await.unwrap();
+        assert_eq!(paths(&entries), vec!["/admin_api/node_stewards"]);
+
+        let all = repo
+            .page(&pool, &AuditLogFilter::default(), None, 100)
+            .await
+            .unwrap();
+        let first_created_at = all.last().unwrap().created_at;
+        let filter = AuditLogFilter {
+            until: Some(first_created_at),
+            ..Default::default()
+        };
+        assert!(
+            repo.page(&pool, &filter, None, 100)
+                .await
+                .unwrap()
+                .is_empty()
+        );
+        let filter = AuditLogFilter {
+            since: Some(first_created_at),
+            ..Default::default()
+        };
+        assert_eq!(repo.page(&pool, &filter, None, 100).await.unwrap().len(), 5);
+    }
+
+    #[tokio::test]
+    async fn test_paging_back_through_the_log() {
+        let dir = tempfile::tempdir().unwrap();
+        let pool = test_pool(&dir).await;
+        let repo = AuditLogRepo::init();
+        let filter = AuditLogFilter::default();
+
+        let first = repo.page(&pool, &filter, None, 2).await.unwrap();
+        assert_eq!(
+            paths(&first),
+            vec!["steward create Kim", "/node_steward_api/my_regions/create"]
+        );
+
+        let second = repo
+            .page(&pool, &filter, Some(first[1].id), 2)
+            .await
+            .unwrap();
+        assert_eq!(
+            paths(&second),
+            vec![
+                "/node_steward_api/account/name",
+                "/node_steward_api/app_backups/apps/gitea"
+            ]
+        );
+
+        let last = repo
+            .page(&pool, &filter, Some(second[1].id), 2)
+            .await
+            .unwrap();
+        assert_eq!(paths(&last), vec!["/admin_api/node_stewards"]);
+    }
+}
@@ -1,3 +1,5 @@
+use p2panda_core::Hash;
+use pwgen2::pwgen::{PasswordConfig, generate_password};
 use sqlx::{Sqlite, SqlitePool};
 
 use crate::data::entities::{LocalApp, LocalAppSource, NodeAppUrl};
@@ -151,26 +153,113 @@ impl LocalAppsRepo {
         })
     }
 
+    /// Binds the app to a region and mints the token it must present to the
+    /// gRPC Panda service. Only the token's hash is stored, so the returned
+    /// token has to be handed to the app now.
     pub async fn bind_to_region(
         &self,
         pool: &SqlitePool,
         name: &str,
         instance_id: &Option<String>,
         region_id: &str,
-    ) -> Result<(), sqlx::Error> {
+    ) -> Result<String, sqlx::Error> {
+        let token = new_app_token();
+
         sqlx::query::<Sqlite>(
             "
-            INSERT INTO local_apps (name, version, instance_id, bound_to_region_id)
-            VALUES (?, '', ?, ?)
-            ON CONFLICT(name, instance_id) DO UPDATE SET bound_to_region_id = excluded.bound_to_region_id
+            INSERT INTO local_apps (name, version, instance_id, bound_to_region_id, app_token_hash)
+            VALUES (?, '', ?, ?, ?)
+            ON CONFLICT(name, instance_id) DO UPDATE SET
+                bound_to_region_id = excluded.bound_to_region_id,
+                app_token_hash = excluded.app_token_hash
             ",
         )
         .bind(name)
         .bind(instance_id)
         .bind(region_id)
+        .bind(hash_app_token(&token))
+        .execute(pool)
+        .await?;
+
+        Ok(token)
+    }
+
+    /// Replaces the token of an app that is bound to a region. Returns `None`
+    /// if there is no such binding.
+    pub async fn rotate_token(
+        &self,
+        pool: &SqlitePool,
+        name: &str,
+        instance_id: &Option<String>,
+    ) -> Result<Option<String>, sqlx::Error> {
+        let token = new_app_token();
+
+        let rows_affected = sqlx::query::<Sqlite>(
+            "
+            UPDATE local_apps
+            SET app_token_hash = ?
+            WHERE name = ? AND (instance_id = ? OR (instance_id IS NULL AND ? IS NULL))
+                AND bound_to_region_id IS NOT NULL
+            ",
+        )
+        .bind(hash_app_token(&token))
+        .bind(name)
+        .bind(instance_id)
+        .bind(instance_id)
         .execute(pool)
+        .await?
+        .rows_affected();
+
+        Ok((rows_affected > 0).then_some(token))
+    }
+
+    /// Names of apps bound to a region that have no token, i.e. that were
+    /// bound before tokens were required. Their calls are refused until a
+    /// steward rotates their token.
+    pub async fn bound_without_token(&self, pool: &SqlitePool) -> Result<Vec<String>, sqlx::Error> {
+        let rows: Vec<(String,)> = sqlx::query_as(
+            "
+            SELECT name
+            FROM local_apps
+            WHERE bound_to_region_id IS NOT NULL AND app_token_hash IS NULL
+            ORDER BY name ASC
+            ",
+        )
+        .fetch_all(pool)
+        .await?;
+
+        Ok(rows.into_iter().map(|(name,)| name).collect())
+    }
+
+    pub async fn token_matches(
+        &self,
+        pool: &SqlitePool,
+        name: &str,
+        instance_id: &Option<String>,
+        token: &str,
+    ) -> Result<bool, sqlx::Error> {
+        let stored: Option<(Option<String>,)> = sqlx::query_as(
+            "
+            SELECT app_token_hash
+            FROM local_apps
+            WHERE name = ? AND (instance_id = ? OR (instance_id IS NULL AND ? IS NULL))
+            ",
+        )
+        .bind(name)
+        .bind(instance_id)
+        .bind(instance_id)
+        .fetch_optional(pool)
         .await?;
 
-        Ok(())
+        Ok(matches!(stored, Some((Some(hash),)) if hash == hash_app_token(token)))
     }
 }
+
+fn new_app_token() -> String {
+    let pw_config = PasswordConfig::alphanumeric(32).unwrap();
+    generate_password(&pw_config)
+}
+
+fn hash_app_token(token: &str) -> String {
+    Hash::digest(token.as_bytes()).to_hex()
+}
@@ -1,3 +1,7 @@
+pub mod api_tokens_repo;
+pub mod app_backups_repo;
 pub mod app_instances_repo;
+pub mod audit_log_repo;
 pub mod local_apps_repo;
+pub mod node_steward_sessions_repo;
 pub mod node_stewards;
@@ -0,0 +1,281 @@
+use serde::Serialize;
+use short_uuid::ShortUuid;
+use sqlx::{Sqlite, SqlitePool};
+use utoipa::ToSchema;
+
+// The tower-sessions `SqliteStore` set up in `data::setup` keeps its sessions
+// in the `tower_sessions` table of the node data database, with the session
+// id in `id` and an RFC 3339 timestamp in `expiry_date`.
+
+#[derive(sqlx::FromRow)]
+struct NodeStewardSessionRow {
+    id: String,
+    session_id: String,
+    user_agent: Option<String>,
+    created_at: i64,
+    expires_at: i64,
+}
+
+#[derive(Serialize, ToSchema, Debug, Clone)]
+pub struct NodeStewardSession {
+    pub id: String,
+    pub user_agent: Option<String>,
+    pub created_at: i64,
+    pub expires_at: i64,
+    /// Whether this is the session the request was made with
+    pub current: bool,
+}
+
+pub struct NodeStewardSessionsRepo {}
+
+impl NodeStewardSessionsRepo {
+    pub fn init() -> Self {
+        NodeStewardSessionsRepo {}
+    }
+
+    pub async fn record(
+        &self,
+        pool: &SqlitePool,
+        session_id: &str,
+        node_steward_id: &str,
+        user_agent: Option<&str>,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query::<Sqlite>(
+            "
+            INSERT INTO node_steward_sessions (id, session_id, node_steward_id, user_agent)
+            VALUES (?, ?, ?, ?)
+            ON CONFLICT(session_id) DO NOTHING
+            ",
+        )
+        .bind(ShortUuid::generate().to_string())
+        .bind(session_id)
+        .bind(node_steward_id)
+        .bind(user_agent)
+        .execute(pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// The steward's sessions that haven't expired or been ended, newest
+    /// first. Records of sessions that have are removed.
+    pub async fn active_for_steward(
+        &self,
+        pool: &SqlitePool,
+        node_steward_id: &str,
+        current_session_id: Option<&str>,
+    ) -> Result<Vec<NodeStewardSession>, sqlx::Error> {
+        sqlx::query::<Sqlite>(
+            "
+            DELETE FROM node_steward_sessions
+            WHERE node_steward_id = ? AND session_id NOT IN (
+                SELECT id FROM tower_sessions WHERE unixepoch(expiry_date) > unixepoch()
+            )
+            ",
+        )
+        .bind(node_steward_id)
+        .execute(pool)
+        .await?;
+
+        let rows = sqlx::query_as::<Sqlite, NodeStewardSessionRow>(
+            "
+            SELECT s.id, s.session_id, s.user_agent, s.created_at,
+                unixepoch(t.expiry_date) AS expires_at
+            FROM node_steward_sessions s
+            JOIN tower_sessions t ON t.id = s.session_id
+            WHERE s.node_steward_id = ?
+            ORDER BY s.created_at DESC, s.rowid DESC
+            ",
+        )
+        .bind(node_steward_id)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| NodeStewardSession {
+                current: Some(row.session_id.as_str()) == current_session_id,
+                id: row.id,
+                user_agent: row.user_agent,
+                created_at: row.created_at,
+                expires_at: row.expires_at,
+            })
+            .collect())
+    }
+
+    /// Ends one of the steward's sessions. Returns `false` if they have no
+    /// session with this id.
+    pub async fn end(
+        &self,
+        pool: &SqlitePool,
+        node_steward_id: &str,
+        id: &str,
+    ) -> Result<bool, sqlx::Error> {
+        let mut tx = pool.begin().await?;
+
+        let session_id: Option<(String,)> = sqlx::query_as(
+            "
+            DELETE FROM node_steward_sessions
+            WHERE node_steward_id = ? AND id = ?
+            RETURNING session_id
+            ",
+        )
+        .bind(node_steward_id)
+        .bind(id)
+        .fetch_optional(&mut *tx)
+        .await?;
+
+        let Some((session_id,)) = session_id else {
+            return Ok(false);
+        };
+        sqlx::query::<Sqlite>("DELETE FROM tower_sessions WHERE id = ?")
+            .bind(session_id)
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+        Ok(true)
+    }
+
+    /// Ends every session the steward has logged in with.
+    pub async fn end_all_for_steward(
+        &self,
+        pool: &SqlitePool,
+        node_steward_id: &str,
+    ) -> Result<(), sqlx::Error> {
+        let mut tx = pool.begin().await?;
+
+        sqlx::query::<Sqlite>(
+            "
+            DELETE FROM tower_sessions WHERE id IN (
+                SELECT session_id FROM node_steward_sessions WHERE node_steward_id = ?
+            )
+            ",
+        )
+        .bind(node_steward_id)
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query::<Sqlite>("DELETE FROM node_steward_sessions WHERE node_steward_id = ?")
+            .bind(node_steward_id)
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::setup::{prepare_session_store, prepare_test_node_data_database};
+
+    async fn test_pool(dir: &tempfile::TempDir) -> SqlitePool {
+        let pool = prepare_test_node_data_database(dir).await.unwrap();
+        prepare_session_store(&pool).await.unwrap();
+        pool
+    }
+
+    /// Stores a tower session expiring `expires_in` seconds from now and
+    /// records it for the steward.
+    async fn session(pool: &SqlitePool, session_id: &str, node_steward_id: &str, expires_in: i64) {
+        sqlx::query(
+            "
+            INSERT INTO tower_sessions (id, data, expiry_date)
+            VALUES (?, x'', strftime('%Y-%m-%dT%H:%M:%fZ', unixepoch() + ?, 'unixepoch'))
+            ",
+        )
+        .bind(session_id)
+        .bind(expires_in)
+        .execute(pool)
+        .await
+        .unwrap();
+        NodeStewardSessionsRepo::init()
+            .record(pool, session_id, node_steward_id, Some("curl/8.0"))
+            .await
+            .unwrap();
+    }
+
+    async fn tower_session_ids(pool: &SqlitePool) -> Vec<String> {
+        sqlx::query_as::<Sqlite, (String,)>("SELECT id FROM tower_sessions ORDER BY id")
+            .fetch_all(pool)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|(id,)| id)
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_active_sessions_prune_expired_ones() {
+        let dir = tempfile::tempdir().unwrap();
+        let pool = test_pool(&dir).await;
+        let repo = NodeStewardSessionsRepo::init();
+        session(&pool, "live", "sam", 3600).await;
+        session(&pool, "expired", "sam", -10).await;
+
+        let sessions = repo
+            .active_for_steward(&pool, "sam", Some("live"))
+            .await
+            .unwrap();
+        assert_eq!(sessions.len(), 1);
+        assert!(sessions[0].current);
+        assert_eq!(sessions[0].user_agent.as_deref(), Some("curl/8.0"));
+        assert!(sessions[0].expires_at > sessions[0].created_at);
+
+        let (recorded,): (i64,) = sqlx::query_as("SELECT COUNT(*) FROM node_steward_sessions")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(recorded, 1);
+    }
+
+    #[tokio::test]
+    async fn test_stewards_can_only_end_their_own_sessions() {
+        let dir = tempfile::tempdir().unwrap();
+        let pool = test_pool(&dir).await;
+        let repo = NodeStewardSessionsRepo::init();
+        session(&pool, "sams-session", "sam", 3600).await;
+        let id = repo.active_for_steward(&pool, "sam", None).await.unwrap()[0]
+            .id
+            .clone();
+
+        assert!(!repo.end(&pool, "kim", &id).await.unwrap());
+        assert_eq!(tower_session_ids(&pool).await, vec!["sams-session"]);
+
+        assert!(repo.end(&pool, "sam", &id).await.unwrap());
+        assert!(tower_session_ids(&pool).await.is_empty());
+        assert!(
+            repo.active_for_steward(&pool, "sam", None)
+                .await
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_end_all_for_steward_leaves_other_stewards_logged_in() {
+        let dir = tempfile::tempdir().unwrap();
+        let pool = test_pool(&dir).await;
+        let repo = NodeStewardSessionsRepo::init();
+        session(&pool, "sam-1", "sam", 3600).await;
+        session(&pool, "sam-2", "sam", 3600).await;
+        session(&pool, "kim-1", "kim", 3600).await;
+
+        repo.end_all_for_steward(&pool, "sam").await.unwrap();
+
+        assert_eq!(tower_session_ids(&pool).await, vec!["kim-1"]);
+        assert!(
+            repo.active_for_steward(&pool, "sam", None)
+                .await
+                .unwrap()
+                .is_empty()
+        );
+        assert_eq!(
+            repo.active_for_steward(&pool, "kim", None)
+                .await
+                .unwrap()
+                .len(),
+            1
+        );
+    }
+}
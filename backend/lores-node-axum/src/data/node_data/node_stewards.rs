@@ -1,6 +1,6 @@
 use chrono::NaiveDateTime;
 use pwgen2::pwgen::{generate_password, PasswordConfig};
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use short_uuid::ShortUuid;
 use sqlx::{Sqlite, SqlitePool};
 use utoipa::ToSchema;
@@ -14,6 +14,7 @@ pub struct NodeStewardRow {
     pub password_reset_token: Option<String>,
     pub password_reset_token_expires_at: Option<NaiveDateTime>,
     pub enabled: bool,
+    pub roles: String,
     pub created_at: Option<NaiveDateTime>,
 }
 
@@ -22,6 +23,45 @@ pub struct NodeStewardIdentifier {
     pub id: String,
 }
 
+/// What a node steward may do on this node, beyond logging in.
+#[derive(Serialize, Deserialize, ToSchema, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "kebab-case")]
+pub enum NodeStewardRole {
+    /// See apps, deployments, backups and network status
+    Viewer,
+    /// Register, deploy, upgrade and back up apps
+    AppManager,
+    /// Create, join and manage regions and this node's place in them
+    RegionManager,
+}
+
+impl NodeStewardRole {
+    pub const ALL: [NodeStewardRole; 3] = [
+        NodeStewardRole::Viewer,
+        NodeStewardRole::AppManager,
+        NodeStewardRole::RegionManager,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            NodeStewardRole::Viewer => "viewer",
+            NodeStewardRole::AppManager => "app-manager",
+            NodeStewardRole::RegionManager => "region-manager",
+        }
+    }
+}
+
+impl std::str::FromStr for NodeStewardRole {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        NodeStewardRole::ALL
+            .into_iter()
+            .find(|role| role.as_str() == s)
+            .ok_or_else(|| format!("unknown role: {}", s))
+    }
+}
+
 impl NodeStewardRow {
     pub fn new(name: String) -> Self {
         NodeStewardRow {
@@ -31,8 +71,20 @@ impl NodeStewardRow {
             password_reset_token: None,
             password_reset_token_expires_at: None,
             enabled: true,
+            roles: String::new(),
             created_at: None,
         }
+        .with_roles(&NodeStewardRole::ALL)
+    }
+
+    /// Roles stored for the steward. Unknown names are skipped.
+    pub fn roles(&self) -> Vec<NodeStewardRole> {
+        parse_roles(&self.roles)
+    }
+
+    pub fn with_roles(mut self, roles: &[NodeStewardRole]) -> Self {
+        self.roles = roles_column(roles);
+        self
     }
 
     pub fn token_expired(&self) -> bool {
@@ -77,7 +129,7 @@ impl NodeStewardsRepo {
     pub async fn all(&self, pool: &SqlitePool) -> Result<Vec<NodeStewardRow>, sqlx::Error> {
         let nodes = sqlx::query_as::<Sqlite, NodeStewardRow>(
             "
-            SELECT id, name, hashed_password, password_reset_token, password_reset_token_expires_at, enabled, created_at
+            SELECT id, name, hashed_password, password_reset_token, password_reset_token_expires_at, enabled, roles, created_at
             FROM node_stewards
             ",
         )
@@ -90,16 +142,17 @@ impl NodeStewardsRepo {
     pub async fn create(&self, pool: &SqlitePool, row: &NodeStewardRow) -> Result<(), sqlx::Error> {
         sqlx::query::<Sqlite>(
             "
-            INSERT INTO node_stewards (id, name, hashed_password, password_reset_token, password_reset_token_expires_at, enabled, created_at, updated_at)
-            VALUES (?, ?, ?, ?, ?, ?, CURRENT_TIMESTAMP, CURRENT_TIMESTAMP)
+            INSERT INTO node_stewards (id, name, hashed_password, password_reset_token, password_reset_token_expires_at, enabled, roles, created_at, updated_at)
+            VALUES (?, ?, ?, ?, ?, ?, ?, CURRENT_TIMESTAMP, CURRENT_TIMESTAMP)
             ",
         )
         .bind(&row.id)
         .bind(&row.name)
         .bind(&row.hashed_password)
         .bind(&row.password_reset_token)
-        .bind(&row.password_reset_token_expires_at)
+        .bind(row.password_reset_token_expires_at)
         .bind(row.enabled)
+        .bind(&row.roles)
         .execute(pool)
         .await?;
 
@@ -113,7 +166,7 @@ impl NodeStewardsRepo {
     ) -> Result<Option<NodeStewardRow>, sqlx::Error> {
         let row = sqlx::query_as::<Sqlite, NodeStewardRow>(
             "
-            SELECT id, name, hashed_password, password_reset_token, password_reset_token_expires_at, enabled, created_at
+            SELECT id, name, hashed_password, password_reset_token, password_reset_token_expires_at, enabled, roles, created_at
             FROM node_stewards
             WHERE id = ?
             ",
@@ -138,7 +191,7 @@ impl NodeStewardsRepo {
             ",
         )
         .bind(&row.password_reset_token)
-        .bind(&row.password_reset_token_expires_at)
+        .bind(row.password_reset_token_expires_at)
         .bind(&row.id)
         .execute(pool)
         .await?;
@@ -187,6 +240,67 @@ impl NodeStewardsRepo {
 
         Ok(())
     }
+
+    pub async fn update_name(
+        &self,
+        pool: &SqlitePool,
+        identifier: &NodeStewardIdentifier,
+        name: &str,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query::<Sqlite>(
+            "
+            UPDATE node_stewards
+            SET name = ?, updated_at = CURRENT_TIMESTAMP
+            WHERE id = ?
+            ",
+        )
+        .bind(name)
+        .bind(&identifier.id)
+        .execute(pool)
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn update_roles(
+        &self,
+        pool: &SqlitePool,
+        identifier: &NodeStewardIdentifier,
+        roles: &[NodeStewardRole],
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query::<Sqlite>(
+            "
+            UPDATE node_stewards
+            SET roles = ?, updated_at = CURRENT_TIMESTAMP
+            WHERE id = ?
+            ",
+        )
+        .bind(roles_column(roles))
+        .bind(&identifier.id)
+        .execute(pool)
+        .await?;
+
+        Ok(())
+    }
+}
+
+/// Roles stored as comma-separated names, the form `roles_column` writes.
+pub fn parse_roles(column: &str) -> Vec<NodeStewardRole> {
+    column
+        .split(',')
+        .filter_map(|name| name.trim().parse().ok())
+        .collect()
+}
+
+pub fn roles_column(roles: &[NodeStewardRole]) -> String {
+    let mut names: Vec<&str> = roles.iter().map(|role| role.as_str()).collect();
+    names.sort();
+    names.dedup();
+    names.join(",")
+}
+
+pub fn password_is_valid(password: &str) -> bool {
+    password.len() >= 8
 }
 
 fn new_node_steward_id() -> String {
@@ -201,3 +315,67 @@ fn new_password_reset_token() -> String {
 fn new_reset_token_expiry() -> NaiveDateTime {
     chrono::Utc::now().naive_utc() + chrono::Duration::hours(24)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::setup::prepare_test_node_data_database;
+
+    #[test]
+    fn test_unknown_role_names_are_dropped() {
+        assert_eq!(
+            parse_roles("viewer, owner,app-manager,"),
+            vec![NodeStewardRole::Viewer, NodeStewardRole::AppManager]
+        );
+        assert!(parse_roles("").is_empty());
+    }
+
+    #[test]
+    fn test_roles_column_is_sorted_and_deduplicated() {
+        let column = roles_column(&[
+            NodeStewardRole::RegionManager,
+            NodeStewardRole::Viewer,
+            NodeStewardRole::RegionManager,
+        ]);
+        assert_eq!(column, "region-manager,viewer");
+    }
+
+    #[tokio::test]
+    async fn test_roles_round_trip_through_the_database() {
+        let dir = tempfile::tempdir().unwrap();
+        let pool = prepare_test_node_data_database(&dir).await.unwrap();
+        let repo = NodeStewardsRepo::init();
+
+        let row = NodeStewardRow::new("Sam".to_string()).with_roles(&[NodeStewardRole::AppManager]);
+        repo.create(&pool, &row).await.unwrap();
+        let identifier = NodeStewardIdentifier { id: row.id.clone() };
+
+        let found = repo.find(&pool, &identifier).await.unwrap().unwrap();
+        assert_eq!(found.roles(), vec![NodeStewardRole::AppManager]);
+
+        repo.update_roles(
+            &pool,
+            &identifier,
+            &[NodeStewardRole::Viewer, NodeStewardRole::RegionManager],
+        )
+        .await
+        .unwrap();
+        let found = repo.find(&pool, &identifier).await.unwrap().unwrap();
+        assert_eq!(
+            found.roles(),
+            vec![NodeStewardRole::RegionManager, NodeStewardRole::Viewer]
+        );
+    }
+
+    #[test]
+    fn test_new_stewards_get_every_role() {
+        assert_eq!(
+            NodeStewardRow::new("Sam".to_string()).roles(),
+            vec![
+                NodeStewardRole::AppManager,
+                NodeStewardRole::RegionManager,
+                NodeStewardRole::Viewer,
+            ]
+        );
+    }
+}
@@ -20,6 +20,14 @@ impl InstallationRow {
     }
 }
 
+pub struct AppPeerRow {
+    pub node_id: String,
+    pub node_name: Option<String>,
+    pub version: String,
+    pub internet_url: Option<String>,
+    pub local_network_url: Option<String>,
+}
+
 pub struct AppsReadRepo {}
 
 impl AppsReadRepo {
@@ -99,4 +107,90 @@ impl AppsReadRepo {
 
         Ok(Some(region_app))
     }
+
+    /// Nodes in `region_id` that have registered `app_name`, with the URLs
+    /// they registered for it.
+    pub async fn find_peers(
+        &self,
+        pool: &SqlitePool,
+        region_id: &str,
+        app_name: &str,
+    ) -> Result<Vec<AppPeerRow>, sqlx::Error> {
+        sqlx::query_as!(
+            AppPeerRow,
+            "SELECT region_nodes.node_id, region_nodes.name AS node_name, app_installations.version,
+                app_installations.internet_url, app_installations.local_network_url
+            FROM app_installations
+            INNER JOIN region_nodes ON app_installations.region_node_id = region_nodes.id
+            WHERE region_nodes.region_id = ? AND app_installations.app_name = ?
+            ORDER BY region_nodes.node_id",
+            region_id,
+            app_name
+        )
+        .fetch_all(pool)
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::{
+        entities::NodeAppUrl,
+        projections_write::{
+            app_installations::AppInstallationsWriteRepo, region_nodes::RegionNodesWriteRepo,
+        },
+        setup::prepare_test_projections_database,
+    };
+
+    async fn install(pool: &SqlitePool, node_id: &str, region_id: &str, app_name: &str) {
+        let region_node = RegionNodesWriteRepo::init()
+            .find_or_create_by_keys(pool, node_id, region_id)
+            .await
+            .unwrap();
+        let installation = AppInstallation {
+            app_name: app_name.to_string(),
+            region_node_id: region_node.id,
+            version: "1.0.0".to_string(),
+        };
+        let url = NodeAppUrl {
+            internet_url: Some(format!("https://{app_name}.{node_id}.example.org")),
+            local_network_url: None,
+        };
+        AppInstallationsWriteRepo::init()
+            .upsert(pool, installation, Some(url))
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_find_peers_lists_nodes_in_the_region_with_the_app() {
+        let dir = tempfile::tempdir().unwrap();
+        let pool = prepare_test_projections_database(&dir).await.unwrap();
+        for region_id in ["region-a", "region-b"] {
+            sqlx::query("INSERT INTO regions (id) VALUES (?)")
+                .bind(region_id)
+                .execute(&pool)
+                .await
+                .unwrap();
+        }
+
+        install(&pool, "node-2", "region-a", "notes").await;
+        install(&pool, "node-1", "region-a", "notes").await;
+        install(&pool, "node-3", "region-a", "chat").await;
+        install(&pool, "node-4", "region-b", "notes").await;
+
+        let peers = AppsReadRepo::init()
+            .find_peers(&pool, "region-a", "notes")
+            .await
+            .unwrap();
+
+        let node_ids: Vec<&str> = peers.iter().map(|p| p.node_id.as_str()).collect();
+        assert_eq!(node_ids, vec!["node-1", "node-2"]);
+        assert_eq!(
+            peers[0].internet_url.as_deref(),
+            Some("https://notes.node-1.example.org")
+        );
+        assert_eq!(peers[0].version, "1.0.0");
+    }
 }
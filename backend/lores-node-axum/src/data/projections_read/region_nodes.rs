@@ -45,7 +45,7 @@ impl RegionNodesReadRepo {
         .fetch_optional(pool)
         .await?;
 
-        return Ok(node);
+        Ok(node)
     }
 
     pub async fn append_detail_nodes_to_list(
@@ -99,7 +99,7 @@ impl RegionNodesReadRepo {
         .fetch_optional(pool)
         .await?;
 
-        return Ok(node);
+        Ok(node)
     }
 
     pub async fn find_all_detailed(
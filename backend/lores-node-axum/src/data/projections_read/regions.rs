@@ -81,7 +81,7 @@ impl RegionsReadRepo {
         .await?
         .map(Region::from);
 
-        return Ok(region);
+        Ok(region)
     }
 
     pub async fn find_all_for_node(
@@ -118,6 +118,6 @@ impl RegionsReadRepo {
         .map(Region::from)
         .collect();
 
-        return Ok(regions);
+        Ok(regions)
     }
 }
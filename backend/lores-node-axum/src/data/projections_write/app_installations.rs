@@ -2,7 +2,7 @@ use sqlx::SqlitePool;
 
 use crate::data::projections_write::apps::AppsWriteRepo;
 
-use super::super::entities::AppInstallation;
+use super::super::entities::{AppInstallation, NodeAppUrl};
 
 pub struct AppInstallationsWriteRepo {}
 
@@ -15,18 +15,27 @@ impl AppInstallationsWriteRepo {
         &self,
         pool: &SqlitePool,
         installation: AppInstallation,
+        url: Option<NodeAppUrl>,
     ) -> Result<(), sqlx::Error> {
         let app_write_repo = AppsWriteRepo::init();
         app_write_repo.upsert(pool, &installation.app_name).await?;
 
+        let internet_url = url.as_ref().and_then(|u| u.internet_url.clone());
+        let local_network_url = url.as_ref().and_then(|u| u.local_network_url.clone());
+
         sqlx::query!(
             "
-            INSERT INTO app_installations (app_name, region_node_id, version)
-            VALUES (?,?,?)
-            ON CONFLICT(app_name, region_node_id) DO UPDATE SET version = excluded.version",
+            INSERT INTO app_installations (app_name, region_node_id, version, internet_url, local_network_url)
+            VALUES (?,?,?,?,?)
+            ON CONFLICT(app_name, region_node_id) DO UPDATE SET
+                version = excluded.version,
+                internet_url = excluded.internet_url,
+                local_network_url = excluded.local_network_url",
             installation.app_name,
             installation.region_node_id,
-            installation.version
+            installation.version,
+            internet_url,
+            local_network_url
         )
         .execute(pool)
         .await?;
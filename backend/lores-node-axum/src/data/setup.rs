@@ -78,3 +78,23 @@ pub(crate) async fn prepare_database(
 
     Ok(pool)
 }
+
+/// A migrated node data database in `dir`, for tests.
+#[cfg(test)]
+pub(crate) async fn prepare_test_node_data_database(
+    dir: &tempfile::TempDir,
+) -> Result<Pool<Sqlite>> {
+    let db_url = format!("sqlite:{}/node_data.sqlite", dir.path().display());
+    let migrations = concat!(env!("CARGO_MANIFEST_DIR"), "/../migrations_nodedatadb");
+    prepare_database(&db_url, Some(migrations)).await
+}
+
+/// A migrated projections database in `dir`, for tests.
+#[cfg(test)]
+pub(crate) async fn prepare_test_projections_database(
+    dir: &tempfile::TempDir,
+) -> Result<Pool<Sqlite>> {
+    let db_url = format!("sqlite:{}/projections.sqlite", dir.path().display());
+    let migrations = concat!(env!("CARGO_MANIFEST_DIR"), "/../migrations_projectiondb");
+    prepare_database(&db_url, Some(migrations)).await
+}
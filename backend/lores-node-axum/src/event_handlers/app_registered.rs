@@ -47,10 +47,12 @@ impl AppRegisteredHandler {
 
         let installation = AppInstallation {
             app_name: self.payload.name.clone(),
-            region_node_id: region_node.id.clone(),
+            region_node_id: region_node.id,
             version: self.payload.version.clone(),
         };
-        installations_write_repo.upsert(pool, installation).await?;
+        installations_write_repo
+            .upsert(pool, installation, self.payload.url.clone())
+            .await?;
 
         Ok(())
     }
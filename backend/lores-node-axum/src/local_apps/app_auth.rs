@@ -0,0 +1,44 @@
+use lores_p2panda_server::{AppInstanceIds, AuthenticateApp, AuthenticateAppError};
+use sqlx::SqlitePool;
+use std::sync::Arc;
+use tracing::warn;
+
+use crate::data::node_data::local_apps_repo::LocalAppsRepo;
+
+/// Returns a [`lores_p2panda_server::AuthenticateApp`] callback that checks an
+/// app's bearer token against the hash stored when it was bound to a region.
+pub fn make_app_authenticator(pool: SqlitePool) -> AuthenticateApp {
+    Arc::new(move |ids: AppInstanceIds, token: String| {
+        let pool = pool.clone();
+        Box::pin(async move {
+            let matches = LocalAppsRepo::init()
+                .token_matches(&pool, &ids.app_id, &Some(ids.instance_id.clone()), &token)
+                .await
+                .map_err(|e| {
+                    warn!("[app_auth] database error: {e}");
+                    AuthenticateAppError::Internal
+                })?;
+
+            if matches {
+                Ok(())
+            } else {
+                Err(AuthenticateAppError::InvalidToken)
+            }
+        })
+    })
+}
+
+/// Logs the apps bound to a region before tokens were required. They have no
+/// token, so their calls are refused until a steward rotates it.
+pub async fn warn_about_apps_without_tokens(pool: &SqlitePool) {
+    match LocalAppsRepo::init().bound_without_token(pool).await {
+        Ok(names) => {
+            for name in names {
+                warn!(
+                    "App {name} is bound to a region but has no token; rotate its token and give it to the app"
+                );
+            }
+        }
+        Err(e) => warn!("[app_auth] database error: {e}"),
+    }
+}
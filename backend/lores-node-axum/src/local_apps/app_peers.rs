@@ -0,0 +1,35 @@
+use lores_p2panda_server::{AppPeer, ListAppPeers, ListAppPeersError};
+use sqlx::SqlitePool;
+use std::sync::Arc;
+use tracing::warn;
+
+use crate::data::projections_read::apps::AppsReadRepo;
+
+/// Returns a [`lores_p2panda_server::ListAppPeers`] callback that reads the
+/// nodes which have registered an app in a region from the projections
+/// database.
+pub fn make_app_peers_lister(pool: SqlitePool) -> ListAppPeers {
+    Arc::new(move |region_id, app_id| {
+        let pool = pool.clone();
+        Box::pin(async move {
+            let rows = AppsReadRepo::init()
+                .find_peers(&pool, &region_id.to_hex(), &app_id)
+                .await
+                .map_err(|e| {
+                    warn!("[app_peers] database error: {e}");
+                    ListAppPeersError::Internal
+                })?;
+
+            Ok(rows
+                .into_iter()
+                .map(|row| AppPeer {
+                    node_id: row.node_id,
+                    node_name: row.node_name,
+                    version: row.version,
+                    internet_url: row.internet_url,
+                    local_network_url: row.local_network_url,
+                })
+                .collect())
+        })
+    })
+}
@@ -0,0 +1,21 @@
+use std::env;
+
+use lores_p2panda_server::QuotaConfig;
+
+/// Reads the publish quotas for apps from the environment, falling back to
+/// the defaults for any that aren't set.
+pub fn quota_config_from_env() -> QuotaConfig {
+    let defaults = QuotaConfig::default();
+    QuotaConfig {
+        max_payload_bytes: env_or("PUBLISH_MAX_PAYLOAD_BYTES", defaults.max_payload_bytes),
+        ops_per_minute: env_or("PUBLISH_OPS_PER_MINUTE", defaults.ops_per_minute),
+        bytes_per_day: env_or("PUBLISH_BYTES_PER_DAY", defaults.bytes_per_day),
+    }
+}
+
+fn env_or<T: std::str::FromStr>(name: &str, default: T) -> T {
+    env::var(name)
+        .ok()
+        .and_then(|s| s.parse().ok())
+        .unwrap_or(default)
+}
@@ -0,0 +1,441 @@
+use coop_cloud_docker_apps::{
+    app_backup_plan, backup_app, docker_stacks_with_services, restore_app,
+};
+use short_uuid::ShortUuid;
+use sqlx::SqlitePool;
+use std::{
+    collections::HashSet,
+    env, fs,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use tracing::{info, warn};
+
+use crate::{
+    api::public_api::{client_events::ClientEvent, realtime::RealtimeState},
+    data::node_data::app_backups_repo::{AppBackup, AppBackupStatus, AppBackupsRepo},
+};
+
+#[derive(thiserror::Error, Debug)]
+pub enum AppBackupError {
+    #[error("Invalid stack name: {0}")]
+    InvalidStackName(String),
+    #[error("No services in {0} have backups enabled")]
+    NotConfigured(String),
+    #[error("A backup or restore is already running for {0}")]
+    AlreadyRunning(String),
+    #[error("Backup not found: {0}")]
+    NotFound(String),
+    #[error("Backup {0} did not complete and cannot be restored")]
+    NotRestorable(String),
+    #[error("Database error: {0}")]
+    Database(#[from] sqlx::Error),
+    #[error("{0}")]
+    Docker(anyhow::Error),
+}
+
+/// Backs up Co-op Cloud app volumes into `BACKUP_DIR/<stack>/<backup id>/`,
+/// keeping the newest `BACKUP_RETENTION` successful backups per app, and the
+/// records of as many failed ones.
+#[derive(Clone)]
+pub struct AppBackups {
+    backup_dir: PathBuf,
+    retention: usize,
+    interval: Option<Duration>,
+    busy_stacks: Arc<Mutex<HashSet<String>>>,
+}
+
+impl AppBackups {
+    pub fn from_env() -> Self {
+        let backup_dir = env::var("BACKUP_DIR").unwrap_or_else(|_| {
+            let data_dir = env::var("DATA_DIR").unwrap_or_else(|_| ".".to_string());
+            format!("{}/backups", data_dir)
+        });
+        let retention = env::var("BACKUP_RETENTION")
+            .ok()
+            .and_then(|s| s.parse::<usize>().ok())
+            .unwrap_or(7)
+            .max(1);
+        // 0 disables scheduled backups.
+        let interval_secs = env::var("BACKUP_INTERVAL_SECS")
+            .ok()
+            .and_then(|s| s.parse::<u64>().ok())
+            .unwrap_or(24 * 60 * 60);
+
+        Self {
+            backup_dir: PathBuf::from(backup_dir),
+            retention,
+            interval: (interval_secs > 0).then(|| Duration::from_secs(interval_secs)),
+            busy_stacks: Arc::new(Mutex::new(HashSet::new())),
+        }
+    }
+
+    /// Starts a backup of `stack_name` in the background and returns its
+    /// running record. Progress is broadcast as `AppBackupUpdated` events.
+    pub async fn trigger(
+        &self,
+        pool: &SqlitePool,
+        realtime_state: &RealtimeState,
+        stack_name: &str,
+    ) -> Result<AppBackup, AppBackupError> {
+        validate_stack_name(stack_name)?;
+        let guard = self.lock_stack(stack_name)?;
+
+        let plan = {
+            let stack_name = stack_name.to_string();
+            tokio::task::spawn_blocking(move || app_backup_plan(&stack_name))
+                .await
+                .map_err(|e| AppBackupError::Docker(e.into()))?
+                .map_err(AppBackupError::Docker)?
+        };
+        if plan.is_empty() {
+            return Err(AppBackupError::NotConfigured(stack_name.to_string()));
+        }
+
+        let (backup, dir) = self.create_backup(pool, stack_name).await?;
+        let id = backup.id.clone();
+        info!("Started backup {} of {}", id, stack_name);
+
+        let this = self.clone();
+        let pool = pool.clone();
+        let realtime_state = realtime_state.clone();
+        tokio::spawn(async move {
+            let _guard = guard;
+
+            let result = {
+                let dir = dir.clone();
+                tokio::task::spawn_blocking(move || backup_app(&plan, &dir))
+                    .await
+                    .map_err(anyhow::Error::from)
+                    .and_then(|r| r)
+            };
+
+            let (status, error) = match result {
+                Ok(archives) => {
+                    info!("Backup {} wrote {} archive(s)", id, archives.len());
+                    (AppBackupStatus::Succeeded, None)
+                }
+                Err(e) => {
+                    warn!("Backup {} failed: {:?}", id, e);
+                    if let Err(e) = fs::remove_dir_all(&dir) {
+                        warn!("Failed to remove incomplete backup {}: {}", id, e);
+                    }
+                    (AppBackupStatus::Failed, Some(e.to_string()))
+                }
+            };
+
+            match AppBackupsRepo::init()
+                .finish(&pool, &id, status, error)
+                .await
+            {
+                Ok(Some(backup)) => {
+                    realtime_state
+                        .broadcast_app_event(ClientEvent::AppBackupUpdated(backup.clone()))
+                        .await;
+                    if let Err(e) = this.prune(&pool, &backup.stack_name).await {
+                        warn!("Failed to prune backups of {}: {}", backup.stack_name, e);
+                    }
+                }
+                Ok(None) => warn!("Backup {} disappeared before it finished", id),
+                Err(e) => warn!("Failed to record result of backup {}: {}", id, e),
+            }
+        });
+
+        Ok(backup)
+    }
+
+    /// Creates the directory and running record for a new backup of
+    /// `stack_name`.
+    async fn create_backup(
+        &self,
+        pool: &SqlitePool,
+        stack_name: &str,
+    ) -> Result<(AppBackup, PathBuf), AppBackupError> {
+        let id = ShortUuid::generate().to_string();
+        let dir = self.backup_path(stack_name, &id);
+        fs::create_dir_all(&dir).map_err(|e| AppBackupError::Docker(e.into()))?;
+
+        match AppBackupsRepo::init().create(pool, &id, stack_name).await {
+            Ok(backup) => Ok((backup, dir)),
+            Err(e) => {
+                // Without a record nothing would ever prune the directory.
+                if let Err(e) = fs::remove_dir_all(&dir) {
+                    warn!("Failed to remove unrecorded backup {}: {}", id, e);
+                }
+                Err(e.into())
+            }
+        }
+    }
+
+    /// Marks backups that were still running when the node last stopped as
+    /// failed, and removes what they had written. Nothing is left to finish
+    /// them, and running backups are never pruned.
+    pub async fn fail_interrupted(&self, pool: &SqlitePool) -> Result<(), sqlx::Error> {
+        let repo = AppBackupsRepo::init();
+        for backup in repo.all(pool).await? {
+            if backup.status != AppBackupStatus::Running {
+                continue;
+            }
+
+            warn!(
+                "Backup {} of {} was interrupted",
+                backup.id, backup.stack_name
+            );
+            let dir = self.backup_path(&backup.stack_name, &backup.id);
+            if dir.exists()
+                && let Err(e) = fs::remove_dir_all(&dir)
+            {
+                warn!("Failed to remove incomplete backup {}: {}", backup.id, e);
+            }
+            repo.finish(
+                pool,
+                &backup.id,
+                AppBackupStatus::Failed,
+                Some("Interrupted by the node stopping".to_string()),
+            )
+            .await?;
+        }
+
+        Ok(())
+    }
+
+    /// Restores a completed backup over the app's current volumes, waiting for
+    /// it to finish.
+    pub async fn restore(&self, pool: &SqlitePool, backup_id: &str) -> Result<(), AppBackupError> {
+        let backup = AppBackupsRepo::init()
+            .find(pool, backup_id)
+            .await?
+            .ok_or_else(|| AppBackupError::NotFound(backup_id.to_string()))?;
+        if backup.status != AppBackupStatus::Succeeded {
+            return Err(AppBackupError::NotRestorable(backup_id.to_string()));
+        }
+
+        let _guard = self.lock_stack(&backup.stack_name)?;
+        let dir = self.backup_path(&backup.stack_name, &backup.id);
+
+        info!("Restoring backup {} of {}", backup.id, backup.stack_name);
+        let stack_name = backup.stack_name.clone();
+        tokio::task::spawn_blocking(move || {
+            let plan = app_backup_plan(&stack_name)?;
+            restore_app(&plan, &dir)
+        })
+        .await
+        .map_err(|e| AppBackupError::Docker(e.into()))?
+        .map_err(AppBackupError::Docker)
+    }
+
+    /// Periodically backs up every deployed app that has backups enabled.
+    pub fn start_schedule(&self, pool: SqlitePool, realtime_state: RealtimeState) {
+        let Some(interval) = self.interval else {
+            info!("Scheduled app backups disabled");
+            return;
+        };
+
+        let this = self.clone();
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            // The first tick fires immediately; don't back everything up on boot.
+            ticker.tick().await;
+
+            loop {
+                ticker.tick().await;
+
+                let stacks = match tokio::task::spawn_blocking(docker_stacks_with_services).await {
+                    Ok(Ok(stacks)) => stacks,
+                    Ok(Err(e)) => {
+                        warn!("Skipping scheduled backups: {:?}", e);
+                        continue;
+                    }
+                    Err(e) => {
+                        warn!("Skipping scheduled backups: {:?}", e);
+                        continue;
+                    }
+                };
+
+                for stack in stacks {
+                    match this.trigger(&pool, &realtime_state, &stack.name).await {
+                        Ok(_) | Err(AppBackupError::NotConfigured(_)) => {}
+                        Err(e) => warn!("Scheduled backup of {} failed: {}", stack.name, e),
+                    }
+                }
+            }
+        });
+    }
+
+    async fn prune(&self, pool: &SqlitePool, stack_name: &str) -> Result<(), sqlx::Error> {
+        let repo = AppBackupsRepo::init();
+        let backups = repo.for_stack(pool, stack_name).await?;
+
+        for backup in expired_backups(backups, self.retention) {
+            // Failed backups have already had their directory removed.
+            if backup.status == AppBackupStatus::Succeeded {
+                let dir = self.backup_path(stack_name, &backup.id);
+                if let Err(e) = fs::remove_dir_all(&dir) {
+                    warn!("Failed to remove backup {}: {}", backup.id, e);
+                }
+            }
+            repo.delete(pool, &backup.id).await?;
+            info!("Pruned backup {} of {}", backup.id, stack_name);
+        }
+
+        Ok(())
+    }
+
+    fn backup_path(&self, stack_name: &str, backup_id: &str) -> PathBuf {
+        self.backup_dir.join(stack_name).join(backup_id)
+    }
+
+    fn lock_stack(&self, stack_name: &str) -> Result<StackGuard, AppBackupError> {
+        let mut busy = self.busy_stacks.lock().unwrap();
+        if !busy.insert(stack_name.to_string()) {
+            return Err(AppBackupError::AlreadyRunning(stack_name.to_string()));
+        }
+
+        Ok(StackGuard {
+            busy_stacks: self.busy_stacks.clone(),
+            stack_name: stack_name.to_string(),
+        })
+    }
+}
+
+/// Marks a stack as busy until dropped, so backups and restores of the same
+/// app don't overlap.
+struct StackGuard {
+    busy_stacks: Arc<Mutex<HashSet<String>>>,
+    stack_name: String,
+}
+
+impl Drop for StackGuard {
+    fn drop(&mut self) {
+        self.busy_stacks.lock().unwrap().remove(&self.stack_name);
+    }
+}
+
+/// The backups past the newest `retention` of each finished status, given
+/// backups newest first. Running backups are never expired.
+fn expired_backups(backups: Vec<AppBackup>, retention: usize) -> Vec<AppBackup> {
+    let mut kept_succeeded = 0;
+    let mut kept_failed = 0;
+    backups
+        .into_iter()
+        .filter(|backup| {
+            let kept = match backup.status {
+                AppBackupStatus::Running => return false,
+                AppBackupStatus::Succeeded => &mut kept_succeeded,
+                AppBackupStatus::Failed => &mut kept_failed,
+            };
+            if *kept < retention {
+                *kept += 1;
+                false
+            } else {
+                true
+            }
+        })
+        .collect()
+}
+
+/// Stack names become directory names, so only allow what Docker itself does.
+fn validate_stack_name(stack_name: &str) -> Result<(), AppBackupError> {
+    let valid = !stack_name.is_empty()
+        && !stack_name.starts_with('.')
+        && stack_name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-' || c == '.');
+
+    if valid {
+        Ok(())
+    } else {
+        Err(AppBackupError::InvalidStackName(stack_name.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::setup::prepare_test_node_data_database;
+
+    fn backup(id: &str, status: AppBackupStatus) -> AppBackup {
+        AppBackup {
+            id: id.to_string(),
+            stack_name: "gitea".to_string(),
+            status,
+            error: None,
+            created_at: 0,
+            finished_at: None,
+        }
+    }
+
+    #[test]
+    fn test_expired_backups_keeps_newest_of_each_status() {
+        let backups = vec![
+            backup("running", AppBackupStatus::Running),
+            backup("ok-3", AppBackupStatus::Succeeded),
+            backup("failed-2", AppBackupStatus::Failed),
+            backup("ok-2", AppBackupStatus::Succeeded),
+            backup("failed-1", AppBackupStatus::Failed),
+            backup("ok-1", AppBackupStatus::Succeeded),
+            backup("failed-0", AppBackupStatus::Failed),
+        ];
+
+        let expired: Vec<String> = expired_backups(backups, 2)
+            .into_iter()
+            .map(|b| b.id)
+            .collect();
+        assert_eq!(expired, vec!["ok-1", "failed-0"]);
+    }
+
+    fn app_backups(dir: &tempfile::TempDir) -> AppBackups {
+        AppBackups {
+            backup_dir: dir.path().join("backups"),
+            retention: 7,
+            interval: None,
+            busy_stacks: Arc::new(Mutex::new(HashSet::new())),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_interrupted_backups_are_failed_and_removed() {
+        let dir = tempfile::tempdir().unwrap();
+        let pool = prepare_test_node_data_database(&dir).await.unwrap();
+        let backups = app_backups(&dir);
+        let repo = AppBackupsRepo::init();
+
+        let (interrupted, interrupted_dir) = backups.create_backup(&pool, "gitea").await.unwrap();
+        let (finished, finished_dir) = backups.create_backup(&pool, "gitea").await.unwrap();
+        repo.finish(&pool, &finished.id, AppBackupStatus::Succeeded, None)
+            .await
+            .unwrap();
+
+        backups.fail_interrupted(&pool).await.unwrap();
+
+        let interrupted = repo.find(&pool, &interrupted.id).await.unwrap().unwrap();
+        assert_eq!(interrupted.status, AppBackupStatus::Failed);
+        assert!(interrupted.error.is_some());
+        assert!(!interrupted_dir.exists());
+        let finished = repo.find(&pool, &finished.id).await.unwrap().unwrap();
+        assert_eq!(finished.status, AppBackupStatus::Succeeded);
+        assert!(finished_dir.exists());
+    }
+
+    #[tokio::test]
+    async fn test_backup_directory_is_removed_if_it_cannot_be_recorded() {
+        let dir = tempfile::tempdir().unwrap();
+        let pool = prepare_test_node_data_database(&dir).await.unwrap();
+        let backups = app_backups(&dir);
+        pool.close().await;
+
+        let result = backups.create_backup(&pool, "gitea").await;
+        assert!(matches!(result, Err(AppBackupError::Database(_))));
+        let leftovers = fs::read_dir(dir.path().join("backups").join("gitea")).unwrap();
+        assert_eq!(leftovers.count(), 0);
+    }
+
+    #[test]
+    fn test_validate_stack_name() {
+        assert!(validate_stack_name("gitea_example_org").is_ok());
+        assert!(validate_stack_name("").is_err());
+        assert!(validate_stack_name("..").is_err());
+        assert!(validate_stack_name("a/b").is_err());
+    }
+}
@@ -0,0 +1,513 @@
+use chrono::Utc;
+use coop_cloud_docker_apps::{
+    Abra, AbraCommand, CommandRunner, LogLine, LogStream, ProcessCommandRunner,
+};
+use serde::Serialize;
+use short_uuid::ShortUuid;
+use std::{collections::HashMap, env, sync::Arc};
+use tokio::sync::{RwLock, mpsc};
+use tracing::{info, warn};
+use utoipa::ToSchema;
+
+use crate::api::public_api::{client_events::ClientEvent, realtime::RealtimeState};
+
+#[derive(Serialize, ToSchema, Debug, Clone, PartialEq, Eq)]
+pub enum AppDeploymentAction {
+    New,
+    Deploy,
+    Upgrade,
+    Undeploy,
+}
+
+#[derive(Serialize, ToSchema, Debug, Clone, PartialEq, Eq)]
+pub enum AppDeploymentJobStatus {
+    Running,
+    Succeeded,
+    Failed,
+}
+
+#[derive(Serialize, ToSchema, Debug, Clone)]
+pub struct AppDeploymentJob {
+    pub id: String,
+    pub action: AppDeploymentAction,
+    pub domain: String,
+    pub recipe: Option<String>,
+    pub version: Option<String>,
+    pub status: AppDeploymentJobStatus,
+    pub error: Option<String>,
+    pub started_at: String,
+    pub finished_at: Option<String>,
+}
+
+#[derive(Serialize, ToSchema, Debug, Clone)]
+#[serde(rename_all = "lowercase")]
+pub enum AppDeploymentLogStream {
+    Stdout,
+    Stderr,
+}
+
+#[derive(Serialize, ToSchema, Debug, Clone)]
+pub struct AppDeploymentLogLine {
+    pub job_id: String,
+    pub stream: AppDeploymentLogStream,
+    pub text: String,
+}
+
+#[derive(Serialize, ToSchema, Debug, Clone)]
+pub struct AppDeploymentJobWithLogs {
+    pub job: AppDeploymentJob,
+    pub logs: Vec<AppDeploymentLogLine>,
+}
+
+#[derive(Debug, Clone)]
+pub enum AppDeploymentRequest {
+    New {
+        recipe: String,
+        domain: String,
+        version: Option<String>,
+    },
+    Deploy {
+        domain: String,
+    },
+    Upgrade {
+        domain: String,
+        version: Option<String>,
+    },
+    Undeploy {
+        domain: String,
+    },
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum StartDeploymentError {
+    #[error("{0}")]
+    Invalid(String),
+    #[error("A job is already running for {0}")]
+    AlreadyRunning(String),
+}
+
+/// How many finished jobs are kept, with their logs, for stewards to look
+/// back at.
+const MAX_FINISHED_JOBS: usize = 50;
+
+/// Tracks `abra` jobs run on behalf of node stewards. Each job runs in the
+/// background, with its output kept in memory and streamed to clients over
+/// the realtime websocket. Only the most recently finished jobs are kept.
+#[derive(Clone)]
+pub struct AppDeployments {
+    abra: Abra,
+    server: String,
+    jobs: Arc<RwLock<HashMap<String, AppDeploymentJobWithLogs>>>,
+    max_finished_jobs: usize,
+}
+
+impl AppDeployments {
+    pub fn new(runner: Arc<dyn CommandRunner>, server: String) -> Self {
+        Self {
+            abra: Abra::new(runner),
+            server,
+            jobs: Arc::new(RwLock::new(HashMap::new())),
+            max_finished_jobs: MAX_FINISHED_JOBS,
+        }
+    }
+
+    /// Uses the `abra` binary at `ABRA_PATH` (default: `abra` on the `PATH`),
+    /// deploying to the abra server named by `ABRA_SERVER` (default: `default`,
+    /// which is what `abra server add --local` creates).
+    pub fn from_env() -> Self {
+        let abra_path = env::var("ABRA_PATH").unwrap_or_else(|_| "abra".to_string());
+        let server = env::var("ABRA_SERVER").unwrap_or_else(|_| "default".to_string());
+
+        Self::new(Arc::new(ProcessCommandRunner::new(abra_path)), server)
+    }
+
+    pub async fn list(&self) -> Vec<AppDeploymentJob> {
+        let mut jobs: Vec<AppDeploymentJob> = self
+            .jobs
+            .read()
+            .await
+            .values()
+            .map(|tracked| tracked.job.clone())
+            .collect();
+        jobs.sort_by(|a, b| b.started_at.cmp(&a.started_at));
+        jobs
+    }
+
+    pub async fn find(&self, job_id: &str) -> Option<AppDeploymentJobWithLogs> {
+        self.jobs.read().await.get(job_id).cloned()
+    }
+
+    pub async fn start(
+        &self,
+        request: AppDeploymentRequest,
+        realtime_state: RealtimeState,
+    ) -> Result<AppDeploymentJob, StartDeploymentError> {
+        let command = request.to_abra_command(&self.server);
+        command
+            .validate()
+            .map_err(|e| StartDeploymentError::Invalid(e.to_string()))?;
+
+        let job = AppDeploymentJob {
+            id: ShortUuid::generate().to_string(),
+            action: request.action(),
+            domain: command.domain().to_string(),
+            recipe: request.recipe(),
+            version: request.version(),
+            status: AppDeploymentJobStatus::Running,
+            error: None,
+            started_at: Utc::now().to_rfc3339(),
+            finished_at: None,
+        };
+
+        {
+            let mut jobs = self.jobs.write().await;
+            let already_running = jobs.values().any(|tracked| {
+                tracked.job.domain == job.domain
+                    && tracked.job.status == AppDeploymentJobStatus::Running
+            });
+            if already_running {
+                return Err(StartDeploymentError::AlreadyRunning(job.domain));
+            }
+
+            jobs.insert(
+                job.id.clone(),
+                AppDeploymentJobWithLogs {
+                    job: job.clone(),
+                    logs: vec![],
+                },
+            );
+        }
+
+        info!("Starting abra job {}: {:?}", job.id, command);
+        realtime_state
+            .broadcast_app_event(ClientEvent::AppDeploymentJobUpdated(job.clone()))
+            .await;
+
+        let (log_tx, mut log_rx) = mpsc::channel::<LogLine>(64);
+        let abra = self.abra.clone();
+        let run = tokio::task::spawn_blocking(move || {
+            abra.run(&command, &mut |line| {
+                let _ = log_tx.blocking_send(line);
+            })
+        });
+
+        let this = self.clone();
+        let job_id = job.id.clone();
+        tokio::spawn(async move {
+            // The log channel closes once the command has exited.
+            while let Some(line) = log_rx.recv().await {
+                this.append_log(&job_id, line, &realtime_state).await;
+            }
+
+            let result = match run.await {
+                Ok(result) => result,
+                Err(e) => Err(anyhow::anyhow!("abra job panicked: {}", e)),
+            };
+            this.finish(&job_id, result, &realtime_state).await;
+        });
+
+        Ok(job)
+    }
+
+    async fn append_log(&self, job_id: &str, line: LogLine, realtime_state: &RealtimeState) {
+        let log_line = AppDeploymentLogLine {
+            job_id: job_id.to_string(),
+            stream: match line.stream {
+                LogStream::Stdout => AppDeploymentLogStream::Stdout,
+                LogStream::Stderr => AppDeploymentLogStream::Stderr,
+            },
+            text: line.text,
+        };
+
+        if let Some(tracked) = self.jobs.write().await.get_mut(job_id) {
+            tracked.logs.push(log_line.clone());
+        }
+
+        realtime_state
+            .broadcast_app_event(ClientEvent::AppDeploymentLog(log_line))
+            .await;
+    }
+
+    async fn finish(
+        &self,
+        job_id: &str,
+        result: Result<(), anyhow::Error>,
+        realtime_state: &RealtimeState,
+    ) {
+        let job = {
+            let mut jobs = self.jobs.write().await;
+            let Some(tracked) = jobs.get_mut(job_id) else {
+                return;
+            };
+
+            match result {
+                Ok(()) => {
+                    info!("abra job {} succeeded", job_id);
+                    tracked.job.status = AppDeploymentJobStatus::Succeeded;
+                }
+                Err(e) => {
+                    warn!("abra job {} failed: {}", job_id, e);
+                    tracked.job.status = AppDeploymentJobStatus::Failed;
+                    tracked.job.error = Some(e.to_string());
+                }
+            }
+            tracked.job.finished_at = Some(Utc::now().to_rfc3339());
+            let job = tracked.job.clone();
+            evict_finished_jobs(&mut jobs, self.max_finished_jobs);
+            job
+        };
+
+        realtime_state
+            .broadcast_app_event(ClientEvent::AppDeploymentJobUpdated(job))
+            .await;
+    }
+}
+
+/// Forgets the jobs that finished longest ago, leaving at most `keep`
+/// finished jobs.
+fn evict_finished_jobs(jobs: &mut HashMap<String, AppDeploymentJobWithLogs>, keep: usize) {
+    let mut finished: Vec<(String, String)> = jobs
+        .values()
+        .filter_map(|tracked| {
+            let finished_at = tracked.job.finished_at.clone()?;
+            Some((finished_at, tracked.job.id.clone()))
+        })
+        .collect();
+    if finished.len() <= keep {
+        return;
+    }
+
+    finished.sort();
+    let evict = finished.len() - keep;
+    for (_, job_id) in finished.into_iter().take(evict) {
+        jobs.remove(&job_id);
+    }
+}
+
+impl AppDeploymentRequest {
+    fn to_abra_command(&self, server: &str) -> AbraCommand {
+        match self.clone() {
+            AppDeploymentRequest::New {
+                recipe,
+                domain,
+                version,
+            } => AbraCommand::New {
+                recipe,
+                domain,
+                server: server.to_string(),
+                version,
+            },
+            AppDeploymentRequest::Deploy { domain } => AbraCommand::Deploy { domain },
+            AppDeploymentRequest::Upgrade { domain, version } => {
+                AbraCommand::Upgrade { domain, version }
+            }
+            AppDeploymentRequest::Undeploy { domain } => AbraCommand::Undeploy { domain },
+        }
+    }
+
+    fn action(&self) -> AppDeploymentAction {
+        match self {
+            AppDeploymentRequest::New { .. } => AppDeploymentAction::New,
+            AppDeploymentRequest::Deploy { .. } => AppDeploymentAction::Deploy,
+            AppDeploymentRequest::Upgrade { .. } => AppDeploymentAction::Upgrade,
+            AppDeploymentRequest::Undeploy { .. } => AppDeploymentAction::Undeploy,
+        }
+    }
+
+    fn recipe(&self) -> Option<String> {
+        match self {
+            AppDeploymentRequest::New { recipe, .. } => Some(recipe.clone()),
+            _ => None,
+        }
+    }
+
+    fn version(&self) -> Option<String> {
+        match self {
+            AppDeploymentRequest::New { version, .. }
+            | AppDeploymentRequest::Upgrade { version, .. } => version.clone(),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Mutex, mpsc as std_mpsc};
+    use std::time::Duration;
+
+    use tokio::sync::broadcast;
+
+    use super::*;
+
+    /// Writes a line to each output stream, then waits for the test to send
+    /// the result the command should end with.
+    struct FakeRunner {
+        results: Mutex<std_mpsc::Receiver<Result<(), String>>>,
+    }
+
+    impl CommandRunner for FakeRunner {
+        fn run(
+            &self,
+            args: &[String],
+            on_line: &mut dyn FnMut(LogLine),
+        ) -> Result<(), anyhow::Error> {
+            on_line(LogLine {
+                stream: LogStream::Stdout,
+                text: args.join(" "),
+            });
+            on_line(LogLine {
+                stream: LogStream::Stderr,
+                text: "warning".to_string(),
+            });
+            let result = self.results.lock().unwrap().recv()?;
+            result.map_err(|e| anyhow::anyhow!(e))
+        }
+    }
+
+    fn deployments() -> (AppDeployments, std_mpsc::Sender<Result<(), String>>) {
+        let (results_tx, results_rx) = std_mpsc::channel();
+        let runner = FakeRunner {
+            results: Mutex::new(results_rx),
+        };
+        let deployments = AppDeployments::new(Arc::new(runner), "default".to_string());
+        (deployments, results_tx)
+    }
+
+    fn deploy(domain: &str) -> AppDeploymentRequest {
+        AppDeploymentRequest::Deploy {
+            domain: domain.to_string(),
+        }
+    }
+
+    /// Waits for `job_id` to finish, returning its final state.
+    async fn finished(
+        events: &mut broadcast::Receiver<ClientEvent>,
+        job_id: &str,
+    ) -> AppDeploymentJob {
+        loop {
+            let event = tokio::time::timeout(Duration::from_secs(5), events.recv())
+                .await
+                .unwrap()
+                .unwrap();
+            if let ClientEvent::AppDeploymentJobUpdated(job) = event
+                && job.id == job_id
+                && job.status != AppDeploymentJobStatus::Running
+            {
+                return job;
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_job_streams_its_logs_and_succeeds() {
+        let (deployments, results_tx) = deployments();
+        let realtime_state = RealtimeState::new();
+        let mut events = realtime_state.subscribe().await;
+
+        let job = deployments
+            .start(deploy("blog.example.org"), realtime_state.clone())
+            .await
+            .unwrap();
+        assert_eq!(job.status, AppDeploymentJobStatus::Running);
+        assert_eq!(job.action, AppDeploymentAction::Deploy);
+        assert_eq!(job.domain, "blog.example.org");
+
+        results_tx.send(Ok(())).unwrap();
+        let mut logs = vec![];
+        loop {
+            match events.recv().await.unwrap() {
+                ClientEvent::AppDeploymentLog(line) => logs.push(line.text),
+                ClientEvent::AppDeploymentJobUpdated(updated)
+                    if updated.status != AppDeploymentJobStatus::Running =>
+                {
+                    assert_eq!(updated.status, AppDeploymentJobStatus::Succeeded);
+                    assert!(updated.finished_at.is_some());
+                    break;
+                }
+                _ => {}
+            }
+        }
+        assert_eq!(logs, vec!["app deploy blog.example.org --no-input", "warning"]);
+
+        let tracked = deployments.find(&job.id).await.unwrap();
+        assert_eq!(tracked.job.status, AppDeploymentJobStatus::Succeeded);
+        assert_eq!(tracked.logs.len(), 2);
+        assert!(matches!(
+            tracked.logs[1].stream,
+            AppDeploymentLogStream::Stderr
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_failed_job_keeps_the_error() {
+        let (deployments, results_tx) = deployments();
+        let realtime_state = RealtimeState::new();
+        let mut events = realtime_state.subscribe().await;
+
+        let job = deployments
+            .start(deploy("blog.example.org"), realtime_state)
+            .await
+            .unwrap();
+        results_tx
+            .send(Err("abra exited with 1".to_string()))
+            .unwrap();
+
+        let job = finished(&mut events, &job.id).await;
+        assert_eq!(job.status, AppDeploymentJobStatus::Failed);
+        assert_eq!(job.error.as_deref(), Some("abra exited with 1"));
+    }
+
+    #[tokio::test]
+    async fn test_second_job_for_the_same_app_is_refused_while_one_runs() {
+        let (deployments, results_tx) = deployments();
+        let realtime_state = RealtimeState::new();
+        let mut events = realtime_state.subscribe().await;
+
+        let job = deployments
+            .start(deploy("blog.example.org"), realtime_state.clone())
+            .await
+            .unwrap();
+        let refused = deployments
+            .start(deploy("blog.example.org"), realtime_state.clone())
+            .await;
+        assert!(matches!(
+            refused,
+            Err(StartDeploymentError::AlreadyRunning(domain)) if domain == "blog.example.org"
+        ));
+
+        results_tx.send(Ok(())).unwrap();
+        finished(&mut events, &job.id).await;
+        let job = deployments
+            .start(deploy("blog.example.org"), realtime_state)
+            .await
+            .unwrap();
+        results_tx.send(Ok(())).unwrap();
+        finished(&mut events, &job.id).await;
+    }
+
+    #[tokio::test]
+    async fn test_oldest_finished_jobs_are_forgotten() {
+        let (deployments, results_tx) = deployments();
+        let deployments = AppDeployments {
+            max_finished_jobs: 2,
+            ..deployments
+        };
+        let realtime_state = RealtimeState::new();
+        let mut events = realtime_state.subscribe().await;
+
+        let mut job_ids = vec![];
+        for _ in 0..3 {
+            let job = deployments
+                .start(deploy("blog.example.org"), realtime_state.clone())
+                .await
+                .unwrap();
+            results_tx.send(Ok(())).unwrap();
+            finished(&mut events, &job.id).await;
+            job_ids.push(job.id);
+        }
+
+        assert_eq!(deployments.list().await.len(), 2);
+        assert!(deployments.find(&job_ids[0]).await.is_none());
+        assert!(deployments.find(&job_ids[2]).await.is_some());
+    }
+}
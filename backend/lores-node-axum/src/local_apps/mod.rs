@@ -5,7 +5,13 @@ use crate::{
     local_apps::stack_apps::find_deployed_local_apps,
 };
 
+pub mod app_auth;
 pub mod app_instances;
+pub mod app_peers;
+pub mod app_quotas;
+pub mod backups;
+pub mod deployments;
+pub mod recipe_catalogue;
 pub mod region_resolver;
 pub mod stack_apps;
 
@@ -0,0 +1,283 @@
+use semver::Version;
+use serde::{Deserialize, Serialize};
+use sqlx::SqlitePool;
+use std::{
+    collections::{BTreeMap, HashMap},
+    env, fs,
+    path::PathBuf,
+    time::Duration,
+};
+use tracing::{info, warn};
+use utoipa::ToSchema;
+
+use crate::{
+    api::public_api::{client_events::ClientEvent, realtime::RealtimeState},
+    data::entities::LocalApp,
+    local_apps::find_local_apps,
+};
+
+#[derive(Serialize, ToSchema, Debug, Clone, PartialEq, Eq)]
+pub struct Recipe {
+    pub name: String,
+    pub description: Option<String>,
+    pub category: Option<String>,
+    pub website: Option<String>,
+    pub repository: Option<String>,
+    pub icon: Option<String>,
+    /// Published versions, oldest first.
+    pub versions: Vec<String>,
+    pub latest_version: Option<String>,
+}
+
+#[derive(Serialize, ToSchema, Debug, Clone, PartialEq, Eq)]
+pub struct AppUpgrade {
+    pub name: String,
+    pub instance_id: Option<String>,
+    pub installed_version: String,
+    pub latest_version: String,
+}
+
+/// An entry in the Co-op Cloud `recipes.json` index. Each version is a
+/// single-key map from the version string to its service images, which we
+/// don't need here.
+#[derive(Deserialize, Debug)]
+struct RecipeIndexEntry {
+    name: Option<String>,
+    description: Option<String>,
+    category: Option<String>,
+    website: Option<String>,
+    repository: Option<String>,
+    icon: Option<String>,
+    #[serde(default)]
+    versions: Vec<HashMap<String, serde_json::Value>>,
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum RecipeCatalogueError {
+    #[error("Failed to read recipe catalogue {0}: {1}")]
+    Read(String, std::io::Error),
+    #[error("Failed to parse recipe catalogue {0}: {1}")]
+    Parse(String, serde_json::Error),
+}
+
+/// Reads the Co-op Cloud recipe index from a locally mirrored `recipes.json`.
+#[derive(Clone, Debug)]
+pub struct RecipeCatalogue {
+    path: PathBuf,
+}
+
+impl RecipeCatalogue {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+
+    pub fn from_env() -> Self {
+        let path = env::var("RECIPE_CATALOGUE_PATH").unwrap_or_else(|_| {
+            let data_dir = env::var("DATA_DIR").unwrap_or_else(|_| ".".to_string());
+            format!("{}/recipes.json", data_dir)
+        });
+        Self::new(path)
+    }
+
+    pub fn recipes(&self) -> Result<Vec<Recipe>, RecipeCatalogueError> {
+        let path = self.path.display().to_string();
+        let json = fs::read_to_string(&self.path)
+            .map_err(|e| RecipeCatalogueError::Read(path.clone(), e))?;
+        parse_recipe_index(&json).map_err(|e| RecipeCatalogueError::Parse(path, e))
+    }
+
+    pub fn upgrades_for(&self, apps: &[LocalApp]) -> Result<Vec<AppUpgrade>, RecipeCatalogueError> {
+        Ok(find_app_upgrades(&self.recipes()?, apps))
+    }
+}
+
+pub fn parse_recipe_index(json: &str) -> Result<Vec<Recipe>, serde_json::Error> {
+    let index: BTreeMap<String, RecipeIndexEntry> = serde_json::from_str(json)?;
+
+    Ok(index
+        .into_iter()
+        .map(|(key, entry)| {
+            let versions: Vec<String> = entry
+                .versions
+                .into_iter()
+                .flat_map(|v| v.into_keys())
+                .collect();
+            let latest_version = latest_version(&versions);
+
+            Recipe {
+                name: entry.name.unwrap_or(key),
+                description: entry.description,
+                category: entry.category,
+                website: entry.website,
+                repository: entry.repository,
+                icon: entry.icon,
+                versions,
+                latest_version,
+            }
+        })
+        .collect())
+}
+
+/// Flags apps whose installed version is older than the latest release of the
+/// recipe with the same name. Apps or releases without a parseable semver
+/// version are ignored.
+pub fn find_app_upgrades(recipes: &[Recipe], apps: &[LocalApp]) -> Vec<AppUpgrade> {
+    apps.iter()
+        .filter_map(|app| {
+            let recipe = recipes.iter().find(|r| r.name == app.name)?;
+            let latest = recipe.latest_version.as_ref()?;
+            let installed_version = parse_version(&app.version)?;
+
+            if parse_version(latest)? > installed_version {
+                Some(AppUpgrade {
+                    name: app.name.clone(),
+                    instance_id: app.instance_id.clone(),
+                    installed_version: app.version.clone(),
+                    latest_version: latest.clone(),
+                })
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
+fn latest_version(versions: &[String]) -> Option<String> {
+    versions
+        .iter()
+        .filter_map(|v| parse_version(v).map(|parsed| (parsed, v)))
+        .max_by(|(a, _), (b, _)| a.cmp(b))
+        .map(|(_, v)| v.clone())
+}
+
+/// Co-op Cloud versions look like `2.1.0+1.24.3`, where the build metadata is
+/// the upstream version. Drop it so only the recipe version decides ordering.
+fn parse_version(version: &str) -> Option<Version> {
+    let mut parsed = Version::parse(version.trim().trim_start_matches('v')).ok()?;
+    parsed.build = semver::BuildMetadata::EMPTY;
+    Some(parsed)
+}
+
+pub fn start_upgrade_checks(
+    catalogue: RecipeCatalogue,
+    node_data_pool: SqlitePool,
+    realtime_state: RealtimeState,
+) {
+    let interval_secs = env::var("RECIPE_UPGRADE_CHECK_INTERVAL_SECS")
+        .ok()
+        .and_then(|s| s.parse::<u64>().ok())
+        .unwrap_or(3600);
+
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(interval_secs));
+        let mut last_upgrades: Vec<AppUpgrade> = vec![];
+
+        loop {
+            interval.tick().await;
+
+            let apps = match find_local_apps(&node_data_pool).await {
+                Ok(apps) => apps,
+                Err(e) => {
+                    warn!("Failed to load local apps for upgrade check: {}", e);
+                    continue;
+                }
+            };
+            let upgrades = match catalogue.upgrades_for(&apps) {
+                Ok(upgrades) => upgrades,
+                Err(e) => {
+                    warn!("Skipping app upgrade check: {}", e);
+                    continue;
+                }
+            };
+
+            if upgrades != last_upgrades {
+                info!("{} app upgrade(s) available", upgrades.len());
+                realtime_state
+                    .broadcast_app_event(ClientEvent::AppUpgradesAvailable(upgrades.clone()))
+                    .await;
+                last_upgrades = upgrades;
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::entities::LocalAppSource;
+
+    const INDEX: &str = r#"{
+        "gitea": {
+            "name": "gitea",
+            "category": "Development",
+            "description": "Git with a cup of tea",
+            "versions": [
+                {"2.9.0+1.21.11-rootless": {"app": {"image": "gitea/gitea"}}},
+                {"2.10.0+1.22.0-rootless": {"app": {"image": "gitea/gitea"}}},
+                {"2.2.0+1.17.0-rootless": {"app": {"image": "gitea/gitea"}}}
+            ]
+        },
+        "nextcloud": {
+            "versions": []
+        }
+    }"#;
+
+    fn app(name: &str, version: &str) -> LocalApp {
+        LocalApp {
+            name: name.to_string(),
+            version: version.to_string(),
+            url: None,
+            source: LocalAppSource::Docker,
+            instance_id: None,
+            bound_to_region_id: None,
+        }
+    }
+
+    #[test]
+    fn test_parse_recipe_index_picks_latest_by_semver() {
+        let recipes = parse_recipe_index(INDEX).unwrap();
+
+        assert_eq!(recipes.len(), 2);
+        assert_eq!(recipes[0].name, "gitea");
+        assert_eq!(recipes[0].versions.len(), 3);
+        assert_eq!(
+            recipes[0].latest_version.as_deref(),
+            Some("2.10.0+1.22.0-rootless")
+        );
+        assert_eq!(recipes[1].name, "nextcloud");
+        assert_eq!(recipes[1].latest_version, None);
+    }
+
+    #[test]
+    fn test_find_app_upgrades_flags_outdated_apps_only() {
+        let recipes = parse_recipe_index(INDEX).unwrap();
+        let apps = vec![
+            app("gitea", "2.9.0+1.21.11-rootless"),
+            app("nextcloud", "1.0.0"),
+            app("unknown", "0.1.0"),
+        ];
+
+        let upgrades = find_app_upgrades(&recipes, &apps);
+
+        assert_eq!(
+            upgrades,
+            vec![AppUpgrade {
+                name: "gitea".to_string(),
+                instance_id: None,
+                installed_version: "2.9.0+1.21.11-rootless".to_string(),
+                latest_version: "2.10.0+1.22.0-rootless".to_string(),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_find_app_upgrades_ignores_current_and_unparseable_versions() {
+        let recipes = parse_recipe_index(INDEX).unwrap();
+        let apps = vec![
+            app("gitea", "2.10.0+1.22.0-rootless"),
+            app("gitea", "unknown"),
+        ];
+
+        assert!(find_app_upgrades(&recipes, &apps).is_empty());
+    }
+}
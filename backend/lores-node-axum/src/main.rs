@@ -1,9 +1,11 @@
 use axum::{
     Extension,
     http::{Method, header},
+    middleware,
     routing::get,
 };
 use axum_login::AuthManagerLayerBuilder;
+use clap::Parser;
 use sqlx::SqlitePool;
 use std::env;
 use time::Duration;
@@ -11,7 +13,7 @@ use tokio::sync::mpsc;
 use tonic::transport::Server as GrpcServer;
 use tower_http::{cors::CorsLayer, trace::TraceLayer};
 use tower_sessions::{Expiry, SessionManagerLayer};
-use tracing::info;
+use tracing::{info, warn};
 use tracing_subscriber::EnvFilter;
 use utoipa::OpenApi;
 use utoipa_axum::router::OpenApiRouter;
@@ -21,10 +23,13 @@ use crate::{
     // event_handlers::handle_event,,
     api::{
         api_router,
-        auth_api::auth_backend::AppAuthBackend,
+        auth_api::{auth_backend::AppAuthBackend, bearer_auth::bearer_auth},
         public_api::realtime::{self, RealtimeState},
     },
     config::{config::LoresNodeConfig, config_state::LoresNodeConfigState},
+    local_apps::{
+        backups::AppBackups, deployments::AppDeployments, recipe_catalogue::RecipeCatalogue,
+    },
     panda_comms::{
         PandaContainer, lores_events::LoResEvent, start_panda, start_panda_event_handler,
     },
@@ -32,10 +37,12 @@ use crate::{
 };
 
 mod api;
+mod cli;
 mod config;
 mod data;
 mod event_handlers;
 mod local_apps;
+mod node_admin;
 mod panda_comms;
 mod static_server;
 
@@ -52,6 +59,15 @@ struct DatabaseState {
 
 #[tokio::main]
 async fn main() {
+    let cli = cli::Cli::parse();
+
+    match cli.command {
+        None | Some(cli::Command::Serve) => serve().await,
+        Some(command) => cli::run(command, cli.json).await,
+    }
+}
+
+async fn serve() {
     #[derive(OpenApi)]
     #[openapi()]
     struct ApiDoc;
@@ -109,6 +125,20 @@ async fn main() {
     // REALTIME COMMS
     let realtime_state = RealtimeState::new();
 
+    // APP DEPLOYMENTS
+    let app_deployments = AppDeployments::from_env();
+    let app_backups = AppBackups::from_env();
+    if let Err(e) = app_backups.fail_interrupted(&node_data_pool).await {
+        warn!("Failed to mark interrupted backups as failed: {}", e);
+    }
+    app_backups.start_schedule(node_data_pool.clone(), realtime_state.clone());
+    let recipe_catalogue = RecipeCatalogue::from_env();
+    local_apps::recipe_catalogue::start_upgrade_checks(
+        recipe_catalogue.clone(),
+        node_data_pool.clone(),
+        realtime_state.clone(),
+    );
+
     // P2PANDA
     let (channel_tx, channel_rx): (mpsc::Sender<LoResEvent>, mpsc::Receiver<LoResEvent>) =
         mpsc::channel(32);
@@ -126,23 +156,63 @@ async fn main() {
             local_apps::app_instances::make_instance_seen_callback(node_data_pool.clone());
         let resolve_region_id =
             local_apps::region_resolver::make_region_resolver(node_data_pool.clone());
+        let list_app_peers =
+            local_apps::app_peers::make_app_peers_lister(projections_pool.clone());
+        let authenticate_app =
+            local_apps::app_auth::make_app_authenticator(node_data_pool.clone());
+        local_apps::app_auth::warn_about_apps_without_tokens(&node_data_pool).await;
         lores_p2panda_server::PandaService::new(
             panda_container.node_arc(),
             node_data_pool.clone(),
-            None,
+            lores_p2panda_server::PandaServiceConfig {
+                quota: local_apps::app_quotas::quota_config_from_env(),
+                ..Default::default()
+            },
             on_instance_seen,
             resolve_region_id,
+            list_app_peers,
+            authenticate_app,
         )
         .await
         .expect("Failed to initialise PandaService")
     };
+    let quota_tracker = panda_service.quota_tracker();
+    // The app key-value store can be turned off with GRPC_KEY_VALUE_STORE=disabled.
+    let key_value_service = if env::var("GRPC_KEY_VALUE_STORE").as_deref() == Ok("disabled") {
+        None
+    } else {
+        Some(
+            lores_p2panda_server::KeyValueService::new(&panda_service, node_data_pool.clone())
+                .await
+                .expect("Failed to initialise KeyValueService")
+                .into_server(),
+        )
+    };
+    // Setting GRPC_UNIX_SOCKET serves gRPC on that socket instead of TCP, so
+    // only apps on this host whose user can open the socket (mode
+    // GRPC_UNIX_SOCKET_MODE, octal, default 660) can reach it.
+    let grpc_unix_socket = env::var("GRPC_UNIX_SOCKET").ok();
+    let grpc_unix_socket_mode = env::var("GRPC_UNIX_SOCKET_MODE")
+        .map(|mode| u32::from_str_radix(&mode, 8).expect("GRPC_UNIX_SOCKET_MODE must be octal"))
+        .unwrap_or(0o660);
     tokio::spawn(async move {
-        info!("gRPC listening on {}", grpc_addr);
-        GrpcServer::builder()
+        let grpc_router = GrpcServer::builder()
             .add_service(panda_service.into_server())
-            .serve(grpc_addr)
-            .await
-            .expect("gRPC server failed");
+            .add_optional_service(key_value_service);
+        match grpc_unix_socket {
+            Some(path) => {
+                let incoming =
+                    lores_p2panda_server::bind_unix_socket(&path, grpc_unix_socket_mode)
+                        .expect("Failed to bind gRPC Unix socket");
+                info!("gRPC listening on unix://{}", path);
+                grpc_router.serve_with_incoming(incoming).await
+            }
+            None => {
+                info!("gRPC listening on {}", grpc_addr);
+                grpc_router.serve(grpc_addr).await
+            }
+        }
+        .expect("gRPC server failed");
     });
 
     // ROUTES
@@ -162,8 +232,13 @@ async fn main() {
         }))
         .layer(Extension(config_state))
         .layer(Extension(panda_container))
+        .layer(middleware::from_fn(bearer_auth))
         .layer(auth_layer)
-        .layer(Extension(realtime_state));
+        .layer(Extension(realtime_state))
+        .layer(Extension(app_deployments))
+        .layer(Extension(app_backups))
+        .layer(Extension(recipe_catalogue))
+        .layer(Extension(quota_tracker));
 
     // SERVICE
 
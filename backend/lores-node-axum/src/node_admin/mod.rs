@@ -0,0 +1,28 @@
+//! Steward actions shared by the HTTP API and the `lores-node` command line.
+
+use thiserror::Error;
+
+pub mod network;
+pub mod node_stewards;
+pub mod regions;
+
+#[derive(Debug, Error)]
+pub enum NodeAdminError {
+    #[error("{0}")]
+    BadRequest(String),
+    #[error("{0}")]
+    NotFound(String),
+    #[error(transparent)]
+    Internal(#[from] anyhow::Error),
+}
+
+impl From<sqlx::Error> for NodeAdminError {
+    fn from(e: sqlx::Error) -> Self {
+        NodeAdminError::Internal(e.into())
+    }
+}
+
+/// Wraps errors that are the node's fault rather than the caller's.
+pub(crate) fn internal<E: std::fmt::Debug>(error: E) -> NodeAdminError {
+    NodeAdminError::Internal(anyhow::anyhow!("{:?}", error))
+}
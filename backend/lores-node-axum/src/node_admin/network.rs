@@ -0,0 +1,113 @@
+use serde::Serialize;
+use sqlx::SqlitePool;
+use tokio::sync::mpsc;
+use utoipa::ToSchema;
+
+use super::NodeAdminError;
+use crate::{
+    api::public_api::realtime::RealtimeState,
+    config::config_state::LoresNodeConfigState,
+    data::projections_write::truncate_all,
+    event_handlers::handle_event,
+    panda_comms::{PandaContainer, build_public_key_from_hex},
+};
+
+#[derive(Serialize, ToSchema)]
+pub struct OperationCountEntry {
+    pub topic: String,
+    pub author_node_id: String,
+    pub count: i64,
+}
+
+/// Connects to another node to find peers through, now and on every restart.
+pub async fn add_bootstrap_node(
+    panda_container: &PandaContainer,
+    config_state: &LoresNodeConfigState,
+    node_id: &str,
+) -> Result<(), NodeAdminError> {
+    build_public_key_from_hex(node_id).map_err(|_| {
+        NodeAdminError::BadRequest(
+            "Invalid node_id format. Must be a hex string representing a public key.".to_string(),
+        )
+    })?;
+
+    // Add the bootstrap node to the current PandaContainer
+    panda_container
+        .add_bootstrap_node(node_id, None)
+        .await
+        .map_err(|e| anyhow::anyhow!("Failed to add bootstrap node: {}", e))?;
+
+    // Add the new bootstrap node ID to the config
+    config_state
+        .update(|config| {
+            let mut result = config.clone();
+            let mut bootstrap_node_ids = result.bootstrap_node_ids.unwrap_or_default();
+            if !bootstrap_node_ids.contains(&node_id.to_string()) {
+                bootstrap_node_ids.push(node_id.to_string());
+            }
+            result.bootstrap_node_ids = Some(bootstrap_node_ids);
+            result
+        })
+        .await
+        .map_err(|e| anyhow::anyhow!("Failed to update config: {}", e))?;
+
+    Ok(())
+}
+
+/// Empties the projection tables and starts rebuilding them from the
+/// operations store in the background. Returns the number of regions being
+/// replayed.
+pub async fn start_replay(
+    projections_pool: &SqlitePool,
+    panda_container: &PandaContainer,
+) -> Result<usize, NodeAdminError> {
+    truncate_projections(projections_pool).await?;
+
+    let count = panda_container
+        .replay_all_regions()
+        .await
+        .map_err(|e| anyhow::anyhow!("Failed to start replay: {e}"))?;
+    Ok(count)
+}
+
+/// Like [`start_replay`], but returns once the projection tables have been
+/// rebuilt.
+pub async fn replay(
+    projections_pool: &SqlitePool,
+    panda_container: &PandaContainer,
+    realtime_state: &RealtimeState,
+) -> Result<usize, NodeAdminError> {
+    truncate_projections(projections_pool).await?;
+
+    let (events_tx, mut events_rx) = mpsc::channel(32);
+    let count = panda_container
+        .replay_all_regions_into(events_tx)
+        .await
+        .map_err(|e| anyhow::anyhow!("Failed to start replay: {e}"))?;
+    while let Some(event) = events_rx.recv().await {
+        handle_event(event, projections_pool, realtime_state).await;
+    }
+
+    Ok(count)
+}
+
+pub async fn operation_counts(
+    panda_container: &PandaContainer,
+) -> Result<Vec<OperationCountEntry>, NodeAdminError> {
+    let counts = panda_container.get_operation_counts_by_topic().await?;
+    Ok(counts
+        .into_iter()
+        .map(|c| OperationCountEntry {
+            topic: c.topic_hex,
+            author_node_id: c.author_node_id,
+            count: c.count,
+        })
+        .collect())
+}
+
+async fn truncate_projections(projections_pool: &SqlitePool) -> Result<(), NodeAdminError> {
+    truncate_all(projections_pool)
+        .await
+        .map_err(|e| anyhow::anyhow!("Failed to truncate projection tables: {e}"))?;
+    Ok(())
+}
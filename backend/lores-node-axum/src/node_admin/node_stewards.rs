@@ -0,0 +1,141 @@
+use serde::Serialize;
+use sqlx::SqlitePool;
+use utoipa::ToSchema;
+
+use super::NodeAdminError;
+use crate::data::node_data::node_stewards::{
+    NodeStewardIdentifier, NodeStewardRole, NodeStewardRow, NodeStewardsRepo,
+};
+
+#[derive(Serialize, ToSchema, Debug)]
+pub enum NodeStewardStatus {
+    Enabled,
+    Disabled,
+    Invited,
+    TokenExpired,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct NodeSteward {
+    pub id: String,
+    pub name: String,
+    pub created_at: String,
+    pub status: NodeStewardStatus,
+    pub roles: Vec<NodeStewardRole>,
+}
+
+impl NodeSteward {
+    pub fn from_row(row: &NodeStewardRow) -> Self {
+        let mut status: NodeStewardStatus = match row.enabled {
+            true => NodeStewardStatus::Enabled,
+            false => NodeStewardStatus::Disabled,
+        };
+        if row.hashed_password.is_none() {
+            match row.token_expired() {
+                true => status = NodeStewardStatus::TokenExpired,
+                false => status = NodeStewardStatus::Invited,
+            }
+        }
+
+        NodeSteward {
+            id: row.id.clone(),
+            name: row.name.clone(),
+            created_at: row.created_at.unwrap_or_default().to_string(),
+            status,
+            roles: row.roles(),
+        }
+    }
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct NodeStewardCreationResult {
+    pub node_steward: NodeSteward,
+    pub password_reset_token: String,
+}
+
+pub async fn list(pool: &SqlitePool) -> Result<Vec<NodeSteward>, NodeAdminError> {
+    let stewards = NodeStewardsRepo::init().all(pool).await?;
+    Ok(stewards.iter().map(NodeSteward::from_row).collect())
+}
+
+/// Invites a new steward. They set their password with the returned token.
+/// Without `roles` they get every role.
+pub async fn create(
+    pool: &SqlitePool,
+    name: String,
+    roles: Option<Vec<NodeStewardRole>>,
+) -> Result<NodeStewardCreationResult, NodeAdminError> {
+    let mut new_row = NodeStewardRow::new(name);
+    if let Some(roles) = roles {
+        new_row = new_row.with_roles(&roles);
+    }
+    new_row.set_password_reset_token();
+
+    NodeStewardsRepo::init().create(pool, &new_row).await?;
+
+    Ok(NodeStewardCreationResult {
+        node_steward: NodeSteward::from_row(&new_row),
+        password_reset_token: new_row.password_reset_token.unwrap_or_default(),
+    })
+}
+
+/// Issues a fresh password reset token for a steward.
+pub async fn reset_token(
+    pool: &SqlitePool,
+    steward_id: String,
+) -> Result<NodeStewardCreationResult, NodeAdminError> {
+    let repo = NodeStewardsRepo::init();
+    let identifier = NodeStewardIdentifier { id: steward_id };
+
+    let mut row = repo
+        .find(pool, &identifier)
+        .await?
+        .ok_or_else(|| not_found(&identifier))?;
+    row.set_password_reset_token();
+
+    repo.update_password_reset_token(pool, &row).await?;
+
+    Ok(NodeStewardCreationResult {
+        node_steward: NodeSteward::from_row(&row),
+        password_reset_token: row.password_reset_token.unwrap_or_default(),
+    })
+}
+
+pub async fn set_enabled(
+    pool: &SqlitePool,
+    steward_id: String,
+    enabled: bool,
+) -> Result<NodeSteward, NodeAdminError> {
+    let repo = NodeStewardsRepo::init();
+    let identifier = NodeStewardIdentifier { id: steward_id };
+
+    repo.update_enabled(pool, &identifier, enabled).await?;
+
+    let row = repo
+        .find(pool, &identifier)
+        .await?
+        .ok_or_else(|| not_found(&identifier))?;
+    Ok(NodeSteward::from_row(&row))
+}
+
+/// Replaces a steward's roles. They apply from the steward's next request.
+pub async fn set_roles(
+    pool: &SqlitePool,
+    steward_id: String,
+    roles: &[NodeStewardRole],
+) -> Result<NodeSteward, NodeAdminError> {
+    let repo = NodeStewardsRepo::init();
+    let identifier = NodeStewardIdentifier { id: steward_id };
+
+    repo.update_roles(pool, &identifier, roles).await?;
+
+    let row = repo
+        .find(pool, &identifier)
+        .await?
+        .ok_or_else(|| not_found(&identifier))?;
+    Ok(NodeSteward::from_row(&row))
+}
+
+fn not_found(identifier: &NodeStewardIdentifier) -> NodeAdminError {
+    NodeAdminError::NotFound(format!("No node steward with id {}", identifier.id))
+}
@@ -0,0 +1,380 @@
+use lores_p2panda::p2panda_core::VerifyingKey;
+use serde::Deserialize;
+use sqlx::SqlitePool;
+use tracing::{info, warn};
+use utoipa::ToSchema;
+
+use super::{NodeAdminError, internal};
+use crate::{
+    api::auth_api::auth_backend::User,
+    config::config_state::LoresNodeConfigState,
+    data::{
+        entities::{Region, RegionWithNodes},
+        projections_read::{region_nodes::RegionNodesReadRepo, regions::RegionsReadRepo},
+    },
+    panda_comms::{
+        PandaContainer, PandaSubscriptionError, RegionAdminTopic, RegionId, SubscriptionError,
+        lores_events::{
+            LoResEventPayload, RegionCreatedDataV1, RegionJoinRequestApprovedDataV1,
+            RegionJoinRequestedDataV1,
+        },
+    },
+};
+
+#[derive(Deserialize, ToSchema, Debug)]
+#[allow(dead_code)]
+pub struct CreateRegionData {
+    pub slug: String,
+    pub name: String,
+    pub organisation_name: Option<String>,
+    pub organisation_url: Option<String>,
+    pub node_steward_conduct_url: Option<String>,
+    pub user_conduct_url: Option<String>,
+    pub user_privacy_url: Option<String>,
+}
+
+#[derive(Deserialize, ToSchema, Debug)]
+#[allow(dead_code)]
+pub struct JoinRegionRequestData {
+    pub region_id: String,
+    pub about_your_node: String,
+    pub about_your_stewards: String,
+    pub agreed_node_steward_conduct_url: Option<String>,
+}
+
+#[derive(Deserialize, ToSchema, Debug)]
+#[allow(dead_code)]
+pub struct ApproveJoinRequestData {
+    pub region_id: String,
+    pub node_id: String,
+}
+
+/// The regions this node has created or joined, with their nodes. Regions
+/// that haven't been seen on the network yet are returned unnamed.
+pub async fn list_regions(
+    config_state: &LoresNodeConfigState,
+    projections_pool: &SqlitePool,
+    node_id: &VerifyingKey,
+) -> Result<Vec<RegionWithNodes>, NodeAdminError> {
+    let config = config_state.get().await;
+
+    // Get region_ids from config
+    let region_ids: Vec<RegionId> = match config.region_ids {
+        Some(region_ids) => {
+            info!("got region ids {:?}", region_ids);
+
+            region_ids
+                .into_iter()
+                .map(|id| match RegionId::from_hex(&id) {
+                    Ok(id) => id,
+                    Err(_) => panic!("Invalid region ID in config: {}", id),
+                })
+                .collect()
+        }
+        None => {
+            info!("no region ids found in config");
+            return Ok(vec![]);
+        }
+    };
+
+    // Get regions from database
+    let db_regions = RegionsReadRepo::init()
+        .find_all_for_node(projections_pool, &node_id.to_hex())
+        .await?;
+
+    // Only return regions that are in the config, and if a region is in the config but not in the database, return an unnamed region with just the ID
+    let mut result_regions: Vec<Region> = Vec::with_capacity(region_ids.len());
+    for id in region_ids {
+        let db_region: Option<&Region> = db_regions.iter().find(|r| r.id == id.to_hex());
+
+        let region = match db_region {
+            Some(region) => region.clone(),
+            None => {
+                warn!("Region {} not found in database", id);
+                Region::unnamed(id, *node_id)
+            }
+        };
+
+        result_regions.push(region);
+    }
+
+    // Build region with nodes for each region
+    let result = RegionNodesReadRepo::init()
+        .append_detail_nodes_to_list(projections_pool, result_regions)
+        .await?;
+
+    Ok(result)
+}
+
+/// Creates a region with this node as its controller, and returns its id.
+pub async fn create_region(
+    panda_container: &PandaContainer,
+    config_state: &LoresNodeConfigState,
+    data: &CreateRegionData,
+    user: Option<User>,
+) -> Result<RegionId, NodeAdminError> {
+    info!("Creating region with data: {:?}", data);
+
+    if data.slug.is_empty() || data.name.is_empty() {
+        return Err(NodeAdminError::BadRequest(
+            "Slug and name are required".to_string(),
+        ));
+    }
+
+    info!(
+        "Validated region data: slug={}, name={}",
+        data.slug, data.name
+    );
+
+    // Generate a region ID and store it in the config
+    let region_id = store_new_region_id(config_state).await?;
+    info!("Generated new region ID: {}", region_id);
+
+    // Subscribe to the new region
+    panda_container
+        .join_region(region_id.clone())
+        .await
+        .map_err(internal)?;
+
+    // Publish the RegionCreated event
+    let event_payload = LoResEventPayload::RegionCreated(RegionCreatedDataV1 {
+        slug: data.slug.clone(),
+        name: data.name.clone(),
+        organisation_name: data.organisation_name.clone(),
+        organisation_url: data.organisation_url.clone(),
+        node_steward_conduct_url: data.node_steward_conduct_url.clone(),
+        user_conduct_url: data.user_conduct_url.clone(),
+        user_privacy_url: data.user_privacy_url.clone(),
+    });
+    info!("Prepared event payload: {:?}", event_payload);
+
+    panda_container
+        .publish_persisted(
+            &RegionAdminTopic::new(region_id.clone()),
+            event_payload,
+            user,
+        )
+        .await
+        .map_err(internal)?;
+
+    info!("Created new region with ID: {:?}", region_id);
+
+    Ok(region_id)
+}
+
+/// Subscribes to a region and asks its controller node to let this node join.
+pub async fn join_region(
+    panda_container: &PandaContainer,
+    config_state: &LoresNodeConfigState,
+    data: &JoinRegionRequestData,
+    user: Option<User>,
+) -> Result<(), NodeAdminError> {
+    // Validate data
+    if data.region_id.is_empty()
+        || data.region_id.len() != 64
+        || data.about_your_node.is_empty()
+        || data.about_your_stewards.is_empty()
+    {
+        return Err(NodeAdminError::BadRequest(
+            "Invalid request data".to_string(),
+        ));
+    }
+
+    let region_id = parse_region_id(&data.region_id)?;
+
+    // Store the region ID in the config if it's not already there
+    store_region_id(config_state, &region_id).await?;
+
+    // Subscribe to the new region
+    if let Err(e) = panda_container.join_region(region_id.clone()).await {
+        if matches!(
+            e,
+            PandaSubscriptionError::SubscriptionError(SubscriptionError::AlreadySubscribed(_))
+        ) {
+            warn!("Already subscribed to region {:?}, proceeding", region_id);
+        } else {
+            return Err(internal(e));
+        }
+    }
+
+    // Publish the RegionJoinRequested event
+    let event_payload = LoResEventPayload::RegionJoinRequested(RegionJoinRequestedDataV1 {
+        about_your_node: data.about_your_node.clone(),
+        about_your_stewards: data.about_your_stewards.clone(),
+        agreed_node_steward_conduct_url: data.agreed_node_steward_conduct_url.clone(),
+    });
+    info!("Prepared event payload: {:?}", event_payload);
+
+    panda_container
+        .publish_persisted(&RegionAdminTopic::new(region_id), event_payload, user)
+        .await
+        .map_err(internal)?;
+
+    Ok(())
+}
+
+/// Lets a node that asked to join a region in. Only the region's controller
+/// node can do this.
+pub async fn approve_join_request(
+    panda_container: &PandaContainer,
+    projections_pool: &SqlitePool,
+    data: &ApproveJoinRequestData,
+    user: Option<User>,
+) -> Result<(), NodeAdminError> {
+    // Validate data
+    if data.region_id.is_empty()
+        || data.region_id.len() != 64
+        || data.node_id.is_empty()
+        || data.node_id.len() != 64
+    {
+        return Err(NodeAdminError::BadRequest(
+            "Invalid request data".to_string(),
+        ));
+    }
+
+    let region_id = parse_region_id(&data.region_id)?;
+
+    // Check that I am the controller node for this region
+    if let Err(e) = ensure_controller_node(projections_pool, &region_id, panda_container).await {
+        warn!("Controller node check failed: {:?}", e);
+        return Err(NodeAdminError::BadRequest(format!(
+            "Controller node check failed: {}",
+            e
+        )));
+    }
+
+    // Publish the RegionJoinRequestApproved event
+    let event_payload =
+        LoResEventPayload::RegionJoinRequestApproved(RegionJoinRequestApprovedDataV1 {
+            node_id: data.node_id.clone(),
+        });
+    panda_container
+        .publish_persisted(&RegionAdminTopic::new(region_id), event_payload, user)
+        .await
+        .map_err(internal)?;
+
+    Ok(())
+}
+
+/// Removes a region from this node's config and stops following it.
+pub async fn forget_region(
+    panda_container: &PandaContainer,
+    config_state: &LoresNodeConfigState,
+    region_id: &str,
+) -> Result<(), NodeAdminError> {
+    let parsed_region_id = parse_region_id(region_id)?;
+
+    config_state
+        .update(|config| {
+            let mut result = config.clone();
+            result.region_ids = Some(
+                result
+                    .region_ids
+                    .unwrap_or_default()
+                    .into_iter()
+                    .filter(|id| id != region_id)
+                    .collect(),
+            );
+            result
+        })
+        .await?;
+
+    if !panda_container.leave_region(parsed_region_id).await {
+        info!("Region {} was not subscribed, nothing to leave", region_id);
+    }
+
+    Ok(())
+}
+
+pub(crate) fn parse_region_id(region_id: &str) -> Result<RegionId, NodeAdminError> {
+    RegionId::from_hex(region_id).map_err(|e| {
+        warn!("Invalid region ID: {:?}", e);
+        NodeAdminError::BadRequest("Invalid region ID".to_string())
+    })
+}
+
+async fn store_new_region_id(
+    config_state: &LoresNodeConfigState,
+) -> Result<RegionId, anyhow::Error> {
+    let mut region_id_string: Option<String> = None;
+    config_state
+        .update(|config| {
+            let mut result = config.clone();
+            let mut region_ids: Vec<String> = result.region_ids.unwrap_or_default();
+
+            while region_id_string.is_none()
+                || region_ids.contains(&region_id_string.clone().unwrap())
+            {
+                let new_id_string = RegionId::generate().to_hex();
+                info!("Trying new region id {}", new_id_string);
+                if !region_ids.contains(&new_id_string) {
+                    region_id_string = Some(new_id_string.clone());
+                }
+            }
+
+            region_ids.push(region_id_string.clone().unwrap());
+
+            info!("Setting region_ids to {:?}", region_ids);
+
+            result.region_ids = Some(region_ids);
+            result
+        })
+        .await?;
+
+    match region_id_string {
+        Some(id_string) => match RegionId::from_hex(&id_string) {
+            Ok(id) => Ok(id),
+            Err(e) => Err(anyhow::anyhow!("Failed to parse new region ID: {:?}", e)),
+        },
+        None => Err(anyhow::anyhow!("Failed to store new region ID")),
+    }
+}
+
+async fn store_region_id(
+    config_state: &LoresNodeConfigState,
+    region_id: &RegionId,
+) -> Result<(), anyhow::Error> {
+    config_state
+        .update(|config| {
+            let mut result = config.clone();
+            let mut region_ids: Vec<String> = result.region_ids.unwrap_or_default();
+
+            if !region_ids.contains(&region_id.to_hex()) {
+                region_ids.push(region_id.to_hex());
+            }
+
+            info!("Setting region_ids to {:?}", region_ids);
+
+            result.region_ids = Some(region_ids);
+            result
+        })
+        .await?;
+
+    Ok(())
+}
+
+pub(crate) async fn ensure_controller_node(
+    pool: &SqlitePool,
+    region_id: &RegionId,
+    panda_container: &PandaContainer,
+) -> Result<(), String> {
+    let region = RegionsReadRepo::init()
+        .find(pool, &region_id.to_hex())
+        .await
+        .map_err(|_| "Failed to read region".to_string())?;
+    if region.is_none() {
+        return Err("Region not found".to_string());
+    }
+
+    let my_node_id_string = panda_container
+        .get_public_key()
+        .await
+        .map_err(|_| "Failed to get my node ID".to_string())?
+        .to_hex();
+
+    if region.unwrap().creator_node_id != Some(my_node_id_string) {
+        return Err("Only the controller node can perform this action".to_string());
+    }
+
+    Ok(())
+}
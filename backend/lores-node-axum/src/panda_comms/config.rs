@@ -1,6 +1,5 @@
 use tracing::info;
 use crate::{config::config_state::LoresNodeConfigState, panda_comms::build_public_key_from_hex};
-use hex;
 use p2panda_core::{identity::SIGNING_KEY_LEN, SigningKey, VerifyingKey};
 
 pub struct ThisP2PandaNodeRepo {}
@@ -42,8 +41,7 @@ impl ThisP2PandaNodeRepo {
         config
             .private_key_hex
             .clone()
-            .map(|hex| Self::build_private_key_from_hex(hex))
-            .flatten()
+            .and_then(Self::build_private_key_from_hex)
     }
 
     async fn create_private_key(
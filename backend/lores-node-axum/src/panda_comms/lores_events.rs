@@ -1,7 +1,7 @@
 use p2panda_core::hash::Hash;
 use serde::{Deserialize, Serialize};
 
-use crate::data::entities::LatLng;
+use crate::data::entities::{LatLng, NodeAppUrl};
 
 use super::RegionId;
 
@@ -54,6 +54,9 @@ pub struct NodeStatusPostedDataV1 {
 pub struct AppRegisteredDataV1 {
     pub name: String,
     pub version: String,
+    /// Added after V1 shipped; events from older nodes have no URL.
+    #[serde(default)]
+    pub url: Option<NodeAppUrl>,
 }
 
 #[derive(Deserialize, Serialize, Clone, PartialEq, Debug)]
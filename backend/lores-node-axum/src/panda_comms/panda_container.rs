@@ -131,13 +131,13 @@ impl PandaContainer {
         &self,
         private_key: SigningKey,
         network_name: String,
-        boostrap_node_ids: &Vec<VerifyingKey>,
+        boostrap_node_ids: &[VerifyingKey],
         operations_database_url: &str,
     ) -> Result<(), PandaNodeError> {
         let required_params = RequiredNodeParams {
             private_key,
             network_id: Hash::digest(network_name.as_bytes()),
-            bootstrap_node_ids: boostrap_node_ids.clone(),
+            bootstrap_node_ids: boostrap_node_ids.to_vec(),
             relay_url: None,
         };
 
@@ -173,6 +173,17 @@ impl PandaContainer {
     }
 
     pub async fn replay_all_regions(&self) -> Result<usize, PandaSubscriptionError> {
+        self.replay_all_regions_into(self.lores_events_tx.clone())
+            .await
+    }
+
+    /// Like [`Self::replay_all_regions`], but sends the replayed events to
+    /// `events_tx` rather than the node's event handler. The channel closes
+    /// once every region has been replayed.
+    pub async fn replay_all_regions_into(
+        &self,
+        events_tx: mpsc::Sender<LoResEvent>,
+    ) -> Result<usize, PandaSubscriptionError> {
         let node_lock = self.node.lock().await;
         let node = match node_lock.as_ref() {
             Some(node) => node.clone(),
@@ -187,7 +198,7 @@ impl PandaContainer {
             let (incoming_tx, mut incoming_rx) = mpsc::channel::<IncomingOperation>(32);
             node.replay_topic(topic_id, incoming_tx).await?;
 
-            let events_tx = self.lores_events_tx.clone();
+            let events_tx = events_tx.clone();
             tokio::spawn(async move {
                 while let Some(incoming) = incoming_rx.recv().await {
                     match Self::decode_incoming_to_lores_event(incoming) {
@@ -220,6 +231,23 @@ impl PandaContainer {
         Ok(admin_topic.p2panda_topic())
     }
 
+    /// Leaves the region's admin topic and stops reporting the region. Returns
+    /// `false` if the node isn't running or wasn't subscribed to the region.
+    pub async fn leave_region(&self, region_id: RegionId) -> bool {
+        let node_lock = self.node.lock().await;
+        let node = match node_lock.as_ref() {
+            Some(node) => node.clone(),
+            None => return false,
+        };
+        drop(node_lock);
+
+        let admin_topic = RegionAdminTopic::new(region_id.clone());
+        let left = node.unsubscribe_from_region_topic(&admin_topic).await;
+        node.unregister_region(&region_id).await;
+
+        left
+    }
+
     pub async fn subscribe<T: RegionTopic>(
         &self,
         region_topic: &T,
@@ -14,7 +14,7 @@ lazy_static! {
 }
 
 pub async fn frontend_handler(uri: Uri) -> Result<Response<Body>, (StatusCode, String)> {
-    let api_prefixes = vec![
+    let api_prefixes = [
         "/public_api",
         "/auth_api",
         "/admin_api",
@@ -44,12 +44,12 @@ pub async fn frontend_handler(uri: Uri) -> Result<Response<Body>, (StatusCode, S
                 uri.clone(),
                 spa_file_path_string
             );
-            return serve_file(uri.clone(), spa_file_path_string).await;
+            serve_file(uri.clone(), spa_file_path_string).await
         }
         other => {
             // If the status is not OK or NOT_FOUND, return the response as is
             info!("Got other status: {}", other);
-            return Ok(res);
+            Ok(res)
         }
     }
 }
@@ -64,10 +64,10 @@ async fn serve_file(
         Ok(res) => Ok(res.map(Body::new)),
         Err(err) => {
             info!("Error serving static file: {}", err);
-            return Err((
+            Err((
                 StatusCode::INTERNAL_SERVER_ERROR,
                 format!("Something went wrong: {}", err),
-            ));
+            ))
         }
     }
 }
@@ -80,10 +80,10 @@ async fn serve_dir(uri: Uri, static_dir: String) -> Result<Response<Body>, (Stat
         Ok(res) => Ok(res.map(Body::new)),
         Err(err) => {
             info!("Error serving static file: {}", err);
-            return Err((
+            Err((
                 StatusCode::INTERNAL_SERVER_ERROR,
                 format!("Something went wrong: {}", err),
-            ));
+            ))
         }
     }
 }
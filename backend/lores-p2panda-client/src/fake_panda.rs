@@ -0,0 +1,124 @@
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+use tokio::net::TcpListener;
+use tokio_stream::wrappers::TcpListenerStream;
+use tonic::{Code, Request, Response, Status};
+
+use crate::PandaClient;
+use crate::proto::panda_server::{Panda, PandaServer};
+use crate::proto::*;
+
+/// A server for tests. Answers each publish with the next queued result, or
+/// success once they run out, and records what was published. Each subscribe
+/// is answered with the next queued list of events, after which the stream
+/// ends; once they run out, subscribes are refused with `UNAVAILABLE`.
+#[derive(Default)]
+pub(crate) struct FakePanda {
+    pub results: Mutex<VecDeque<Result<(), Code>>>,
+    pub published: Mutex<Vec<PublishRequest>>,
+    pub subscriptions: Mutex<VecDeque<Vec<OperationEvent>>>,
+    pub subscribe_requests: Mutex<Vec<SubscribeRequest>>,
+}
+
+impl FakePanda {
+    /// Starts serving on a local port and returns a client connected to it.
+    pub async fn serve(self: &Arc<Self>) -> PandaClient {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = tonic::transport::Server::builder()
+            .add_service(PandaServer::new(self.clone()))
+            .serve_with_incoming(TcpListenerStream::new(listener));
+        tokio::spawn(server);
+
+        PandaClient::connect(format!("http://{addr}"))
+            .await
+            .unwrap()
+    }
+}
+
+#[tonic::async_trait]
+impl Panda for Arc<FakePanda> {
+    async fn publish(
+        &self,
+        request: Request<PublishRequest>,
+    ) -> Result<Response<PublishResponse>, Status> {
+        if let Some(Err(code)) = self.results.lock().unwrap().pop_front() {
+            return Err(Status::new(code, "refused"));
+        }
+        self.published.lock().unwrap().push(request.into_inner());
+        Ok(Response::new(PublishResponse::default()))
+    }
+
+    async fn publish_batch(
+        &self,
+        _: Request<PublishBatchRequest>,
+    ) -> Result<Response<PublishBatchResponse>, Status> {
+        Err(Status::unimplemented(""))
+    }
+
+    type SubscribeStream = tokio_stream::Iter<std::vec::IntoIter<Result<OperationEvent, Status>>>;
+
+    async fn subscribe(
+        &self,
+        request: Request<SubscribeRequest>,
+    ) -> Result<Response<Self::SubscribeStream>, Status> {
+        self.subscribe_requests
+            .lock()
+            .unwrap()
+            .push(request.into_inner());
+        let events = self
+            .subscriptions
+            .lock()
+            .unwrap()
+            .pop_front()
+            .ok_or_else(|| Status::unavailable("no more subscriptions"))?;
+        Ok(Response::new(tokio_stream::iter(
+            events.into_iter().map(Ok).collect::<Vec<_>>(),
+        )))
+    }
+
+    async fn list_operations(
+        &self,
+        _: Request<ListOperationsRequest>,
+    ) -> Result<Response<ListOperationsResponse>, Status> {
+        Err(Status::unimplemented(""))
+    }
+
+    async fn get_operation(
+        &self,
+        _: Request<GetOperationRequest>,
+    ) -> Result<Response<GetOperationResponse>, Status> {
+        Err(Status::unimplemented(""))
+    }
+
+    async fn broadcast(
+        &self,
+        _: Request<BroadcastRequest>,
+    ) -> Result<Response<BroadcastResponse>, Status> {
+        Err(Status::unimplemented(""))
+    }
+
+    type SubscribeEphemeralStream = tokio_stream::Empty<Result<EphemeralEvent, Status>>;
+
+    async fn subscribe_ephemeral(
+        &self,
+        _: Request<SubscribeEphemeralRequest>,
+    ) -> Result<Response<Self::SubscribeEphemeralStream>, Status> {
+        Err(Status::unimplemented(""))
+    }
+
+    async fn list_peers(
+        &self,
+        _: Request<ListPeersRequest>,
+    ) -> Result<Response<ListPeersResponse>, Status> {
+        Err(Status::unimplemented(""))
+    }
+
+    async fn register_schema(
+        &self,
+        _: Request<RegisterSchemaRequest>,
+    ) -> Result<Response<RegisterSchemaResponse>, Status> {
+        Err(Status::unimplemented(""))
+    }
+}
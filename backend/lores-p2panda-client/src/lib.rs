@@ -1,11 +1,36 @@
+use tonic::metadata::{Ascii, MetadataValue};
+use tonic::service::{Interceptor, interceptor::InterceptedService};
 use tonic::transport::Channel;
 
 pub mod proto {
     tonic::include_proto!("lores.panda.v2");
 }
 
+#[cfg(test)]
+mod fake_panda;
+
+mod outbox;
+pub use outbox::{Outbox, OutboxError};
+
+mod reconnecting;
+pub use reconnecting::{
+    ConnectionState, ReconnectPolicy, ReconnectingSubscription, SubscriptionEvent,
+};
+
+mod typed;
+pub use typed::{OperationMeta, TypedError, TypedEvent, TypedPandaClient, TypedSubscription};
+
+#[cfg(unix)]
+mod unix_socket;
+
+pub use proto::subscribe_request::StartFrom;
 use proto::{
-    OperationEvent, PublishRequest, PublishResponse, SubscribeRequest,
+    BroadcastRequest, BroadcastResponse, DeleteRequest, EphemeralEvent, Frontier,
+    GetOperationRequest, GetRequest, KeyValueEntry, KeyValueWriteResponse, ListOperationsRequest,
+    ListOperationsResponse, ListPeersRequest, ListPeersResponse, OperationEvent, PublishBatchItem,
+    PublishBatchRequest, PublishBatchResponse, PublishRequest, PublishResponse, PutRequest,
+    RegisterSchemaRequest, RegisterSchemaResponse, SubscribeEphemeralRequest, SubscribeRequest,
+    WatchPrefixRequest, key_value_client::KeyValueClient as TonicKeyValueClient,
     panda_client::PandaClient as TonicPandaClient,
 };
 use tonic::{Code, Response, Status, Streaming};
@@ -17,6 +42,9 @@ pub enum PandaError {
     /// Use your lores-node installation to bind the app to a region.
     /// The inner string is the human-readable message from the server.
     RegionNotBound(String),
+    /// The operation a subscription was asked to resume after isn't stored
+    /// on the server. The inner string is the message from the server.
+    OperationNotFound(String),
     /// Any other gRPC-level error.
     Rpc(Status),
 }
@@ -25,6 +53,7 @@ impl std::fmt::Display for PandaError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             PandaError::RegionNotBound(msg) => write!(f, "{msg}"),
+            PandaError::OperationNotFound(msg) => write!(f, "{msg}"),
             PandaError::Rpc(s) => write!(f, "RPC error: {s}"),
         }
     }
@@ -34,17 +63,99 @@ impl std::error::Error for PandaError {}
 
 impl From<Status> for PandaError {
     fn from(s: Status) -> Self {
-        if s.code() == Code::NotFound {
-            PandaError::RegionNotBound(s.message().to_string())
-        } else {
-            PandaError::Rpc(s)
+        if s.code() != Code::NotFound {
+            return PandaError::Rpc(s);
+        }
+        // Servers that don't say what wasn't found only used NOT_FOUND for
+        // unbound apps.
+        match s
+            .metadata()
+            .get("lores-not-found")
+            .map(|what| what.to_str())
+        {
+            Some(Ok("operation")) => PandaError::OperationNotFound(s.message().to_string()),
+            Some(Ok("region")) | None => PandaError::RegionNotBound(s.message().to_string()),
+            Some(_) => PandaError::Rpc(s),
         }
     }
 }
 
+/// Errors returned when creating an authenticated [`PandaClient`].
+#[derive(Debug)]
+pub enum ConnectError {
+    /// The token contains characters that can't be sent as gRPC metadata.
+    InvalidToken,
+    /// The endpoint was invalid or could not be reached.
+    Transport(tonic::transport::Error),
+}
+
+impl std::fmt::Display for ConnectError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConnectError::InvalidToken => write!(f, "app token is not valid gRPC metadata"),
+            ConnectError::Transport(e) => write!(f, "transport error: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for ConnectError {}
+
+impl From<tonic::transport::Error> for ConnectError {
+    fn from(e: tonic::transport::Error) -> Self {
+        ConnectError::Transport(e)
+    }
+}
+
+/// Adds the app's `authorization: Bearer <token>` header, if it has one, to
+/// every call.
+#[derive(Debug, Clone, Default)]
+pub struct AppTokenInterceptor {
+    authorization: Option<MetadataValue<Ascii>>,
+}
+
+impl AppTokenInterceptor {
+    fn with_token(token: &str) -> Result<Self, ConnectError> {
+        let authorization = format!("Bearer {token}")
+            .parse()
+            .map_err(|_| ConnectError::InvalidToken)?;
+        Ok(Self {
+            authorization: Some(authorization),
+        })
+    }
+}
+
+impl Interceptor for AppTokenInterceptor {
+    fn call(&mut self, mut request: tonic::Request<()>) -> Result<tonic::Request<()>, Status> {
+        if let Some(authorization) = &self.authorization {
+            request
+                .metadata_mut()
+                .insert("authorization", authorization.clone());
+        }
+        Ok(request)
+    }
+}
+
+/// Filters and paging for [`PandaClient::list_operations`]. The default lists
+/// the newest page of operations by any author.
+#[derive(Debug, Clone, Default)]
+pub struct ListOperationsOptions {
+    /// Only operations by this 32-byte author public key.
+    pub author: Option<Vec<u8>>,
+    /// Only operations with a timestamp at or after this value.
+    pub since_timestamp: Option<u64>,
+    /// Only operations with a timestamp before this value.
+    pub until_timestamp: Option<u64>,
+    /// Page size; the server defaults to 100 and caps it at 1000.
+    pub limit: Option<u32>,
+    /// `next_page_token` from the previous page.
+    pub page_token: Option<Vec<u8>>,
+}
+
 /// Client for the lores-p2panda-server gRPC API.
+#[derive(Clone)]
 pub struct PandaClient {
-    inner: TonicPandaClient<Channel>,
+    inner: TonicPandaClient<InterceptedService<Channel, AppTokenInterceptor>>,
+    key_value: TonicKeyValueClient<InterceptedService<Channel, AppTokenInterceptor>>,
 }
 
 impl PandaClient {
@@ -52,17 +163,30 @@ impl PandaClient {
     ///
     /// # Example
     /// ```no_run
-    /// # tokio_test::block_on(async {
+    /// # #[tokio::main]
+    /// # async fn main() {
     /// let client = lores_p2panda_client::PandaClient::connect("http://[::1]:50051").await.unwrap();
-    /// # });
+    /// # }
     /// ```
     pub async fn connect<D>(dst: D) -> Result<Self, tonic::transport::Error>
     where
         D: TryInto<tonic::transport::Endpoint>,
         D::Error: Into<Box<dyn std::error::Error + Send + Sync>>,
     {
-        let inner = TonicPandaClient::connect(dst).await?;
-        Ok(Self { inner })
+        let channel = tonic::transport::Endpoint::new(dst)?.connect().await?;
+        Ok(Self::with_channel(channel, AppTokenInterceptor::default()))
+    }
+
+    /// Connect with the token issued to this app instance when a steward bound
+    /// it to a region. The server rejects calls without a valid token.
+    pub async fn connect_with_token<D>(dst: D, token: &str) -> Result<Self, ConnectError>
+    where
+        D: TryInto<tonic::transport::Endpoint>,
+        D::Error: Into<Box<dyn std::error::Error + Send + Sync>>,
+    {
+        let interceptor = AppTokenInterceptor::with_token(token)?;
+        let channel = tonic::transport::Endpoint::new(dst)?.connect().await?;
+        Ok(Self::with_channel(channel, interceptor))
     }
 
     /// Create a client with a lazy channel — no connection is made until the
@@ -74,8 +198,76 @@ impl PandaClient {
         D::Error: Into<Box<dyn std::error::Error + Send + Sync>>,
     {
         let endpoint = tonic::transport::Endpoint::new(dst)?;
-        let inner = TonicPandaClient::new(endpoint.connect_lazy());
-        Ok(Self { inner })
+        Ok(Self::with_channel(
+            endpoint.connect_lazy(),
+            AppTokenInterceptor::default(),
+        ))
+    }
+
+    /// Lazy-connecting version of [`Self::connect_with_token`].
+    pub fn connect_lazy_with_token<D>(dst: D, token: &str) -> Result<Self, ConnectError>
+    where
+        D: TryInto<tonic::transport::Endpoint>,
+        D::Error: Into<Box<dyn std::error::Error + Send + Sync>>,
+    {
+        let interceptor = AppTokenInterceptor::with_token(token)?;
+        let endpoint = tonic::transport::Endpoint::new(dst)?;
+        Ok(Self::with_channel(endpoint.connect_lazy(), interceptor))
+    }
+
+    /// Connect to a server given as a URI string, which may be a
+    /// `unix:///path/to.sock` URI for a server listening on a Unix socket on
+    /// this host, or an `http://` or `https://` URI. Pass `token` as for
+    /// [`Self::connect_with_token`].
+    ///
+    /// # Example
+    /// ```no_run
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// let client = lores_p2panda_client::PandaClient::connect_uri(
+    ///     "unix:///run/lores/grpc.sock",
+    ///     Some("app-token"),
+    /// )
+    /// .await
+    /// .unwrap();
+    /// # }
+    /// ```
+    pub async fn connect_uri(uri: &str, token: Option<&str>) -> Result<Self, ConnectError> {
+        let interceptor = match token {
+            Some(token) => AppTokenInterceptor::with_token(token)?,
+            None => AppTokenInterceptor::default(),
+        };
+        #[cfg(unix)]
+        if let Some(path) = unix_socket::socket_path(uri) {
+            let channel = unix_socket::connect(path).await?;
+            return Ok(Self::with_channel(channel, interceptor));
+        }
+        let channel = tonic::transport::Endpoint::new(uri.to_string())?
+            .connect()
+            .await?;
+        Ok(Self::with_channel(channel, interceptor))
+    }
+
+    /// Lazy-connecting version of [`Self::connect_uri`].
+    pub fn connect_lazy_uri(uri: &str, token: Option<&str>) -> Result<Self, ConnectError> {
+        let interceptor = match token {
+            Some(token) => AppTokenInterceptor::with_token(token)?,
+            None => AppTokenInterceptor::default(),
+        };
+        #[cfg(unix)]
+        if let Some(path) = unix_socket::socket_path(uri) {
+            let channel = unix_socket::connect_lazy(path);
+            return Ok(Self::with_channel(channel, interceptor));
+        }
+        let endpoint = tonic::transport::Endpoint::new(uri.to_string())?;
+        Ok(Self::with_channel(endpoint.connect_lazy(), interceptor))
+    }
+
+    fn with_channel(channel: Channel, interceptor: AppTokenInterceptor) -> Self {
+        Self {
+            inner: TonicPandaClient::with_interceptor(channel.clone(), interceptor.clone()),
+            key_value: TonicKeyValueClient::with_interceptor(channel, interceptor),
+        }
     }
 
     /// Publish an operation to a region+namespace topic.
@@ -83,9 +275,13 @@ impl PandaClient {
     /// Returns only after the operation has been persisted by the remote
     /// p2panda node, guaranteeing eventual propagation to peers.
     ///
+    /// The response carries the new operation's id, which matches
+    /// [`OperationEvent::operation_id`] when the operation is delivered to
+    /// subscribers.
+    ///
     /// If `idempotency_key` is `Some`, the server will deduplicate within its
-    /// retention window: retrying with the same key returns success without
-    /// re-inserting the operation.
+    /// retention window: retrying with the same key returns the original
+    /// operation, marked as a duplicate, without re-inserting it.
     pub async fn publish(
         &mut self,
         app_id: impl Into<String>,
@@ -102,22 +298,329 @@ impl PandaClient {
         self.inner.publish(request).await.map_err(PandaError::from)
     }
 
+    /// Publish several `(payload, idempotency_key)` items in one call. Results
+    /// come back in the same order as `items`.
+    ///
+    /// Every item is checked before any is published, so if one is rejected
+    /// the call fails with nothing published. If publishing then fails
+    /// partway, the items before the failure stay published and the server
+    /// remembers their idempotency keys, so retrying the same batch publishes
+    /// only the rest. Items without a key would be published again.
+    pub async fn publish_batch(
+        &mut self,
+        app_id: impl Into<String>,
+        instance_id: impl Into<String>,
+        items: impl IntoIterator<Item = (Vec<u8>, Option<Vec<u8>>)>,
+    ) -> Result<Response<PublishBatchResponse>, PandaError> {
+        let request = PublishBatchRequest {
+            app_id: app_id.into(),
+            instance_id: instance_id.into(),
+            items: items
+                .into_iter()
+                .map(|(payload, idempotency_key)| PublishBatchItem {
+                    payload,
+                    idempotency_key: idempotency_key.unwrap_or_default(),
+                })
+                .collect(),
+        };
+        self.inner
+            .publish_batch(request)
+            .await
+            .map_err(PandaError::from)
+    }
+
     /// Subscribe to a region+namespace topic and receive a stream of
-    /// [`OperationEvent`]s.
+    /// [`OperationEvent`]s that arrive after subscribing.
     ///
     /// HTTP/2 flow control provides natural backpressure.
     pub async fn subscribe(
         &mut self,
         app_id: impl Into<String>,
         instance_id: impl Into<String>,
+    ) -> Result<Response<Streaming<OperationEvent>>, PandaError> {
+        self.subscribe_from(app_id, instance_id, StartFrom::Frontier(Frontier {}))
+            .await
+    }
+
+    /// Like [`Self::subscribe`], but first replays stored operations from
+    /// `start_from`, e.g. `StartFrom::Beginning` to rebuild state from an empty
+    /// database or `StartFrom::AfterOperationId` to resume after the last
+    /// operation the app processed.
+    ///
+    /// If the app reads too slowly the server drops operations and ends the
+    /// stream with a [`Code::DataLoss`] status; resubscribe from the last
+    /// processed operation id to continue without a gap.
+    pub async fn subscribe_from(
+        &mut self,
+        app_id: impl Into<String>,
+        instance_id: impl Into<String>,
+        start_from: StartFrom,
     ) -> Result<Response<Streaming<OperationEvent>>, PandaError> {
         let request = SubscribeRequest {
             app_id: app_id.into(),
             instance_id: instance_id.into(),
+            start_from: Some(start_from),
         };
         self.inner
             .subscribe(request)
             .await
             .map_err(PandaError::from)
     }
+
+    /// Like [`Self::subscribe_from`], but the subscription reconnects with
+    /// backoff whenever the stream from the server ends, resuming after the
+    /// last operation it delivered. Connection changes are reported in the
+    /// stream alongside operations.
+    ///
+    /// The subscription runs on its own clone of this client, so it must be
+    /// called from within a Tokio runtime.
+    pub fn subscribe_reconnecting(
+        &self,
+        app_id: impl Into<String>,
+        instance_id: impl Into<String>,
+        start_from: StartFrom,
+        policy: ReconnectPolicy,
+    ) -> ReconnectingSubscription {
+        ReconnectingSubscription::spawn(
+            self.clone(),
+            app_id.into(),
+            instance_id.into(),
+            start_from,
+            policy,
+        )
+    }
+
+    /// Read stored operations for this app's topic, newest first, without
+    /// subscribing. Keep passing the response's `next_page_token` as
+    /// [`ListOperationsOptions::page_token`] until it comes back empty.
+    pub async fn list_operations(
+        &mut self,
+        app_id: impl Into<String>,
+        instance_id: impl Into<String>,
+        options: ListOperationsOptions,
+    ) -> Result<Response<ListOperationsResponse>, PandaError> {
+        let request = ListOperationsRequest {
+            app_id: app_id.into(),
+            instance_id: instance_id.into(),
+            author: options.author.unwrap_or_default(),
+            since_timestamp: options.since_timestamp,
+            until_timestamp: options.until_timestamp,
+            limit: options.limit.unwrap_or_default(),
+            page_token: options.page_token.unwrap_or_default(),
+        };
+        self.inner
+            .list_operations(request)
+            .await
+            .map_err(PandaError::from)
+    }
+
+    /// Read a single operation from this app's topic by id. Returns `None` if
+    /// the topic has no operation with that id.
+    pub async fn get_operation(
+        &mut self,
+        app_id: impl Into<String>,
+        instance_id: impl Into<String>,
+        operation_id: impl Into<Vec<u8>>,
+    ) -> Result<Option<OperationEvent>, PandaError> {
+        let request = GetOperationRequest {
+            app_id: app_id.into(),
+            instance_id: instance_id.into(),
+            operation_id: operation_id.into(),
+        };
+        let response = self
+            .inner
+            .get_operation(request)
+            .await
+            .map_err(PandaError::from)?;
+        Ok(response.into_inner().operation)
+    }
+
+    /// Send a message to this app's instances on nodes that are currently
+    /// online, without persisting it. Fails with [`Code::ResourceExhausted`]
+    /// when sending faster than the server allows.
+    pub async fn broadcast(
+        &mut self,
+        app_id: impl Into<String>,
+        instance_id: impl Into<String>,
+        payload: impl Into<Vec<u8>>,
+    ) -> Result<Response<BroadcastResponse>, PandaError> {
+        let request = BroadcastRequest {
+            app_id: app_id.into(),
+            instance_id: instance_id.into(),
+            payload: payload.into(),
+        };
+        self.inner
+            .broadcast(request)
+            .await
+            .map_err(PandaError::from)
+    }
+
+    /// Receive messages sent with [`Self::broadcast`] from now on. A reader
+    /// that falls behind misses messages instead of the stream ending.
+    pub async fn subscribe_ephemeral(
+        &mut self,
+        app_id: impl Into<String>,
+        instance_id: impl Into<String>,
+    ) -> Result<Response<Streaming<EphemeralEvent>>, PandaError> {
+        let request = SubscribeEphemeralRequest {
+            app_id: app_id.into(),
+            instance_id: instance_id.into(),
+        };
+        self.inner
+            .subscribe_ephemeral(request)
+            .await
+            .map_err(PandaError::from)
+    }
+
+    /// List the nodes in this app's region that have the same app installed,
+    /// along with the URLs they registered for it.
+    pub async fn list_peers(
+        &mut self,
+        app_id: impl Into<String>,
+        instance_id: impl Into<String>,
+    ) -> Result<Response<ListPeersResponse>, PandaError> {
+        let request = ListPeersRequest {
+            app_id: app_id.into(),
+            instance_id: instance_id.into(),
+        };
+        self.inner
+            .list_peers(request)
+            .await
+            .map_err(PandaError::from)
+    }
+
+    /// Register a JSON Schema that this app's CBOR payloads must match, or
+    /// remove the app's schema by passing an empty string.
+    pub async fn register_schema(
+        &mut self,
+        app_id: impl Into<String>,
+        instance_id: impl Into<String>,
+        schema: impl Into<String>,
+    ) -> Result<Response<RegisterSchemaResponse>, PandaError> {
+        let request = RegisterSchemaRequest {
+            app_id: app_id.into(),
+            instance_id: instance_id.into(),
+            schema: schema.into(),
+        };
+        self.inner
+            .register_schema(request)
+            .await
+            .map_err(PandaError::from)
+    }
+
+    /// Set `key` in this app's region-wide key-value store. Concurrent writes
+    /// from other nodes are resolved by timestamp, so the latest write wins
+    /// everywhere.
+    pub async fn put(
+        &mut self,
+        app_id: impl Into<String>,
+        instance_id: impl Into<String>,
+        key: impl Into<String>,
+        value: impl Into<Vec<u8>>,
+    ) -> Result<Response<KeyValueWriteResponse>, PandaError> {
+        let request = PutRequest {
+            app_id: app_id.into(),
+            instance_id: instance_id.into(),
+            key: key.into(),
+            value: value.into(),
+        };
+        self.key_value.put(request).await.map_err(PandaError::from)
+    }
+
+    /// Remove `key` from this app's key-value store.
+    pub async fn delete(
+        &mut self,
+        app_id: impl Into<String>,
+        instance_id: impl Into<String>,
+        key: impl Into<String>,
+    ) -> Result<Response<KeyValueWriteResponse>, PandaError> {
+        let request = DeleteRequest {
+            app_id: app_id.into(),
+            instance_id: instance_id.into(),
+            key: key.into(),
+        };
+        self.key_value
+            .delete(request)
+            .await
+            .map_err(PandaError::from)
+    }
+
+    /// Read `key` from this node's copy of the app's key-value store. Returns
+    /// `None` if the key isn't set.
+    pub async fn get(
+        &mut self,
+        app_id: impl Into<String>,
+        instance_id: impl Into<String>,
+        key: impl Into<String>,
+    ) -> Result<Option<KeyValueEntry>, PandaError> {
+        let request = GetRequest {
+            app_id: app_id.into(),
+            instance_id: instance_id.into(),
+            key: key.into(),
+        };
+        let response = self
+            .key_value
+            .get(request)
+            .await
+            .map_err(PandaError::from)?;
+        Ok(response.into_inner().entry)
+    }
+
+    /// Stream the entries whose key starts with `prefix`, then every change to
+    /// them. Deleted keys arrive with no value.
+    pub async fn watch_prefix(
+        &mut self,
+        app_id: impl Into<String>,
+        instance_id: impl Into<String>,
+        prefix: impl Into<String>,
+    ) -> Result<Response<Streaming<KeyValueEntry>>, PandaError> {
+        let request = WatchPrefixRequest {
+            app_id: app_id.into(),
+            instance_id: instance_id.into(),
+            prefix: prefix.into(),
+        };
+        self.key_value
+            .watch_prefix(request)
+            .await
+            .map_err(PandaError::from)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn not_found(what: Option<&'static str>) -> Status {
+        let mut status = Status::not_found("not found");
+        if let Some(what) = what {
+            status
+                .metadata_mut()
+                .insert("lores-not-found", MetadataValue::from_static(what));
+        }
+        status
+    }
+
+    #[test]
+    fn test_not_found_statuses_say_what_was_not_found() {
+        assert!(matches!(
+            PandaError::from(not_found(Some("operation"))),
+            PandaError::OperationNotFound(_)
+        ));
+        assert!(matches!(
+            PandaError::from(not_found(Some("region"))),
+            PandaError::RegionNotBound(_)
+        ));
+        assert!(matches!(
+            PandaError::from(not_found(None)),
+            PandaError::RegionNotBound(_)
+        ));
+        assert!(matches!(
+            PandaError::from(not_found(Some("schema"))),
+            PandaError::Rpc(_)
+        ));
+        assert!(matches!(
+            PandaError::from(Status::unavailable("")),
+            PandaError::Rpc(_)
+        ));
+    }
 }
@@ -0,0 +1,306 @@
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use tokio::fs;
+use tokio::io::AsyncWriteExt;
+use tokio::sync::{Mutex, Notify};
+use tokio::task::JoinHandle;
+use tonic::Code;
+
+use crate::reconnecting::backoff;
+use crate::{PandaClient, PandaError, ReconnectPolicy};
+
+const QUEUED_EXTENSION: &str = "msg";
+const PARTIAL_EXTENSION: &str = "tmp";
+/// Subdirectory for publishes the server refused outright.
+const REJECTED_DIR: &str = "rejected";
+
+/// Errors returned by [`Outbox`] methods.
+#[derive(Debug)]
+pub enum OutboxError {
+    /// Reading or writing the outbox directory failed.
+    Io(io::Error),
+    /// The server could not be reached, or failed the publish.
+    Panda(PandaError),
+}
+
+impl std::fmt::Display for OutboxError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OutboxError::Io(e) => write!(f, "outbox storage error: {e}"),
+            OutboxError::Panda(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl std::error::Error for OutboxError {}
+
+impl From<io::Error> for OutboxError {
+    fn from(e: io::Error) -> Self {
+        OutboxError::Io(e)
+    }
+}
+
+impl From<PandaError> for OutboxError {
+    fn from(e: PandaError) -> Self {
+        OutboxError::Panda(e)
+    }
+}
+
+/// A durable queue of publishes for apps that need to keep working while
+/// lores-node is down.
+///
+/// Each publish is written to its own file in the outbox directory before
+/// [`Self::enqueue`] returns, then published in order once the server can be
+/// reached. Every queued publish gets an idempotency key, so one that reached
+/// the server before the app crashed or lost its connection isn't published
+/// twice when it's sent again.
+///
+/// Publishes the server rejects as invalid are moved to a `rejected`
+/// subdirectory instead of blocking the queue.
+pub struct Outbox {
+    client: Mutex<PandaClient>,
+    dir: PathBuf,
+    app_id: String,
+    instance_id: String,
+    queued: Notify,
+    next_id: AtomicU64,
+}
+
+impl Outbox {
+    /// Opens the outbox stored in `dir`, creating it if needed. Publishes
+    /// queued by an earlier run are kept.
+    pub async fn open(
+        client: PandaClient,
+        dir: impl AsRef<Path>,
+        app_id: impl Into<String>,
+        instance_id: impl Into<String>,
+    ) -> Result<Self, OutboxError> {
+        let dir = dir.as_ref().to_path_buf();
+        fs::create_dir_all(dir.join(REJECTED_DIR)).await?;
+
+        // A partial file is a publish that was never acknowledged to the app,
+        // so it can be dropped.
+        for path in list_files(&dir, PARTIAL_EXTENSION).await? {
+            fs::remove_file(path).await?;
+        }
+
+        Ok(Self {
+            client: Mutex::new(client),
+            dir,
+            app_id: app_id.into(),
+            instance_id: instance_id.into(),
+            queued: Notify::new(),
+            next_id: AtomicU64::new(0),
+        })
+    }
+
+    /// Queues `payload` for publishing and returns the idempotency key it
+    /// will be published with. Once this returns the publish survives
+    /// restarts.
+    pub async fn enqueue(&self, payload: impl Into<Vec<u8>>) -> Result<Vec<u8>, OutboxError> {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_nanos();
+        let counter = self.next_id.fetch_add(1, Ordering::Relaxed);
+        // Zero-padded so the names sort in the order they were queued.
+        let name = format!("{nanos:024}-{counter:010}-{}", std::process::id());
+
+        let partial = self.dir.join(&name).with_extension(PARTIAL_EXTENSION);
+        let mut file = fs::File::create(&partial).await?;
+        file.write_all(&payload.into()).await?;
+        file.sync_all().await?;
+        drop(file);
+        fs::rename(
+            &partial,
+            self.dir.join(&name).with_extension(QUEUED_EXTENSION),
+        )
+        .await?;
+
+        self.queued.notify_one();
+        Ok(name.into_bytes())
+    }
+
+    /// Number of publishes waiting to be sent.
+    pub async fn len(&self) -> Result<usize, OutboxError> {
+        Ok(list_files(&self.dir, QUEUED_EXTENSION).await?.len())
+    }
+
+    pub async fn is_empty(&self) -> Result<bool, OutboxError> {
+        Ok(self.len().await? == 0)
+    }
+
+    /// Publishes everything queued, oldest first, and returns how many were
+    /// sent. Stops at the first publish that fails for a reason other than
+    /// being invalid; it and those after it stay queued.
+    pub async fn flush(&self) -> Result<usize, OutboxError> {
+        // Holding the client lock keeps concurrent flushes from sending the
+        // same file twice.
+        let mut client = self.client.lock().await;
+        let mut sent = 0;
+
+        for path in list_files(&self.dir, QUEUED_EXTENSION).await? {
+            let Some(key) = path.file_stem().and_then(|stem| stem.to_str()) else {
+                continue;
+            };
+            let payload = fs::read(&path).await?;
+
+            match client
+                .publish(
+                    &self.app_id,
+                    &self.instance_id,
+                    payload,
+                    Some(key.as_bytes().to_vec()),
+                )
+                .await
+            {
+                Ok(_) => {
+                    fs::remove_file(&path).await?;
+                    sent += 1;
+                }
+                Err(PandaError::Rpc(status)) if status.code() == Code::InvalidArgument => {
+                    let file_name = path.file_name().unwrap_or_default();
+                    fs::rename(&path, self.dir.join(REJECTED_DIR).join(file_name)).await?;
+                }
+                Err(e) => return Err(e.into()),
+            }
+        }
+
+        Ok(sent)
+    }
+
+    /// Keeps flushing in the background: whenever something is queued, and
+    /// with backoff while the server can't be reached. Stops when the returned
+    /// handle is aborted.
+    pub fn spawn_drain(self: Arc<Self>, policy: ReconnectPolicy) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut attempt = 0u32;
+            loop {
+                match self.flush().await {
+                    Ok(_) => {
+                        attempt = 0;
+                        self.queued.notified().await;
+                    }
+                    Err(_) => {
+                        attempt += 1;
+                        tokio::time::sleep(backoff(&policy, attempt)).await;
+                    }
+                }
+            }
+        })
+    }
+}
+
+/// Files in `dir` with the given extension, sorted by name.
+async fn list_files(dir: &Path, extension: &str) -> io::Result<Vec<PathBuf>> {
+    let mut paths = Vec::new();
+    let mut entries = fs::read_dir(dir).await?;
+    while let Some(entry) = entries.next_entry().await? {
+        let path = entry.path();
+        if path.extension().and_then(|ext| ext.to_str()) == Some(extension) {
+            paths.push(path);
+        }
+    }
+    paths.sort();
+    Ok(paths)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fake_panda::FakePanda;
+
+    async fn fake_client(results: Vec<Result<(), Code>>) -> (PandaClient, Arc<FakePanda>) {
+        let fake = Arc::new(FakePanda {
+            results: std::sync::Mutex::new(results.into()),
+            ..Default::default()
+        });
+        (fake.serve().await, fake)
+    }
+
+    async fn open(client: PandaClient, dir: &tempfile::TempDir) -> Outbox {
+        Outbox::open(client, dir.path(), "app", "instance")
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_queued_publishes_survive_reopening() {
+        let dir = tempfile::tempdir().unwrap();
+        let (client, fake) = fake_client(vec![]).await;
+
+        let outbox = open(client.clone(), &dir).await;
+        let first_key = outbox.enqueue(b"one".to_vec()).await.unwrap();
+        outbox.enqueue(b"two".to_vec()).await.unwrap();
+        drop(outbox);
+
+        let outbox = open(client, &dir).await;
+        assert_eq!(outbox.len().await.unwrap(), 2);
+        assert_eq!(outbox.flush().await.unwrap(), 2);
+
+        let published = fake.published.lock().unwrap();
+        let payloads: Vec<&[u8]> = published.iter().map(|p| p.payload.as_slice()).collect();
+        assert_eq!(payloads, vec![b"one".as_slice(), b"two".as_slice()]);
+        assert_eq!(published[0].idempotency_key, first_key);
+    }
+
+    #[tokio::test]
+    async fn test_flush_removes_sent_publishes() {
+        let dir = tempfile::tempdir().unwrap();
+        let (client, _fake) = fake_client(vec![]).await;
+        let outbox = open(client, &dir).await;
+
+        outbox.enqueue(b"one".to_vec()).await.unwrap();
+        assert_eq!(outbox.flush().await.unwrap(), 1);
+
+        assert!(outbox.is_empty().await.unwrap());
+        assert!(
+            list_files(dir.path(), QUEUED_EXTENSION)
+                .await
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_invalid_publishes_are_moved_aside() {
+        let dir = tempfile::tempdir().unwrap();
+        let (client, fake) = fake_client(vec![Err(Code::InvalidArgument)]).await;
+        let outbox = open(client, &dir).await;
+
+        outbox.enqueue(b"bad".to_vec()).await.unwrap();
+        outbox.enqueue(b"good".to_vec()).await.unwrap();
+        assert_eq!(outbox.flush().await.unwrap(), 1);
+
+        assert!(outbox.is_empty().await.unwrap());
+        let rejected = list_files(&dir.path().join(REJECTED_DIR), QUEUED_EXTENSION)
+            .await
+            .unwrap();
+        assert_eq!(rejected.len(), 1);
+        assert_eq!(fs::read(&rejected[0]).await.unwrap(), b"bad");
+        assert_eq!(fake.published.lock().unwrap()[0].payload, b"good");
+    }
+
+    #[tokio::test]
+    async fn test_failed_flush_keeps_the_queue() {
+        let dir = tempfile::tempdir().unwrap();
+        let (client, fake) = fake_client(vec![Err(Code::Unavailable)]).await;
+        let outbox = open(client, &dir).await;
+
+        outbox.enqueue(b"one".to_vec()).await.unwrap();
+        outbox.enqueue(b"two".to_vec()).await.unwrap();
+        assert!(matches!(
+            outbox.flush().await,
+            Err(OutboxError::Panda(PandaError::Rpc(status))) if status.code() == Code::Unavailable
+        ));
+        assert_eq!(outbox.len().await.unwrap(), 2);
+
+        // Sent in order once the server is back.
+        assert_eq!(outbox.flush().await.unwrap(), 2);
+        assert_eq!(fake.published.lock().unwrap()[0].payload, b"one");
+    }
+}
@@ -0,0 +1,294 @@
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tokio_stream::Stream;
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::Code;
+
+use crate::proto::OperationEvent;
+use crate::{PandaClient, PandaError, StartFrom};
+
+/// How [`ReconnectingSubscription`] and [`crate::Outbox`] wait between
+/// attempts to reach the server. The delay starts at `initial_backoff` and doubles after each failed
+/// attempt, up to `max_backoff`.
+#[derive(Debug, Clone)]
+pub struct ReconnectPolicy {
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+}
+
+impl Default for ReconnectPolicy {
+    fn default() -> Self {
+        Self {
+            initial_backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(30),
+        }
+    }
+}
+
+/// The state of the connection behind a [`ReconnectingSubscription`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConnectionState {
+    /// The subscription is open and operations are being delivered.
+    Connected,
+    /// The subscription was lost and will be retried after `retry_in`.
+    Disconnected {
+        /// Why the subscription was lost.
+        reason: String,
+        /// Failed attempts since the subscription was last open.
+        attempt: u32,
+        retry_in: Duration,
+    },
+}
+
+/// An item from a [`ReconnectingSubscription`].
+#[derive(Debug, Clone)]
+pub enum SubscriptionEvent {
+    /// An operation on the app's topic.
+    Operation(OperationEvent),
+    /// The connection to the server was opened or lost.
+    ConnectionState(ConnectionState),
+}
+
+/// A subscription that survives server restarts and dropped connections.
+///
+/// Whenever the stream from the server ends, the subscription is opened
+/// again with backoff, resuming after the last operation delivered so none
+/// are missed or repeated. A subscription that started at the frontier and
+/// is lost before anything was delivered resumes from the time it was first
+/// opened. Errors that retrying can't fix, such as an invalid
+/// token or an app that isn't bound to a region, end the stream with `Err`.
+///
+/// Dropping the subscription stops it.
+pub struct ReconnectingSubscription {
+    events: ReceiverStream<Result<SubscriptionEvent, PandaError>>,
+    task: JoinHandle<()>,
+}
+
+impl ReconnectingSubscription {
+    pub(crate) fn spawn(
+        client: PandaClient,
+        app_id: String,
+        instance_id: String,
+        start_from: StartFrom,
+        policy: ReconnectPolicy,
+    ) -> Self {
+        let (events_tx, events_rx) = mpsc::channel(64);
+        let task = tokio::spawn(run(
+            client,
+            app_id,
+            instance_id,
+            start_from,
+            policy,
+            events_tx,
+        ));
+        Self {
+            events: ReceiverStream::new(events_rx),
+            task,
+        }
+    }
+}
+
+impl Stream for ReconnectingSubscription {
+    type Item = Result<SubscriptionEvent, PandaError>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        Pin::new(&mut self.events).poll_next(cx)
+    }
+}
+
+impl Drop for ReconnectingSubscription {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+async fn run(
+    mut client: PandaClient,
+    app_id: String,
+    instance_id: String,
+    mut start_from: StartFrom,
+    policy: ReconnectPolicy,
+    events_tx: mpsc::Sender<Result<SubscriptionEvent, PandaError>>,
+) {
+    let mut attempt = 0u32;
+    // Reopening at the frontier would miss whatever was published while
+    // disconnected, so until there is an operation to resume after, resume
+    // from when the subscription was first opened.
+    let opened_at = now_ms();
+
+    loop {
+        let reason = match client
+            .subscribe_from(&app_id, &instance_id, start_from.clone())
+            .await
+        {
+            Ok(response) => {
+                attempt = 0;
+                let connected = SubscriptionEvent::ConnectionState(ConnectionState::Connected);
+                if events_tx.send(Ok(connected)).await.is_err() {
+                    return;
+                }
+
+                let mut stream = response.into_inner();
+                loop {
+                    match stream.message().await {
+                        Ok(Some(event)) => {
+                            start_from = StartFrom::AfterOperationId(event.operation_id.clone());
+                            let event = SubscriptionEvent::Operation(event);
+                            if events_tx.send(Ok(event)).await.is_err() {
+                                return;
+                            }
+                        }
+                        Ok(None) => break "the server closed the stream".to_string(),
+                        Err(status) if is_fatal(&status) => {
+                            let _ = events_tx.send(Err(PandaError::from(status))).await;
+                            return;
+                        }
+                        Err(status) => break status.to_string(),
+                    }
+                }
+            }
+            Err(PandaError::Rpc(status)) if !is_fatal(&status) => status.to_string(),
+            Err(e) => {
+                let _ = events_tx.send(Err(e)).await;
+                return;
+            }
+        };
+
+        if matches!(start_from, StartFrom::Frontier(_)) {
+            start_from = StartFrom::SinceTimestamp(opened_at);
+        }
+
+        attempt += 1;
+        let retry_in = backoff(&policy, attempt);
+        let disconnected = SubscriptionEvent::ConnectionState(ConnectionState::Disconnected {
+            reason,
+            attempt,
+            retry_in,
+        });
+        if events_tx.send(Ok(disconnected)).await.is_err() {
+            return;
+        }
+
+        tokio::select! {
+            _ = events_tx.closed() => return,
+            _ = tokio::time::sleep(retry_in) => {}
+        }
+    }
+}
+
+/// Statuses that will come back the same however often the subscription is
+/// retried.
+fn is_fatal(status: &tonic::Status) -> bool {
+    matches!(
+        status.code(),
+        Code::Unauthenticated | Code::PermissionDenied | Code::InvalidArgument | Code::NotFound
+    )
+}
+
+/// Unix time in milliseconds, the unit of `StartFrom::SinceTimestamp`.
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+pub(crate) fn backoff(policy: &ReconnectPolicy, attempt: u32) -> Duration {
+    let factor = 2u32.saturating_pow(attempt.saturating_sub(1));
+    policy
+        .initial_backoff
+        .saturating_mul(factor)
+        .min(policy.max_backoff)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use tokio_stream::StreamExt;
+
+    use super::*;
+    use crate::fake_panda::FakePanda;
+    use crate::proto::Frontier;
+
+    #[test]
+    fn test_backoff_doubles_up_to_max() {
+        let policy = ReconnectPolicy {
+            initial_backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(3),
+        };
+
+        assert_eq!(backoff(&policy, 1), Duration::from_millis(500));
+        assert_eq!(backoff(&policy, 2), Duration::from_secs(1));
+        assert_eq!(backoff(&policy, 3), Duration::from_secs(2));
+        assert_eq!(backoff(&policy, 4), Duration::from_secs(3));
+        assert_eq!(backoff(&policy, u32::MAX), Duration::from_secs(3));
+    }
+
+    #[test]
+    fn test_only_unretryable_statuses_are_fatal() {
+        for code in [
+            Code::Unauthenticated,
+            Code::PermissionDenied,
+            Code::InvalidArgument,
+            Code::NotFound,
+        ] {
+            assert!(is_fatal(&tonic::Status::new(code, "")), "{code:?}");
+        }
+        for code in [
+            Code::Unavailable,
+            Code::DataLoss,
+            Code::Internal,
+            Code::DeadlineExceeded,
+            Code::Cancelled,
+        ] {
+            assert!(!is_fatal(&tonic::Status::new(code, "")), "{code:?}");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_frontier_lost_before_any_operation_resumes_from_when_it_opened() {
+        let event = OperationEvent {
+            operation_id: vec![1; 32],
+            ..Default::default()
+        };
+        let fake = Arc::new(FakePanda {
+            subscriptions: std::sync::Mutex::new(vec![vec![], vec![event]].into()),
+            ..Default::default()
+        });
+        let client = fake.serve().await;
+
+        let before = now_ms();
+        let mut subscription = client.subscribe_reconnecting(
+            "app",
+            "instance",
+            StartFrom::Frontier(Frontier {}),
+            ReconnectPolicy {
+                initial_backoff: Duration::from_millis(1),
+                max_backoff: Duration::from_millis(1),
+            },
+        );
+        // Opened and lost, opened again with the operation then lost, and
+        // refused once the fake runs out of subscriptions.
+        for _ in 0..6 {
+            subscription.next().await.unwrap().unwrap();
+        }
+        let after = now_ms();
+
+        let requests = fake.subscribe_requests.lock().unwrap();
+        let start_froms: Vec<_> = requests
+            .iter()
+            .map(|request| request.start_from.clone().unwrap())
+            .collect();
+        assert_eq!(start_froms[0], StartFrom::Frontier(Frontier {}));
+        match start_froms[1] {
+            StartFrom::SinceTimestamp(since) => assert!((before..=after).contains(&since)),
+            ref other => panic!("expected since_timestamp, got {other:?}"),
+        }
+        assert_eq!(start_froms[2], StartFrom::AfterOperationId(vec![1; 32]));
+    }
+}
@@ -0,0 +1,287 @@
+use std::marker::PhantomData;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use tokio_stream::Stream;
+use tonic::Streaming;
+
+use crate::proto::{Frontier, OperationEvent, PublishResponse};
+use crate::{PandaClient, PandaError, StartFrom};
+
+/// Errors returned by [`TypedPandaClient`] methods.
+#[derive(Debug)]
+pub enum TypedError {
+    /// The value could not be encoded as CBOR.
+    Encode(String),
+    /// The call to the server failed.
+    Panda(PandaError),
+}
+
+impl std::fmt::Display for TypedError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TypedError::Encode(msg) => write!(f, "failed to encode payload as CBOR: {msg}"),
+            TypedError::Panda(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl std::error::Error for TypedError {}
+
+impl From<PandaError> for TypedError {
+    fn from(e: PandaError) -> Self {
+        TypedError::Panda(e)
+    }
+}
+
+/// Where an operation came from, as delivered alongside its payload.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OperationMeta {
+    /// 32-byte p2panda topic the operation was published on.
+    pub topic_id: Vec<u8>,
+    /// 32-byte public key of the node that published it.
+    pub author: Vec<u8>,
+    /// 32-byte id of the operation.
+    pub operation_id: Vec<u8>,
+    /// Unix timestamp in milliseconds.
+    pub timestamp: u64,
+}
+
+/// An operation received by a typed subscription.
+#[derive(Debug, Clone)]
+pub enum TypedEvent<T> {
+    /// The payload decoded as `T`.
+    Decoded { meta: OperationMeta, value: T },
+    /// The payload isn't a CBOR encoding of `T`, e.g. because it was
+    /// published by another version of the app. The raw payload is kept so
+    /// the app can decide what to do with it.
+    Undecodable {
+        meta: OperationMeta,
+        error: String,
+        payload: Vec<u8>,
+    },
+}
+
+impl<T: DeserializeOwned> TypedEvent<T> {
+    /// Decodes the payload of an operation from any subscription.
+    pub fn decode(event: OperationEvent) -> Self {
+        let meta = OperationMeta {
+            topic_id: event.topic_id,
+            author: event.author,
+            operation_id: event.operation_id,
+            timestamp: event.timestamp,
+        };
+        match ciborium::from_reader(event.payload.as_slice()) {
+            Ok(value) => TypedEvent::Decoded { meta, value },
+            Err(e) => TypedEvent::Undecodable {
+                meta,
+                error: e.to_string(),
+                payload: event.payload,
+            },
+        }
+    }
+}
+
+impl<T> TypedEvent<T> {
+    pub fn meta(&self) -> &OperationMeta {
+        match self {
+            TypedEvent::Decoded { meta, .. } | TypedEvent::Undecodable { meta, .. } => meta,
+        }
+    }
+}
+
+/// Wraps a [`PandaClient`] for one app instance whose payloads are all
+/// CBOR-encoded values of type `T`.
+pub struct TypedPandaClient<T> {
+    client: PandaClient,
+    app_id: String,
+    instance_id: String,
+    _payload: PhantomData<fn() -> T>,
+}
+
+impl<T> Clone for TypedPandaClient<T> {
+    fn clone(&self) -> Self {
+        Self {
+            client: self.client.clone(),
+            app_id: self.app_id.clone(),
+            instance_id: self.instance_id.clone(),
+            _payload: PhantomData,
+        }
+    }
+}
+
+impl<T: Serialize + DeserializeOwned> TypedPandaClient<T> {
+    pub fn new(
+        client: PandaClient,
+        app_id: impl Into<String>,
+        instance_id: impl Into<String>,
+    ) -> Self {
+        Self {
+            client,
+            app_id: app_id.into(),
+            instance_id: instance_id.into(),
+            _payload: PhantomData,
+        }
+    }
+
+    /// The untyped client, for calls this wrapper doesn't cover.
+    pub fn inner(&mut self) -> &mut PandaClient {
+        &mut self.client
+    }
+
+    /// Encodes `value` as CBOR and publishes it. See [`PandaClient::publish`].
+    pub async fn publish(
+        &mut self,
+        value: &T,
+        idempotency_key: Option<Vec<u8>>,
+    ) -> Result<PublishResponse, TypedError> {
+        let payload = encode(value)?;
+        let response = self
+            .client
+            .publish(&self.app_id, &self.instance_id, payload, idempotency_key)
+            .await?;
+        Ok(response.into_inner())
+    }
+
+    /// Receives operations published from now on, decoded as `T`.
+    pub async fn subscribe(&mut self) -> Result<TypedSubscription<T>, PandaError> {
+        self.subscribe_from(StartFrom::Frontier(Frontier {})).await
+    }
+
+    /// Like [`Self::subscribe`], but first replays stored operations from
+    /// `start_from`. See [`PandaClient::subscribe_from`].
+    pub async fn subscribe_from(
+        &mut self,
+        start_from: StartFrom,
+    ) -> Result<TypedSubscription<T>, PandaError> {
+        let response = self
+            .client
+            .subscribe_from(&self.app_id, &self.instance_id, start_from)
+            .await?;
+        Ok(TypedSubscription {
+            stream: response.into_inner(),
+            _payload: PhantomData,
+        })
+    }
+}
+
+/// A stream of operations decoded as `T`. A payload that doesn't decode is
+/// delivered as [`TypedEvent::Undecodable`] and the stream carries on; the
+/// stream ends with `Err` only when the subscription itself fails.
+pub struct TypedSubscription<T> {
+    stream: Streaming<OperationEvent>,
+    _payload: PhantomData<fn() -> T>,
+}
+
+impl<T: DeserializeOwned> Stream for TypedSubscription<T> {
+    type Item = Result<TypedEvent<T>, PandaError>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        Pin::new(&mut self.stream)
+            .poll_next(cx)
+            .map(|item| item.map(|result| result.map(TypedEvent::decode).map_err(PandaError::from)))
+    }
+}
+
+fn encode<T: Serialize>(value: &T) -> Result<Vec<u8>, TypedError> {
+    let mut payload = Vec::new();
+    ciborium::into_writer(value, &mut payload).map_err(|e| TypedError::Encode(e.to_string()))?;
+    Ok(payload)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use serde::Deserialize;
+    use tokio_stream::StreamExt;
+
+    use super::*;
+    use crate::fake_panda::FakePanda;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Note {
+        text: String,
+        pinned: bool,
+    }
+
+    fn event(operation_id: u8, payload: Vec<u8>) -> OperationEvent {
+        OperationEvent {
+            topic_id: vec![1; 32],
+            author: vec![2; 32],
+            operation_id: vec![operation_id; 32],
+            timestamp: 1_700_000_000_000 + u64::from(operation_id),
+            payload,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_published_values_decode_with_their_meta() {
+        let fake = Arc::new(FakePanda::default());
+        let mut client = TypedPandaClient::<Note>::new(fake.serve().await, "app", "instance");
+
+        let note = Note {
+            text: "hello".to_string(),
+            pinned: true,
+        };
+        client.publish(&note, Some(b"key".to_vec())).await.unwrap();
+        let published = fake.published.lock().unwrap().remove(0);
+        assert_eq!(published.app_id, "app");
+        assert_eq!(published.idempotency_key, b"key");
+
+        let delivered = event(3, published.payload);
+        *fake.subscriptions.lock().unwrap() = vec![vec![delivered.clone()]].into();
+        let mut subscription = client.subscribe().await.unwrap();
+
+        match subscription.next().await.unwrap().unwrap() {
+            TypedEvent::Decoded { meta, value } => {
+                assert_eq!(value, note);
+                assert_eq!(
+                    meta,
+                    OperationMeta {
+                        topic_id: delivered.topic_id,
+                        author: delivered.author,
+                        operation_id: delivered.operation_id,
+                        timestamp: delivered.timestamp,
+                    }
+                );
+            }
+            other => panic!("expected a decoded note, got {other:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_undecodable_payload_keeps_its_bytes_and_the_stream_goes_on() {
+        let mut payload = Vec::new();
+        ciborium::into_writer(
+            &Note {
+                text: "after".to_string(),
+                pinned: false,
+            },
+            &mut payload,
+        )
+        .unwrap();
+        let fake = Arc::new(FakePanda {
+            subscriptions: Mutex::new(
+                vec![vec![event(1, b"not cbor".to_vec()), event(2, payload)]].into(),
+            ),
+            ..Default::default()
+        });
+        let mut client = TypedPandaClient::<Note>::new(fake.serve().await, "app", "instance");
+        let mut subscription = client.subscribe().await.unwrap();
+
+        match subscription.next().await.unwrap().unwrap() {
+            TypedEvent::Undecodable { meta, payload, .. } => {
+                assert_eq!(payload, b"not cbor");
+                assert_eq!(meta.operation_id, vec![1; 32]);
+            }
+            other => panic!("expected an undecodable payload, got {other:?}"),
+        }
+        match subscription.next().await.unwrap().unwrap() {
+            TypedEvent::Decoded { value, .. } => assert_eq!(value.text, "after"),
+            other => panic!("expected a decoded note, got {other:?}"),
+        }
+    }
+}
@@ -0,0 +1,75 @@
+use hyper_util::rt::TokioIo;
+use tokio::net::UnixStream;
+use tonic::transport::{Channel, Endpoint, Uri};
+
+/// The socket path in a `unix:///path/to.sock` or `unix:/path/to.sock` URI.
+pub(crate) fn socket_path(uri: &str) -> Option<&str> {
+    uri.strip_prefix("unix://")
+        .or_else(|| uri.strip_prefix("unix:"))
+}
+
+// The URI only sets the `:authority` of requests; every connection goes to
+// the socket.
+fn endpoint() -> Endpoint {
+    Endpoint::from_static("http://localhost")
+}
+
+pub(crate) async fn connect(path: &str) -> Result<Channel, tonic::transport::Error> {
+    let path = path.to_string();
+    endpoint()
+        .connect_with_connector(tower::service_fn(move |_: Uri| {
+            let path = path.clone();
+            async move { Ok::<_, std::io::Error>(TokioIo::new(UnixStream::connect(path).await?)) }
+        }))
+        .await
+}
+
+pub(crate) fn connect_lazy(path: &str) -> Channel {
+    let path = path.to_string();
+    endpoint().connect_with_connector_lazy(tower::service_fn(move |_: Uri| {
+        let path = path.clone();
+        async move { Ok::<_, std::io::Error>(TokioIo::new(UnixStream::connect(path).await?)) }
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use tokio::net::UnixListener;
+    use tokio_stream::wrappers::UnixListenerStream;
+
+    use crate::PandaClient;
+    use crate::fake_panda::FakePanda;
+    use crate::proto::panda_server::PandaServer;
+
+    #[tokio::test]
+    async fn test_connects_through_a_unix_uri() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("grpc.sock");
+        let fake = Arc::new(FakePanda::default());
+        let server = tonic::transport::Server::builder()
+            .add_service(PandaServer::new(fake.clone()))
+            .serve_with_incoming(UnixListenerStream::new(UnixListener::bind(&path).unwrap()));
+        tokio::spawn(server);
+
+        let uri = format!("unix://{}", path.display());
+        let mut client = PandaClient::connect_uri(&uri, Some("app-token"))
+            .await
+            .unwrap();
+        client
+            .publish("app", "instance", b"one".to_vec(), None)
+            .await
+            .unwrap();
+
+        let mut lazy_client = PandaClient::connect_lazy_uri(&uri, None).unwrap();
+        lazy_client
+            .publish("app", "instance", b"two".to_vec(), None)
+            .await
+            .unwrap();
+
+        let published = fake.published.lock().unwrap();
+        assert_eq!(published[0].payload, b"one");
+        assert_eq!(published[1].payload, b"two");
+    }
+}
@@ -0,0 +1,71 @@
+use tonic::{Request, Status, service::Interceptor};
+
+/// Bearer token presented by an app, extracted from the `authorization`
+/// metadata by [`AppTokenInterceptor`].
+#[derive(Debug, Clone)]
+pub(crate) struct AppToken(pub String);
+
+/// Rejects calls without an `authorization: Bearer <token>` header and
+/// stashes the token in the request extensions for the handler to verify
+/// against the caller's app/instance ids.
+#[derive(Debug, Clone, Default)]
+pub struct AppTokenInterceptor;
+
+impl Interceptor for AppTokenInterceptor {
+    fn call(&mut self, mut request: Request<()>) -> Result<Request<()>, Status> {
+        let token = request
+            .metadata()
+            .get("authorization")
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .map(|token| token.trim().to_string())
+            .filter(|token| !token.is_empty())
+            .ok_or_else(|| {
+                Status::unauthenticated(
+                    "Missing app token. Send `authorization: Bearer <token>` with the token issued when this app was bound to a region.",
+                )
+            })?;
+
+        request.extensions_mut().insert(AppToken(token));
+        Ok(request)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request_with_authorization(value: &str) -> Request<()> {
+        let mut request = Request::new(());
+        request
+            .metadata_mut()
+            .insert("authorization", value.parse().unwrap());
+        request
+    }
+
+    #[test]
+    fn test_extracts_bearer_token() {
+        let request = AppTokenInterceptor
+            .call(request_with_authorization("Bearer abc123"))
+            .unwrap();
+
+        let token = request.extensions().get::<AppToken>().unwrap();
+        assert_eq!(token.0, "abc123");
+    }
+
+    #[test]
+    fn test_rejects_missing_header() {
+        let err = AppTokenInterceptor.call(Request::new(())).unwrap_err();
+        assert_eq!(err.code(), tonic::Code::Unauthenticated);
+    }
+
+    #[test]
+    fn test_rejects_other_schemes_and_empty_tokens() {
+        for value in ["Basic abc123", "Bearer ", "abc123"] {
+            let err = AppTokenInterceptor
+                .call(request_with_authorization(value))
+                .unwrap_err();
+            assert_eq!(err.code(), tonic::Code::Unauthenticated, "{value}");
+        }
+    }
+}
@@ -0,0 +1,89 @@
+use lores_p2panda::OperationCursor;
+use lores_p2panda::p2panda_core::{Hash, VerifyingKey};
+use tonic::Status;
+
+/// Page size used when `ListOperationsRequest.limit` is unset.
+pub const DEFAULT_PAGE_SIZE: u32 = 100;
+/// Largest page size a caller can ask for.
+pub const MAX_PAGE_SIZE: u32 = 1000;
+
+pub fn page_size(limit: u32) -> u32 {
+    match limit {
+        0 => DEFAULT_PAGE_SIZE,
+        limit => limit.min(MAX_PAGE_SIZE),
+    }
+}
+
+/// Page tokens are the cursor of the last operation on the page: an 8-byte
+/// big-endian timestamp followed by the 32-byte operation id.
+pub fn encode_page_token(cursor: &OperationCursor) -> Vec<u8> {
+    let mut token = cursor.timestamp.to_be_bytes().to_vec();
+    token.extend_from_slice(cursor.operation_id.as_bytes());
+    token
+}
+
+pub fn decode_page_token(token: &[u8]) -> Result<Option<OperationCursor>, Status> {
+    if token.is_empty() {
+        return Ok(None);
+    }
+    let invalid = || Status::invalid_argument("page_token is not valid");
+    if token.len() != 40 {
+        return Err(invalid());
+    }
+    let (timestamp, operation_id) = token.split_at(8);
+    Ok(Some(OperationCursor {
+        timestamp: u64::from_be_bytes(timestamp.try_into().map_err(|_| invalid())?),
+        operation_id: Hash::from_bytes(operation_id.try_into().map_err(|_| invalid())?),
+    }))
+}
+
+pub fn parse_author(author: &[u8]) -> Result<Option<VerifyingKey>, Status> {
+    if author.is_empty() {
+        return Ok(None);
+    }
+    let invalid = || Status::invalid_argument("author must be a 32-byte public key");
+    let bytes: [u8; 32] = author.try_into().map_err(|_| invalid())?;
+    VerifyingKey::from_bytes(&bytes)
+        .map(Some)
+        .map_err(|_| invalid())
+}
+
+pub fn parse_operation_id(operation_id: &[u8]) -> Result<Hash, Status> {
+    let bytes: [u8; 32] = operation_id
+        .try_into()
+        .map_err(|_| Status::invalid_argument("operation_id must be a 32-byte operation id"))?;
+    Ok(Hash::from_bytes(bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_page_token_round_trips() {
+        let cursor = OperationCursor {
+            timestamp: 1_700_000_000_123,
+            operation_id: Hash::from_bytes([9u8; 32]),
+        };
+        let token = encode_page_token(&cursor);
+        assert_eq!(decode_page_token(&token).unwrap(), Some(cursor));
+    }
+
+    #[test]
+    fn test_empty_page_token_starts_at_newest() {
+        assert_eq!(decode_page_token(&[]).unwrap(), None);
+    }
+
+    #[test]
+    fn test_truncated_page_token_is_rejected() {
+        let err = decode_page_token(&[0; 39]).unwrap_err();
+        assert_eq!(err.code(), tonic::Code::InvalidArgument);
+    }
+
+    #[test]
+    fn test_page_size_defaults_and_caps() {
+        assert_eq!(page_size(0), DEFAULT_PAGE_SIZE);
+        assert_eq!(page_size(10), 10);
+        assert_eq!(page_size(50_000), MAX_PAGE_SIZE);
+    }
+}
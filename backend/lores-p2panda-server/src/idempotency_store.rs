@@ -1,15 +1,52 @@
+use std::collections::HashMap;
+use std::sync::Arc;
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
-use lores_p2panda::{RegionAppTopic, RegionTopic};
+use lores_p2panda::{RegionAppTopic, RegionTopic, Topic};
 use sqlx::SqlitePool;
+use tokio::sync::Mutex;
 use tokio::time::interval;
 use tonic::Status;
 
-pub struct IdempotencyStore {
+use crate::proto::PublishResponse;
+
+/// Remembers the idempotency keys of recent publishes so retries don't
+/// publish twice. Keys are scoped by topic and app instance, so two instances
+/// of an app can't collide by choosing the same key.
+#[tonic::async_trait]
+pub trait IdempotencyStore: Send + Sync {
+    /// Returns the response of the publish that recorded this key, marked as a
+    /// duplicate, so a retry gets the same operation id as the original call.
+    /// Returns `None` immediately when no key is supplied.
+    async fn find_published(
+        &self,
+        region_app_topic: &RegionAppTopic,
+        instance_id: &str,
+        idempotency_key: &[u8],
+    ) -> Result<Option<PublishResponse>, Status>;
+
+    /// Records an idempotency key as processed, along with the response for
+    /// the operation it published. No-op when the key is empty.
+    async fn record(
+        &self,
+        region_app_topic: &RegionAppTopic,
+        instance_id: &str,
+        idempotency_key: &[u8],
+        published: &PublishResponse,
+    ) -> Result<(), Status>;
+}
+
+/// `instance_id` of keys recorded before keys were scoped by instance. They
+/// match every instance of the app.
+const ANY_INSTANCE: &str = "";
+
+/// Keeps idempotency keys in the node's SQLite database, so they survive
+/// restarts.
+pub struct SqliteIdempotencyStore {
     db: SqlitePool,
 }
 
-impl IdempotencyStore {
+impl SqliteIdempotencyStore {
     pub async fn new(
         db: SqlitePool,
         cleanup_frequency: Duration,
@@ -20,107 +57,260 @@ impl IdempotencyStore {
         Ok(Self { db })
     }
 
-    /// Returns `true` if the idempotency key has already been recorded for
-    /// this topic. Returns `false` immediately when no key is supplied.
-    pub async fn is_duplicate(
+    async fn setup_table(db: &SqlitePool) -> Result<(), sqlx::Error> {
+        let mut tx = db.begin().await?;
+
+        // Tables created before keys were scoped by instance can't say which
+        // instance used each key, so their keys are kept under
+        // `ANY_INSTANCE` until they expire.
+        let columns: Vec<(String,)> =
+            sqlx::query_as("SELECT name FROM pragma_table_info('publish_idempotency_keys')")
+                .fetch_all(&mut *tx)
+                .await?;
+        let needs_migration =
+            !columns.is_empty() && !columns.iter().any(|(name,)| name == "instance_id");
+        if needs_migration {
+            sqlx::query(
+                "DROP INDEX IF EXISTS idx_pik_seen_at;
+                 ALTER TABLE publish_idempotency_keys RENAME TO publish_idempotency_keys_old;",
+            )
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS publish_idempotency_keys (
+                topic        BLOB    NOT NULL,
+                instance_id  TEXT    NOT NULL,
+                key          BLOB    NOT NULL,
+                seen_at      INTEGER NOT NULL,
+                operation_id BLOB,
+                timestamp    INTEGER,
+                seq_num      INTEGER,
+                PRIMARY KEY (topic, instance_id, key)
+            );
+            CREATE INDEX IF NOT EXISTS idx_pik_seen_at
+                ON publish_idempotency_keys(seen_at);",
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        if needs_migration {
+            sqlx::query(
+                "INSERT INTO publish_idempotency_keys (topic, instance_id, key, seen_at)
+                 SELECT topic, ?, key, seen_at FROM publish_idempotency_keys_old",
+            )
+            .bind(ANY_INSTANCE)
+            .execute(&mut *tx)
+            .await?;
+            sqlx::query("DROP TABLE publish_idempotency_keys_old")
+                .execute(&mut *tx)
+                .await?;
+        }
+
+        tx.commit().await
+    }
+
+    fn spawn_cleanup(db: &SqlitePool, cleanup_frequency: Duration, retention: Duration) {
+        let db = db.clone();
+        let retention_secs = retention.as_secs() as i64;
+
+        tokio::spawn(async move {
+            let mut timer = interval(cleanup_frequency);
+            // The first tick completes immediately; sweep only once a period has passed.
+            timer.tick().await;
+            loop {
+                timer.tick().await;
+                let cutoff = now_secs() - retention_secs;
+                let _ = sqlx::query("DELETE FROM publish_idempotency_keys WHERE seen_at < ?")
+                    .bind(cutoff)
+                    .execute(&db)
+                    .await;
+            }
+        });
+    }
+}
+
+#[tonic::async_trait]
+impl IdempotencyStore for SqliteIdempotencyStore {
+    async fn find_published(
         &self,
         region_app_topic: &RegionAppTopic,
+        instance_id: &str,
         idempotency_key: &[u8],
-    ) -> Result<bool, Status> {
+    ) -> Result<Option<PublishResponse>, Status> {
         if idempotency_key.is_empty() {
-            return Ok(false);
+            return Ok(None);
         }
 
         let topic_bytes = region_app_topic.p2panda_topic().to_bytes().to_vec();
-        let exists: Option<(i64,)> =
-            sqlx::query_as("SELECT 1 FROM publish_idempotency_keys WHERE topic = ? AND key = ?")
-                .bind(&topic_bytes)
-                .bind(idempotency_key)
-                .fetch_optional(&self.db)
-                .await
-                .map_err(|e| Status::internal(e.to_string()))?;
+        let row: Option<(Option<Vec<u8>>, Option<i64>, Option<i64>)> = sqlx::query_as(
+            "SELECT operation_id, timestamp, seq_num FROM publish_idempotency_keys
+             WHERE topic = ? AND instance_id IN (?, ?) AND key = ?
+             ORDER BY instance_id = ? DESC
+             LIMIT 1",
+        )
+        .bind(&topic_bytes)
+        .bind(instance_id)
+        .bind(ANY_INSTANCE)
+        .bind(idempotency_key)
+        .bind(instance_id)
+        .fetch_optional(&self.db)
+        .await
+        .map_err(|e| Status::internal(e.to_string()))?;
 
-        Ok(exists.is_some())
+        // Keys carried over from before acks were stored have none to return.
+        Ok(
+            row.map(|(operation_id, timestamp, seq_num)| PublishResponse {
+                operation_id: operation_id.unwrap_or_default(),
+                timestamp: timestamp.unwrap_or_default() as u64,
+                seq_num: seq_num.unwrap_or_default() as u64,
+                duplicate: true,
+            }),
+        )
     }
 
-    /// Records an idempotency key as processed. No-op when the key is empty.
-    pub async fn record(
+    async fn record(
         &self,
         region_app_topic: &RegionAppTopic,
+        instance_id: &str,
         idempotency_key: &[u8],
+        published: &PublishResponse,
     ) -> Result<(), Status> {
         if idempotency_key.is_empty() {
             return Ok(());
         }
 
         let topic_bytes = region_app_topic.p2panda_topic().to_bytes().to_vec();
-        let seen_at = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap()
-            .as_secs() as i64;
 
         sqlx::query(
-            "INSERT OR IGNORE INTO publish_idempotency_keys (topic, key, seen_at)
-             VALUES (?, ?, ?)",
+            "INSERT OR IGNORE INTO publish_idempotency_keys
+                (topic, instance_id, key, seen_at, operation_id, timestamp, seq_num)
+             VALUES (?, ?, ?, ?, ?, ?, ?)",
         )
         .bind(&topic_bytes)
+        .bind(instance_id)
         .bind(idempotency_key)
-        .bind(seen_at)
+        .bind(now_secs())
+        .bind(&published.operation_id)
+        .bind(published.timestamp as i64)
+        .bind(published.seq_num as i64)
         .execute(&self.db)
         .await
         .map_err(|e| Status::internal(e.to_string()))?;
 
         Ok(())
     }
+}
 
-    async fn setup_table(db: &SqlitePool) -> Result<(), sqlx::Error> {
-        sqlx::query(
-            "CREATE TABLE IF NOT EXISTS publish_idempotency_keys (
-                topic   BLOB    NOT NULL,
-                key     BLOB    NOT NULL,
-                seen_at INTEGER NOT NULL,
-                PRIMARY KEY (topic, key)
-            );
-            CREATE INDEX IF NOT EXISTS idx_pik_seen_at
-                ON publish_idempotency_keys(seen_at);",
-        )
-        .execute(db)
-        .await?;
-        Ok(())
+type MemoryKey = (Topic, String, Vec<u8>);
+
+/// Keeps idempotency keys in memory only. Keys are lost on restart, so a
+/// retry after a restart may publish again.
+pub struct MemoryIdempotencyStore {
+    keys: Arc<Mutex<HashMap<MemoryKey, (i64, PublishResponse)>>>,
+}
+
+impl MemoryIdempotencyStore {
+    pub fn new(cleanup_frequency: Duration, retention: Duration) -> Self {
+        let keys = Arc::new(Mutex::new(HashMap::new()));
+        Self::spawn_cleanup(&keys, cleanup_frequency, retention);
+        Self { keys }
     }
 
-    fn spawn_cleanup(db: &SqlitePool, cleanup_frequency: Duration, retention: Duration) {
-        let db = db.clone();
+    fn spawn_cleanup(
+        keys: &Arc<Mutex<HashMap<MemoryKey, (i64, PublishResponse)>>>,
+        cleanup_frequency: Duration,
+        retention: Duration,
+    ) {
+        let keys = keys.clone();
         let retention_secs = retention.as_secs() as i64;
 
         tokio::spawn(async move {
             let mut timer = interval(cleanup_frequency);
+            // The first tick completes immediately; sweep only once a period has passed.
+            timer.tick().await;
             loop {
                 timer.tick().await;
-                let cutoff = SystemTime::now()
-                    .duration_since(UNIX_EPOCH)
-                    .unwrap()
-                    .as_secs() as i64
-                    - retention_secs;
-                let _ = sqlx::query("DELETE FROM publish_idempotency_keys WHERE seen_at < ?")
-                    .bind(cutoff)
-                    .execute(&db)
-                    .await;
+                let cutoff = now_secs() - retention_secs;
+                keys.lock()
+                    .await
+                    .retain(|_, (seen_at, _)| *seen_at >= cutoff);
             }
         });
     }
 }
 
+#[tonic::async_trait]
+impl IdempotencyStore for MemoryIdempotencyStore {
+    async fn find_published(
+        &self,
+        region_app_topic: &RegionAppTopic,
+        instance_id: &str,
+        idempotency_key: &[u8],
+    ) -> Result<Option<PublishResponse>, Status> {
+        if idempotency_key.is_empty() {
+            return Ok(None);
+        }
+
+        let key = (
+            region_app_topic.p2panda_topic(),
+            instance_id.to_string(),
+            idempotency_key.to_vec(),
+        );
+        Ok(self
+            .keys
+            .lock()
+            .await
+            .get(&key)
+            .map(|(_, published)| PublishResponse {
+                duplicate: true,
+                ..published.clone()
+            }))
+    }
+
+    async fn record(
+        &self,
+        region_app_topic: &RegionAppTopic,
+        instance_id: &str,
+        idempotency_key: &[u8],
+        published: &PublishResponse,
+    ) -> Result<(), Status> {
+        if idempotency_key.is_empty() {
+            return Ok(());
+        }
+
+        let key = (
+            region_app_topic.p2panda_topic(),
+            instance_id.to_string(),
+            idempotency_key.to_vec(),
+        );
+        self.keys
+            .lock()
+            .await
+            .entry(key)
+            .or_insert_with(|| (now_secs(), published.clone()));
+        Ok(())
+    }
+}
+
+fn now_secs() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs() as i64
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use lores_p2panda::{RegionAppTopic, RegionId};
     use sqlx::SqlitePool;
 
-    async fn test_store() -> IdempotencyStore {
+    async fn test_store() -> SqliteIdempotencyStore {
         let db = SqlitePool::connect("sqlite::memory:").await.unwrap();
         // Use a very long cleanup frequency so the background task never fires during tests.
-        IdempotencyStore::new(
+        SqliteIdempotencyStore::new(
             db,
             Duration::from_secs(u64::MAX / 2),
             Duration::from_hours(48),
@@ -129,44 +319,77 @@ mod tests {
         .unwrap()
     }
 
+    fn test_memory_store() -> MemoryIdempotencyStore {
+        MemoryIdempotencyStore::new(Duration::from_secs(u64::MAX / 2), Duration::from_hours(48))
+    }
+
     fn topic(namespace: &str) -> RegionAppTopic {
         RegionAppTopic::new(RegionId::from([1u8; 32]), namespace)
     }
 
-    // 1. is_duplicate returns false when key has not been recorded.
+    const INSTANCE: &str = "instance-1";
+
+    fn published() -> PublishResponse {
+        PublishResponse {
+            operation_id: vec![7u8; 32],
+            timestamp: 1_700_000_000_000,
+            seq_num: 3,
+            duplicate: false,
+        }
+    }
+
+    // 1. find_published returns nothing when key has not been recorded.
     #[tokio::test]
     async fn test_unknown_key_is_not_duplicate() {
         let store = test_store().await;
-        let result = store.is_duplicate(&topic("app"), b"key-1").await.unwrap();
-        assert!(!result);
+        let result = store
+            .find_published(&topic("app"), INSTANCE, b"key-1")
+            .await
+            .unwrap();
+        assert!(result.is_none());
     }
 
-    // 2. is_duplicate returns false when no key is supplied.
+    // 2. find_published returns nothing when no key is supplied.
     #[tokio::test]
     async fn test_empty_key_is_not_duplicate() {
         let store = test_store().await;
-        let result = store.is_duplicate(&topic("app"), b"").await.unwrap();
-        assert!(!result);
+        let result = store
+            .find_published(&topic("app"), INSTANCE, b"")
+            .await
+            .unwrap();
+        assert!(result.is_none());
     }
 
-    // 3. After record, is_duplicate returns true for the same key and topic.
+    // 3. After record, find_published returns the ack for the same key and topic.
     #[tokio::test]
     async fn test_recorded_key_is_duplicate() {
         let store = test_store().await;
-        store.record(&topic("app"), b"key-1").await.unwrap();
-        let result = store.is_duplicate(&topic("app"), b"key-1").await.unwrap();
-        assert!(result);
+        store
+            .record(&topic("app"), INSTANCE, b"key-1", &published())
+            .await
+            .unwrap();
+        let result = store
+            .find_published(&topic("app"), INSTANCE, b"key-1")
+            .await
+            .unwrap();
+        assert!(result.is_some());
     }
 
     // 4. Calling record twice with the same key does not error.
     #[tokio::test]
     async fn test_record_is_idempotent() {
         let store = test_store().await;
-        store.record(&topic("app"), b"key-1").await.unwrap();
-        store.record(&topic("app"), b"key-1").await.unwrap();
+        store
+            .record(&topic("app"), INSTANCE, b"key-1", &published())
+            .await
+            .unwrap();
+        store
+            .record(&topic("app"), INSTANCE, b"key-1", &published())
+            .await
+            .unwrap();
     }
 
-    // 5. After manual cleanup removes an expired row, is_duplicate returns false.
+    // 5. After manual cleanup removes an expired row, find_published returns nothing.
     #[tokio::test]
     async fn test_expired_key_removed_by_cleanup() {
         let store = test_store().await;
@@ -174,30 +397,44 @@ mod tests {
         // Insert a row with seen_at in the distant past.
         let topic = topic("app");
         let topic_bytes = topic.p2panda_topic().to_bytes().to_vec();
-        sqlx::query("INSERT INTO publish_idempotency_keys (topic, key, seen_at) VALUES (?, ?, ?)")
-            .bind(&topic_bytes)
-            .bind(b"old-key".as_slice())
-            .bind(0i64) // epoch — definitely expired
-            .execute(&store.db)
-            .await
-            .unwrap();
+        sqlx::query(
+            "INSERT INTO publish_idempotency_keys
+                (topic, instance_id, key, seen_at, operation_id, timestamp, seq_num)
+             VALUES (?, ?, ?, ?, ?, 0, 0)",
+        )
+        .bind(&topic_bytes)
+        .bind(INSTANCE)
+        .bind(b"old-key".as_slice())
+        .bind(0i64) // epoch — definitely expired
+        .bind(vec![7u8; 32])
+        .execute(&store.db)
+        .await
+        .unwrap();
 
         // Confirm it looks like a duplicate before cleanup.
-        assert!(store.is_duplicate(&topic, b"old-key").await.unwrap());
+        assert!(
+            store
+                .find_published(&topic, INSTANCE, b"old-key")
+                .await
+                .unwrap()
+                .is_some()
+        );
 
         // Run cleanup with a cutoff of now (removes anything seen_at < now).
-        let cutoff = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap()
-            .as_secs() as i64;
         sqlx::query("DELETE FROM publish_idempotency_keys WHERE seen_at < ?")
-            .bind(cutoff)
+            .bind(now_secs())
             .execute(&store.db)
             .await
             .unwrap();
 
         // Now the key should be gone.
-        assert!(!store.is_duplicate(&topic, b"old-key").await.unwrap());
+        assert!(
+            store
+                .find_published(&topic, INSTANCE, b"old-key")
+                .await
+                .unwrap()
+                .is_none()
+        );
     }
 
     // 6. Keys are scoped by topic: the same key on different topics is independent.
@@ -207,9 +444,156 @@ mod tests {
         let topic_a = topic("app-a");
         let topic_b = topic("app-b");
 
-        store.record(&topic_a, b"key-1").await.unwrap();
+        store
+            .record(&topic_a, INSTANCE, b"key-1", &published())
+            .await
+            .unwrap();
+
+        assert!(
+            store
+                .find_published(&topic_a, INSTANCE, b"key-1")
+                .await
+                .unwrap()
+                .is_some()
+        );
+        assert!(
+            store
+                .find_published(&topic_b, INSTANCE, b"key-1")
+                .await
+                .unwrap()
+                .is_none()
+        );
+    }
+
+    // 7. A duplicate returns the ack of the original publish.
+    #[tokio::test]
+    async fn test_duplicate_returns_original_ack() {
+        let store = test_store().await;
+        store
+            .record(&topic("app"), INSTANCE, b"key-1", &published())
+            .await
+            .unwrap();
+
+        let ack = store
+            .find_published(&topic("app"), INSTANCE, b"key-1")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(ack.operation_id, vec![7u8; 32]);
+        assert_eq!(ack.seq_num, 3);
+        assert!(ack.duplicate);
+    }
+
+    // 8. Tables from before keys were scoped by instance keep their keys, which
+    // then match any instance.
+    #[tokio::test]
+    async fn test_old_table_is_migrated() {
+        let db = SqlitePool::connect("sqlite::memory:").await.unwrap();
+        sqlx::query(
+            "CREATE TABLE publish_idempotency_keys (
+                topic BLOB NOT NULL, key BLOB NOT NULL, seen_at INTEGER NOT NULL,
+                PRIMARY KEY (topic, key)
+            );
+            CREATE INDEX idx_pik_seen_at ON publish_idempotency_keys(seen_at);",
+        )
+        .execute(&db)
+        .await
+        .unwrap();
+        sqlx::query("INSERT INTO publish_idempotency_keys (topic, key, seen_at) VALUES (?, ?, ?)")
+            .bind(topic("app").p2panda_topic().to_bytes().to_vec())
+            .bind(b"old-key".as_slice())
+            .bind(now_secs())
+            .execute(&db)
+            .await
+            .unwrap();
+
+        SqliteIdempotencyStore::setup_table(&db).await.unwrap();
+        SqliteIdempotencyStore::setup_table(&db).await.unwrap();
+        let store = SqliteIdempotencyStore { db };
+
+        for instance_id in ["instance-1", "instance-2"] {
+            let ack = store
+                .find_published(&topic("app"), instance_id, b"old-key")
+                .await
+                .unwrap()
+                .unwrap();
+            assert!(ack.duplicate);
+            assert!(ack.operation_id.is_empty());
+        }
+        assert!(
+            store
+                .find_published(&topic("app-b"), INSTANCE, b"old-key")
+                .await
+                .unwrap()
+                .is_none()
+        );
 
-        assert!(store.is_duplicate(&topic_a, b"key-1").await.unwrap());
-        assert!(!store.is_duplicate(&topic_b, b"key-1").await.unwrap());
+        // New keys are recorded per instance alongside the old ones.
+        store
+            .record(&topic("app"), INSTANCE, b"key-1", &published())
+            .await
+            .unwrap();
+        let ack = store
+            .find_published(&topic("app"), INSTANCE, b"key-1")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(ack.operation_id, vec![7u8; 32]);
+    }
+
+    // 9. Keys are scoped by instance: another instance of the app may reuse a key.
+    #[tokio::test]
+    async fn test_keys_are_scoped_by_instance() {
+        let store = test_store().await;
+        store
+            .record(&topic("app"), "instance-1", b"key-1", &published())
+            .await
+            .unwrap();
+
+        let other = store
+            .find_published(&topic("app"), "instance-2", b"key-1")
+            .await
+            .unwrap();
+        assert!(other.is_none());
+    }
+
+    // 10. The in-memory store behaves the same as the SQLite one.
+    #[tokio::test]
+    async fn test_memory_store_returns_original_ack_per_instance() {
+        let store = test_memory_store();
+        assert!(
+            store
+                .find_published(&topic("app"), INSTANCE, b"")
+                .await
+                .unwrap()
+                .is_none()
+        );
+
+        store
+            .record(&topic("app"), INSTANCE, b"key-1", &published())
+            .await
+            .unwrap();
+
+        let ack = store
+            .find_published(&topic("app"), INSTANCE, b"key-1")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(ack.operation_id, vec![7u8; 32]);
+        assert!(ack.duplicate);
+        assert!(
+            store
+                .find_published(&topic("app-b"), INSTANCE, b"key-1")
+                .await
+                .unwrap()
+                .is_none()
+        );
+        assert!(
+            store
+                .find_published(&topic("app"), "instance-2", b"key-1")
+                .await
+                .unwrap()
+                .is_none()
+        );
     }
 }
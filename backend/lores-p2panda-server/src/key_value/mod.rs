@@ -0,0 +1,363 @@
+use std::collections::HashMap;
+use std::pin::Pin;
+use std::sync::Arc;
+
+use lores_p2panda::{IncomingOperation, PandaNode, RegionAppKeyValueTopic, RegionTopic, Topic};
+use prost::Message;
+use sqlx::SqlitePool;
+use tokio::sync::{Mutex, broadcast, mpsc};
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::service::interceptor::InterceptedService;
+use tonic::{Request, Response, Status};
+use tracing::{info, warn};
+
+mod store;
+use store::{KeyValueStore, KeyValueWrite};
+
+use crate::app_auth::{AppToken, AppTokenInterceptor};
+use crate::proto::{
+    DeleteRequest, GetRequest, GetResponse, KeyValueEntry, KeyValueOperation,
+    KeyValueWriteResponse, PutRequest, Tombstone, WatchPrefixRequest,
+    key_value_operation::Action,
+    key_value_server::{KeyValue, KeyValueServer},
+};
+use crate::replay::lagged_status;
+use crate::topic_subscriptions::{TopicGuard, TopicSubscriptions};
+use crate::{
+    AppInstanceIds, AuthenticateApp, PandaService, QuotaTracker, ResolveRegionId, check_app_token,
+    publish_error_to_status, resolve_region_error_to_status, subscription_error_to_status,
+};
+
+/// gRPC service giving apps a last-writer-wins key-value store per region.
+///
+/// Writes travel as operations on a [`RegionAppKeyValueTopic`]. Once an app
+/// has used the store, this node stays subscribed to its topic and applies
+/// every write it sees to a local table, so reads never have to replay the
+/// topic.
+pub struct KeyValueService {
+    node: Arc<Mutex<Option<Arc<PandaNode>>>>,
+    subscriptions: TopicSubscriptions,
+    resolve_region_id: ResolveRegionId,
+    authenticate_app: AuthenticateApp,
+    store: Arc<KeyValueStore>,
+    quota: QuotaTracker,
+    /// Topics being materialised, kept subscribed for the life of the service.
+    materialised: Mutex<HashMap<Topic, TopicGuard>>,
+    buffer_size: usize,
+}
+
+impl KeyValueService {
+    /// Shares the node, subscriptions and app authentication of `panda`.
+    pub async fn new(panda: &PandaService, db: SqlitePool) -> Result<Self, sqlx::Error> {
+        let buffer_size = panda.subscription_config.buffer_size;
+        Ok(Self {
+            node: panda.node.clone(),
+            subscriptions: panda.subscriptions.clone(),
+            resolve_region_id: panda.resolve_region_id.clone(),
+            authenticate_app: panda.authenticate_app.clone(),
+            store: Arc::new(KeyValueStore::new(db, buffer_size).await?),
+            quota: panda.quota.clone(),
+            materialised: Mutex::new(HashMap::new()),
+            buffer_size,
+        })
+    }
+
+    /// Wraps the service in a server that requires every call to carry the
+    /// app's bearer token.
+    pub fn into_server(self) -> InterceptedService<KeyValueServer<Self>, AppTokenInterceptor> {
+        KeyValueServer::with_interceptor(self, AppTokenInterceptor)
+    }
+
+    /// Authenticates the caller and returns the node along with the app's
+    /// key-value topic, which is materialised from here on.
+    async fn open_topic(
+        &self,
+        ids: &AppInstanceIds,
+        token: Option<AppToken>,
+    ) -> Result<(Arc<PandaNode>, RegionAppKeyValueTopic), Status> {
+        check_app_token(&self.authenticate_app, ids, token).await?;
+
+        let region_id = (self.resolve_region_id)(ids.clone())
+            .await
+            .map_err(|e| resolve_region_error_to_status(e, ids))?;
+
+        let node_lock = self.node.lock().await;
+        let node = node_lock
+            .as_ref()
+            .ok_or_else(|| Status::unavailable("p2panda node is not yet started"))?
+            .clone();
+        drop(node_lock);
+
+        let topic = RegionAppKeyValueTopic::new(region_id, ids.app_id.clone());
+        self.ensure_materialised(&node, &topic).await?;
+        Ok((node, topic))
+    }
+
+    /// The first time a topic is used, subscribes to it, applies writes as
+    /// they arrive, and replays the writes already stored.
+    async fn ensure_materialised(
+        &self,
+        node: &Arc<PandaNode>,
+        topic: &RegionAppKeyValueTopic,
+    ) -> Result<(), Status> {
+        let mut materialised = self.materialised.lock().await;
+        let p2panda_topic = topic.p2panda_topic();
+        if materialised.contains_key(&p2panda_topic) {
+            return Ok(());
+        }
+
+        let (mut live_rx, guard) = self.subscriptions.acquire(node, topic).await?;
+        let (replay_tx, mut replay_rx) = mpsc::channel::<IncomingOperation>(self.buffer_size);
+        node.replay_topic(p2panda_topic, replay_tx)
+            .await
+            .map_err(subscription_error_to_status)?;
+
+        info!(
+            "[key_value] materialising region={} app_id={}",
+            topic.region_id, topic.app_id
+        );
+
+        let store = self.store.clone();
+        tokio::spawn(async move {
+            while let Some(op) = replay_rx.recv().await {
+                apply_operation(&store, op).await;
+            }
+        });
+
+        let store = self.store.clone();
+        let replay_node = node.clone();
+        let buffer_size = self.buffer_size;
+        tokio::spawn(async move {
+            loop {
+                match live_rx.recv().await {
+                    Ok(op) => apply_operation(&store, op).await,
+                    Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                        // Writes apply in any order, so replaying the topic
+                        // again catches up on the ones that were dropped.
+                        warn!("[key_value] lagged {skipped} writes, replaying topic");
+                        let (replay_tx, mut replay_rx) = mpsc::channel(buffer_size);
+                        if let Err(e) = replay_node.replay_topic(p2panda_topic, replay_tx).await {
+                            warn!("[key_value] replay failed: {e}");
+                            continue;
+                        }
+                        while let Some(op) = replay_rx.recv().await {
+                            apply_operation(&store, op).await;
+                        }
+                    }
+                    Err(broadcast::error::RecvError::Closed) => break,
+                }
+            }
+        });
+
+        materialised.insert(p2panda_topic, guard);
+        Ok(())
+    }
+
+    /// Publishes a write and applies it locally without waiting for it to come
+    /// back from the node.
+    async fn write(
+        &self,
+        ids: AppInstanceIds,
+        token: Option<AppToken>,
+        key: String,
+        action: Action,
+    ) -> Result<Response<KeyValueWriteResponse>, Status> {
+        if key.is_empty() {
+            return Err(Status::invalid_argument("key must not be empty"));
+        }
+
+        let (node, topic) = self.open_topic(&ids, token).await?;
+
+        let value = match &action {
+            Action::Put(value) => Some(value.clone()),
+            Action::Delete(_) => None,
+        };
+        let payload = KeyValueOperation {
+            key: key.clone(),
+            action: Some(action),
+        }
+        .encode_to_vec();
+        self.quota.consume(&ids, &[payload.len()]).await?;
+
+        let published = node
+            .publish_to_region_topic(&topic, payload)
+            .await
+            .map_err(publish_error_to_status)?;
+
+        let write = KeyValueWrite {
+            key,
+            value,
+            timestamp: published.timestamp,
+            author: node.public_key.as_bytes().to_vec(),
+            operation_id: published.operation_id.as_bytes().to_vec(),
+        };
+        self.store
+            .apply(topic.p2panda_topic(), write)
+            .await
+            .map_err(store_error_to_status)?;
+
+        Ok(Response::new(KeyValueWriteResponse {
+            operation_id: published.operation_id.as_bytes().to_vec(),
+            timestamp: published.timestamp,
+        }))
+    }
+}
+
+#[tonic::async_trait]
+impl KeyValue for KeyValueService {
+    async fn put(
+        &self,
+        request: Request<PutRequest>,
+    ) -> Result<Response<KeyValueWriteResponse>, Status> {
+        let token = request.extensions().get::<AppToken>().cloned();
+        let req = request.into_inner();
+
+        let ids = AppInstanceIds {
+            app_id: req.app_id,
+            instance_id: req.instance_id,
+        };
+        self.write(ids, token, req.key, Action::Put(req.value))
+            .await
+    }
+
+    async fn delete(
+        &self,
+        request: Request<DeleteRequest>,
+    ) -> Result<Response<KeyValueWriteResponse>, Status> {
+        let token = request.extensions().get::<AppToken>().cloned();
+        let req = request.into_inner();
+
+        let ids = AppInstanceIds {
+            app_id: req.app_id,
+            instance_id: req.instance_id,
+        };
+        self.write(ids, token, req.key, Action::Delete(Tombstone {}))
+            .await
+    }
+
+    async fn get(&self, request: Request<GetRequest>) -> Result<Response<GetResponse>, Status> {
+        let token = request.extensions().get::<AppToken>().cloned();
+        let req = request.into_inner();
+
+        let ids = AppInstanceIds {
+            app_id: req.app_id,
+            instance_id: req.instance_id,
+        };
+        let (_node, topic) = self.open_topic(&ids, token).await?;
+
+        let entry = self
+            .store
+            .get(topic.p2panda_topic(), &req.key)
+            .await
+            .map_err(store_error_to_status)?;
+
+        Ok(Response::new(GetResponse {
+            entry: entry.map(write_to_entry),
+        }))
+    }
+
+    type WatchPrefixStream =
+        Pin<Box<dyn tokio_stream::Stream<Item = Result<KeyValueEntry, Status>> + Send + 'static>>;
+
+    async fn watch_prefix(
+        &self,
+        request: Request<WatchPrefixRequest>,
+    ) -> Result<Response<Self::WatchPrefixStream>, Status> {
+        let token = request.extensions().get::<AppToken>().cloned();
+        let req = request.into_inner();
+
+        let ids = AppInstanceIds {
+            app_id: req.app_id,
+            instance_id: req.instance_id,
+        };
+        let (_node, topic) = self.open_topic(&ids, token).await?;
+        let p2panda_topic = topic.p2panda_topic();
+
+        // Listen for changes before reading the snapshot so none fall between
+        // the two.
+        let mut changes = self.store.changes();
+        let snapshot = self
+            .store
+            .list_prefix(p2panda_topic, &req.prefix)
+            .await
+            .map_err(store_error_to_status)?;
+
+        let (out_tx, out_rx) = mpsc::channel(self.buffer_size);
+        let prefix = req.prefix;
+        tokio::spawn(async move {
+            for write in snapshot {
+                if out_tx.send(Ok(write_to_entry(write))).await.is_err() {
+                    return;
+                }
+            }
+            loop {
+                let result = tokio::select! {
+                    _ = out_tx.closed() => return,
+                    result = changes.recv() => result,
+                };
+                match result {
+                    Ok(change) => {
+                        if change.topic != p2panda_topic || !change.write.key.starts_with(&prefix) {
+                            continue;
+                        }
+                        if out_tx.send(Ok(write_to_entry(change.write))).await.is_err() {
+                            return;
+                        }
+                    }
+                    Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                        let _ = out_tx.send(Err(lagged_status(skipped))).await;
+                        return;
+                    }
+                    Err(broadcast::error::RecvError::Closed) => return,
+                }
+            }
+        });
+
+        Ok(Response::new(Box::pin(ReceiverStream::new(out_rx))))
+    }
+}
+
+/// Decodes a write received over p2panda and applies it. Operations that
+/// aren't key-value writes are skipped.
+async fn apply_operation(store: &KeyValueStore, op: IncomingOperation) {
+    let operation = match KeyValueOperation::decode(op.bytes.as_slice()) {
+        Ok(operation) => operation,
+        Err(e) => {
+            warn!("[key_value] skipping undecodable operation: {e}");
+            return;
+        }
+    };
+    let value = match operation.action {
+        Some(Action::Put(value)) => Some(value),
+        Some(Action::Delete(_)) => None,
+        None => {
+            warn!("[key_value] skipping operation without an action");
+            return;
+        }
+    };
+    let write = KeyValueWrite {
+        key: operation.key,
+        value,
+        timestamp: op.received_timestamp,
+        author: op.author.as_bytes().to_vec(),
+        operation_id: op.operation_id.as_bytes().to_vec(),
+    };
+    if let Err(e) = store.apply(op.topic, write).await {
+        warn!("[key_value] failed to apply write: {e}");
+    }
+}
+
+fn write_to_entry(write: KeyValueWrite) -> KeyValueEntry {
+    KeyValueEntry {
+        key: write.key,
+        value: write.value,
+        timestamp: write.timestamp,
+        author: write.author,
+        operation_id: write.operation_id,
+    }
+}
+
+fn store_error_to_status(e: sqlx::Error) -> Status {
+    warn!("key-value store error: {e}");
+    Status::internal("Failed to access the key-value store")
+}
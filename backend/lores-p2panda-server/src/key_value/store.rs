@@ -0,0 +1,241 @@
+use lores_p2panda::Topic;
+use sqlx::SqlitePool;
+use tokio::sync::broadcast;
+
+/// The winning write for a key, or its tombstone when `value` is `None`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct KeyValueWrite {
+    pub key: String,
+    pub value: Option<Vec<u8>>,
+    pub timestamp: u64,
+    pub author: Vec<u8>,
+    pub operation_id: Vec<u8>,
+}
+
+/// A write that changed the materialised state of a topic.
+#[derive(Debug, Clone)]
+pub struct KeyValueChange {
+    pub topic: Topic,
+    pub write: KeyValueWrite,
+}
+
+/// Materialised last-writer-wins state of every key-value topic this node has
+/// seen. Writes can be applied in any order, any number of times, and the
+/// table ends up the same.
+pub struct KeyValueStore {
+    db: SqlitePool,
+    changes: broadcast::Sender<KeyValueChange>,
+}
+
+type WriteRow = (String, Option<Vec<u8>>, i64, Vec<u8>, Vec<u8>);
+
+impl KeyValueStore {
+    pub async fn new(db: SqlitePool, buffer_size: usize) -> Result<Self, sqlx::Error> {
+        Self::setup_table(&db).await?;
+        let (changes, _) = broadcast::channel(buffer_size);
+        Ok(Self { db, changes })
+    }
+
+    /// Applies `write` if it wins over the stored write for its key. Returns
+    /// whether the state changed.
+    pub async fn apply(&self, topic: Topic, write: KeyValueWrite) -> Result<bool, sqlx::Error> {
+        // The row value comparison orders writes by timestamp, then author,
+        // then operation id, so every node picks the same winner.
+        let result = sqlx::query(
+            "INSERT INTO app_key_values (topic, key, value, timestamp, author, operation_id)
+             VALUES (?, ?, ?, ?, ?, ?)
+             ON CONFLICT (topic, key) DO UPDATE SET
+                value = excluded.value,
+                timestamp = excluded.timestamp,
+                author = excluded.author,
+                operation_id = excluded.operation_id
+             WHERE (excluded.timestamp, excluded.author, excluded.operation_id)
+                 > (app_key_values.timestamp, app_key_values.author, app_key_values.operation_id)",
+        )
+        .bind(topic.to_bytes().to_vec())
+        .bind(&write.key)
+        .bind(&write.value)
+        .bind(write.timestamp as i64)
+        .bind(&write.author)
+        .bind(&write.operation_id)
+        .execute(&self.db)
+        .await?;
+
+        let changed = result.rows_affected() > 0;
+        if changed {
+            let _ = self.changes.send(KeyValueChange { topic, write });
+        }
+        Ok(changed)
+    }
+
+    /// Returns the current value of `key`, or `None` if it was never set or
+    /// has been deleted.
+    pub async fn get(&self, topic: Topic, key: &str) -> Result<Option<KeyValueWrite>, sqlx::Error> {
+        let row: Option<WriteRow> = sqlx::query_as(
+            "SELECT key, value, timestamp, author, operation_id FROM app_key_values
+             WHERE topic = ? AND key = ? AND value IS NOT NULL",
+        )
+        .bind(topic.to_bytes().to_vec())
+        .bind(key)
+        .fetch_optional(&self.db)
+        .await?;
+
+        Ok(row.map(write_from_row))
+    }
+
+    /// Returns every live entry whose key starts with `prefix`, ordered by key.
+    pub async fn list_prefix(
+        &self,
+        topic: Topic,
+        prefix: &str,
+    ) -> Result<Vec<KeyValueWrite>, sqlx::Error> {
+        let rows: Vec<WriteRow> = sqlx::query_as(
+            "SELECT key, value, timestamp, author, operation_id FROM app_key_values
+             WHERE topic = ? AND substr(key, 1, length(?2)) = ?2 AND value IS NOT NULL
+             ORDER BY key",
+        )
+        .bind(topic.to_bytes().to_vec())
+        .bind(prefix)
+        .fetch_all(&self.db)
+        .await?;
+
+        Ok(rows.into_iter().map(write_from_row).collect())
+    }
+
+    /// Changes applied from now on, across all topics.
+    pub fn changes(&self) -> broadcast::Receiver<KeyValueChange> {
+        self.changes.subscribe()
+    }
+
+    async fn setup_table(db: &SqlitePool) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS app_key_values (
+                topic        BLOB    NOT NULL,
+                key          TEXT    NOT NULL,
+                value        BLOB,
+                timestamp    INTEGER NOT NULL,
+                author       BLOB    NOT NULL,
+                operation_id BLOB    NOT NULL,
+                PRIMARY KEY (topic, key)
+            );",
+        )
+        .execute(db)
+        .await?;
+        Ok(())
+    }
+}
+
+fn write_from_row((key, value, timestamp, author, operation_id): WriteRow) -> KeyValueWrite {
+    KeyValueWrite {
+        key,
+        value,
+        timestamp: timestamp as u64,
+        author,
+        operation_id,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn test_store() -> KeyValueStore {
+        let db = SqlitePool::connect("sqlite::memory:").await.unwrap();
+        KeyValueStore::new(db, 16).await.unwrap()
+    }
+
+    fn topic() -> Topic {
+        Topic::from([1u8; 32])
+    }
+
+    fn write(key: &str, value: Option<&[u8]>, timestamp: u64, author: u8) -> KeyValueWrite {
+        KeyValueWrite {
+            key: key.to_string(),
+            value: value.map(|value| value.to_vec()),
+            timestamp,
+            author: vec![author; 32],
+            operation_id: vec![timestamp as u8; 32],
+        }
+    }
+
+    #[tokio::test]
+    async fn test_later_write_wins_in_any_order() {
+        let store = test_store().await;
+        let older = write("colour", Some(b"red"), 10, 1);
+        let newer = write("colour", Some(b"blue"), 20, 1);
+
+        assert!(store.apply(topic(), newer.clone()).await.unwrap());
+        assert!(!store.apply(topic(), older).await.unwrap());
+
+        let current = store.get(topic(), "colour").await.unwrap();
+        assert_eq!(current, Some(newer));
+    }
+
+    #[tokio::test]
+    async fn test_same_timestamp_goes_to_greater_author() {
+        let store = test_store().await;
+        let low = write("colour", Some(b"red"), 10, 1);
+        let high = write("colour", Some(b"blue"), 10, 2);
+
+        store.apply(topic(), high.clone()).await.unwrap();
+        store.apply(topic(), low).await.unwrap();
+
+        let current = store.get(topic(), "colour").await.unwrap();
+        assert_eq!(current, Some(high));
+    }
+
+    #[tokio::test]
+    async fn test_reapplying_a_write_changes_nothing() {
+        let store = test_store().await;
+        let put = write("colour", Some(b"red"), 10, 1);
+
+        assert!(store.apply(topic(), put.clone()).await.unwrap());
+        assert!(!store.apply(topic(), put).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_delete_hides_key_and_beats_older_put() {
+        let store = test_store().await;
+        store
+            .apply(topic(), write("colour", None, 20, 1))
+            .await
+            .unwrap();
+        store
+            .apply(topic(), write("colour", Some(b"red"), 10, 2))
+            .await
+            .unwrap();
+
+        assert_eq!(store.get(topic(), "colour").await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_list_prefix_is_scoped_by_topic() {
+        let store = test_store().await;
+        let other_topic = Topic::from([2u8; 32]);
+        store
+            .apply(topic(), write("user/1", Some(b"a"), 1, 1))
+            .await
+            .unwrap();
+        store
+            .apply(topic(), write("user/2", Some(b"b"), 2, 1))
+            .await
+            .unwrap();
+        store
+            .apply(topic(), write("group/1", Some(b"c"), 3, 1))
+            .await
+            .unwrap();
+        store
+            .apply(other_topic, write("user/3", Some(b"d"), 4, 1))
+            .await
+            .unwrap();
+
+        let keys: Vec<String> = store
+            .list_prefix(topic(), "user/")
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|entry| entry.key)
+            .collect();
+        assert_eq!(keys, vec!["user/1", "user/2"]);
+    }
+}
@@ -1,49 +1,175 @@
-use std::collections::HashMap;
+use std::collections::HashSet;
 use std::future::Future;
 use std::pin::Pin;
 use std::sync::Arc;
 use tracing::{info, warn};
 
 use lores_p2panda::{
-    IncomingOperation, PandaNode, PandaPublishError, RegionAppTopic, RegionId, RegionTopic,
-    SubscriptionError, Topic,
+    EphemeralMessage, IncomingOperation, OperationHistoryError, OperationQuery, PandaNode,
+    PandaPublishError, PublishedOperation, RegionAppTopic, RegionId, RegionTopic,
+    SubscriptionError,
 };
 use sqlx::SqlitePool;
-use std::time::Duration;
-use tokio::sync::{Mutex, RwLock, broadcast};
-use tokio_stream::StreamExt;
-use tokio_stream::wrappers::BroadcastStream;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::sync::{Mutex, broadcast};
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::metadata::MetadataValue;
+use tonic::service::interceptor::InterceptedService;
 use tonic::{Request, Response, Status};
 
+mod app_auth;
+use app_auth::AppToken;
+pub use app_auth::AppTokenInterceptor;
+
+mod history;
+
 mod idempotency_store;
-use idempotency_store::IdempotencyStore;
+pub use idempotency_store::{IdempotencyStore, MemoryIdempotencyStore, SqliteIdempotencyStore};
+
+mod key_value;
+pub use key_value::KeyValueService;
 
 mod instance_notifier;
 use instance_notifier::InstanceNotifier;
 
+mod quota;
+pub use quota::{QuotaTracker, QuotaUsage};
+
+mod rate_limit;
+use rate_limit::RateLimiter;
+
+mod replay;
+use replay::{ReplayFrom, forward_arrived_then_live, forward_live, forward_replay_then_live};
+
+mod schema_registry;
+use schema_registry::SchemaRegistry;
+
+mod topic_subscriptions;
+use topic_subscriptions::{Ephemeral, TopicSubscriptions};
+
+#[cfg(unix)]
+mod unix_socket;
+#[cfg(unix)]
+pub use unix_socket::bind_unix_socket;
+
+/// Largest number of items accepted in one `PublishBatch` call.
+const MAX_PUBLISH_BATCH_SIZE: usize = 1000;
+
 /// Configuration for the publish idempotency deduplication store.
 pub struct IdempotencyConfig {
-    /// How often the cleanup task runs to remove expired keys.
+    /// Where idempotency keys are kept.
+    pub backend: IdempotencyBackend,
+    /// How often the cleanup task runs to remove expired keys. Not used by
+    /// custom backends.
     pub cleanup_frequency: Duration,
-    /// How long keys are retained before being eligible for cleanup.
+    /// How long keys are retained before being eligible for cleanup. Not used
+    /// by custom backends.
     pub retention: Duration,
 }
 
 impl Default for IdempotencyConfig {
     fn default() -> Self {
         Self {
+            backend: IdempotencyBackend::Sqlite,
             cleanup_frequency: Duration::from_hours(12),
             retention: Duration::from_hours(48),
         }
     }
 }
 
+/// Where the publish idempotency keys are kept.
+pub enum IdempotencyBackend {
+    /// The `publish_idempotency_keys` table in the service's database.
+    Sqlite,
+    /// In memory, forgotten on restart.
+    Memory,
+    /// Any other [`IdempotencyStore`].
+    Custom(Arc<dyn IdempotencyStore>),
+}
+
+/// Configuration for gRPC subscriptions.
+pub struct SubscriptionConfig {
+    /// How many operations are buffered per topic, and per subscriber, before
+    /// a subscriber that isn't keeping up is considered lagged. Lagged
+    /// subscribers receive a `DATA_LOSS` status and can resume with
+    /// `after_operation_id`.
+    pub buffer_size: usize,
+    /// How long a topic is kept subscribed after its last gRPC subscriber
+    /// disconnects before the node leaves it.
+    pub idle_timeout: Duration,
+}
+
+impl Default for SubscriptionConfig {
+    fn default() -> Self {
+        Self {
+            buffer_size: 128,
+            idle_timeout: Duration::from_secs(60),
+        }
+    }
+}
+
+/// Limits on ephemeral messages sent with the `Broadcast` RPC.
+pub struct EphemeralConfig {
+    /// Largest payload accepted, in bytes.
+    pub max_payload_bytes: usize,
+    /// Sustained messages per second allowed for each app instance.
+    pub messages_per_second: u32,
+    /// Messages an app instance may send at once before being rate limited.
+    pub burst: u32,
+}
+
+impl Default for EphemeralConfig {
+    fn default() -> Self {
+        Self {
+            max_payload_bytes: 16 * 1024,
+            messages_per_second: 10,
+            burst: 20,
+        }
+    }
+}
+
+/// Settings for [`PandaService::new`]. Start from the default and override
+/// what needs changing.
+#[derive(Default)]
+pub struct PandaServiceConfig {
+    pub idempotency: IdempotencyConfig,
+    pub subscription: SubscriptionConfig,
+    pub ephemeral: EphemeralConfig,
+    pub quota: QuotaConfig,
+}
+
+/// Quotas on what each app instance may publish, through `Publish`,
+/// `PublishBatch` or key-value writes. Publishes over a quota receive a
+/// `RESOURCE_EXHAUSTED` status.
+#[derive(Debug, Clone)]
+pub struct QuotaConfig {
+    /// Largest payload accepted, in bytes.
+    pub max_payload_bytes: usize,
+    /// Operations an app instance may publish per minute.
+    pub ops_per_minute: u32,
+    /// Payload bytes an app instance may publish per day (UTC).
+    pub bytes_per_day: u64,
+}
+
+impl Default for QuotaConfig {
+    fn default() -> Self {
+        Self {
+            max_payload_bytes: 64 * 1024,
+            ops_per_minute: 600,
+            bytes_per_day: 256 * 1024 * 1024,
+        }
+    }
+}
+
 pub mod proto {
     tonic::include_proto!("lores.panda.v2");
 }
 
 use proto::{
-    OperationEvent, PublishRequest, PublishResponse, SubscribeRequest,
+    BroadcastRequest, BroadcastResponse, EphemeralEvent, GetOperationRequest, GetOperationResponse,
+    ListOperationsRequest, ListOperationsResponse, ListPeersRequest, ListPeersResponse,
+    OperationEvent, PublishBatchRequest, PublishBatchResponse, PublishRequest, PublishResponse,
+    RegisterSchemaRequest, RegisterSchemaResponse, SubscribeEphemeralRequest, SubscribeRequest,
     panda_server::{Panda, PandaServer},
 };
 
@@ -74,79 +200,191 @@ pub type ResolveRegionId = Arc<
         + Sync,
 >;
 
+/// Error returned by the [`AuthenticateApp`] callback.
+#[derive(Debug)]
+pub enum AuthenticateAppError {
+    /// The token does not match the one issued for this app/instance.
+    InvalidToken,
+    /// The token could not be checked, e.g. a database error.
+    Internal,
+}
+
+/// Async callback type for checking the bearer token an app presents against
+/// the one issued for its [`AppInstanceIds`]. The owner (e.g.
+/// `lores-node-axum`) supplies this when constructing [`PandaService`].
+pub type AuthenticateApp = Arc<
+    dyn Fn(
+            AppInstanceIds,
+            String,
+        ) -> Pin<Box<dyn Future<Output = Result<(), AuthenticateAppError>> + Send>>
+        + Send
+        + Sync,
+>;
+
+/// An installation of an app on a node in the caller's region.
+#[derive(Debug, Clone)]
+pub struct AppPeer {
+    pub node_id: String,
+    pub node_name: Option<String>,
+    pub version: String,
+    pub internet_url: Option<String>,
+    pub local_network_url: Option<String>,
+}
+
+/// Error returned by the [`ListAppPeers`] callback.
+#[derive(Debug)]
+pub enum ListAppPeersError {
+    /// The peers could not be read, e.g. a database error.
+    Internal,
+}
+
+/// Async callback type for listing the [`AppPeer`]s of an app within a
+/// region. The owner (e.g. `lores-node-axum`) supplies this when constructing
+/// [`PandaService`].
+pub type ListAppPeers = Arc<
+    dyn Fn(
+            RegionId,
+            String,
+        ) -> Pin<Box<dyn Future<Output = Result<Vec<AppPeer>, ListAppPeersError>> + Send>>
+        + Send
+        + Sync,
+>;
+
 /// gRPC service that exposes [`PandaNode`] publish and subscribe over the
 /// network.
 ///
 /// A single p2panda network subscription is maintained per topic.  Multiple
 /// gRPC subscribers to the same topic share that subscription via a broadcast
 /// channel, so the underlying p2panda node only sees one subscriber per topic
-/// regardless of how many gRPC clients are connected.
+/// regardless of how many gRPC clients are connected. The node leaves the topic
+/// again once no subscribers are left.
 pub struct PandaService {
     node: Arc<Mutex<Option<Arc<PandaNode>>>>,
-    /// One broadcast sender per subscribed topic.  Shared across all gRPC
-    /// connections so the p2panda-level subscription is created only once.
-    subscriptions: Arc<RwLock<HashMap<Topic, broadcast::Sender<IncomingOperation>>>>,
-    idempotency: IdempotencyStore,
+    /// One reference-counted broadcast channel per subscribed topic.  Shared
+    /// across all gRPC connections so the p2panda-level subscription is
+    /// created only once.
+    subscriptions: TopicSubscriptions,
+    /// The same for the topics' ephemeral gossip channels.
+    ephemeral_subscriptions: TopicSubscriptions<Ephemeral>,
+    ephemeral_limiter: RateLimiter,
+    max_ephemeral_payload_bytes: usize,
+    idempotency: Arc<dyn IdempotencyStore>,
+    quota: QuotaTracker,
+    schemas: SchemaRegistry,
     instance_notifier: InstanceNotifier,
     resolve_region_id: ResolveRegionId,
+    list_app_peers: ListAppPeers,
+    authenticate_app: AuthenticateApp,
+    subscription_config: SubscriptionConfig,
 }
 
 impl PandaService {
     pub async fn new(
         node: Arc<Mutex<Option<Arc<PandaNode>>>>,
         db: SqlitePool,
-        idempotency_config: Option<IdempotencyConfig>,
+        config: PandaServiceConfig,
         on_instance_seen: Arc<dyn Fn(String, String) + Send + Sync>,
         resolve_region_id: ResolveRegionId,
+        list_app_peers: ListAppPeers,
+        authenticate_app: AuthenticateApp,
     ) -> Result<Self, sqlx::Error> {
-        let config = idempotency_config.unwrap_or_default();
-        let idempotency =
-            IdempotencyStore::new(db, config.cleanup_frequency, config.retention).await?;
+        let PandaServiceConfig {
+            idempotency: idempotency_config,
+            subscription: subscription_config,
+            ephemeral: ephemeral_config,
+            quota: quota_config,
+        } = config;
+        let idempotency: Arc<dyn IdempotencyStore> = match idempotency_config.backend {
+            IdempotencyBackend::Sqlite => Arc::new(
+                SqliteIdempotencyStore::new(
+                    db.clone(),
+                    idempotency_config.cleanup_frequency,
+                    idempotency_config.retention,
+                )
+                .await?,
+            ),
+            IdempotencyBackend::Memory => Arc::new(MemoryIdempotencyStore::new(
+                idempotency_config.cleanup_frequency,
+                idempotency_config.retention,
+            )),
+            IdempotencyBackend::Custom(store) => store,
+        };
+        let quota = QuotaTracker::new(db.clone(), quota_config).await?;
+        let schemas = SchemaRegistry::new(db).await?;
         Ok(Self {
             node,
-            subscriptions: Arc::new(RwLock::new(HashMap::new())),
+            subscriptions: TopicSubscriptions::new(
+                subscription_config.buffer_size,
+                subscription_config.idle_timeout,
+            ),
+            ephemeral_subscriptions: TopicSubscriptions::new(
+                subscription_config.buffer_size,
+                subscription_config.idle_timeout,
+            ),
+            ephemeral_limiter: RateLimiter::new(
+                ephemeral_config.messages_per_second,
+                ephemeral_config.burst,
+            ),
+            max_ephemeral_payload_bytes: ephemeral_config.max_payload_bytes,
             idempotency,
+            quota,
+            schemas,
             instance_notifier: InstanceNotifier::new(on_instance_seen),
             resolve_region_id,
+            list_app_peers,
+            authenticate_app,
+            subscription_config,
         })
     }
 
-    pub fn into_server(self) -> PandaServer<Self> {
-        PandaServer::new(self)
+    /// Wraps the service in a server that requires every call to carry the
+    /// app's bearer token.
+    pub fn into_server(self) -> InterceptedService<PandaServer<Self>, AppTokenInterceptor> {
+        PandaServer::with_interceptor(self, AppTokenInterceptor)
     }
 
-    /// Returns a broadcast receiver for the topic derived from `region_id` and
-    /// `app_id`, creating the underlying p2panda subscription and
-    /// forwarding task the first time this topic is seen.
-    async fn ensure_broadcast_subscription(
-        &self,
-        node: &PandaNode,
-        region_app_topic: &RegionAppTopic,
-    ) -> Result<broadcast::Receiver<IncomingOperation>, Status> {
-        let topic = region_app_topic.p2panda_topic();
-        let mut subs = self.subscriptions.write().await;
-
-        if let Some(tx) = subs.get(&topic) {
-            return Ok(tx.subscribe());
-        }
+    /// The tracker counting publishes against each app instance's quotas,
+    /// for reporting usage.
+    pub fn quota_tracker(&self) -> QuotaTracker {
+        self.quota.clone()
+    }
 
-        let (broadcast_tx, broadcast_rx) = broadcast::channel::<IncomingOperation>(128);
-        let (incoming_tx, mut incoming_rx) = tokio::sync::mpsc::channel::<IncomingOperation>(128);
+    /// Checks the caller's token against the one issued for `ids`.
+    async fn authenticate(
+        &self,
+        ids: &AppInstanceIds,
+        token: Option<AppToken>,
+    ) -> Result<(), Status> {
+        check_app_token(&self.authenticate_app, ids, token).await
+    }
+}
 
-        node.subscribe_to_region_topic(region_app_topic, incoming_tx)
-            .await
-            .map_err(subscription_error_to_status)?;
+/// Checks `token` against the one issued for `ids`, for any of the services
+/// apps call.
+async fn check_app_token(
+    authenticate_app: &AuthenticateApp,
+    ids: &AppInstanceIds,
+    token: Option<AppToken>,
+) -> Result<(), Status> {
+    let token = token.ok_or_else(|| Status::unauthenticated("Missing app token"))?;
 
-        let fwd_tx = broadcast_tx.clone();
-        tokio::spawn(async move {
-            while let Some(op) = incoming_rx.recv().await {
-                let _ = fwd_tx.send(op);
+    authenticate_app(ids.clone(), token.0)
+        .await
+        .map_err(|e| match e {
+            AuthenticateAppError::InvalidToken => {
+                warn!(
+                    "rejected invalid app token for app '{}' instance '{}'",
+                    ids.app_id, ids.instance_id
+                );
+                Status::unauthenticated(format!(
+                    "Invalid app token for app '{}' instance '{}'. Use your lores-node installation to issue a new token for this app.",
+                    ids.app_id, ids.instance_id,
+                ))
             }
-        });
-
-        subs.insert(topic, broadcast_tx);
-        Ok(broadcast_rx)
-    }
+            AuthenticateAppError::Internal => {
+                Status::internal("Failed to check app token. This may be an internal server issue with lores-node.")
+            }
+        })
 }
 
 #[tonic::async_trait]
@@ -155,12 +393,14 @@ impl Panda for PandaService {
         &self,
         request: Request<PublishRequest>,
     ) -> Result<Response<PublishResponse>, Status> {
+        let token = request.extensions().get::<AppToken>().cloned();
         let req = request.into_inner();
 
         let ids = AppInstanceIds {
             app_id: req.app_id,
             instance_id: req.instance_id,
         };
+        self.authenticate(&ids, token).await?;
 
         let region_id = (self.resolve_region_id)(ids.clone())
             .await
@@ -173,7 +413,7 @@ impl Panda for PandaService {
             .clone();
         drop(node_lock);
 
-        let region_app_topic = RegionAppTopic::new(region_id, ids.app_id);
+        let region_app_topic = RegionAppTopic::new(region_id, ids.app_id.clone());
 
         info!(
             "[publish] region={} app_id={} payload_bytes={}",
@@ -183,37 +423,208 @@ impl Panda for PandaService {
         );
 
         // If the client supplied an idempotency key, return early on duplicate.
-        if self
+        if let Some(published) = self
             .idempotency
-            .is_duplicate(&region_app_topic, &req.idempotency_key)
+            .find_published(&region_app_topic, &ids.instance_id, &req.idempotency_key)
             .await?
         {
             info!("[publish] duplicate idempotency key, skipping re-insert");
-            return Ok(Response::new(PublishResponse {}));
+            return Ok(Response::new(published));
         }
 
-        // Ensure a subscription exists for this topic so the publisher is
-        // available. This is idempotent: if already subscribed the existing
-        // broadcast channel is reused.
-        let _rx = self
-            .ensure_broadcast_subscription(&node, &region_app_topic)
-            .await?;
+        self.schemas.validate(&ids.app_id, &req.payload).await?;
+
+        // Hold a subscription for this topic so the publisher is available.
+        // If already subscribed the existing broadcast channel is reused.
+        let (_rx, _topic_guard) = self.subscriptions.acquire(&node, &region_app_topic).await?;
+        self.quota.consume(&ids, &[req.payload.len()]).await?;
 
-        node.publish_to_region_topic(&region_app_topic, req.payload)
+        let payload_size = req.payload.len();
+        let published = match node
+            .publish_to_region_topic(&region_app_topic, req.payload)
             .await
-            .map_err(publish_error_to_status)?;
+        {
+            Ok(published) => published_to_response(published),
+            Err(e) => {
+                if !is_stored(&e) {
+                    self.quota.refund(&ids, &[payload_size]).await?;
+                }
+                return Err(publish_error_to_status(e));
+            }
+        };
 
         // Record the key only after a successful publish so a publish failure
         // does not burn the key — the client can safely retry.
         self.idempotency
-            .record(&region_app_topic, &req.idempotency_key)
+            .record(
+                &region_app_topic,
+                &ids.instance_id,
+                &req.idempotency_key,
+                &published,
+            )
             .await?;
 
         self.instance_notifier
             .notify(&region_app_topic.app_id, &ids.instance_id)
             .await;
 
-        Ok(Response::new(PublishResponse {}))
+        Ok(Response::new(published))
+    }
+
+    async fn publish_batch(
+        &self,
+        request: Request<PublishBatchRequest>,
+    ) -> Result<Response<PublishBatchResponse>, Status> {
+        let token = request.extensions().get::<AppToken>().cloned();
+        let req = request.into_inner();
+
+        let ids = AppInstanceIds {
+            app_id: req.app_id,
+            instance_id: req.instance_id,
+        };
+        self.authenticate(&ids, token).await?;
+
+        if req.items.is_empty() {
+            return Err(Status::invalid_argument("Batch has no items"));
+        }
+        if req.items.len() > MAX_PUBLISH_BATCH_SIZE {
+            return Err(Status::invalid_argument(format!(
+                "Batch has {} items, the limit is {MAX_PUBLISH_BATCH_SIZE}",
+                req.items.len()
+            )));
+        }
+        let mut keys = HashSet::new();
+        for item in &req.items {
+            if !item.idempotency_key.is_empty() && !keys.insert(&item.idempotency_key) {
+                return Err(Status::invalid_argument(
+                    "Idempotency keys must be unique within a batch",
+                ));
+            }
+        }
+
+        let region_id = (self.resolve_region_id)(ids.clone())
+            .await
+            .map_err(|e| resolve_region_error_to_status(e, &ids))?;
+
+        let node_lock = self.node.lock().await;
+        let node = node_lock
+            .as_ref()
+            .ok_or_else(|| Status::unavailable("p2panda node is not yet started"))?
+            .clone();
+        drop(node_lock);
+
+        let region_app_topic = RegionAppTopic::new(region_id, ids.app_id.clone());
+
+        info!(
+            "[publish_batch] region={} app_id={} items={}",
+            region_app_topic.region_id,
+            region_app_topic.app_id,
+            req.items.len()
+        );
+
+        // Items whose key was already used keep their original result; only
+        // the rest are published.
+        let mut results: Vec<Option<PublishResponse>> = Vec::with_capacity(req.items.len());
+        let mut to_publish = Vec::new();
+        for item in req.items {
+            let duplicate = self
+                .idempotency
+                .find_published(&region_app_topic, &ids.instance_id, &item.idempotency_key)
+                .await?;
+            if duplicate.is_none() {
+                to_publish.push((results.len(), item));
+            }
+            results.push(duplicate);
+        }
+
+        if !to_publish.is_empty() {
+            for (index, item) in &to_publish {
+                self.schemas
+                    .validate(&ids.app_id, &item.payload)
+                    .await
+                    .map_err(|e| {
+                        let status = Status::from(e);
+                        Status::new(status.code(), format!("Item {index}: {}", status.message()))
+                    })?;
+            }
+            let sizes: Vec<usize> = to_publish
+                .iter()
+                .map(|(_, item)| item.payload.len())
+                .collect();
+            let (_rx, _topic_guard) = self.subscriptions.acquire(&node, &region_app_topic).await?;
+            self.quota.consume(&ids, &sizes).await?;
+
+            let payloads = to_publish
+                .iter_mut()
+                .map(|(_, item)| std::mem::take(&mut item.payload))
+                .collect();
+            let (published, failure) = match node
+                .publish_batch_to_region_topic(&region_app_topic, payloads)
+                .await
+            {
+                Ok(published) => (published, None),
+                Err(e) => (e.published, Some((e.stored, e.error))),
+            };
+
+            // Keys are recorded for whatever made it into the log, even if the
+            // batch then failed, so a retry doesn't publish those items again.
+            let mut published_count = published.len();
+            for ((index, item), operation) in to_publish.iter().zip(published) {
+                let response = published_to_response(operation);
+                self.idempotency
+                    .record(
+                        &region_app_topic,
+                        &ids.instance_id,
+                        &item.idempotency_key,
+                        &response,
+                    )
+                    .await?;
+                results[*index] = Some(response);
+            }
+
+            if let Some((stored, error)) = failure {
+                let failed_index = to_publish[published_count].0;
+                if let Some(operation_id) = stored {
+                    let stored_response = PublishResponse {
+                        operation_id: operation_id.as_bytes().to_vec(),
+                        ..Default::default()
+                    };
+                    self.idempotency
+                        .record(
+                            &region_app_topic,
+                            &ids.instance_id,
+                            &to_publish[published_count].1.idempotency_key,
+                            &stored_response,
+                        )
+                        .await?;
+                    published_count += 1;
+                }
+                self.quota.refund(&ids, &sizes[published_count..]).await?;
+                if published_count > 0 {
+                    self.instance_notifier
+                        .notify(&region_app_topic.app_id, &ids.instance_id)
+                        .await;
+                }
+
+                let status = publish_error_to_status(error);
+                return Err(Status::new(
+                    status.code(),
+                    format!(
+                        "Item {failed_index}: {}. {published_count} of {} items were published; retry with the same idempotency keys to publish the rest",
+                        status.message(),
+                        to_publish.len(),
+                    ),
+                ));
+            }
+        }
+
+        self.instance_notifier
+            .notify(&region_app_topic.app_id, &ids.instance_id)
+            .await;
+
+        Ok(Response::new(PublishBatchResponse {
+            results: results.into_iter().flatten().collect(),
+        }))
     }
 
     type SubscribeStream =
@@ -223,12 +634,15 @@ impl Panda for PandaService {
         &self,
         request: Request<SubscribeRequest>,
     ) -> Result<Response<Self::SubscribeStream>, Status> {
+        let token = request.extensions().get::<AppToken>().cloned();
         let req = request.into_inner();
+        let replay_from = ReplayFrom::from_request(req.start_from)?;
 
         let ids = AppInstanceIds {
             app_id: req.app_id,
             instance_id: req.instance_id,
         };
+        self.authenticate(&ids, token).await?;
 
         let region_id = (self.resolve_region_id)(ids.clone())
             .await
@@ -244,30 +658,420 @@ impl Panda for PandaService {
         let region_app_topic = RegionAppTopic::new(region_id, ids.app_id);
 
         info!(
-            "[subscribe] region={} app_id={}",
-            region_app_topic.region_id, region_app_topic.app_id
+            "[subscribe] region={} app_id={} replay_from={:?}",
+            region_app_topic.region_id, region_app_topic.app_id, replay_from
         );
 
         self.instance_notifier
             .notify(&region_app_topic.app_id, &ids.instance_id)
             .await;
 
-        // Under a write lock, ensure a p2panda subscription exists for this
-        // topic and return a broadcast receiver for it.
-        let receiver = self
-            .ensure_broadcast_subscription(&node, &region_app_topic)
-            .await?;
+        // Under the subscriptions lock, ensure a p2panda subscription exists
+        // for this topic and return a broadcast receiver for it. The guard
+        // moves into the forwarding task, so the topic is released when this
+        // subscriber disconnects.
+        let (receiver, topic_guard) = self.subscriptions.acquire(&node, &region_app_topic).await?;
 
         // Record the region/namespace so ListRegions can report it.
-        node.register_region(region_app_topic.region_id).await;
+        node.register_region(region_app_topic.region_id.clone())
+            .await;
+
+        let buffer_size = self.subscription_config.buffer_size;
+        let (out_tx, out_rx) = tokio::sync::mpsc::channel(buffer_size);
+        let schema = self.schemas.for_app(region_app_topic.app_id.clone());
+
+        match replay_from {
+            Some(ReplayFrom::AfterOperation(after)) => {
+                let topic = region_app_topic.p2panda_topic();
+                let after = node
+                    .get_operation(topic, after)
+                    .await
+                    .map_err(history_error_to_status)?
+                    .ok_or_else(|| {
+                        not_found(
+                            "operation",
+                            "after_operation_id was not found in this topic",
+                        )
+                    })?;
+
+                tokio::spawn(async move {
+                    forward_arrived_then_live(
+                        node,
+                        topic,
+                        after.arrival,
+                        buffer_size as u32,
+                        receiver,
+                        out_tx,
+                        schema,
+                    )
+                    .await;
+                    drop(topic_guard);
+                });
+            }
+            Some(replay_from) => {
+                // The live receiver above is already buffering, so operations
+                // published while the replay runs are not lost.
+                let (replay_tx, replay_rx) =
+                    tokio::sync::mpsc::channel::<IncomingOperation>(buffer_size);
+                node.replay_topic(region_app_topic.p2panda_topic(), replay_tx)
+                    .await
+                    .map_err(subscription_error_to_status)?;
+
+                tokio::spawn(async move {
+                    forward_replay_then_live(replay_from, replay_rx, receiver, out_tx, schema)
+                        .await;
+                    drop(topic_guard);
+                });
+            }
+            None => {
+                tokio::spawn(async move {
+                    forward_live(receiver, out_tx, schema).await;
+                    drop(topic_guard);
+                });
+            }
+        }
+
+        Ok(Response::new(Box::pin(ReceiverStream::new(out_rx))))
+    }
+
+    async fn list_operations(
+        &self,
+        request: Request<ListOperationsRequest>,
+    ) -> Result<Response<ListOperationsResponse>, Status> {
+        let token = request.extensions().get::<AppToken>().cloned();
+        let req = request.into_inner();
+
+        let ids = AppInstanceIds {
+            app_id: req.app_id,
+            instance_id: req.instance_id,
+        };
+        self.authenticate(&ids, token).await?;
+
+        let author = history::parse_author(&req.author)?;
+        let after = history::decode_page_token(&req.page_token)?;
+        let limit = history::page_size(req.limit);
+
+        let region_id = (self.resolve_region_id)(ids.clone())
+            .await
+            .map_err(|e| resolve_region_error_to_status(e, &ids))?;
+
+        let node_lock = self.node.lock().await;
+        let node = node_lock
+            .as_ref()
+            .ok_or_else(|| Status::unavailable("p2panda node is not yet started"))?
+            .clone();
+        drop(node_lock);
+
+        let region_app_topic = RegionAppTopic::new(region_id, ids.app_id);
 
-        let stream = BroadcastStream::new(receiver).filter_map(|result| match result {
-            Ok(op) => Some(Ok(incoming_to_event(op))),
-            // Lagged means the consumer fell behind; skip the lost messages.
-            Err(_lagged) => None,
+        info!(
+            "[list_operations] region={} app_id={} limit={}",
+            region_app_topic.region_id, region_app_topic.app_id, limit
+        );
+
+        // Ask for one extra operation to find out whether there's another page.
+        let mut operations = node
+            .list_operations(&OperationQuery {
+                topic: region_app_topic.p2panda_topic(),
+                author,
+                since_timestamp: req.since_timestamp,
+                until_timestamp: req.until_timestamp,
+                after,
+                limit: limit + 1,
+            })
+            .await
+            .map_err(history_error_to_status)?;
+
+        let next_page_token = if operations.len() > limit as usize {
+            operations.truncate(limit as usize);
+            operations
+                .last()
+                .map(|last| history::encode_page_token(&last.cursor()))
+                .unwrap_or_default()
+        } else {
+            Vec::new()
+        };
+
+        self.instance_notifier
+            .notify(&region_app_topic.app_id, &ids.instance_id)
+            .await;
+
+        Ok(Response::new(ListOperationsResponse {
+            operations: operations
+                .into_iter()
+                .map(|stored| incoming_to_event(stored.operation))
+                .collect(),
+            next_page_token,
+        }))
+    }
+
+    async fn get_operation(
+        &self,
+        request: Request<GetOperationRequest>,
+    ) -> Result<Response<GetOperationResponse>, Status> {
+        let token = request.extensions().get::<AppToken>().cloned();
+        let req = request.into_inner();
+
+        let ids = AppInstanceIds {
+            app_id: req.app_id,
+            instance_id: req.instance_id,
+        };
+        self.authenticate(&ids, token).await?;
+
+        let operation_id = history::parse_operation_id(&req.operation_id)?;
+
+        let region_id = (self.resolve_region_id)(ids.clone())
+            .await
+            .map_err(|e| resolve_region_error_to_status(e, &ids))?;
+
+        let node_lock = self.node.lock().await;
+        let node = node_lock
+            .as_ref()
+            .ok_or_else(|| Status::unavailable("p2panda node is not yet started"))?
+            .clone();
+        drop(node_lock);
+
+        let region_app_topic = RegionAppTopic::new(region_id, ids.app_id);
+
+        // Only operations in the caller's own topic are visible to it.
+        let operation = node
+            .get_operation(region_app_topic.p2panda_topic(), operation_id)
+            .await
+            .map_err(history_error_to_status)?;
+
+        self.instance_notifier
+            .notify(&region_app_topic.app_id, &ids.instance_id)
+            .await;
+
+        Ok(Response::new(GetOperationResponse {
+            operation: operation.map(|stored| incoming_to_event(stored.operation)),
+        }))
+    }
+
+    async fn broadcast(
+        &self,
+        request: Request<BroadcastRequest>,
+    ) -> Result<Response<BroadcastResponse>, Status> {
+        let token = request.extensions().get::<AppToken>().cloned();
+        let req = request.into_inner();
+
+        let ids = AppInstanceIds {
+            app_id: req.app_id,
+            instance_id: req.instance_id,
+        };
+        self.authenticate(&ids, token).await?;
+
+        if req.payload.len() > self.max_ephemeral_payload_bytes {
+            return Err(Status::invalid_argument(format!(
+                "Ephemeral payload is {} bytes, the limit is {}",
+                req.payload.len(),
+                self.max_ephemeral_payload_bytes
+            )));
+        }
+        if !self.ephemeral_limiter.try_acquire(&ids).await {
+            return Err(Status::resource_exhausted(
+                "Sending ephemeral messages too fast, slow down and retry",
+            ));
+        }
+
+        let region_id = (self.resolve_region_id)(ids.clone())
+            .await
+            .map_err(|e| resolve_region_error_to_status(e, &ids))?;
+
+        let node_lock = self.node.lock().await;
+        let node = node_lock
+            .as_ref()
+            .ok_or_else(|| Status::unavailable("p2panda node is not yet started"))?
+            .clone();
+        drop(node_lock);
+
+        let region_app_topic = RegionAppTopic::new(region_id, ids.app_id);
+        let topic = region_app_topic.p2panda_topic();
+
+        // Joining the gossip channel to send on it keeps it joined for the idle
+        // timeout, so a burst of messages doesn't rejoin for each one.
+        let (_rx, _topic_guard) = self
+            .ephemeral_subscriptions
+            .acquire(&node, &region_app_topic)
+            .await?;
+
+        node.broadcast_ephemeral(topic, req.payload.clone())
+            .await
+            .map_err(publish_error_to_status)?;
+
+        // Gossip isn't delivered back to the sender, so hand the message to
+        // subscribers on this node directly.
+        self.ephemeral_subscriptions
+            .send_local(
+                topic,
+                EphemeralMessage {
+                    author: node.public_key,
+                    topic,
+                    bytes: req.payload,
+                    received_timestamp: SystemTime::now()
+                        .duration_since(UNIX_EPOCH)
+                        .map(|duration| duration.as_millis() as u64)
+                        .unwrap_or_default(),
+                },
+            )
+            .await;
+
+        self.instance_notifier
+            .notify(&region_app_topic.app_id, &ids.instance_id)
+            .await;
+
+        Ok(Response::new(BroadcastResponse {}))
+    }
+
+    type SubscribeEphemeralStream =
+        Pin<Box<dyn tokio_stream::Stream<Item = Result<EphemeralEvent, Status>> + Send + 'static>>;
+
+    async fn subscribe_ephemeral(
+        &self,
+        request: Request<SubscribeEphemeralRequest>,
+    ) -> Result<Response<Self::SubscribeEphemeralStream>, Status> {
+        let token = request.extensions().get::<AppToken>().cloned();
+        let req = request.into_inner();
+
+        let ids = AppInstanceIds {
+            app_id: req.app_id,
+            instance_id: req.instance_id,
+        };
+        self.authenticate(&ids, token).await?;
+
+        let region_id = (self.resolve_region_id)(ids.clone())
+            .await
+            .map_err(|e| resolve_region_error_to_status(e, &ids))?;
+
+        let node_lock = self.node.lock().await;
+        let node = node_lock
+            .as_ref()
+            .ok_or_else(|| Status::unavailable("p2panda node is not yet started"))?
+            .clone();
+        drop(node_lock);
+
+        let region_app_topic = RegionAppTopic::new(region_id, ids.app_id);
+
+        info!(
+            "[subscribe_ephemeral] region={} app_id={}",
+            region_app_topic.region_id, region_app_topic.app_id
+        );
+
+        self.instance_notifier
+            .notify(&region_app_topic.app_id, &ids.instance_id)
+            .await;
+
+        let (mut receiver, topic_guard) = self
+            .ephemeral_subscriptions
+            .acquire(&node, &region_app_topic)
+            .await?;
+
+        let (out_tx, out_rx) = tokio::sync::mpsc::channel(self.subscription_config.buffer_size);
+        tokio::spawn(async move {
+            // Ephemeral messages are disposable, so a slow reader just misses
+            // some rather than having its stream ended.
+            loop {
+                let result = tokio::select! {
+                    _ = out_tx.closed() => break,
+                    result = receiver.recv() => result,
+                };
+                match result {
+                    Ok(message) => {
+                        if out_tx.send(Ok(ephemeral_to_event(message))).await.is_err() {
+                            break;
+                        }
+                    }
+                    Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                        warn!(
+                            "[subscribe_ephemeral] subscriber lagged, dropped {skipped} messages"
+                        );
+                    }
+                    Err(broadcast::error::RecvError::Closed) => break,
+                }
+            }
+            drop(topic_guard);
         });
 
-        Ok(Response::new(Box::pin(stream)))
+        Ok(Response::new(Box::pin(ReceiverStream::new(out_rx))))
+    }
+
+    async fn register_schema(
+        &self,
+        request: Request<RegisterSchemaRequest>,
+    ) -> Result<Response<RegisterSchemaResponse>, Status> {
+        let token = request.extensions().get::<AppToken>().cloned();
+        let req = request.into_inner();
+
+        let ids = AppInstanceIds {
+            app_id: req.app_id,
+            instance_id: req.instance_id,
+        };
+        self.authenticate(&ids, token).await?;
+
+        info!(
+            "[register_schema] app_id={} schema_bytes={}",
+            ids.app_id,
+            req.schema.len()
+        );
+
+        self.schemas.register(&ids.app_id, &req.schema).await?;
+
+        Ok(Response::new(RegisterSchemaResponse {}))
+    }
+
+    async fn list_peers(
+        &self,
+        request: Request<ListPeersRequest>,
+    ) -> Result<Response<ListPeersResponse>, Status> {
+        let token = request.extensions().get::<AppToken>().cloned();
+        let req = request.into_inner();
+
+        let ids = AppInstanceIds {
+            app_id: req.app_id,
+            instance_id: req.instance_id,
+        };
+        self.authenticate(&ids, token).await?;
+
+        let region_id = (self.resolve_region_id)(ids.clone())
+            .await
+            .map_err(|e| resolve_region_error_to_status(e, &ids))?;
+
+        let local_node_id = self
+            .node
+            .lock()
+            .await
+            .as_ref()
+            .map(|node| node.public_key.to_hex());
+
+        info!("[list_peers] region={} app_id={}", region_id, ids.app_id);
+
+        let peers = (self.list_app_peers)(region_id, ids.app_id.clone())
+            .await
+            .map_err(|_| {
+                warn!(
+                    "list_peers error: failed to read peers for app '{}'",
+                    ids.app_id
+                );
+                Status::internal("Failed to list app peers")
+            })?;
+
+        self.instance_notifier
+            .notify(&ids.app_id, &ids.instance_id)
+            .await;
+
+        let peers = peers
+            .into_iter()
+            .map(|peer| proto::AppPeer {
+                is_local_node: local_node_id.as_deref() == Some(peer.node_id.as_str()),
+                node_id: peer.node_id,
+                node_name: peer.node_name,
+                version: peer.version,
+                internet_url: peer.internet_url,
+                local_network_url: peer.local_network_url,
+            })
+            .collect();
+
+        Ok(Response::new(ListPeersResponse { peers }))
     }
 }
 
@@ -281,6 +1085,32 @@ fn incoming_to_event(op: IncomingOperation) -> OperationEvent {
     }
 }
 
+fn ephemeral_to_event(message: EphemeralMessage) -> EphemeralEvent {
+    EphemeralEvent {
+        topic_id: message.topic.to_bytes().to_vec(),
+        author: message.author.as_bytes().to_vec(),
+        received_timestamp: message.received_timestamp,
+        payload: message.bytes,
+    }
+}
+
+fn published_to_response(operation: PublishedOperation) -> PublishResponse {
+    PublishResponse {
+        operation_id: operation.operation_id.as_bytes().to_vec(),
+        timestamp: operation.timestamp,
+        seq_num: operation.seq_num,
+        duplicate: false,
+    }
+}
+
+/// Whether the operation made it into the log before publishing failed.
+fn is_stored(e: &PandaPublishError) -> bool {
+    matches!(
+        e,
+        PandaPublishError::NotProcessed(_) | PandaPublishError::Processing(_)
+    )
+}
+
 fn publish_error_to_status(e: PandaPublishError) -> Status {
     match e {
         PandaPublishError::NodeNotStarted => {
@@ -296,6 +1126,12 @@ fn publish_error_to_status(e: PandaPublishError) -> Status {
             warn!("publish error: {e}");
             Status::internal(e.to_string())
         }
+        e @ (PandaPublishError::PublishEphemeral(_)
+        | PandaPublishError::NotProcessed(_)
+        | PandaPublishError::Processing(_)) => {
+            warn!("publish error: {e}");
+            Status::internal(e.to_string())
+        }
         PandaPublishError::AppError(msg) => {
             warn!("publish error: {msg}");
             Status::internal(msg)
@@ -303,6 +1139,11 @@ fn publish_error_to_status(e: PandaPublishError) -> Status {
     }
 }
 
+fn history_error_to_status(e: OperationHistoryError) -> Status {
+    warn!("operation history error: {e}");
+    Status::internal("Failed to read stored operations")
+}
+
 fn subscription_error_to_status(e: SubscriptionError) -> Status {
     match e {
         SubscriptionError::AlreadySubscribed(t) => {
@@ -317,14 +1158,27 @@ fn subscription_error_to_status(e: SubscriptionError) -> Status {
     }
 }
 
+/// A `NOT_FOUND` status saying what wasn't found in its `lores-not-found`
+/// metadata, so clients can tell an unbound app from an unknown operation.
+fn not_found(what: &'static str, message: impl Into<String>) -> Status {
+    let mut status = Status::not_found(message);
+    status
+        .metadata_mut()
+        .insert("lores-not-found", MetadataValue::from_static(what));
+    status
+}
+
 fn resolve_region_error_to_status(e: ResolveRegionIdError, ids: &AppInstanceIds) -> Status {
     match e {
-        ResolveRegionIdError::NotFound => Status::not_found(format!(
-            "No region bound to app '{}' instance '{}'. Use your lores-node installation to bind this app to a region, matching both the app name and instance ID.",
-            ids.app_id, ids.instance_id,
-        )),
-        ResolveRegionIdError::Internal => Status::internal(format!(
+        ResolveRegionIdError::NotFound => not_found(
+            "region",
+            format!(
+                "No region bound to app '{}' instance '{}'. Use your lores-node installation to bind this app to a region, matching both the app name and instance ID.",
+                ids.app_id, ids.instance_id,
+            ),
+        ),
+        ResolveRegionIdError::Internal => Status::internal(
             "Failed to resolve region for app instance. This may be an internal server issue with lores-node.",
-        )),
+        ),
     }
 }
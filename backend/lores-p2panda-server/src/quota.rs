@@ -0,0 +1,427 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use sqlx::SqlitePool;
+use tokio::sync::Mutex;
+use tokio::time::interval;
+use tonic::Status;
+use tracing::warn;
+
+use crate::{AppInstanceIds, QuotaConfig};
+
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+/// How many days of usage are kept for stewards to look back on.
+const USAGE_RETENTION_DAYS: i64 = 30;
+
+/// Today's publishing by one app instance, as counted against its quota.
+#[derive(Debug, Clone, PartialEq)]
+pub struct QuotaUsage {
+    pub app_id: String,
+    pub instance_id: String,
+    /// Days since the Unix epoch, in UTC.
+    pub day: i64,
+    pub ops: u64,
+    pub bytes: u64,
+    /// Operations refused because they would have exceeded a quota.
+    pub rejected: u64,
+    /// Operations published in the current minute.
+    pub ops_this_minute: u32,
+}
+
+/// Which quota a publish would have exceeded.
+#[derive(Debug, Clone, PartialEq)]
+pub enum QuotaExceeded {
+    PayloadTooLarge { size: usize, limit: usize },
+    OpsPerMinute { limit: u32 },
+    BytesPerDay { limit: u64 },
+}
+
+impl From<QuotaExceeded> for Status {
+    fn from(e: QuotaExceeded) -> Self {
+        Status::resource_exhausted(match e {
+            QuotaExceeded::PayloadTooLarge { size, limit } => {
+                format!("Payload is {size} bytes, the limit is {limit} bytes")
+            }
+            QuotaExceeded::OpsPerMinute { limit } => format!(
+                "This app instance has published its limit of {limit} operations this minute, retry later"
+            ),
+            QuotaExceeded::BytesPerDay { limit } => format!(
+                "This app instance has published its limit of {limit} bytes today, retry tomorrow (UTC)"
+            ),
+        })
+    }
+}
+
+#[derive(Default)]
+struct MinuteWindow {
+    minute: u64,
+    ops: u32,
+}
+
+/// Counts what each `(app_id, instance_id)` publishes and refuses publishes
+/// over the configured [`QuotaConfig`]. Daily totals are stored so they
+/// survive restarts; the per-minute count is kept in memory.
+#[derive(Clone)]
+pub struct QuotaTracker {
+    db: SqlitePool,
+    config: Arc<QuotaConfig>,
+    minutes: Arc<Mutex<HashMap<(String, String), MinuteWindow>>>,
+}
+
+impl QuotaTracker {
+    pub async fn new(db: SqlitePool, config: QuotaConfig) -> Result<Self, sqlx::Error> {
+        Self::setup_table(&db).await?;
+        Self::spawn_cleanup(&db);
+        Ok(Self {
+            db,
+            config: Arc::new(config),
+            minutes: Arc::new(Mutex::new(HashMap::new())),
+        })
+    }
+
+    pub fn config(&self) -> &QuotaConfig {
+        &self.config
+    }
+
+    /// Counts publishing `payload_sizes` against the instance's quotas, or
+    /// refuses all of them if any quota would be exceeded. Operations that are
+    /// then not published should be handed back with [`Self::refund`].
+    pub async fn consume(
+        &self,
+        ids: &AppInstanceIds,
+        payload_sizes: &[usize],
+    ) -> Result<(), Status> {
+        self.consume_at(ids, payload_sizes, SystemTime::now()).await
+    }
+
+    async fn consume_at(
+        &self,
+        ids: &AppInstanceIds,
+        payload_sizes: &[usize],
+        now: SystemTime,
+    ) -> Result<(), Status> {
+        let seconds = now.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+        let day = (seconds / SECONDS_PER_DAY) as i64;
+        let ops = payload_sizes.len() as u64;
+        let bytes: u64 = payload_sizes.iter().map(|size| *size as u64).sum();
+
+        // Holding the minute lock for the whole check keeps concurrent
+        // publishes from the same node from overshooting together.
+        let mut minutes = self.minutes.lock().await;
+        let window = minutes
+            .entry((ids.app_id.clone(), ids.instance_id.clone()))
+            .or_default();
+        if window.minute != seconds / 60 {
+            *window = MinuteWindow {
+                minute: seconds / 60,
+                ops: 0,
+            };
+        }
+
+        let exceeded = match payload_sizes
+            .iter()
+            .find(|size| **size > self.config.max_payload_bytes)
+        {
+            Some(size) => Some(QuotaExceeded::PayloadTooLarge {
+                size: *size,
+                limit: self.config.max_payload_bytes,
+            }),
+            None if window.ops as u64 + ops > self.config.ops_per_minute as u64 => {
+                Some(QuotaExceeded::OpsPerMinute {
+                    limit: self.config.ops_per_minute,
+                })
+            }
+            None => {
+                let bytes_today = self.bytes_on(ids, day).await.map_err(internal)?;
+                (bytes_today + bytes > self.config.bytes_per_day).then_some(
+                    QuotaExceeded::BytesPerDay {
+                        limit: self.config.bytes_per_day,
+                    },
+                )
+            }
+        };
+
+        if let Some(exceeded) = exceeded {
+            warn!(
+                "[quota] refused {ops} operations from app '{}' instance '{}': {exceeded:?}",
+                ids.app_id, ids.instance_id
+            );
+            self.add(ids, day, 0, 0, ops).await.map_err(internal)?;
+            return Err(exceeded.into());
+        }
+
+        window.ops += ops as u32;
+        self.add(ids, day, ops, bytes, 0).await.map_err(internal)?;
+        Ok(())
+    }
+
+    /// Gives back what [`Self::consume`] counted for operations that were not
+    /// published after all.
+    pub async fn refund(
+        &self,
+        ids: &AppInstanceIds,
+        payload_sizes: &[usize],
+    ) -> Result<(), Status> {
+        self.refund_at(ids, payload_sizes, SystemTime::now()).await
+    }
+
+    async fn refund_at(
+        &self,
+        ids: &AppInstanceIds,
+        payload_sizes: &[usize],
+        now: SystemTime,
+    ) -> Result<(), Status> {
+        if payload_sizes.is_empty() {
+            return Ok(());
+        }
+        let seconds = now.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+        let day = (seconds / SECONDS_PER_DAY) as i64;
+        let ops = payload_sizes.len() as u64;
+        let bytes: u64 = payload_sizes.iter().map(|size| *size as u64).sum();
+
+        let mut minutes = self.minutes.lock().await;
+        if let Some(window) = minutes.get_mut(&(ids.app_id.clone(), ids.instance_id.clone()))
+            && window.minute == seconds / 60
+        {
+            window.ops = window.ops.saturating_sub(ops as u32);
+        }
+
+        sqlx::query(
+            "UPDATE app_quota_usage SET ops = MAX(ops - ?, 0), bytes = MAX(bytes - ?, 0)
+             WHERE app_id = ? AND instance_id = ? AND day = ?",
+        )
+        .bind(ops as i64)
+        .bind(bytes as i64)
+        .bind(&ids.app_id)
+        .bind(&ids.instance_id)
+        .bind(day)
+        .execute(&self.db)
+        .await
+        .map_err(internal)?;
+        Ok(())
+    }
+
+    /// Usage of every instance that has published today.
+    pub async fn usage_today(&self) -> Result<Vec<QuotaUsage>, sqlx::Error> {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let day = (now / SECONDS_PER_DAY) as i64;
+
+        let rows: Vec<(String, String, i64, i64, i64, i64)> = sqlx::query_as(
+            "SELECT app_id, instance_id, day, ops, bytes, rejected FROM app_quota_usage
+             WHERE day = ? ORDER BY app_id, instance_id",
+        )
+        .bind(day)
+        .fetch_all(&self.db)
+        .await?;
+
+        let minutes = self.minutes.lock().await;
+        Ok(rows
+            .into_iter()
+            .map(|(app_id, instance_id, day, ops, bytes, rejected)| {
+                let ops_this_minute = minutes
+                    .get(&(app_id.clone(), instance_id.clone()))
+                    .filter(|window| window.minute == now / 60)
+                    .map(|window| window.ops)
+                    .unwrap_or_default();
+                QuotaUsage {
+                    app_id,
+                    instance_id,
+                    day,
+                    ops: ops as u64,
+                    bytes: bytes as u64,
+                    rejected: rejected as u64,
+                    ops_this_minute,
+                }
+            })
+            .collect())
+    }
+
+    async fn bytes_on(&self, ids: &AppInstanceIds, day: i64) -> Result<u64, sqlx::Error> {
+        let bytes: Option<(i64,)> = sqlx::query_as(
+            "SELECT bytes FROM app_quota_usage WHERE app_id = ? AND instance_id = ? AND day = ?",
+        )
+        .bind(&ids.app_id)
+        .bind(&ids.instance_id)
+        .bind(day)
+        .fetch_optional(&self.db)
+        .await?;
+        Ok(bytes.map(|(bytes,)| bytes as u64).unwrap_or_default())
+    }
+
+    async fn add(
+        &self,
+        ids: &AppInstanceIds,
+        day: i64,
+        ops: u64,
+        bytes: u64,
+        rejected: u64,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            "INSERT INTO app_quota_usage (app_id, instance_id, day, ops, bytes, rejected)
+             VALUES (?, ?, ?, ?, ?, ?)
+             ON CONFLICT (app_id, instance_id, day) DO UPDATE SET
+                ops = ops + excluded.ops,
+                bytes = bytes + excluded.bytes,
+                rejected = rejected + excluded.rejected",
+        )
+        .bind(&ids.app_id)
+        .bind(&ids.instance_id)
+        .bind(day)
+        .bind(ops as i64)
+        .bind(bytes as i64)
+        .bind(rejected as i64)
+        .execute(&self.db)
+        .await?;
+        Ok(())
+    }
+
+    async fn setup_table(db: &SqlitePool) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS app_quota_usage (
+                app_id      TEXT    NOT NULL,
+                instance_id TEXT    NOT NULL,
+                day         INTEGER NOT NULL,
+                ops         INTEGER NOT NULL,
+                bytes       INTEGER NOT NULL,
+                rejected    INTEGER NOT NULL,
+                PRIMARY KEY (app_id, instance_id, day)
+            );",
+        )
+        .execute(db)
+        .await?;
+        Ok(())
+    }
+
+    fn spawn_cleanup(db: &SqlitePool) {
+        let db = db.clone();
+
+        tokio::spawn(async move {
+            let mut timer = interval(Duration::from_hours(1));
+            // The first tick completes immediately; sweep only once a period has passed.
+            timer.tick().await;
+            loop {
+                timer.tick().await;
+                let today = (SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_secs()
+                    / SECONDS_PER_DAY) as i64;
+                let _ = sqlx::query("DELETE FROM app_quota_usage WHERE day < ?")
+                    .bind(today - USAGE_RETENTION_DAYS)
+                    .execute(&db)
+                    .await;
+            }
+        });
+    }
+}
+
+fn internal(e: sqlx::Error) -> Status {
+    warn!("quota store error: {e}");
+    Status::internal("Failed to check publish quota")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn test_tracker(config: QuotaConfig) -> QuotaTracker {
+        let db = SqlitePool::connect("sqlite::memory:").await.unwrap();
+        QuotaTracker::new(db, config).await.unwrap()
+    }
+
+    fn ids() -> AppInstanceIds {
+        AppInstanceIds {
+            app_id: "app".to_string(),
+            instance_id: "instance".to_string(),
+        }
+    }
+
+    fn at(seconds: u64) -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(seconds)
+    }
+
+    #[tokio::test]
+    async fn test_payload_over_limit_is_refused() {
+        let tracker = test_tracker(QuotaConfig {
+            max_payload_bytes: 10,
+            ..QuotaConfig::default()
+        })
+        .await;
+
+        let err = tracker.consume(&ids(), &[11]).await.unwrap_err();
+        assert_eq!(err.code(), tonic::Code::ResourceExhausted);
+        assert!(tracker.consume(&ids(), &[10]).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_ops_per_minute_resets_next_minute() {
+        let tracker = test_tracker(QuotaConfig {
+            ops_per_minute: 2,
+            ..QuotaConfig::default()
+        })
+        .await;
+
+        assert!(tracker.consume_at(&ids(), &[1, 1], at(60)).await.is_ok());
+        assert!(tracker.consume_at(&ids(), &[1], at(119)).await.is_err());
+        assert!(tracker.consume_at(&ids(), &[1], at(120)).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_bytes_per_day_counts_refusals() {
+        let tracker = test_tracker(QuotaConfig {
+            bytes_per_day: 100,
+            ..QuotaConfig::default()
+        })
+        .await;
+        let day = SECONDS_PER_DAY * 20_000;
+
+        assert!(tracker.consume_at(&ids(), &[60], at(day)).await.is_ok());
+        assert!(
+            tracker
+                .consume_at(&ids(), &[50], at(day + 60))
+                .await
+                .is_err()
+        );
+        assert!(
+            tracker
+                .consume_at(&ids(), &[50], at(day + SECONDS_PER_DAY))
+                .await
+                .is_ok()
+        );
+
+        let (ops, bytes, rejected): (i64, i64, i64) =
+            sqlx::query_as("SELECT ops, bytes, rejected FROM app_quota_usage WHERE day = ?")
+                .bind(20_000i64)
+                .fetch_one(&tracker.db)
+                .await
+                .unwrap();
+        assert_eq!((ops, bytes, rejected), (1, 60, 1));
+    }
+
+    #[tokio::test]
+    async fn test_refund_gives_back_unpublished_operations() {
+        let tracker = test_tracker(QuotaConfig {
+            ops_per_minute: 2,
+            bytes_per_day: 100,
+            ..QuotaConfig::default()
+        })
+        .await;
+        let day = SECONDS_PER_DAY * 20_000;
+
+        assert!(tracker.consume_at(&ids(), &[40, 40], at(day)).await.is_ok());
+        tracker.refund_at(&ids(), &[40], at(day)).await.unwrap();
+        assert!(tracker.consume_at(&ids(), &[50], at(day)).await.is_ok());
+
+        let (ops, bytes, rejected): (i64, i64, i64) =
+            sqlx::query_as("SELECT ops, bytes, rejected FROM app_quota_usage WHERE day = ?")
+                .bind(20_000i64)
+                .fetch_one(&tracker.db)
+                .await
+                .unwrap();
+        assert_eq!((ops, bytes, rejected), (2, 90, 0));
+    }
+}
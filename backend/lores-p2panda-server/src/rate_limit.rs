@@ -0,0 +1,104 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use tokio::sync::Mutex;
+
+use crate::AppInstanceIds;
+
+/// Token bucket per app instance: each instance may send `burst` messages at
+/// once, refilled at `per_second`.
+pub struct RateLimiter {
+    per_second: f64,
+    burst: f64,
+    buckets: Mutex<HashMap<(String, String), Bucket>>,
+}
+
+struct Bucket {
+    tokens: f64,
+    refilled_at: Instant,
+}
+
+impl Bucket {
+    fn try_take(&mut self, now: Instant, per_second: f64, burst: f64) -> bool {
+        let elapsed = now.saturating_duration_since(self.refilled_at);
+        self.tokens = (self.tokens + elapsed.as_secs_f64() * per_second).min(burst);
+        self.refilled_at = now;
+        if self.tokens < 1.0 {
+            return false;
+        }
+        self.tokens -= 1.0;
+        true
+    }
+}
+
+impl RateLimiter {
+    pub fn new(per_second: u32, burst: u32) -> Self {
+        Self {
+            per_second: per_second as f64,
+            burst: burst.max(1) as f64,
+            buckets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Takes a token for `ids`, returning `false` if the instance is over its
+    /// rate.
+    pub async fn try_acquire(&self, ids: &AppInstanceIds) -> bool {
+        self.try_acquire_at(ids, Instant::now()).await
+    }
+
+    async fn try_acquire_at(&self, ids: &AppInstanceIds, now: Instant) -> bool {
+        let mut buckets = self.buckets.lock().await;
+
+        // Buckets that have refilled completely carry no state worth keeping.
+        if self.per_second > 0.0 {
+            let full_after = Duration::from_secs_f64(self.burst / self.per_second);
+            buckets
+                .retain(|_, bucket| now.saturating_duration_since(bucket.refilled_at) < full_after);
+        }
+
+        buckets
+            .entry((ids.app_id.clone(), ids.instance_id.clone()))
+            .or_insert(Bucket {
+                tokens: self.burst,
+                refilled_at: now,
+            })
+            .try_take(now, self.per_second, self.burst)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ids(instance_id: &str) -> AppInstanceIds {
+        AppInstanceIds {
+            app_id: "app".to_string(),
+            instance_id: instance_id.to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_burst_then_refill() {
+        let limiter = RateLimiter::new(2, 3);
+        let start = Instant::now();
+
+        for _ in 0..3 {
+            assert!(limiter.try_acquire_at(&ids("a"), start).await);
+        }
+        assert!(!limiter.try_acquire_at(&ids("a"), start).await);
+
+        let later = start + Duration::from_millis(500);
+        assert!(limiter.try_acquire_at(&ids("a"), later).await);
+        assert!(!limiter.try_acquire_at(&ids("a"), later).await);
+    }
+
+    #[tokio::test]
+    async fn test_instances_have_separate_buckets() {
+        let limiter = RateLimiter::new(1, 1);
+        let now = Instant::now();
+
+        assert!(limiter.try_acquire_at(&ids("a"), now).await);
+        assert!(!limiter.try_acquire_at(&ids("a"), now).await);
+        assert!(limiter.try_acquire_at(&ids("b"), now).await);
+    }
+}
@@ -0,0 +1,263 @@
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use lores_p2panda::p2panda_core::Hash;
+use lores_p2panda::{IncomingOperation, PandaNode, Topic};
+use tokio::sync::{broadcast, mpsc};
+use tonic::Status;
+use tracing::warn;
+
+use crate::incoming_to_event;
+use crate::proto::{OperationEvent, subscribe_request::StartFrom};
+use crate::schema_registry::AppSchema;
+
+/// Where a subscription starts replaying stored operations from.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ReplayFrom {
+    Beginning,
+    SinceTimestamp(u64),
+    AfterOperation(Hash),
+}
+
+impl ReplayFrom {
+    /// Parses `SubscribeRequest.start_from`. `None` means the subscription
+    /// starts at the frontier and nothing is replayed.
+    pub fn from_request(start_from: Option<StartFrom>) -> Result<Option<Self>, Status> {
+        match start_from {
+            None | Some(StartFrom::Frontier(_)) => Ok(None),
+            Some(StartFrom::Beginning(_)) => Ok(Some(ReplayFrom::Beginning)),
+            Some(StartFrom::SinceTimestamp(timestamp)) => {
+                Ok(Some(ReplayFrom::SinceTimestamp(timestamp)))
+            }
+            Some(StartFrom::AfterOperationId(id)) => {
+                let id: [u8; 32] = id.try_into().map_err(|_| {
+                    Status::invalid_argument("after_operation_id must be a 32-byte operation id")
+                })?;
+                Ok(Some(ReplayFrom::AfterOperation(Hash::from_bytes(id))))
+            }
+        }
+    }
+
+    /// Whether a replayed operation with this timestamp is delivered.
+    fn accepts(&self, timestamp: u64) -> bool {
+        match self {
+            ReplayFrom::SinceTimestamp(since) => timestamp >= *since,
+            ReplayFrom::Beginning | ReplayFrom::AfterOperation(_) => true,
+        }
+    }
+}
+
+/// Sends the replayed operations to `out_tx`, then switches over to the live
+/// broadcast. `live_rx` must be subscribed before the replay starts so nothing
+/// published in between is missed; live operations that were also replayed are
+/// dropped. Operations that don't match the app's schema are skipped.
+pub async fn forward_replay_then_live(
+    from: ReplayFrom,
+    mut replay_rx: mpsc::Receiver<IncomingOperation>,
+    live_rx: broadcast::Receiver<IncomingOperation>,
+    out_tx: mpsc::Sender<Result<OperationEvent, Status>>,
+    schema: AppSchema,
+) {
+    let mut replayed_ids: HashSet<Hash> = HashSet::new();
+
+    while let Some(op) = replay_rx.recv().await {
+        replayed_ids.insert(op.operation_id);
+        if !from.accepts(op.received_timestamp) || !schema.accepts(&op).await {
+            continue;
+        }
+        if out_tx.send(Ok(incoming_to_event(op))).await.is_err() {
+            return;
+        }
+    }
+
+    forward_buffered_then_live(replayed_ids, live_rx, out_tx, schema).await;
+}
+
+/// Like [`forward_replay_then_live`], but replays the operations stored under
+/// `topic` that arrived after the operation at `after_arrival`, in the order
+/// they arrived. p2panda replays author by author, so only the store's arrival
+/// order lets a subscriber resume after an operation without skipping what
+/// other authors published in the meantime.
+pub async fn forward_arrived_then_live(
+    node: Arc<PandaNode>,
+    topic: Topic,
+    mut after_arrival: i64,
+    page_size: u32,
+    live_rx: broadcast::Receiver<IncomingOperation>,
+    out_tx: mpsc::Sender<Result<OperationEvent, Status>>,
+    schema: AppSchema,
+) {
+    let mut replayed_ids: HashSet<Hash> = HashSet::new();
+
+    loop {
+        let page = match node
+            .operations_arrived_after(topic, after_arrival, page_size)
+            .await
+        {
+            Ok(page) => page,
+            Err(e) => {
+                warn!("[subscribe] failed to replay stored operations: {e}");
+                let _ = out_tx
+                    .send(Err(Status::internal("Failed to read stored operations")))
+                    .await;
+                return;
+            }
+        };
+        let page_len = page.len();
+        for stored in page {
+            after_arrival = stored.arrival;
+            let op = stored.operation;
+            replayed_ids.insert(op.operation_id);
+            if !schema.accepts(&op).await {
+                continue;
+            }
+            if out_tx.send(Ok(incoming_to_event(op))).await.is_err() {
+                return;
+            }
+        }
+        if page_len < page_size as usize {
+            break;
+        }
+    }
+
+    forward_buffered_then_live(replayed_ids, live_rx, out_tx, schema).await;
+}
+
+/// Sends the live operations buffered while a replay ran, skipping those the
+/// replay already delivered, then carries on with [`forward_live`].
+async fn forward_buffered_then_live(
+    replayed_ids: HashSet<Hash>,
+    mut live_rx: broadcast::Receiver<IncomingOperation>,
+    out_tx: mpsc::Sender<Result<OperationEvent, Status>>,
+    schema: AppSchema,
+) {
+    loop {
+        match live_rx.try_recv() {
+            Ok(op) => {
+                if replayed_ids.contains(&op.operation_id) || !schema.accepts(&op).await {
+                    continue;
+                }
+                if out_tx.send(Ok(incoming_to_event(op))).await.is_err() {
+                    return;
+                }
+            }
+            Err(broadcast::error::TryRecvError::Empty) => break,
+            Err(broadcast::error::TryRecvError::Lagged(skipped)) => {
+                let _ = out_tx.send(Err(lagged_status(skipped))).await;
+                return;
+            }
+            Err(broadcast::error::TryRecvError::Closed) => return,
+        }
+    }
+
+    // Anything received from here on arrived after the replay finished.
+    drop(replayed_ids);
+
+    forward_live(live_rx, out_tx, schema).await;
+}
+
+/// Sends live operations to `out_tx` until the subscriber goes away. If the
+/// subscriber falls far enough behind that the broadcast drops operations, the
+/// stream ends with a `DATA_LOSS` status rather than silently skipping them.
+/// Operations that don't match the app's schema are skipped.
+pub async fn forward_live(
+    mut live_rx: broadcast::Receiver<IncomingOperation>,
+    out_tx: mpsc::Sender<Result<OperationEvent, Status>>,
+    schema: AppSchema,
+) {
+    loop {
+        let result = tokio::select! {
+            _ = out_tx.closed() => return,
+            result = live_rx.recv() => result,
+        };
+        match result {
+            Ok(op) => {
+                if !schema.accepts(&op).await {
+                    continue;
+                }
+                if out_tx.send(Ok(incoming_to_event(op))).await.is_err() {
+                    return;
+                }
+            }
+            Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                let _ = out_tx.send(Err(lagged_status(skipped))).await;
+                return;
+            }
+            Err(broadcast::error::RecvError::Closed) => return,
+        }
+    }
+}
+
+pub(crate) fn lagged_status(skipped: u64) -> Status {
+    warn!("[subscribe] subscriber lagged, {skipped} operations dropped");
+    Status::data_loss(format!(
+        "Subscriber fell behind and {skipped} operations were dropped. Resubscribe with after_operation_id set to the last operation you processed."
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::proto::{Beginning, Frontier};
+    use crate::schema_registry::SchemaRegistry;
+    use lores_p2panda::p2panda_core::SigningKey;
+    use sqlx::SqlitePool;
+
+    fn incoming(n: u8) -> IncomingOperation {
+        IncomingOperation {
+            author: SigningKey::generate().verifying_key(),
+            topic: Topic::from([0; 32]),
+            bytes: vec![n],
+            operation_id: Hash::digest([n]),
+            received_timestamp: n.into(),
+        }
+    }
+
+    #[test]
+    fn test_frontier_and_unset_do_not_replay() {
+        assert_eq!(ReplayFrom::from_request(None).unwrap(), None);
+        assert_eq!(
+            ReplayFrom::from_request(Some(StartFrom::Frontier(Frontier {}))).unwrap(),
+            None
+        );
+        assert_eq!(
+            ReplayFrom::from_request(Some(StartFrom::Beginning(Beginning {}))).unwrap(),
+            Some(ReplayFrom::Beginning)
+        );
+    }
+
+    #[test]
+    fn test_after_operation_id_must_be_32_bytes() {
+        let err =
+            ReplayFrom::from_request(Some(StartFrom::AfterOperationId(vec![1, 2, 3]))).unwrap_err();
+        assert_eq!(err.code(), tonic::Code::InvalidArgument);
+    }
+
+    #[test]
+    fn test_since_timestamp_filter() {
+        let from = ReplayFrom::SinceTimestamp(100);
+        assert!(!from.accepts(99));
+        assert!(from.accepts(100));
+        assert!(from.accepts(101));
+    }
+
+    #[tokio::test]
+    async fn test_live_overrun_ends_stream_with_data_loss() {
+        let db = SqlitePool::connect("sqlite::memory:").await.unwrap();
+        let schema = SchemaRegistry::new(db).await.unwrap().for_app("app".into());
+
+        let buffer_size = 4;
+        let (live_tx, live_rx) = broadcast::channel(buffer_size);
+        let (out_tx, mut out_rx) = mpsc::channel(buffer_size);
+
+        // Overrun the receiver before it reads anything.
+        for n in 0..=buffer_size as u8 {
+            assert!(live_tx.send(incoming(n)).is_ok());
+        }
+        forward_live(live_rx, out_tx, schema).await;
+
+        let status = out_rx.recv().await.unwrap().unwrap_err();
+        assert_eq!(status.code(), tonic::Code::DataLoss);
+        assert!(out_rx.recv().await.is_none());
+    }
+}
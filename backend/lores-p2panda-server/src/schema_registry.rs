@@ -0,0 +1,349 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use jsonschema::Validator;
+use lores_p2panda::IncomingOperation;
+use sqlx::SqlitePool;
+use thiserror::Error;
+use tokio::sync::RwLock;
+use tonic::Status;
+use tracing::warn;
+
+#[derive(Debug, Error)]
+pub enum SchemaError {
+    #[error("Schema is not valid JSON: {0}")]
+    InvalidJson(#[from] serde_json::Error),
+    #[error("Schema is not a valid JSON Schema: {0}")]
+    InvalidSchema(String),
+    #[error("Failed to store schema: {0}")]
+    Database(#[from] sqlx::Error),
+}
+
+#[derive(Debug, Error)]
+pub enum PayloadError {
+    #[error("Payload is not valid CBOR: {0}")]
+    NotCbor(String),
+    #[error("Payload does not match the app's schema: {0}")]
+    DoesNotMatch(String),
+    #[error("Failed to load the app's schema: {0}")]
+    Database(#[from] sqlx::Error),
+}
+
+impl From<SchemaError> for Status {
+    fn from(e: SchemaError) -> Self {
+        match e {
+            SchemaError::InvalidJson(_) | SchemaError::InvalidSchema(_) => {
+                Status::invalid_argument(e.to_string())
+            }
+            SchemaError::Database(_) => {
+                warn!("schema registry error: {e}");
+                Status::internal("Failed to store schema")
+            }
+        }
+    }
+}
+
+impl From<PayloadError> for Status {
+    fn from(e: PayloadError) -> Self {
+        match e {
+            PayloadError::NotCbor(_) | PayloadError::DoesNotMatch(_) => {
+                Status::invalid_argument(e.to_string())
+            }
+            PayloadError::Database(_) => {
+                warn!("schema registry error: {e}");
+                Status::internal("Failed to check payload against the app's schema")
+            }
+        }
+    }
+}
+
+/// JSON Schemas registered by apps for the payloads on their topics.
+///
+/// Payloads are decoded from CBOR and checked against the schema registered
+/// for the topic's `app_id`. Apps without a schema can publish anything.
+/// Operations from other nodes that fail the check are recorded in
+/// `flagged_operations` instead of reaching subscribers.
+#[derive(Clone)]
+pub struct SchemaRegistry {
+    db: SqlitePool,
+    /// Compiled schemas by app id, with `None` for apps known to have none.
+    validators: Arc<RwLock<HashMap<String, Option<Arc<Validator>>>>>,
+}
+
+impl SchemaRegistry {
+    pub async fn new(db: SqlitePool) -> Result<Self, sqlx::Error> {
+        Self::setup_tables(&db).await?;
+        Ok(Self {
+            db,
+            validators: Arc::new(RwLock::new(HashMap::new())),
+        })
+    }
+
+    /// Registers `schema` for `app_id`, replacing any earlier one. An empty
+    /// schema removes it.
+    pub async fn register(&self, app_id: &str, schema: &str) -> Result<(), SchemaError> {
+        if schema.trim().is_empty() {
+            sqlx::query("DELETE FROM app_payload_schemas WHERE app_id = ?")
+                .bind(app_id)
+                .execute(&self.db)
+                .await?;
+            self.validators
+                .write()
+                .await
+                .insert(app_id.to_string(), None);
+            return Ok(());
+        }
+
+        let validator = compile(schema)?;
+
+        sqlx::query(
+            "INSERT INTO app_payload_schemas (app_id, schema, registered_at) VALUES (?, ?, ?)
+             ON CONFLICT (app_id) DO UPDATE SET
+                schema = excluded.schema,
+                registered_at = excluded.registered_at",
+        )
+        .bind(app_id)
+        .bind(schema)
+        .bind(now_secs())
+        .execute(&self.db)
+        .await?;
+
+        self.validators
+            .write()
+            .await
+            .insert(app_id.to_string(), Some(Arc::new(validator)));
+        Ok(())
+    }
+
+    /// Checks `payload` against the schema registered for `app_id`, if any.
+    pub async fn validate(&self, app_id: &str, payload: &[u8]) -> Result<(), PayloadError> {
+        let Some(validator) = self.validator(app_id).await? else {
+            return Ok(());
+        };
+
+        let value: ciborium::Value =
+            ciborium::from_reader(payload).map_err(|e| PayloadError::NotCbor(e.to_string()))?;
+        // Byte strings become arrays of numbers; maps need text keys.
+        let instance =
+            serde_json::to_value(&value).map_err(|e| PayloadError::NotCbor(e.to_string()))?;
+
+        validator
+            .validate(&instance)
+            .map_err(|e| PayloadError::DoesNotMatch(e.to_string()))
+    }
+
+    /// The checks for operations on `app_id`'s topic.
+    pub fn for_app(&self, app_id: String) -> AppSchema {
+        AppSchema {
+            registry: self.clone(),
+            app_id,
+        }
+    }
+
+    /// Whether `op` can be delivered to the app's subscribers. Operations that
+    /// fail validation are flagged and logged.
+    async fn accepts(&self, app_id: &str, op: &IncomingOperation) -> bool {
+        match self.validate(app_id, &op.bytes).await {
+            Ok(()) => true,
+            Err(PayloadError::Database(e)) => {
+                // Not the operation's fault, so don't hold it back.
+                warn!("[schema] failed to load schema for app '{app_id}': {e}");
+                true
+            }
+            Err(e) => {
+                warn!(
+                    "[schema] flagging operation {} from {} for app '{app_id}': {e}",
+                    op.operation_id, op.author
+                );
+                if let Err(e) = self.flag(app_id, op, &e.to_string()).await {
+                    warn!("[schema] failed to flag operation: {e}");
+                }
+                false
+            }
+        }
+    }
+
+    async fn flag(
+        &self,
+        app_id: &str,
+        op: &IncomingOperation,
+        reason: &str,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            "INSERT OR IGNORE INTO flagged_operations
+                (operation_id, app_id, author, reason, flagged_at)
+             VALUES (?, ?, ?, ?, ?)",
+        )
+        .bind(op.operation_id.as_bytes().to_vec())
+        .bind(app_id)
+        .bind(op.author.as_bytes().to_vec())
+        .bind(reason)
+        .bind(now_secs())
+        .execute(&self.db)
+        .await?;
+        Ok(())
+    }
+
+    async fn validator(&self, app_id: &str) -> Result<Option<Arc<Validator>>, sqlx::Error> {
+        if let Some(validator) = self.validators.read().await.get(app_id) {
+            return Ok(validator.clone());
+        }
+
+        let schema: Option<(String,)> =
+            sqlx::query_as("SELECT schema FROM app_payload_schemas WHERE app_id = ?")
+                .bind(app_id)
+                .fetch_optional(&self.db)
+                .await?;
+        let validator = match schema {
+            Some((schema,)) => match compile(&schema) {
+                Ok(validator) => Some(Arc::new(validator)),
+                Err(e) => {
+                    warn!("[schema] ignoring stored schema for app '{app_id}': {e}");
+                    None
+                }
+            },
+            None => None,
+        };
+
+        self.validators
+            .write()
+            .await
+            .insert(app_id.to_string(), validator.clone());
+        Ok(validator)
+    }
+
+    async fn setup_tables(db: &SqlitePool) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS app_payload_schemas (
+                app_id        TEXT    PRIMARY KEY,
+                schema        TEXT    NOT NULL,
+                registered_at INTEGER NOT NULL
+            );",
+        )
+        .execute(db)
+        .await?;
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS flagged_operations (
+                operation_id BLOB    PRIMARY KEY,
+                app_id       TEXT    NOT NULL,
+                author       BLOB    NOT NULL,
+                reason       TEXT    NOT NULL,
+                flagged_at   INTEGER NOT NULL
+            );",
+        )
+        .execute(db)
+        .await?;
+        Ok(())
+    }
+}
+
+/// Checks operations on one app's topic before they reach its subscribers.
+pub struct AppSchema {
+    registry: SchemaRegistry,
+    app_id: String,
+}
+
+impl AppSchema {
+    /// Whether `op` matches the app's schema. Operations that don't are
+    /// flagged and should not be delivered.
+    pub async fn accepts(&self, op: &IncomingOperation) -> bool {
+        self.registry.accepts(&self.app_id, op).await
+    }
+}
+
+fn compile(schema: &str) -> Result<Validator, SchemaError> {
+    let schema: serde_json::Value = serde_json::from_str(schema)?;
+    jsonschema::validator_for(&schema).map_err(|e| SchemaError::InvalidSchema(e.to_string()))
+}
+
+fn now_secs() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs() as i64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SCHEMA: &str = r#"{
+        "type": "object",
+        "properties": { "title": { "type": "string" } },
+        "required": ["title"]
+    }"#;
+
+    async fn test_registry() -> SchemaRegistry {
+        let db = SqlitePool::connect("sqlite::memory:").await.unwrap();
+        SchemaRegistry::new(db).await.unwrap()
+    }
+
+    fn cbor(value: &ciborium::Value) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        ciborium::into_writer(value, &mut bytes).unwrap();
+        bytes
+    }
+
+    fn note(title: ciborium::Value) -> Vec<u8> {
+        cbor(&ciborium::Value::Map(vec![("title".into(), title)]))
+    }
+
+    #[tokio::test]
+    async fn test_apps_without_schema_accept_anything() {
+        let registry = test_registry().await;
+        assert!(registry.validate("app", b"not cbor").await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_payloads_are_checked_against_schema() {
+        let registry = test_registry().await;
+        registry.register("app", SCHEMA).await.unwrap();
+
+        assert!(registry.validate("app", &note("hi".into())).await.is_ok());
+        assert!(matches!(
+            registry.validate("app", &note(3.into())).await,
+            Err(PayloadError::DoesNotMatch(_))
+        ));
+        assert!(matches!(
+            registry.validate("app", &[0xff]).await,
+            Err(PayloadError::NotCbor(_))
+        ));
+        assert!(
+            registry
+                .validate("other_app", &note(3.into()))
+                .await
+                .is_ok()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_invalid_schema_is_rejected() {
+        let registry = test_registry().await;
+        assert!(matches!(
+            registry.register("app", "{").await,
+            Err(SchemaError::InvalidJson(_))
+        ));
+        assert!(matches!(
+            registry.register("app", r#"{"type": 3}"#).await,
+            Err(SchemaError::InvalidSchema(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_schema_survives_restart_and_can_be_removed() {
+        let db = SqlitePool::connect("sqlite::memory:").await.unwrap();
+        SchemaRegistry::new(db.clone())
+            .await
+            .unwrap()
+            .register("app", SCHEMA)
+            .await
+            .unwrap();
+
+        let registry = SchemaRegistry::new(db).await.unwrap();
+        assert!(registry.validate("app", &note(3.into())).await.is_err());
+
+        registry.register("app", "").await.unwrap();
+        assert!(registry.validate("app", &note(3.into())).await.is_ok());
+    }
+}
@@ -0,0 +1,268 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+
+use lores_p2panda::{
+    EphemeralMessage, IncomingOperation, PandaNode, RegionTopic, SubscriptionError, Topic,
+};
+use tokio::sync::{Mutex, broadcast, mpsc};
+use tonic::Status;
+use tracing::{info, warn};
+
+use crate::subscription_error_to_status;
+
+/// A kind of p2panda topic subscription that can be shared between callers.
+pub trait TopicKind: Send + Sync + 'static {
+    type Item: Clone + Send + 'static;
+
+    fn join(
+        node: &PandaNode,
+        topic: Topic,
+        events_tx: mpsc::Sender<Self::Item>,
+    ) -> impl Future<Output = Result<(), SubscriptionError>> + Send;
+
+    fn leave(node: &PandaNode, topic: Topic) -> impl Future<Output = ()> + Send;
+}
+
+/// The topic's persisted operation log.
+pub struct OperationLog;
+
+impl TopicKind for OperationLog {
+    type Item = IncomingOperation;
+
+    async fn join(
+        node: &PandaNode,
+        topic: Topic,
+        events_tx: mpsc::Sender<IncomingOperation>,
+    ) -> Result<(), SubscriptionError> {
+        node.subscribe_to_topic(topic, events_tx).await
+    }
+
+    async fn leave(node: &PandaNode, topic: Topic) {
+        node.unsubscribe(topic).await;
+    }
+}
+
+/// The topic's ephemeral gossip channel.
+pub struct Ephemeral;
+
+impl TopicKind for Ephemeral {
+    type Item = EphemeralMessage;
+
+    async fn join(
+        node: &PandaNode,
+        topic: Topic,
+        events_tx: mpsc::Sender<EphemeralMessage>,
+    ) -> Result<(), SubscriptionError> {
+        node.subscribe_ephemeral(topic, events_tx).await
+    }
+
+    async fn leave(node: &PandaNode, topic: Topic) {
+        node.unsubscribe_ephemeral(topic).await;
+    }
+}
+
+/// The p2panda subscriptions shared by gRPC callers, one per topic.
+///
+/// Each caller holds a [`TopicGuard`] for as long as it needs the topic. Once
+/// the last guard is dropped and the topic has stayed unused for
+/// `idle_timeout`, the broadcast channel is removed and the node leaves the
+/// p2panda topic. The grace period stops a client that reconnects, or an app
+/// that only publishes now and then, from rejoining the topic every time.
+pub struct TopicSubscriptions<K: TopicKind = OperationLog> {
+    topics: Arc<Mutex<HashMap<Topic, SharedTopic<K::Item>>>>,
+    buffer_size: usize,
+    idle_timeout: Duration,
+}
+
+impl<K: TopicKind> Clone for TopicSubscriptions<K> {
+    fn clone(&self) -> Self {
+        Self {
+            topics: self.topics.clone(),
+            buffer_size: self.buffer_size,
+            idle_timeout: self.idle_timeout,
+        }
+    }
+}
+
+struct SharedTopic<T> {
+    sender: broadcast::Sender<T>,
+    holders: usize,
+    /// Bumped each time the topic becomes unused, so a pending teardown can
+    /// tell whether the topic has been used again since it was scheduled.
+    idle_generation: u64,
+}
+
+impl<T: Clone> SharedTopic<T> {
+    fn retain(&mut self) -> broadcast::Receiver<T> {
+        self.holders += 1;
+        self.sender.subscribe()
+    }
+
+    /// Returns the generation to check against once the idle timeout has
+    /// passed, or `None` while the topic is still held.
+    fn release(&mut self) -> Option<u64> {
+        self.holders = self.holders.saturating_sub(1);
+        if self.holders > 0 {
+            return None;
+        }
+        self.idle_generation += 1;
+        Some(self.idle_generation)
+    }
+
+    fn is_idle_since(&self, generation: u64) -> bool {
+        self.holders == 0 && self.idle_generation == generation
+    }
+}
+
+/// Keeps a topic subscribed while held. Dropping it releases the topic.
+pub struct TopicGuard<K: TopicKind = OperationLog> {
+    subscriptions: TopicSubscriptions<K>,
+    node: Arc<PandaNode>,
+    topic: Topic,
+}
+
+impl<K: TopicKind> Drop for TopicGuard<K> {
+    fn drop(&mut self) {
+        // Guards can outlive the runtime, e.g. when a stream is dropped during
+        // shutdown; the subscription goes away with the node then anyway.
+        let Ok(handle) = tokio::runtime::Handle::try_current() else {
+            warn!("No runtime to release topic subscription on");
+            return;
+        };
+        let subscriptions = self.subscriptions.clone();
+        let node = self.node.clone();
+        let topic = self.topic;
+        handle.spawn(async move { subscriptions.release(node, topic).await });
+    }
+}
+
+impl<K: TopicKind> TopicSubscriptions<K> {
+    pub fn new(buffer_size: usize, idle_timeout: Duration) -> Self {
+        Self {
+            topics: Arc::new(Mutex::new(HashMap::new())),
+            buffer_size,
+            idle_timeout,
+        }
+    }
+
+    /// Returns a broadcast receiver for `region_topic`, creating the
+    /// underlying p2panda subscription and forwarding task the first time the
+    /// topic is used, along with the guard that keeps it alive.
+    pub async fn acquire<T: RegionTopic>(
+        &self,
+        node: &Arc<PandaNode>,
+        region_topic: &T,
+    ) -> Result<(broadcast::Receiver<K::Item>, TopicGuard<K>), Status> {
+        let topic = region_topic.p2panda_topic();
+        let mut topics = self.topics.lock().await;
+
+        let receiver = match topics.get_mut(&topic) {
+            Some(shared) => shared.retain(),
+            None => {
+                let (broadcast_tx, _) = broadcast::channel::<K::Item>(self.buffer_size);
+                let (incoming_tx, mut incoming_rx) = mpsc::channel::<K::Item>(self.buffer_size);
+
+                K::join(node, topic, incoming_tx)
+                    .await
+                    .map_err(subscription_error_to_status)?;
+
+                // Ends once the node unsubscribes and drops `incoming_tx`.
+                let fwd_tx = broadcast_tx.clone();
+                tokio::spawn(async move {
+                    while let Some(op) = incoming_rx.recv().await {
+                        let _ = fwd_tx.send(op);
+                    }
+                });
+
+                let mut shared = SharedTopic {
+                    sender: broadcast_tx,
+                    holders: 0,
+                    idle_generation: 0,
+                };
+                let receiver = shared.retain();
+                topics.insert(topic, shared);
+                receiver
+            }
+        };
+
+        let guard = TopicGuard {
+            subscriptions: self.clone(),
+            node: node.clone(),
+            topic,
+        };
+        Ok((receiver, guard))
+    }
+
+    /// Delivers `item` to the local holders of `topic`, e.g. a message this
+    /// node broadcast itself. Does nothing if the topic isn't subscribed.
+    pub async fn send_local(&self, topic: Topic, item: K::Item) {
+        if let Some(shared) = self.topics.lock().await.get(&topic) {
+            let _ = shared.sender.send(item);
+        }
+    }
+
+    async fn release(&self, node: Arc<PandaNode>, topic: Topic) {
+        let generation = match self.topics.lock().await.get_mut(&topic) {
+            Some(shared) => shared.release(),
+            None => None,
+        };
+        let Some(generation) = generation else {
+            return;
+        };
+
+        tokio::time::sleep(self.idle_timeout).await;
+
+        // Hold the lock while leaving the topic so a concurrent `acquire`
+        // can't try to subscribe before the old subscription is gone.
+        let mut topics = self.topics.lock().await;
+        if !topics
+            .get(&topic)
+            .is_some_and(|shared| shared.is_idle_since(generation))
+        {
+            return;
+        }
+        topics.remove(&topic);
+        K::leave(&node, topic).await;
+        info!("[subscriptions] left unused topic {}", topic.to_hex());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn shared_topic() -> SharedTopic<IncomingOperation> {
+        SharedTopic {
+            sender: broadcast::channel(4).0,
+            holders: 0,
+            idle_generation: 0,
+        }
+    }
+
+    #[test]
+    fn test_idle_only_after_last_holder_releases() {
+        let mut shared = shared_topic();
+        let _a = shared.retain();
+        let _b = shared.retain();
+
+        assert_eq!(shared.release(), None);
+        let generation = shared.release().unwrap();
+        assert!(shared.is_idle_since(generation));
+    }
+
+    #[test]
+    fn test_reuse_cancels_pending_teardown() {
+        let mut shared = shared_topic();
+        let _a = shared.retain();
+        let generation = shared.release().unwrap();
+
+        let _b = shared.retain();
+        assert!(!shared.is_idle_since(generation));
+
+        let next_generation = shared.release().unwrap();
+        assert!(!shared.is_idle_since(generation));
+        assert!(shared.is_idle_since(next_generation));
+    }
+}
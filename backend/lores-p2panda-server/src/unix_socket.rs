@@ -0,0 +1,92 @@
+use std::fs::{self, DirBuilder, Permissions};
+use std::io;
+use std::os::unix::fs::{DirBuilderExt, FileTypeExt, PermissionsExt};
+use std::path::Path;
+
+use tokio::net::UnixListener;
+use tokio_stream::wrappers::UnixListenerStream;
+
+/// Binds a Unix socket at `path` for serving gRPC to apps on the same host,
+/// for use with `Server::serve_with_incoming`. Only users that the socket's
+/// `mode` permits can connect, so apps can be given access through group
+/// membership rather than network reachability.
+///
+/// A socket left behind by an earlier run is replaced; any other file at
+/// `path` is an error.
+///
+/// The socket is created with whatever permissions the process umask allows,
+/// so it is bound inside a directory only this user can enter, and only moved
+/// to `path` once it has `mode`.
+pub fn bind_unix_socket(path: impl AsRef<Path>, mode: u32) -> io::Result<UnixListenerStream> {
+    let path = path.as_ref();
+    match fs::symlink_metadata(path) {
+        Ok(metadata) if metadata.file_type().is_socket() => fs::remove_file(path)?,
+        Ok(_) => {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!("{} exists and is not a socket", path.display()),
+            ));
+        }
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+        Err(e) => return Err(e),
+    }
+
+    let file_name = path.file_name().ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{} is not a socket path", path.display()),
+        )
+    })?;
+    let private_dir = path.with_file_name(format!(
+        ".{}.{}",
+        file_name.to_string_lossy(),
+        std::process::id()
+    ));
+    DirBuilder::new().mode(0o700).create(&private_dir)?;
+
+    let private_path = private_dir.join(file_name);
+    let bound = UnixListener::bind(&private_path).and_then(|listener| {
+        fs::set_permissions(&private_path, Permissions::from_mode(mode))?;
+        fs::rename(&private_path, path)?;
+        Ok(listener)
+    });
+    let _ = fs::remove_dir_all(&private_dir);
+    Ok(UnixListenerStream::new(bound?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn socket_path(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("lores-{name}-{}.sock", std::process::id()))
+    }
+
+    #[tokio::test]
+    async fn test_socket_gets_mode_and_replaces_stale_socket() {
+        let path = socket_path("mode");
+        drop(bind_unix_socket(&path, 0o600).unwrap());
+        // The first listener is gone but its socket file remains.
+        let _listener = bind_unix_socket(&path, 0o660).unwrap();
+
+        let mode = fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o660);
+        let private_dir = path.with_file_name(format!(
+            ".{}.{}",
+            path.file_name().unwrap().to_string_lossy(),
+            std::process::id()
+        ));
+        assert!(!private_dir.exists());
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_refuses_to_replace_other_files() {
+        let path = socket_path("file");
+        fs::write(&path, b"not a socket").unwrap();
+
+        let err = bind_unix_socket(&path, 0o660).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::AlreadyExists);
+        fs::remove_file(&path).unwrap();
+    }
+}
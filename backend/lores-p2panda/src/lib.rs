@@ -1,14 +1,19 @@
 pub mod node_status;
+pub mod operation_history;
 pub mod panda_node;
 pub mod region;
 pub mod topic_status;
 
 pub use node_status::NodeStatus;
+pub use operation_history::{
+    OperationCursor, OperationHistoryError, OperationQuery, StoredOperation,
+};
 pub use panda_node::{
-    IncomingOperation, LogCount, OperationCountByAuthorAndTopic, PandaNode, PandaNodeError,
-    PandaPublishError, RequiredNodeParams, SubscriptionError,
+    EphemeralMessage, IncomingOperation, LogCount, OperationCountByAuthorAndTopic, PandaNode,
+    PandaNodeError, PandaPublishError, PublishBatchError, PublishedOperation, RequiredNodeParams,
+    SubscriptionError,
 };
-pub use region::{RegionAdminTopic, RegionAppTopic, RegionId, RegionTopic};
+pub use region::{RegionAdminTopic, RegionAppKeyValueTopic, RegionAppTopic, RegionId, RegionTopic};
 pub use topic_status::{ConnectionStatus, TopicStatus};
 
 pub use p2panda_core;
@@ -25,6 +25,12 @@ impl NodeStatus {
         status
     }
 
+    /// Stop tracking a topic. Called by [`crate::PandaNode`] when it
+    /// unsubscribes.
+    pub(crate) fn unregister_topic(&mut self, topic: &Topic) -> bool {
+        self.topics.remove(topic).is_some()
+    }
+
     /// Returns the [`TopicStatus`] for a given topic, or `None` if not subscribed.
     pub fn get_topic(&self, topic: &Topic) -> Option<Arc<RwLock<TopicStatus>>> {
         self.topics.get(topic).cloned()
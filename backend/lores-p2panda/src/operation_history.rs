@@ -0,0 +1,493 @@
+use p2panda::operation::Header;
+use p2panda_core::cbor::decode_cbor;
+use p2panda_core::{Hash, Topic, VerifyingKey};
+use sqlx::Row;
+use sqlx::sqlite::{SqlitePool, SqliteRow};
+use thiserror::Error;
+
+use crate::panda_node::{IncomingOperation, PandaNode};
+
+/// Filters for [`PandaNode::list_operations`]. Operations are returned newest
+/// first.
+#[derive(Debug, Clone)]
+pub struct OperationQuery {
+    pub topic: Topic,
+    pub author: Option<VerifyingKey>,
+    /// Only operations with a timestamp at or after this value.
+    pub since_timestamp: Option<u64>,
+    /// Only operations with a timestamp before this value.
+    pub until_timestamp: Option<u64>,
+    /// Continue after the last operation of a previous page.
+    pub after: Option<OperationCursor>,
+    pub limit: u32,
+}
+
+/// Position of an operation in the newest-first ordering used by
+/// [`PandaNode::list_operations`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct OperationCursor {
+    pub timestamp: u64,
+    pub operation_id: Hash,
+}
+
+/// An operation read back from the store.
+#[derive(Clone)]
+pub struct StoredOperation {
+    pub operation: IncomingOperation,
+    pub seq_num: u64,
+    /// Position in the order the store received operations in, across all
+    /// authors. Later arrivals have higher positions.
+    pub arrival: i64,
+}
+
+impl StoredOperation {
+    pub fn cursor(&self) -> OperationCursor {
+        OperationCursor {
+            timestamp: self.operation.received_timestamp,
+            operation_id: self.operation.operation_id,
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum OperationHistoryError {
+    #[error(transparent)]
+    Database(#[from] sqlx::Error),
+    #[error("Stored operation {0} could not be read: {1}")]
+    InvalidOperation(String, String),
+}
+
+const SELECT_OPERATIONS: &str = "SELECT o.rowid AS store_rowid, o.hash, o.verifying_key,
+        CAST(o.seq_num AS INTEGER) AS seq_num,
+        o.header,
+        o.body
+     FROM operations_v1 o
+     JOIN topics_v1 t ON o.verifying_key = t.author AND o.log_id = t.data_id
+     WHERE substr(t.topic, 3) = ?1";
+
+/// How many newly stored operations are indexed per transaction.
+const INDEX_BATCH_SIZE: i64 = 500;
+
+/// The store keeps an operation's timestamp only inside its encoded header,
+/// so it is copied into a table of our own to filter and order by in SQL.
+/// Operations are indexed in the order the store inserted them, which p2panda
+/// only ever appends to, so the highest indexed rowid marks how far it got.
+pub(crate) async fn setup_operation_index(pool: &SqlitePool) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "CREATE TABLE IF NOT EXISTS lores_operation_index_v1 (
+            store_rowid INTEGER NOT NULL,
+            hash        TEXT    NOT NULL UNIQUE,
+            timestamp   INTEGER NOT NULL
+        )",
+    )
+    .execute(pool)
+    .await?;
+    sqlx::query(
+        "CREATE INDEX IF NOT EXISTS lores_operation_index_v1_timestamp
+         ON lores_operation_index_v1 (timestamp, hash)",
+    )
+    .execute(pool)
+    .await?;
+    sqlx::query(
+        "CREATE INDEX IF NOT EXISTS lores_operation_index_v1_store_rowid
+         ON lores_operation_index_v1 (store_rowid)",
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+impl PandaNode {
+    /// Reads stored operations for a topic from the p2panda store, newest
+    /// first.
+    pub async fn list_operations(
+        &self,
+        query: &OperationQuery,
+    ) -> Result<Vec<StoredOperation>, OperationHistoryError> {
+        self.update_operation_index().await?;
+
+        let rows = sqlx::query(
+            "SELECT o.rowid AS store_rowid, o.hash, o.verifying_key,
+                CAST(o.seq_num AS INTEGER) AS seq_num,
+                o.header,
+                o.body
+             FROM lores_operation_index_v1 i
+             JOIN operations_v1 o ON o.hash = i.hash
+             JOIN topics_v1 t ON o.verifying_key = t.author AND o.log_id = t.data_id
+             WHERE substr(t.topic, 3) = ?1
+               AND (?2 IS NULL OR o.verifying_key = ?2)
+               AND (?3 IS NULL OR i.timestamp >= ?3)
+               AND (?4 IS NULL OR i.timestamp < ?4)
+               AND (?5 IS NULL OR (i.timestamp, i.hash) < (?5, ?6))
+             ORDER BY i.timestamp DESC, i.hash DESC
+             LIMIT ?7",
+        )
+        .bind(query.topic.to_bytes().to_vec())
+        .bind(query.author.as_ref().map(|author| author.to_hex()))
+        .bind(query.since_timestamp.map(|since| since as i64))
+        .bind(query.until_timestamp.map(|until| until as i64))
+        .bind(query.after.as_ref().map(|after| after.timestamp as i64))
+        .bind(
+            query
+                .after
+                .as_ref()
+                .map(|after| after.operation_id.to_hex()),
+        )
+        .bind(query.limit)
+        .fetch_all(self.pool())
+        .await?;
+
+        rows.iter()
+            .map(|row| stored_operation(row, query.topic))
+            .collect()
+    }
+
+    /// Reads a single operation by id, if it is stored under `topic`.
+    pub async fn get_operation(
+        &self,
+        topic: Topic,
+        operation_id: Hash,
+    ) -> Result<Option<StoredOperation>, OperationHistoryError> {
+        let sql = format!("{SELECT_OPERATIONS} AND o.hash = ?2");
+        let row = sqlx::query(&sql)
+            .bind(topic.to_bytes().to_vec())
+            .bind(operation_id.to_hex())
+            .fetch_optional(self.pool())
+            .await?;
+
+        row.map(|row| stored_operation(&row, topic)).transpose()
+    }
+
+    /// Reads stored operations for a topic that arrived after the operation
+    /// at `after_arrival`, in the order they arrived.
+    pub async fn operations_arrived_after(
+        &self,
+        topic: Topic,
+        after_arrival: i64,
+        limit: u32,
+    ) -> Result<Vec<StoredOperation>, OperationHistoryError> {
+        let sql = format!("{SELECT_OPERATIONS} AND o.rowid > ?2 ORDER BY o.rowid LIMIT ?3");
+        let rows = sqlx::query(&sql)
+            .bind(topic.to_bytes().to_vec())
+            .bind(after_arrival)
+            .bind(limit)
+            .fetch_all(self.pool())
+            .await?;
+
+        rows.iter()
+            .map(|row| stored_operation(row, topic))
+            .collect()
+    }
+
+    /// Indexes the operations stored since the index was last brought up to
+    /// date.
+    async fn update_operation_index(&self) -> Result<(), OperationHistoryError> {
+        let _indexing = self.index_lock().lock().await;
+        loop {
+            let rows = sqlx::query(
+                "SELECT o.rowid AS store_rowid, o.hash, o.header
+                 FROM operations_v1 o
+                 WHERE o.rowid > (SELECT IFNULL(MAX(store_rowid), 0) FROM lores_operation_index_v1)
+                 ORDER BY o.rowid
+                 LIMIT ?",
+            )
+            .bind(INDEX_BATCH_SIZE)
+            .fetch_all(self.pool())
+            .await?;
+
+            let mut tx = self.pool().begin().await?;
+            for row in &rows {
+                let hash: String = row.get("hash");
+                let timestamp = header_timestamp(row, &hash)?;
+                sqlx::query(
+                    "INSERT OR IGNORE INTO lores_operation_index_v1 (store_rowid, hash, timestamp)
+                     VALUES (?, ?, ?)",
+                )
+                .bind(row.get::<i64, _>("store_rowid"))
+                .bind(&hash)
+                .bind(timestamp as i64)
+                .execute(&mut *tx)
+                .await?;
+            }
+            tx.commit().await?;
+
+            if (rows.len() as i64) < INDEX_BATCH_SIZE {
+                return Ok(());
+            }
+        }
+    }
+}
+
+fn header_timestamp(row: &SqliteRow, hash_hex: &str) -> Result<u64, OperationHistoryError> {
+    let header: Vec<u8> = row.get("header");
+    let header: Header = decode_cbor(&header[..]).map_err(|e| {
+        OperationHistoryError::InvalidOperation(hash_hex.to_string(), e.to_string())
+    })?;
+    Ok(header.extensions.timestamp().into())
+}
+
+fn stored_operation(
+    row: &SqliteRow,
+    topic: Topic,
+) -> Result<StoredOperation, OperationHistoryError> {
+    let hash_hex: String = row.get("hash");
+    let invalid = |reason: &str| {
+        OperationHistoryError::InvalidOperation(hash_hex.clone(), reason.to_string())
+    };
+
+    let operation_id = decode_hex_32(&hash_hex)
+        .map(Hash::from_bytes)
+        .ok_or_else(|| invalid("invalid hash"))?;
+    let author = decode_hex_32(row.get("verifying_key"))
+        .and_then(|bytes| VerifyingKey::from_bytes(&bytes).ok())
+        .ok_or_else(|| invalid("invalid author"))?;
+    let body: Option<Vec<u8>> = row.get("body");
+    let bytes = match body {
+        Some(body) => decode_cbor(&body[..]).map_err(|e| invalid(&e.to_string()))?,
+        None => Vec::new(),
+    };
+    let timestamp = header_timestamp(row, &hash_hex)?;
+    let seq_num: i64 = row.get("seq_num");
+
+    Ok(StoredOperation {
+        operation: IncomingOperation {
+            author,
+            topic,
+            bytes,
+            operation_id,
+            received_timestamp: timestamp,
+        },
+        seq_num: seq_num as u64,
+        arrival: row.get("store_rowid"),
+    })
+}
+
+fn decode_hex_32(value: &str) -> Option<[u8; 32]> {
+    hex::decode(value).ok()?.try_into().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use p2panda_core::SigningKey;
+    use tokio::sync::mpsc;
+
+    use super::*;
+    use crate::panda_node::PublishedOperation;
+    use crate::panda_node::tests::{test_node, topic};
+    use crate::region::RegionTopic;
+
+    fn query(limit: u32) -> OperationQuery {
+        OperationQuery {
+            topic: topic().p2panda_topic(),
+            author: None,
+            since_timestamp: None,
+            until_timestamp: None,
+            after: None,
+            limit,
+        }
+    }
+
+    fn payloads(operations: &[StoredOperation]) -> Vec<String> {
+        operations
+            .iter()
+            .map(|stored| String::from_utf8(stored.operation.bytes.clone()).unwrap())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_list_operations_reads_the_store() {
+        let dir = tempfile::tempdir().unwrap();
+        let node = test_node(&dir).await;
+        let (events_tx, _events_rx) = mpsc::channel(16);
+        node.subscribe_to_region_topic(&topic(), events_tx)
+            .await
+            .unwrap();
+        let mut published = Vec::new();
+        for payload in [b"one", b"two", b"six"] {
+            published.push(
+                node.publish_to_region_topic(&topic(), payload.to_vec())
+                    .await
+                    .unwrap(),
+            );
+            tokio::time::sleep(std::time::Duration::from_millis(2)).await;
+        }
+
+        let all = node.list_operations(&query(10)).await.unwrap();
+        assert_eq!(payloads(&all), vec!["six", "two", "one"]);
+        assert_eq!(all[0].operation.received_timestamp, published[2].timestamp);
+        assert_eq!(all[0].seq_num, 2);
+
+        let first_page = node.list_operations(&query(2)).await.unwrap();
+        assert_eq!(payloads(&first_page), vec!["six", "two"]);
+        let next_page = node
+            .list_operations(&OperationQuery {
+                after: Some(first_page[1].cursor()),
+                ..query(2)
+            })
+            .await
+            .unwrap();
+        assert_eq!(payloads(&next_page), vec!["one"]);
+
+        let since = node
+            .list_operations(&OperationQuery {
+                since_timestamp: Some(published[1].timestamp),
+                ..query(10)
+            })
+            .await
+            .unwrap();
+        assert_eq!(payloads(&since), vec!["six", "two"]);
+        let until = node
+            .list_operations(&OperationQuery {
+                until_timestamp: Some(published[1].timestamp),
+                ..query(10)
+            })
+            .await
+            .unwrap();
+        assert_eq!(payloads(&until), vec!["one"]);
+
+        let by_author = node
+            .list_operations(&OperationQuery {
+                author: Some(node.public_key),
+                ..query(10)
+            })
+            .await
+            .unwrap();
+        assert_eq!(by_author.len(), 3);
+        let by_other_author = node
+            .list_operations(&OperationQuery {
+                author: Some(SigningKey::generate().verifying_key()),
+                ..query(10)
+            })
+            .await
+            .unwrap();
+        assert!(by_other_author.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_list_operations_indexes_later_operations() {
+        let dir = tempfile::tempdir().unwrap();
+        let node = test_node(&dir).await;
+        let (events_tx, _events_rx) = mpsc::channel(16);
+        node.subscribe_to_region_topic(&topic(), events_tx)
+            .await
+            .unwrap();
+        node.publish_to_region_topic(&topic(), b"one".to_vec())
+            .await
+            .unwrap();
+        assert_eq!(
+            payloads(&node.list_operations(&query(10)).await.unwrap()),
+            vec!["one"]
+        );
+
+        tokio::time::sleep(std::time::Duration::from_millis(2)).await;
+        node.publish_to_region_topic(&topic(), b"two".to_vec())
+            .await
+            .unwrap();
+        assert_eq!(
+            payloads(&node.list_operations(&query(10)).await.unwrap()),
+            vec!["two", "one"]
+        );
+    }
+
+    /// Copies an operation another node published into `node`'s store, as if
+    /// it had arrived over the network.
+    async fn receive(node: &PandaNode, from_dir: &tempfile::TempDir, operation_id: Hash) {
+        let mut conn = node.pool().acquire().await.unwrap();
+        let other_db = from_dir.path().join("operations.db");
+        sqlx::query("ATTACH DATABASE ? AS other")
+            .bind(other_db.display().to_string())
+            .execute(&mut *conn)
+            .await
+            .unwrap();
+        sqlx::query("INSERT INTO operations_v1 SELECT * FROM other.operations_v1 WHERE hash = ?")
+            .bind(operation_id.to_hex())
+            .execute(&mut *conn)
+            .await
+            .unwrap();
+        sqlx::query("INSERT OR IGNORE INTO topics_v1 SELECT * FROM other.topics_v1")
+            .execute(&mut *conn)
+            .await
+            .unwrap();
+        sqlx::query("DETACH DATABASE other")
+            .execute(&mut *conn)
+            .await
+            .unwrap();
+    }
+
+    async fn publish(node: &PandaNode, payload: &[u8]) -> PublishedOperation {
+        node.publish_to_region_topic(&topic(), payload.to_vec())
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_operations_arrived_after_interleaves_authors() {
+        let dir = tempfile::tempdir().unwrap();
+        let node = test_node(&dir).await;
+        let other_dir = tempfile::tempdir().unwrap();
+        let other_node = test_node(&other_dir).await;
+        let (events_tx, _events_rx) = mpsc::channel(16);
+        node.subscribe_to_region_topic(&topic(), events_tx.clone())
+            .await
+            .unwrap();
+        other_node
+            .subscribe_to_region_topic(&topic(), events_tx)
+            .await
+            .unwrap();
+
+        // Arrival order is a1, b1, a2, b2; replaying author by author would
+        // give a1, a2, b1, b2.
+        publish(&node, b"a1").await;
+        let b1 = publish(&other_node, b"b1").await;
+        let b2 = publish(&other_node, b"b2").await;
+        receive(&node, &other_dir, b1.operation_id).await;
+        publish(&node, b"a2").await;
+        receive(&node, &other_dir, b2.operation_id).await;
+
+        let b1 = node
+            .get_operation(topic().p2panda_topic(), b1.operation_id)
+            .await
+            .unwrap()
+            .unwrap();
+        let after_b1 = node
+            .operations_arrived_after(topic().p2panda_topic(), b1.arrival, 10)
+            .await
+            .unwrap();
+        assert_eq!(payloads(&after_b1), vec!["a2", "b2"]);
+
+        let first_page = node
+            .operations_arrived_after(topic().p2panda_topic(), 0, 2)
+            .await
+            .unwrap();
+        assert_eq!(payloads(&first_page), vec!["a1", "b1"]);
+    }
+
+    #[tokio::test]
+    async fn test_get_operation_is_scoped_by_topic() {
+        let dir = tempfile::tempdir().unwrap();
+        let node = test_node(&dir).await;
+        let (events_tx, _events_rx) = mpsc::channel(16);
+        node.subscribe_to_region_topic(&topic(), events_tx)
+            .await
+            .unwrap();
+        let published = node
+            .publish_to_region_topic(&topic(), b"one".to_vec())
+            .await
+            .unwrap();
+
+        let stored = node
+            .get_operation(topic().p2panda_topic(), published.operation_id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(stored.operation.bytes, b"one");
+        assert_eq!(stored.operation.author, node.public_key);
+        assert_eq!(stored.cursor().timestamp, published.timestamp);
+
+        let other_topic = node
+            .get_operation(Topic::from([2u8; 32]), published.operation_id)
+            .await
+            .unwrap();
+        assert!(other_topic.is_none());
+    }
+}
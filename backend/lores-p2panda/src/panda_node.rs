@@ -1,22 +1,29 @@
 use std::collections::HashMap;
 use std::collections::HashSet;
 use std::sync::{Arc, LazyLock};
+use std::time::{SystemTime, UNIX_EPOCH};
 
 use p2panda::Node;
 use p2panda::NodeId;
 use p2panda::network::NetworkError;
 use p2panda::node::SpawnError;
-use p2panda::streams::{PublishError, StreamEvent, StreamFrom, StreamPublisher};
+use p2panda::processor::ProcessorError;
+use p2panda::streams::{
+    EphemeralPublishError, EphemeralStreamPublisher, PublishError, PublishFuture, StreamEvent,
+    StreamFrom, StreamPublisher,
+};
 use p2panda_core::{Hash, SigningKey, Topic, VerifyingKey};
 use p2panda_net::iroh_endpoint::RelayUrl;
 use p2panda_store::SqliteError;
 use sqlx::Row;
 use sqlx::sqlite::{SqliteConnectOptions, SqlitePool, SqlitePoolOptions};
 use thiserror::Error;
-use tokio::sync::{RwLock, mpsc};
+use tokio::sync::{Mutex, RwLock, mpsc};
+use tokio::task::JoinHandle;
 use tokio_stream::StreamExt;
 
 use crate::node_status::NodeStatus;
+use crate::operation_history::setup_operation_index;
 use crate::region::{RegionId, RegionTopic};
 
 static DEFAULT_IROH_RELAY_URL: LazyLock<RelayUrl> = LazyLock::new(|| {
@@ -34,6 +41,25 @@ pub struct IncomingOperation {
     pub received_timestamp: u64,
 }
 
+/// A message received on a topic's ephemeral gossip channel. Ephemeral
+/// messages are never written to the operation log.
+#[derive(Clone)]
+pub struct EphemeralMessage {
+    pub author: VerifyingKey,
+    pub topic: Topic,
+    pub bytes: Vec<u8>,
+    /// Unix timestamp in milliseconds at which this node received the message.
+    pub received_timestamp: u64,
+}
+
+/// Identifies an operation this node has published and persisted.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PublishedOperation {
+    pub operation_id: Hash,
+    pub timestamp: u64,
+    pub seq_num: u64,
+}
+
 #[derive(Debug, Error)]
 pub enum PandaNodeError {
     #[error(transparent)]
@@ -50,10 +76,30 @@ pub enum PandaPublishError {
     NoSubscription(Topic),
     #[error(transparent)]
     Publish(#[from] PublishError),
+    #[error(transparent)]
+    PublishEphemeral(#[from] EphemeralPublishError),
+    #[error("Operation {0} was dropped before it was processed")]
+    NotProcessed(Hash),
+    #[error(transparent)]
+    Processing(#[from] ProcessorError),
     #[error("App error: {0}")]
     AppError(String),
 }
 
+/// A batch that stopped partway through. Items before the one that failed
+/// are published and stay published.
+#[derive(Debug, Error)]
+#[error("{error}")]
+pub struct PublishBatchError {
+    /// The items published before the failure, in order.
+    pub published: Vec<PublishedOperation>,
+    /// Set when the failed item was stored in the log before processing it
+    /// failed. It will still reach peers, so it counts as published.
+    pub stored: Option<Hash>,
+    #[source]
+    pub error: PandaPublishError,
+}
+
 #[derive(Debug, Error)]
 pub enum SubscriptionError {
     #[error("Already subscribed to topic {0:?}")]
@@ -72,6 +118,13 @@ pub struct RequiredNodeParams {
 pub struct PandaNode {
     network: RwLock<Node>,
     publishers: RwLock<HashMap<Topic, StreamPublisher<Vec<u8>>>>,
+    forwarders: RwLock<HashMap<Topic, JoinHandle<()>>>,
+    ephemeral_publishers: RwLock<HashMap<Topic, EphemeralStreamPublisher<Vec<u8>>>>,
+    ephemeral_forwarders: RwLock<HashMap<Topic, JoinHandle<()>>>,
+    /// Serialises publishing so a batch lands contiguously in this node's log.
+    publish_lock: Mutex<()>,
+    /// Serialises updates to the operation index.
+    index_lock: Mutex<()>,
     regions: RwLock<HashSet<RegionId>>,
     node_status: Arc<RwLock<NodeStatus>>,
     pool: SqlitePool,
@@ -105,12 +158,19 @@ impl PandaNode {
 
         let node = builder.spawn().await?;
 
-        // Open a read-only pool against the same file for diagnostic queries.
+        // Open a second pool against the same file for diagnostic queries and
+        // the operation index.
         let pool = open_pool(database_url).await?;
+        setup_operation_index(&pool).await?;
 
         Ok(Self {
             network: RwLock::new(node),
             publishers: RwLock::new(HashMap::new()),
+            forwarders: RwLock::new(HashMap::new()),
+            ephemeral_publishers: RwLock::new(HashMap::new()),
+            ephemeral_forwarders: RwLock::new(HashMap::new()),
+            publish_lock: Mutex::new(()),
+            index_lock: Mutex::new(()),
             regions: RwLock::new(HashSet::new()),
             node_status: Arc::new(RwLock::new(NodeStatus::new())),
             pool,
@@ -118,7 +178,7 @@ impl PandaNode {
         })
     }
 
-    async fn subscribe_to_topic(
+    pub async fn subscribe_to_topic(
         &self,
         topic_id: Topic,
         events_tx: mpsc::Sender<IncomingOperation>,
@@ -136,7 +196,7 @@ impl PandaNode {
         let topic_status = self.node_status.write().await.register_topic(topic_id);
         self.publishers.write().await.insert(topic_id, publisher);
 
-        tokio::spawn(async move {
+        let forwarder = tokio::spawn(async move {
             while let Some(event) = subscription.next().await {
                 match event {
                     StreamEvent::Processed { operation: op, .. } => {
@@ -171,10 +231,97 @@ impl PandaNode {
                 }
             }
         });
+        self.forwarders.write().await.insert(topic_id, forwarder);
+
+        Ok(())
+    }
+
+    /// Leave a topic previously joined with one of the `subscribe_*` methods.
+    /// Drops the publisher, which closes the p2panda stream, stops forwarding
+    /// to the subscription's channel and removes the topic from
+    /// [`NodeStatus`]. Returns `false` if the topic wasn't subscribed.
+    pub async fn unsubscribe(&self, topic_id: Topic) -> bool {
+        let publisher = self.publishers.write().await.remove(&topic_id);
+        if let Some(forwarder) = self.forwarders.write().await.remove(&topic_id) {
+            forwarder.abort();
+        }
+        self.node_status.write().await.unregister_topic(&topic_id);
+        publisher.is_some()
+    }
+
+    /// Join the ephemeral gossip channel of `topic_id` and forward every
+    /// message other nodes broadcast on it to `events_tx`. Independent of any
+    /// operation log subscription to the same topic.
+    pub async fn subscribe_ephemeral(
+        &self,
+        topic_id: Topic,
+        events_tx: mpsc::Sender<EphemeralMessage>,
+    ) -> Result<(), SubscriptionError> {
+        if self
+            .ephemeral_publishers
+            .read()
+            .await
+            .contains_key(&topic_id)
+        {
+            return Err(SubscriptionError::AlreadySubscribed(topic_id));
+        }
+
+        let network = self.network.read().await;
+        let (publisher, mut subscription) = network.ephemeral_stream::<Vec<u8>>(topic_id).await?;
+        drop(network);
+
+        self.ephemeral_publishers
+            .write()
+            .await
+            .insert(topic_id, publisher);
+
+        let forwarder = tokio::spawn(async move {
+            while let Some(message) = subscription.next().await {
+                let incoming = EphemeralMessage {
+                    author: message.author(),
+                    topic: topic_id,
+                    bytes: message.body().clone(),
+                    received_timestamp: now_millis(),
+                };
+                if events_tx.send(incoming).await.is_err() {
+                    break;
+                }
+            }
+        });
+        self.ephemeral_forwarders
+            .write()
+            .await
+            .insert(topic_id, forwarder);
 
         Ok(())
     }
 
+    /// Leave the ephemeral gossip channel of `topic_id`. Returns `false` if it
+    /// wasn't joined.
+    pub async fn unsubscribe_ephemeral(&self, topic_id: Topic) -> bool {
+        let publisher = self.ephemeral_publishers.write().await.remove(&topic_id);
+        if let Some(forwarder) = self.ephemeral_forwarders.write().await.remove(&topic_id) {
+            forwarder.abort();
+        }
+        publisher.is_some()
+    }
+
+    /// Gossip `bytes` to the nodes currently on the ephemeral channel of
+    /// `topic_id`. Nothing is persisted, so nodes that are offline never see
+    /// it.
+    pub async fn broadcast_ephemeral(
+        &self,
+        topic_id: Topic,
+        bytes: Vec<u8>,
+    ) -> Result<(), PandaPublishError> {
+        let publishers = self.ephemeral_publishers.read().await;
+        let publisher = publishers
+            .get(&topic_id)
+            .ok_or(PandaPublishError::NoSubscription(topic_id))?;
+        publisher.publish(bytes).await?;
+        Ok(())
+    }
+
     pub async fn get_subscribed_topics(&self) -> Vec<Topic> {
         self.publishers.read().await.keys().cloned().collect()
     }
@@ -190,6 +337,12 @@ impl PandaNode {
         self.regions.write().await.insert(region_id);
     }
 
+    /// Stop recording participation in `region_id`. This doesn't leave any of
+    /// the region's topics; use [`Self::unsubscribe_from_region_topic`] for that.
+    pub async fn unregister_region(&self, region_id: &RegionId) -> bool {
+        self.regions.write().await.remove(region_id)
+    }
+
     /// Returns all registered region IDs.
     pub async fn get_regions(&self) -> Vec<RegionId> {
         self.regions.read().await.iter().cloned().collect()
@@ -213,6 +366,10 @@ impl PandaNode {
     /// subscribed, so it can be called while a live frontier subscription is
     /// active.  The publisher half of the stream is dropped immediately because
     /// publishing is handled by the existing subscription.
+    ///
+    /// The stream is closed once the stored operations have been replayed, so
+    /// `events_tx` is dropped at `StreamEvent::ReplayEnded`; operations arriving
+    /// later are only delivered to live subscriptions.
     pub async fn replay_topic(
         &self,
         topic_id: Topic,
@@ -247,7 +404,8 @@ impl PandaNode {
                     }
                     StreamEvent::SyncStarted { .. } | StreamEvent::SyncEnded { .. } => {}
                     StreamEvent::ImportStarted { .. } | StreamEvent::ImportEnded { .. } => {}
-                    StreamEvent::ReplayStarted { .. } | StreamEvent::ReplayEnded => {}
+                    StreamEvent::ReplayStarted { .. } => {}
+                    StreamEvent::ReplayEnded => break,
                     StreamEvent::ProcessingFailed { error, .. } => {
                         tracing::error!("operation processing failed during replay: {error}");
                     }
@@ -270,22 +428,117 @@ impl PandaNode {
         self.subscribe_to_topic(topic, events_tx).await
     }
 
+    pub async fn unsubscribe_from_region_topic<T: RegionTopic>(&self, region_topic: &T) -> bool {
+        self.unsubscribe(region_topic.p2panda_topic()).await
+    }
+
     pub async fn publish_to_region_topic<T: RegionTopic>(
         &self,
         region_topic: &T,
         bytes: Vec<u8>,
-    ) -> Result<(), PandaPublishError> {
+    ) -> Result<PublishedOperation, PandaPublishError> {
         let topic = region_topic.p2panda_topic();
-        self.publish(topic, bytes).await
+        let _publishing = self.publish_lock.lock().await;
+        let publisher = self.publisher(topic).await?;
+        Self::publish(&publisher, bytes).await
     }
 
-    async fn publish(&self, topic_id: Topic, bytes: Vec<u8>) -> Result<(), PandaPublishError> {
-        let publishers = self.publishers.read().await;
-        let publisher = publishers
+    /// Publishes `payloads` in order, with no other operation from this node
+    /// in between. p2panda stores each operation on its own, so a batch that
+    /// fails partway leaves the items before the failure published; the error
+    /// says which they are.
+    pub async fn publish_batch_to_region_topic<T: RegionTopic>(
+        &self,
+        region_topic: &T,
+        payloads: Vec<Vec<u8>>,
+    ) -> Result<Vec<PublishedOperation>, PublishBatchError> {
+        let topic = region_topic.p2panda_topic();
+        let _publishing = self.publish_lock.lock().await;
+        let mut published = Vec::with_capacity(payloads.len());
+        let publisher = match self.publisher(topic).await {
+            Ok(publisher) => publisher,
+            Err(error) => {
+                return Err(PublishBatchError {
+                    published,
+                    stored: None,
+                    error,
+                });
+            }
+        };
+
+        for bytes in payloads {
+            let processing = match publisher.publish(bytes).await {
+                Ok(processing) => processing,
+                Err(error) => {
+                    return Err(PublishBatchError {
+                        published,
+                        stored: None,
+                        error: error.into(),
+                    });
+                }
+            };
+            let operation_id = processing.hash();
+            match Self::processed(processing).await {
+                Ok(operation) => published.push(operation),
+                Err(error) => {
+                    return Err(PublishBatchError {
+                        published,
+                        stored: Some(operation_id),
+                        error,
+                    });
+                }
+            }
+        }
+        Ok(published)
+    }
+
+    async fn publisher(
+        &self,
+        topic_id: Topic,
+    ) -> Result<StreamPublisher<Vec<u8>>, PandaPublishError> {
+        self.publishers
+            .read()
+            .await
             .get(&topic_id)
-            .ok_or(PandaPublishError::NoSubscription(topic_id))?;
-        publisher.publish(bytes).await?;
-        Ok(())
+            .cloned()
+            .ok_or(PandaPublishError::NoSubscription(topic_id))
+    }
+
+    async fn publish(
+        publisher: &StreamPublisher<Vec<u8>>,
+        bytes: Vec<u8>,
+    ) -> Result<PublishedOperation, PandaPublishError> {
+        let processing = publisher.publish(bytes).await?;
+        Self::processed(processing).await
+    }
+
+    /// Waits for a published operation to be processed, so it is in the
+    /// store by the time the caller hears about it.
+    async fn processed(processing: PublishFuture) -> Result<PublishedOperation, PandaPublishError> {
+        let operation_id = processing.hash();
+        let event = processing
+            .await
+            .map_err(|_| PandaPublishError::NotProcessed(operation_id))?;
+        if let Some(reason) = event.failure_reason() {
+            return Err(PandaPublishError::Processing(reason));
+        }
+
+        let header = event.header();
+        Ok(PublishedOperation {
+            operation_id,
+            timestamp: header.extensions.timestamp().into(),
+            seq_num: header.seq_num.into(),
+        })
+    }
+
+    /// Pool against the p2panda store. Only the operation index is written
+    /// through it.
+    pub(crate) fn pool(&self) -> &SqlitePool {
+        &self.pool
+    }
+
+    pub(crate) fn index_lock(&self) -> &Mutex<()> {
+        &self.index_lock
     }
 
     pub async fn get_log_counts(&self) -> Result<Vec<LogCount>, SqliteError> {
@@ -338,6 +591,13 @@ pub struct OperationCountByAuthorAndTopic {
     pub count: i64,
 }
 
+fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_millis() as u64)
+        .unwrap_or_default()
+}
+
 async fn open_pool(database_url: &str) -> Result<SqlitePool, sqlx::Error> {
     // Strip the "sqlite:" scheme prefix if present since SqliteConnectOptions
     // wants just the path.
@@ -352,3 +612,81 @@ async fn open_pool(database_url: &str) -> Result<SqlitePool, sqlx::Error> {
         .connect_with(options)
         .await
 }
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::region::RegionAppTopic;
+
+    pub(crate) async fn test_node(dir: &tempfile::TempDir) -> PandaNode {
+        let params = RequiredNodeParams {
+            private_key: SigningKey::generate(),
+            network_id: Hash::digest(b"lores-p2panda-tests"),
+            bootstrap_node_ids: vec![],
+            relay_url: None,
+        };
+        let database_url = format!("sqlite:{}", dir.path().join("operations.db").display());
+        PandaNode::new(&params, &database_url).await.unwrap()
+    }
+
+    pub(crate) fn topic() -> RegionAppTopic {
+        RegionAppTopic::new(RegionId::from([1u8; 32]), "app")
+    }
+
+    #[tokio::test]
+    async fn test_publish_returns_stored_operation() {
+        let dir = tempfile::tempdir().unwrap();
+        let node = test_node(&dir).await;
+        let (events_tx, _events_rx) = mpsc::channel(16);
+        node.subscribe_to_region_topic(&topic(), events_tx)
+            .await
+            .unwrap();
+
+        let first = node
+            .publish_to_region_topic(&topic(), b"one".to_vec())
+            .await
+            .unwrap();
+        let batch = node
+            .publish_batch_to_region_topic(&topic(), vec![b"two".to_vec(), b"three".to_vec()])
+            .await
+            .unwrap();
+
+        assert_eq!(first.seq_num, 0);
+        assert!(first.timestamp > 0);
+        assert_eq!(
+            batch.iter().map(|op| op.seq_num).collect::<Vec<_>>(),
+            vec![1, 2]
+        );
+
+        // Every returned id is the hash the store keeps the operation under.
+        for operation in std::iter::once(&first).chain(&batch) {
+            let (seq_num,): (i64,) =
+                sqlx::query_as("SELECT seq_num FROM operations_v1 WHERE hash = ?")
+                    .bind(operation.operation_id.to_hex())
+                    .fetch_one(node.pool())
+                    .await
+                    .unwrap();
+            assert_eq!(seq_num as u64, operation.seq_num);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_publish_batch_without_subscription_publishes_nothing() {
+        let dir = tempfile::tempdir().unwrap();
+        let node = test_node(&dir).await;
+
+        let result = node
+            .publish_batch_to_region_topic(&topic(), vec![b"one".to_vec(), b"two".to_vec()])
+            .await;
+        let error = result.unwrap_err();
+        assert!(matches!(error.error, PandaPublishError::NoSubscription(_)));
+        assert!(error.published.is_empty());
+        assert_eq!(error.stored, None);
+
+        let (count,): (i64,) = sqlx::query_as("SELECT COUNT(*) FROM operations_v1")
+            .fetch_one(node.pool())
+            .await
+            .unwrap();
+        assert_eq!(count, 0);
+    }
+}
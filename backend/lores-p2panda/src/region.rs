@@ -96,3 +96,33 @@ impl RegionTopic for RegionAppTopic {
         Topic::from(*Hash::digest(&data).as_bytes())
     }
 }
+
+/// Topic carrying the key-value store writes of an app in a region. Kept apart
+/// from the [`RegionAppTopic`] so apps streaming their own operations never see
+/// them.
+#[derive(Clone)]
+pub struct RegionAppKeyValueTopic {
+    pub region_id: RegionId,
+    pub app_id: String,
+}
+
+impl RegionAppKeyValueTopic {
+    pub fn new(region_id: RegionId, app_id: impl Into<String>) -> Self {
+        Self {
+            region_id,
+            app_id: app_id.into(),
+        }
+    }
+}
+
+impl RegionTopic for RegionAppKeyValueTopic {
+    fn p2panda_topic(&self) -> Topic {
+        let mut data = Vec::with_capacity(KEY_VALUE_TOPIC_PREFIX.len() + 32 + self.app_id.len());
+        data.extend_from_slice(KEY_VALUE_TOPIC_PREFIX);
+        data.extend_from_slice(&self.region_id.bytes);
+        data.extend_from_slice(self.app_id.as_bytes());
+        Topic::from(*Hash::digest(&data).as_bytes())
+    }
+}
+
+const KEY_VALUE_TOPIC_PREFIX: &[u8] = b"lores-key-value:";
@@ -0,0 +1,10 @@
+CREATE TABLE app_backups (
+    id              TEXT PRIMARY KEY,
+    stack_name      TEXT NOT NULL,
+    status          TEXT NOT NULL,
+    error           TEXT,
+    created_at      INTEGER NOT NULL DEFAULT (unixepoch()),
+    finished_at     INTEGER
+);
+
+CREATE INDEX app_backups_stack_name_created_at ON app_backups (stack_name, created_at);
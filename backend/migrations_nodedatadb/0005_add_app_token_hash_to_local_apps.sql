@@ -0,0 +1,3 @@
+-- Hash of the bearer token an app presents to the gRPC Panda service.
+ALTER TABLE local_apps
+ADD COLUMN app_token_hash TEXT;
@@ -0,0 +1,4 @@
+-- Comma-separated roles, e.g. "viewer,app-manager". Stewards could do
+-- everything before roles existed, so they keep every role.
+ALTER TABLE node_stewards
+ADD COLUMN roles TEXT NOT NULL DEFAULT 'viewer,app-manager,region-manager';
@@ -0,0 +1,26 @@
+-- Tokens node stewards create for scripts and monitoring. Only the hash of
+-- the token is stored. `scopes` holds comma-separated role names.
+CREATE TABLE api_tokens (
+    id                  TEXT PRIMARY KEY,
+    node_steward_id     TEXT NOT NULL,
+    name                TEXT NOT NULL,
+    token_hash          TEXT NOT NULL UNIQUE,
+    scopes              TEXT NOT NULL,
+    expires_at          INTEGER,
+    created_at          INTEGER NOT NULL DEFAULT (unixepoch()),
+    last_used_at        INTEGER,
+    revoked_at          INTEGER
+);
+
+CREATE INDEX api_tokens_node_steward_id ON api_tokens (node_steward_id);
+
+-- Every request made with an API token.
+CREATE TABLE api_token_uses (
+    id          INTEGER PRIMARY KEY AUTOINCREMENT,
+    token_id    TEXT NOT NULL,
+    method      TEXT NOT NULL,
+    path        TEXT NOT NULL,
+    used_at     INTEGER NOT NULL DEFAULT (unixepoch())
+);
+
+CREATE INDEX api_token_uses_token_id_used_at ON api_token_uses (token_id, used_at);
@@ -0,0 +1,25 @@
+-- Actions taken on this node: every mutating HTTP API request and every
+-- `lores-node` admin command. Entries are never changed or removed.
+CREATE TABLE audit_log (
+    id                  INTEGER PRIMARY KEY AUTOINCREMENT,
+    created_at          INTEGER NOT NULL DEFAULT (unixepoch()),
+    actor               TEXT NOT NULL,
+    node_steward_id     TEXT,
+    api_token_id        TEXT,
+    method              TEXT NOT NULL,
+    path                TEXT NOT NULL,
+    status              INTEGER
+);
+
+CREATE INDEX audit_log_node_steward_id ON audit_log (node_steward_id);
+CREATE INDEX audit_log_created_at ON audit_log (created_at);
+
+CREATE TRIGGER audit_log_no_update BEFORE UPDATE ON audit_log
+BEGIN
+    SELECT RAISE(ABORT, 'audit_log is append-only');
+END;
+
+CREATE TRIGGER audit_log_no_delete BEFORE DELETE ON audit_log
+BEGIN
+    SELECT RAISE(ABORT, 'audit_log is append-only');
+END;
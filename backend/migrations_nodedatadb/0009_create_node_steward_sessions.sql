@@ -0,0 +1,12 @@
+-- Sessions node stewards have logged in with, so they can see and end them.
+-- The sessions themselves are in the tower-sessions store; `id` is shown to
+-- stewards instead of `session_id`, which is as good as a password.
+CREATE TABLE node_steward_sessions (
+    id                  TEXT PRIMARY KEY,
+    session_id          TEXT NOT NULL UNIQUE,
+    node_steward_id     TEXT NOT NULL,
+    user_agent          TEXT,
+    created_at          INTEGER NOT NULL DEFAULT (unixepoch())
+);
+
+CREATE INDEX node_steward_sessions_node_steward_id ON node_steward_sessions (node_steward_id);
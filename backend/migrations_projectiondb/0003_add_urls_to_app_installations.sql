@@ -0,0 +1,5 @@
+ALTER TABLE app_installations
+ADD COLUMN internet_url TEXT NULL;
+
+ALTER TABLE app_installations
+ADD COLUMN local_network_url TEXT NULL;